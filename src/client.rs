@@ -1,5 +1,6 @@
 //! IPC client helper for module connection
 
+use crate::error::LightningError;
 use blvm_node::module::ipc::client::ModuleIpcClient;
 use blvm_node::module::ipc::protocol::{
     EventMessage, LogLevel, ModuleMessage, RequestMessage, RequestPayload, ResponsePayload,
@@ -9,15 +10,69 @@ use blvm_node::module::traits::ModuleError;
 use futures::StreamExt;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+/// Default number of reconnection attempts before the module gives up
+pub const RECONNECT_MAX_ATTEMPTS: u32 = 10;
+
+/// Default delay before the first reconnection attempt
+pub const DEFAULT_RECONNECT_BACKOFF_MS: u64 = 500;
+
+/// Longest delay between reconnection attempts
+pub const MAX_RECONNECT_BACKOFF_MS: u64 = 30_000;
+
+/// How often, and how patiently, to reconnect after the node goes away
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled on each further attempt
+    pub backoff_base: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: RECONNECT_MAX_ATTEMPTS,
+            backoff_base: Duration::from_millis(DEFAULT_RECONNECT_BACKOFF_MS),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Policy from `lightning.reconnect_max_attempts` and `lightning.reconnect_backoff_ms`
+    pub fn from_config(ctx: &blvm_node::module::traits::ModuleContext) -> Self {
+        let defaults = Self::default();
+        Self {
+            max_attempts: ctx.get_config_or("lightning.reconnect_max_attempts", "")
+                .parse::<u32>()
+                .unwrap_or(defaults.max_attempts),
+            backoff_base: ctx.get_config_or("lightning.reconnect_backoff_ms", "")
+                .parse::<u64>()
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.backoff_base),
+        }
+    }
+
+    /// Delay before reconnection attempt `attempt` (1-based), capped at `MAX_RECONNECT_BACKOFF_MS`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.backoff_base
+            .saturating_mul(factor)
+            .min(Duration::from_millis(MAX_RECONNECT_BACKOFF_MS))
+    }
+}
+
 /// Module client that handles IPC connection and event subscription
 pub struct ModuleClient {
     ipc_client: Arc<tokio::sync::Mutex<ModuleIpcClient>>,
+    socket_path: PathBuf,
     module_id: String,
     module_name: String,
     version: String,
+    /// Event types subscribed to, renewed on reconnect
+    event_types: Vec<EventType>,
     event_receiver: mpsc::Receiver<ModuleMessage>,
 }
 
@@ -29,13 +84,37 @@ impl ModuleClient {
         module_name: String,
         version: String,
     ) -> Result<Self, ModuleError> {
+        let ipc_client = Self::handshake(&socket_path, &module_id, &module_name, &version).await?;
+
+        // Spawn event receiver task
+        let ipc_client_arc = Arc::new(tokio::sync::Mutex::new(ipc_client));
+        let event_rx = spawn_event_receiver(Arc::clone(&ipc_client_arc), module_id.clone());
+
+        Ok(Self {
+            ipc_client: ipc_client_arc,
+            socket_path,
+            module_id,
+            module_name,
+            version,
+            event_types: Vec::new(),
+            event_receiver: event_rx,
+        })
+    }
+
+    /// Open the IPC socket and perform the handshake
+    async fn handshake(
+        socket_path: &PathBuf,
+        module_id: &str,
+        module_name: &str,
+        version: &str,
+    ) -> Result<ModuleIpcClient, ModuleError> {
         info!(
             "Connecting to node IPC socket: {:?} (module: {})",
             socket_path, module_name
         );
 
         // Connect to IPC socket
-        let mut ipc_client = ModuleIpcClient::connect(&socket_path).await?;
+        let mut ipc_client = ModuleIpcClient::connect(socket_path).await?;
         info!("Connected to IPC socket");
 
         // Perform handshake
@@ -44,9 +123,9 @@ impl ModuleClient {
             correlation_id,
             request_type: blvm_node::module::ipc::protocol::MessageType::Handshake,
             payload: RequestPayload::Handshake {
-                module_id: module_id.clone(),
-                module_name: module_name.clone(),
-                version: version.clone(),
+                module_id: module_id.to_string(),
+                module_name: module_name.to_string(),
+                version: version.to_string(),
             },
         };
 
@@ -57,50 +136,12 @@ impl ModuleClient {
                     "Handshake successful! Node version: {}, Module: {} v{}",
                     node_version, module_name, version
                 );
+                Ok(ipc_client)
             }
-            _ => {
-                return Err(ModuleError::IpcError(
-                    "Invalid handshake response".to_string(),
-                ));
-            }
+            _ => Err(ModuleError::IpcError(
+                "Invalid handshake response".to_string(),
+            )),
         }
-
-        // Create event channel
-        let (event_tx, event_rx) = mpsc::channel(1000);
-
-        // Spawn event receiver task
-        let ipc_client_arc = Arc::new(tokio::sync::Mutex::new(ipc_client));
-        let ipc_client_for_events = Arc::clone(&ipc_client_arc);
-        let module_id_for_events = module_id.clone();
-        tokio::spawn(async move {
-            loop {
-                match ipc_client_for_events.lock().await.receive_event().await {
-                    Ok(Some(ModuleMessage::Event(event))) => {
-                        if event_tx.send(ModuleMessage::Event(event)).await.is_err() {
-                            break; // Receiver dropped
-                        }
-                    }
-                    Ok(Some(_)) => {
-                        // Non-event message - ignore
-                    }
-                    Ok(None) => {
-                        // No event available - continue
-                    }
-                    Err(e) => {
-                        error!("Error receiving event for module {}: {}", module_id_for_events, e);
-                        break;
-                    }
-                }
-            }
-        });
-
-        Ok(Self {
-            ipc_client: ipc_client_arc,
-            module_id,
-            module_name,
-            version,
-            event_receiver: event_rx,
-        })
     }
 
     /// Subscribe to events
@@ -109,12 +150,13 @@ impl ModuleClient {
         let request = RequestMessage {
             correlation_id,
             request_type: blvm_node::module::ipc::protocol::MessageType::SubscribeEvents,
-            payload: RequestPayload::SubscribeEvents { event_types },
+            payload: RequestPayload::SubscribeEvents { event_types: event_types.clone() },
         };
 
         let response = self.ipc_client.lock().await.request(request).await?;
         if response.success {
             info!("Subscribed to events for module: {}", self.module_name);
+            self.event_types = event_types;
             Ok(())
         } else {
             Err(ModuleError::IpcError(
@@ -123,6 +165,47 @@ impl ModuleClient {
         }
     }
 
+    /// Re-establish the connection to the node after it went away
+    ///
+    /// Reconnects to the same socket, repeats the handshake and renews the
+    /// event subscription. The new connection replaces the old one inside
+    /// the shared client, so holders of `get_ipc_client` (e.g. `NodeApiIpc`)
+    /// use it without being rebuilt.
+    pub async fn reconnect(&mut self) -> Result<(), LightningError> {
+        let ipc_client = Self::handshake(&self.socket_path, &self.module_id, &self.module_name, &self.version).await?;
+        *self.ipc_client.lock().await = ipc_client;
+        self.event_receiver = spawn_event_receiver(Arc::clone(&self.ipc_client), self.module_id.clone());
+
+        if !self.event_types.is_empty() {
+            self.subscribe_events(self.event_types.clone()).await?;
+        }
+        info!("Reconnected to node IPC socket {:?}", self.socket_path);
+        Ok(())
+    }
+
+    /// Reconnect, retrying with exponential backoff up to `policy.max_attempts` times
+    pub async fn reconnect_with_backoff(&mut self, policy: &ReconnectPolicy) -> Result<(), LightningError> {
+        let mut last_error = None;
+        for attempt in 1..=policy.max_attempts {
+            let delay = policy.backoff(attempt);
+            warn!(
+                "Reconnecting to node (attempt {}/{}) in {:?}",
+                attempt, policy.max_attempts, delay
+            );
+            tokio::time::sleep(delay).await;
+            match self.reconnect().await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    warn!("Reconnect attempt {}/{} failed: {}", attempt, policy.max_attempts, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            LightningError::ModuleError("Reconnection disabled (lightning.reconnect_max_attempts = 0)".to_string())
+        }))
+    }
+
     /// Get event receiver
     pub fn event_receiver(&mut self) -> &mut mpsc::Receiver<ModuleMessage> {
         &mut self.event_receiver
@@ -152,9 +235,46 @@ impl ModuleClient {
         &self.module_name
     }
 
+    /// Socket the client connects (and reconnects) to
+    pub fn socket_path(&self) -> &PathBuf {
+        &self.socket_path
+    }
+
     /// Get IPC client for NodeAPI wrapper
     pub fn get_ipc_client(&self) -> Arc<tokio::sync::Mutex<ModuleIpcClient>> {
         Arc::clone(&self.ipc_client)
     }
 }
 
+/// Forward events from the IPC connection to a channel
+///
+/// The channel closes when the connection fails, which is how the event
+/// loop learns the node went away.
+fn spawn_event_receiver(
+    ipc_client: Arc<tokio::sync::Mutex<ModuleIpcClient>>,
+    module_id: String,
+) -> mpsc::Receiver<ModuleMessage> {
+    let (event_tx, event_rx) = mpsc::channel(1000);
+    tokio::spawn(async move {
+        loop {
+            match ipc_client.lock().await.receive_event().await {
+                Ok(Some(ModuleMessage::Event(event))) => {
+                    if event_tx.send(ModuleMessage::Event(event)).await.is_err() {
+                        break; // Receiver dropped
+                    }
+                }
+                Ok(Some(_)) => {
+                    // Non-event message - ignore
+                }
+                Ok(None) => {
+                    // No event available - continue
+                }
+                Err(e) => {
+                    error!("Error receiving event for module {}: {}", module_id, e);
+                    break;
+                }
+            }
+        }
+    });
+    event_rx
+}
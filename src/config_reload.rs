@@ -0,0 +1,49 @@
+//! Live configuration reload
+//!
+//! `ModuleContext.config` is a snapshot taken at startup. When
+//! `lightning.config_refresh_interval_seconds` is set, the processor re-reads
+//! the keys in `RELOADABLE_CONFIG_KEYS` from the node on that interval and
+//! applies changes without a restart: rate limits take effect immediately,
+//! provider settings by recreating the provider.
+
+use crate::error::LightningError;
+use async_trait::async_trait;
+
+/// Config keys re-read on each refresh
+pub const RELOADABLE_CONFIG_KEYS: &[&str] = &[
+    "lightning.provider",
+    "lightning.lnbits.api_key",
+    "lightning.lnbits.invoice_key",
+    "lightning.lnbits.admin_key",
+    "lightning.request_timeout_secs",
+    "lightning.max_retries",
+    "lightning.backoff_base_ms",
+    "lightning.lnbits.request_timeout_secs",
+    "lightning.lnbits.max_retries",
+    "lightning.lnbits.backoff_base_ms",
+    "lightning.rate_limit.max_per_minute",
+    "lightning.rate_limit.window_seconds",
+];
+
+/// Reloadable keys the processor applies in place; changes to any other
+/// reloadable key recreate the provider
+pub const IN_PLACE_CONFIG_KEYS: &[&str] = &[
+    "lightning.rate_limit.max_per_minute",
+    "lightning.rate_limit.window_seconds",
+];
+
+/// Where live config values come from (the node, over IPC)
+#[async_trait]
+pub trait ConfigSource: Send + Sync {
+    /// Current value of `key`, or `None` if it isn't set
+    async fn get_module_config(&self, key: &str) -> Result<Option<String>, LightningError>;
+}
+
+/// Outcome of a config refresh
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigReload {
+    /// Keys whose value changed, in `RELOADABLE_CONFIG_KEYS` order
+    pub changed: Vec<String>,
+    /// Whether the provider was recreated with the new settings
+    pub provider_recreated: bool,
+}
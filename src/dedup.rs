@@ -0,0 +1,129 @@
+//! Deduplication of events delivered more than once
+//!
+//! The node may replay events after an IPC reconnect. The event loop records
+//! an ID for every event it dispatches in a `SeenSet` and skips IDs it has
+//! already seen; an event whose handling fails is removed again so a
+//! redelivery retries it. Entries expire after a TTL so the set stays bounded.
+
+use bitcoin::hashes::{sha256, Hash};
+use blvm_node::module::ipc::protocol::{EventPayload, ModuleMessage};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Default time an event ID is remembered
+pub const DEFAULT_DEDUP_TTL_SECONDS: u64 = 600;
+
+/// Default interval between sweeps of expired event IDs
+pub const DEFAULT_DEDUP_CLEANUP_INTERVAL_SECONDS: u64 = 300;
+
+/// Set of recently dispatched event IDs
+///
+/// Clones share the same set, so the cleanup task and the event loop see the
+/// same entries.
+#[derive(Debug, Clone)]
+pub struct SeenSet {
+    /// Event ID -> time it was first seen
+    entries: Arc<RwLock<HashMap<String, Instant>>>,
+    ttl: Duration,
+}
+
+impl SeenSet {
+    /// Create an empty set remembering IDs for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    /// Record `id` and return whether it was not seen within the TTL
+    pub fn insert(&self, id: &str) -> bool {
+        let now = Instant::now();
+        if let Some(seen_at) = self.entries.read().unwrap().get(id) {
+            if now.duration_since(*seen_at) < self.ttl {
+                return false;
+            }
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        match entries.get(id) {
+            // Another task recorded it between the two locks
+            Some(seen_at) if now.duration_since(*seen_at) < self.ttl => false,
+            _ => {
+                entries.insert(id.to_string(), now);
+                true
+            }
+        }
+    }
+
+    /// Forget `id`, e.g. because handling the event failed
+    pub fn remove(&self, id: &str) {
+        self.entries.write().unwrap().remove(id);
+    }
+
+    /// Whether `id` was seen within the TTL
+    pub fn contains(&self, id: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|seen_at| seen_at.elapsed() < self.ttl)
+            .unwrap_or(false)
+    }
+
+    /// Drop expired IDs and return how many were removed
+    pub fn cleanup(&self) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|_, seen_at| seen_at.elapsed() < self.ttl);
+        before - entries.len()
+    }
+
+    /// Number of IDs currently held, including expired ones not yet cleaned up
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    /// Whether no IDs are held
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap().is_empty()
+    }
+
+    /// Spawn a task that runs `cleanup` every `interval_secs`
+    pub fn spawn_cleanup(&self, interval_secs: u64) -> JoinHandle<()> {
+        let seen = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+            // The first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let removed = seen.cleanup();
+                if removed > 0 {
+                    debug!("Removed {} expired event IDs, {} remaining", removed, seen.len());
+                }
+            }
+        })
+    }
+}
+
+/// ID used to recognize a redelivered event
+///
+/// Events don't carry a correlation ID, so the ID is a hash of the event type
+/// and payment ID. Returns `None` for messages without a payment ID; those are
+/// always dispatched.
+pub fn event_id(message: &ModuleMessage) -> Option<String> {
+    match message {
+        ModuleMessage::Event(event_msg) => match &event_msg.payload {
+            EventPayload::PaymentRequestCreated { payment_id, .. } => {
+                let key = format!("{:?}:{}", event_msg.event_type, payment_id);
+                Some(sha256::Hash::hash(key.as_bytes()).to_string())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
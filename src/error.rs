@@ -3,7 +3,7 @@
 use thiserror::Error;
 use blvm_node::module::traits::ModuleError;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum LightningError {
     #[error("Module error: {0}")]
     ModuleError(String),
@@ -28,6 +28,76 @@ pub enum LightningError {
     
     #[error("Configuration error: {0}")]
     ConfigError(String),
+    
+    #[error("HTTP error {status_code}: {body}")]
+    HttpError { status_code: u16, body: String },
+    
+    #[error("Unsupported operation: {0}")]
+    Unsupported(String),
+    
+    #[error("LNBits API error {status} at {endpoint}: {detail}")]
+    LNBitsApiError { status: u16, detail: String, endpoint: String },
+    
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalance(String),
+    
+    #[error("Invoice expired: {0}")]
+    InvoiceExpired(String),
+    
+    #[error("Wallet not found: {0}")]
+    WalletNotFound(String),
+}
+
+impl LightningError {
+    /// Whether the failure is transient and the operation may succeed if retried
+    ///
+    /// Invalid invoices, bad configuration, failed verifications and
+    /// rejected payments (insufficient balance, expired invoice) are permanent; connection problems, timeouts, routing failures, rate
+    /// limiting (429) and server errors (5xx) are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LightningError::NodeConnectionError(_) | LightningError::RoutingError(_) => true,
+            LightningError::ProcessorError(msg) => {
+                let msg = msg.to_lowercase();
+                msg.contains("timeout") || msg.contains("timed out") || msg.contains("connection refused")
+            }
+            LightningError::HttpError { status_code, .. } => *status_code == 429 || *status_code >= 500,
+            LightningError::LNBitsApiError { status, .. } => *status == 429 || *status >= 500,
+            LightningError::ModuleError(_)
+            | LightningError::InvoiceParseError(_)
+            | LightningError::InvoiceError(_)
+            | LightningError::PaymentVerificationFailed(_)
+            | LightningError::ConfigError(_)
+            | LightningError::Unsupported(_)
+            | LightningError::InsufficientBalance(_)
+            | LightningError::InvoiceExpired(_)
+            | LightningError::WalletNotFound(_) => false,
+        }
+    }
+
+    /// HTTP status code of the response that caused the error, if any
+    pub fn status_code(&self) -> Option<u16> {
+        match self {
+            LightningError::HttpError { status_code, .. } => Some(*status_code),
+            LightningError::LNBitsApiError { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Suggested delay before retrying, or 0 if the error is not retryable
+    pub fn suggested_retry_delay_ms(&self) -> u64 {
+        if !self.is_retryable() {
+            return 0;
+        }
+        match self {
+            // Routes depend on liquidity and gossip, which take a while to change
+            LightningError::RoutingError(_) => 5_000,
+            // Back off harder when the server is shedding load
+            LightningError::HttpError { status_code: 429, .. } => 5_000,
+            LightningError::ProcessorError(_) => 2_000,
+            _ => 1_000,
+        }
+    }
 }
 
 impl From<ModuleError> for LightningError {
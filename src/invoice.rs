@@ -1,82 +1,395 @@
 //! Lightning invoice handling (BOLT11)
 
 use crate::error::LightningError;
-use lightning_invoice::Invoice;
+use crate::provider::{validate_onchain_address, DescriptionKind, InvoiceParams, LightningProvider};
+use bitcoin::address::AddressData;
+use bitcoin::hashes::Hash as _;
+use bitcoin::{Address, Network, WitnessProgram};
+use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Fallback};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
 use tracing::debug;
 
+/// Final hop CLTV delta assumed when an invoice has no `c` field (BOLT11)
+pub const DEFAULT_MIN_FINAL_CLTV_EXPIRY: u64 = 18;
+
 /// Invoice parser for BOLT11 invoices
 pub struct InvoiceParser;
 
 impl InvoiceParser {
     /// Parse a BOLT11 Lightning invoice
     pub fn parse(invoice_str: &str) -> Result<InvoiceData, LightningError> {
-        // Parse BOLT11 invoice using lightning-invoice crate
-        let invoice: Invoice = invoice_str.parse()
-            .map_err(|e| LightningError::InvoiceError(format!("Failed to parse invoice: {:?}", e)))?;
-        
-        // Extract amount (lightning-invoice 0.2 API uses amount_pico_btc)
-        // Convert pico BTC to msats: 1 BTC = 10^12 pico BTC = 10^11 msats
-        // So: 1 pico BTC = 10^-1 msats = 0.1 msats, but we need integer math
-        // Better: amount_pico_btc * 10^-1 = msats, but for integer: (amount_pico_btc + 5) / 10
-        let amount_msats = invoice.amount_pico_btc()
-            .map(|pico_btc| (pico_btc + 5) / 10) // Round to nearest msat
-            .unwrap_or(0);
+        let invoice: Bolt11Invoice = invoice_str.parse()
+            .map_err(|e| LightningError::InvoiceError(format!("Failed to parse invoice: {}", e)))?;
         
-        // Extract expiry time (lightning-invoice 0.2 API - use as_seconds())
-        let expiry = invoice.expiry_time()
-            .map(|et| et.as_seconds())
-            .unwrap_or(3600);
+        let amount_msats = invoice.amount_milli_satoshis().unwrap_or(0);
+        // The `x` field, 3600 seconds if absent per BOLT11
+        let expiry = invoice.expiry_time().as_secs();
+        // Invoice creation time; the expiry is relative to this
+        let timestamp = invoice.duration_since_epoch().as_secs();
         
         debug!("Parsed Lightning invoice: amount={} msats, expiry={}s",
             amount_msats,
             expiry
         );
         
-        // Extract payment hash (lightning-invoice 0.2: payment_hash() returns &Sha256)
-        // Sha256 wraps sha256::Hash, convert to bytes
-        let payment_hash = invoice.payment_hash();
-        // Convert hash to bytes - sha256::Hash implements Display which we can parse
-        // Or use the hash's inner representation
-        let hash_str = format!("{}", payment_hash.0);
-        // Parse hex string back to bytes (sha256::Hash Display outputs hex)
-        let payment_hash_bytes = hex::decode(hash_str)
-            .map_err(|e| LightningError::InvoiceError(format!("Failed to decode payment hash: {}", e)))?;
-        let mut hash_array = [0u8; 32];
-        hash_array.copy_from_slice(&payment_hash_bytes[..32]);
+        let payment_hash = invoice.payment_hash().to_byte_array();
+        
+        // Payment secret (`s` field); lightning-invoice rejects invoices without one
+        let payment_secret = invoice.payment_secret().0;
+        
+        // Final hop CLTV delta (`c` field), 18 blocks if absent per BOLT11
+        let min_final_cltv_expiry = invoice.min_final_cltv_expiry_delta();
+        
+        // Extract description hash (`h` field), set when the invoice commits to
+        // a description held out-of-band instead of embedding it
+        let description_hash = match invoice.description() {
+            Bolt11InvoiceDescription::Hash(hash) => Some(hash.0.to_byte_array()),
+            Bolt11InvoiceDescription::Direct(_) => None,
+        };
+        
+        // Extract the on-chain fallback (`f` field); only the first is exposed
+        let network = Network::from(invoice.currency());
+        let fallback_address = invoice.fallbacks()
+            .into_iter()
+            .find_map(|fallback| fallback_to_address(fallback, network))
+            .map(|address| address.to_string());
+        
+        // Payee node: the `n` field if present, otherwise recovered from the signature
+        let payee_pubkey = Some(payee_pubkey(&invoice));
+        
+        // Extract routing hints (`r` fields), one per private route
+        let routing_hints = invoice.route_hints()
+            .into_iter()
+            .map(|route| RouteHint {
+                hops: route.0.iter()
+                    .map(|hop| RouteHintHop {
+                        src_node_id: hop.src_node_id.serialize(),
+                        short_channel_id: hop.short_channel_id,
+                        fee_base_msats: hop.fees.base_msat,
+                        fee_proportional_millionths: hop.fees.proportional_millionths,
+                        cltv_expiry_delta: hop.cltv_expiry_delta,
+                    })
+                    .collect(),
+            })
+            .collect();
         
         Ok(InvoiceData {
             amount_msats,
-            payment_hash: payment_hash_bytes.to_vec(),
+            payment_hash: payment_hash.to_vec(),
+            payment_secret,
             expiry,
-            invoice: invoice.clone(),
+            timestamp,
+            min_final_cltv_expiry,
+            description_hash,
+            fallback_address,
+            payee_pubkey,
+            routing_hints,
+            invoice,
+            raw: invoice_str.to_string(),
         })
     }
     
     /// Verify invoice signature
-    pub fn verify_signature(invoice: &Invoice) -> Result<bool, LightningError> {
-        // lightning-invoice crate handles signature verification during parsing
-        // If we got here, the signature is valid
-        Ok(true)
+    ///
+    /// Checks the signature against the payee key in the `n` field, or that a
+    /// key can be recovered from it if there is none. Parsing already does
+    /// this, but an invoice may have been built some other way.
+    pub fn verify_signature(invoice: &Bolt11Invoice) -> Result<bool, LightningError> {
+        Ok(invoice.clone().into_signed_raw().check_signature())
+    }
+    
+    /// Verify that an invoice was signed by the node with `expected_pubkey`
+    ///
+    /// Returns `Ok(false)` if the signature is invalid or the payee is a
+    /// different node.
+    pub fn verify_signature_against_pubkey(invoice: &Bolt11Invoice, expected_pubkey: &[u8; 33]) -> Result<bool, LightningError> {
+        if !Self::verify_signature(invoice)? {
+            return Ok(false);
+        }
+        Ok(payee_pubkey(invoice) == *expected_pubkey)
+    }
+}
+
+/// Compressed public key of the node that signed an invoice
+fn payee_pubkey(invoice: &Bolt11Invoice) -> [u8; 33] {
+    match invoice.payee_pub_key() {
+        Some(payee) => payee.serialize(),
+        None => invoice.recover_payee_pub_key().serialize(),
+    }
+}
+
+/// On-chain address encoded by an invoice fallback
+fn fallback_to_address(fallback: &Fallback, network: Network) -> Option<Address> {
+    match fallback {
+        Fallback::PubKeyHash(hash) => Some(Address::p2pkh(*hash, network)),
+        Fallback::ScriptHash(hash) => Some(Address::p2sh_from_hash(*hash, network)),
+        Fallback::SegWitProgram { version, program } => {
+            let program = WitnessProgram::new(*version, program).ok()?;
+            Some(Address::from_witness_program(program, network))
+        }
+    }
+}
+
+/// Invoice fallback for an on-chain address, checked against the network
+pub(crate) fn address_to_fallback(address: &str, network: Network) -> Result<Fallback, LightningError> {
+    let fallback = match validate_onchain_address(address, network)?.to_address_data() {
+        AddressData::P2pkh { pubkey_hash } => Fallback::PubKeyHash(pubkey_hash),
+        AddressData::P2sh { script_hash } => Fallback::ScriptHash(script_hash),
+        AddressData::Segwit { witness_program } => Fallback::SegWitProgram {
+            version: witness_program.version(),
+            program: witness_program.program().as_bytes().to_vec(),
+        },
+    };
+    Ok(fallback)
+}
+
+/// Builder for invoices with optional features
+///
+/// Saves growing a `create_invoice` variant per combination of options.
+/// Options a provider can't embed (e.g. a fallback address on a custodial
+/// wallet) are ignored by that provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceBuilder {
+    pub amount_msats: u64,
+    pub description: String,
+    pub expiry_seconds: u64,
+    /// On-chain address payers can fall back to
+    pub fallback_address: Option<String>,
+    /// Commit to a description held out-of-band instead of embedding `description`
+    pub description_hash: Option<[u8; 32]>,
+    /// Final hop CLTV delta (provider default if unset)
+    pub min_final_cltv_expiry: Option<u64>,
+}
+
+impl InvoiceBuilder {
+    /// Invoice for an amount with an empty description and a one hour expiry
+    pub fn new(amount_msats: u64) -> Self {
+        Self {
+            amount_msats,
+            description: String::new(),
+            expiry_seconds: 3600,
+            fallback_address: None,
+            description_hash: None,
+            min_final_cltv_expiry: None,
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn expiry_seconds(mut self, expiry_seconds: u64) -> Self {
+        self.expiry_seconds = expiry_seconds;
+        self
+    }
+
+    pub fn fallback_address(mut self, address: &str) -> Self {
+        self.fallback_address = Some(address.to_string());
+        self
+    }
+
+    pub fn description_hash(mut self, description_hash: [u8; 32]) -> Self {
+        self.description_hash = Some(description_hash);
+        self
+    }
+
+    pub fn min_final_cltv_expiry(mut self, min_final_cltv_expiry: u64) -> Self {
+        self.min_final_cltv_expiry = Some(min_final_cltv_expiry);
+        self
+    }
+
+    /// Parameters for `LightningProvider::create_invoice_ex`
+    pub fn params(&self) -> InvoiceParams {
+        InvoiceParams {
+            description: match self.description_hash {
+                Some(hash) => DescriptionKind::Hash(hash),
+                None => DescriptionKind::Direct(self.description.clone()),
+            },
+            fallback_address: self.fallback_address.clone(),
+            min_final_cltv_expiry: self.min_final_cltv_expiry,
+            ..InvoiceParams::new(self.amount_msats, "", self.expiry_seconds)
+        }
     }
+
+    /// Create the invoice with a provider
+    pub async fn build(&self, provider: &dyn LightningProvider) -> Result<String, LightningError> {
+        provider.create_invoice_ex(&self.params()).await
+    }
+}
+
+/// Size-bounded cache of parsed invoices
+///
+/// Evicts the least recently used invoice once `max_entries` is reached.
+/// Clones share the same cache.
+#[derive(Clone)]
+pub struct InvoiceCache {
+    max_entries: usize,
+    state: Arc<RwLock<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Invoice string -> (parsed invoice, last use tick)
+    entries: HashMap<String, (Arc<InvoiceData>, u64)>,
+    /// Last use tick -> invoice string, oldest first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl CacheState {
+    /// Mark an entry as most recently used
+    fn touch(&mut self, invoice_str: &str) -> Option<Arc<InvoiceData>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (data, last_used) = self.entries.get_mut(invoice_str)?;
+        self.recency.remove(last_used);
+        *last_used = tick;
+        let data = Arc::clone(data);
+        self.recency.insert(tick, invoice_str.to_string());
+        Some(data)
+    }
+}
+
+impl InvoiceCache {
+    /// Create a cache holding at most `max_entries` invoices
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            state: Arc::new(RwLock::new(CacheState::default())),
+        }
+    }
+
+    /// Return the parsed invoice, parsing and caching it on a miss
+    ///
+    /// Parse errors are not cached.
+    pub fn get_or_parse(&self, invoice_str: &str) -> Result<Arc<InvoiceData>, LightningError> {
+        if let Some(data) = self.state.write().unwrap().touch(invoice_str) {
+            return Ok(data);
+        }
+
+        // Parse outside the lock; a concurrent miss on the same invoice just parses twice
+        let data = Arc::new(InvoiceParser::parse(invoice_str)?);
+        if self.max_entries == 0 {
+            return Ok(data);
+        }
+
+        let mut state = self.state.write().unwrap();
+        if let Some(existing) = state.touch(invoice_str) {
+            return Ok(existing);
+        }
+        while state.entries.len() >= self.max_entries {
+            let oldest = match state.recency.pop_first() {
+                Some((_, oldest)) => oldest,
+                None => break,
+            };
+            state.entries.remove(&oldest);
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(invoice_str.to_string(), (Arc::clone(&data), tick));
+        state.recency.insert(tick, invoice_str.to_string());
+        Ok(data)
+    }
+
+    /// Number of cached invoices
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether an invoice is cached (does not affect recency)
+    pub fn contains(&self, invoice_str: &str) -> bool {
+        self.state.read().unwrap().entries.contains_key(invoice_str)
+    }
+
+    /// Maximum number of cached invoices
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+}
+
+/// Private route to the payee, from an invoice's routing hint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteHint {
+    /// Hops in order, ending at the payee
+    pub hops: Vec<RouteHintHop>,
+}
+
+/// One hop of a routing hint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteHintHop {
+    /// Node at the start of the channel (compressed public key)
+    pub src_node_id: [u8; 33],
+    pub short_channel_id: u64,
+    pub fee_base_msats: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
 }
 
 /// Parsed invoice data
+#[derive(Clone)]
 pub struct InvoiceData {
     pub amount_msats: u64,
     pub payment_hash: Vec<u8>,
+    /// Secret the sender must present to the payee, proving it has the invoice
+    pub payment_secret: [u8; 32],
     pub expiry: u64,
-    pub invoice: Invoice,
+    /// Invoice creation time (unix seconds)
+    pub timestamp: u64,
+    /// CLTV delta the payee requires for the final hop
+    pub min_final_cltv_expiry: u64,
+    /// SHA256 of the description, if the invoice commits to it by hash
+    pub description_hash: Option<[u8; 32]>,
+    /// On-chain address to pay instead if the Lightning payment fails
+    pub fallback_address: Option<String>,
+    /// Public key of the payee node (compressed)
+    pub payee_pubkey: Option<[u8; 33]>,
+    /// Routing hints for reaching a payee behind private channels
+    pub routing_hints: Vec<RouteHint>,
+    pub invoice: Bolt11Invoice,
+    /// The BOLT11 string the invoice was parsed from
+    pub raw: String,
+}
+
+impl std::fmt::Debug for InvoiceData {
+    // The decoded invoice is long and mostly repeats the fields above
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InvoiceData")
+            .field("payment_hash", &self.payment_hash_hex())
+            .field("amount_msats", &self.amount_msats)
+            .field("expires_at", &self.expires_at())
+            .finish_non_exhaustive()
+    }
 }
 
 impl InvoiceData {
+    /// Unix time at which the invoice expires
+    pub fn expires_at(&self) -> u64 {
+        self.timestamp.saturating_add(self.expiry)
+    }
+    
     /// Check if invoice is expired
+    ///
+    /// `expiry` is relative to the invoice's creation time, so this compares
+    /// against `expires_at`.
     pub fn is_expired(&self) -> bool {
         use std::time::{SystemTime, UNIX_EPOCH};
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        now > self.expiry
+        now > self.expires_at()
+    }
+    
+    /// Whether the invoice carries routing hints (the payee has private channels)
+    pub fn has_private_hints(&self) -> bool {
+        !self.routing_hints.is_empty()
     }
     
     /// Get payment hash as hex string
@@ -91,4 +404,14 @@ impl InvoiceData {
         hash[..len].copy_from_slice(&self.payment_hash[..len]);
         hash
     }
+    
+    /// One-line summary for logs
+    pub fn to_summary(&self) -> String {
+        format!(
+            "Invoice {{ hash: {}, amount: {} msats, expiry_at: {} }}",
+            self.payment_hash_hex(),
+            self.amount_msats,
+            self.expires_at()
+        )
+    }
 }
@@ -1,14 +1,25 @@
 //! Lightning Network payment processor module for bllvm-node
 
 pub mod client;
+pub mod config_reload;
+pub mod dedup;
 pub mod error;
 pub mod invoice;
+pub mod metrics;
 pub mod nodeapi_ipc;
+pub mod payment_history;
+pub mod payment_state;
 pub mod processor;
 pub mod provider;
+pub mod rate_limiter;
+pub mod receipt;
+pub mod shutdown;
+pub mod telemetry;
+pub mod webhook;
 
 pub use provider::{
-    ProviderType, LightningProvider, PaymentVerificationResult, create_provider,
-    lnbits, ldk, stub,
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, FeeEstimate, PaymentUpdate, PaymentStream, LightningProvider, PaymentVerificationResult, InvoiceParams, DescriptionKind,
+    create_provider,
+    lnbits, ldk, stub, cln, eclair, phoenixd, lndhub, blink, opennode, strike, watch_only, circuit_breaker, failover, record, routing,
 };
 
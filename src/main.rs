@@ -10,18 +10,30 @@ use blvm_node::module::ipc::protocol::{EventPayload, LogLevel, ModuleMessage};
 use clap::Parser;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{error, info, warn};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
 mod provider;
 mod processor;
 mod invoice;
+mod metrics;
 mod error;
 mod client;
+mod config_reload;
+mod dedup;
 mod nodeapi_ipc;
+mod payment_history;
+mod payment_state;
+mod receipt;
+mod rate_limiter;
+mod shutdown;
+mod telemetry;
 
+use dedup::{SeenSet, DEFAULT_DEDUP_CLEANUP_INTERVAL_SECONDS, DEFAULT_DEDUP_TTL_SECONDS};
 use processor::LightningProcessor;
+use shutdown::ShutdownController;
 use error::LightningError;
-use client::ModuleClient;
+use client::{ModuleClient, ReconnectPolicy};
 use nodeapi_ipc::NodeApiIpc;
 
 /// Command-line arguments for the module
@@ -43,10 +55,17 @@ struct Args {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    // Initialize tracing, exporting spans when an OTLP collector is configured
+    let otlp_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok();
+    let otlp_result = otlp_endpoint.as_deref().map(telemetry::init_tracer);
+    if !matches!(otlp_result, Some(Ok(()))) {
+        tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .init();
+    }
+    if let Some(Err(e)) = otlp_result {
+        warn!("OpenTelemetry export disabled: {}", e);
+    }
 
     let args = Args::parse();
 
@@ -106,34 +125,113 @@ async fn main() -> Result<()> {
     
     // Wrap processor in Arc for parallel processing
     let processor = Arc::new(processor);
+    
+    // Periodically log provider health
+    let _health_monitor = processor.spawn_health_monitor();
+    
+    // Warn when the wallet balance drops below lightning.min_balance_msats
+    let _balance_monitor = processor.spawn_balance_monitor();
+    
+    // Sweep the balance on-chain if lightning.sweep.address is set
+    let _sweeper = processor.spawn_sweeper();
+    
+    // Serve Prometheus metrics if lightning.metrics_port is set
+    let _metrics_server = processor.spawn_metrics_server();
+    
+    // Receive LNBits payment webhooks if lightning.webhook_listen is set
+    let _webhook_server = processor.spawn_webhook_server();
+    
+    // Pick up config changes from the node if lightning.config_refresh_interval_seconds is set
+    let _config_refresh = processor.spawn_config_refresh(node_api.clone());
+    
+    // Settle in-flight payments as the provider confirms them
+    let confirmation_poller = match processor.poller_interval_seconds() {
+        0 => None,
+        interval => Some(Arc::clone(&processor).start_confirmation_poller(interval).await),
+    };
+
+    // Skip events redelivered after an IPC reconnect if lightning.dedup_events is set
+    let seen_events = if ctx.get_config_or("lightning.dedup_events", "").parse::<bool>().unwrap_or(false) {
+        let ttl = ctx.get_config_or("lightning.dedup.ttl_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_DEDUP_TTL_SECONDS);
+        let cleanup_interval = ctx.get_config_or("lightning.dedup.cleanup_interval_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_DEDUP_CLEANUP_INTERVAL_SECONDS);
+        let seen = SeenSet::new(Duration::from_secs(ttl));
+        let cleanup = seen.spawn_cleanup(cleanup_interval);
+        Some((seen, cleanup))
+    } else {
+        None
+    };
+
+    // Stop taking events on SIGTERM/SIGINT
+    let shutdown = ShutdownController::new();
+    let _signal_listener = shutdown.listen_for_signals();
+    let shutdown_token = shutdown.token();
+
+    // Reconnect if the node restarts, giving up after lightning.reconnect_max_attempts
+    let reconnect_policy = ReconnectPolicy::from_config(&ctx);
 
     info!("Lightning module initialized and running");
 
     // Event processing loop with parallel batch processing
-    let mut event_receiver = client.event_receiver();
-    loop {
+    'events: loop {
+        if shutdown_token.is_cancelled() {
+            break;
+        }
+        
         // Collect batch of events (up to 10) for parallel processing
         let mut event_batch = Vec::with_capacity(10);
+        let mut disconnected = false;
         for _ in 0..10 {
-            match event_receiver.try_recv() {
+            match client.event_receiver().try_recv() {
                 Ok(event) => event_batch.push(event),
                 Err(tokio::sync::mpsc::error::TryRecvError::Empty) => break,
                 Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => {
-                    warn!("Event channel disconnected");
-                    return Ok(());
+                    disconnected = true;
+                    break;
                 }
             }
         }
         
-        // If no events in batch, wait for next event
-        if event_batch.is_empty() {
-            if let Some(event) = event_receiver.recv().await {
-                event_batch.push(event);
-            } else {
-                break; // Channel closed
+        // If no events in batch, wait for next event (or shutdown)
+        if event_batch.is_empty() && !disconnected {
+            tokio::select! {
+                event = client.event_receiver().recv() => match event {
+                    Some(event) => event_batch.push(event),
+                    None => disconnected = true, // Channel closed
+                },
+                _ = shutdown_token.cancelled() => break,
             }
         }
         
+        // The node went away: reconnect, or shut down if it doesn't come back
+        if disconnected && event_batch.is_empty() {
+            warn!("Event channel disconnected");
+            tokio::select! {
+                result = client.reconnect_with_backoff(&reconnect_policy) => {
+                    if let Err(e) = result {
+                        error!("Giving up reconnecting to node: {}", e);
+                        break 'events;
+                    }
+                }
+                _ = shutdown_token.cancelled() => break,
+            }
+            continue;
+        }
+        
+        // Drop events already dispatched
+        if let Some((seen, _)) = &seen_events {
+            event_batch.retain(|event| match dedup::event_id(event) {
+                Some(id) if !seen.insert(&id) => {
+                    debug!("Skipping duplicate event {}", id);
+                    false
+                }
+                _ => true,
+            });
+        }
+        
         // Process events in parallel
         let futures: Vec<_> = event_batch
             .iter()
@@ -141,10 +239,15 @@ async fn main() -> Result<()> {
                 let event = event.clone();
                 let processor = Arc::clone(&processor);
                 let node_api = Arc::clone(&node_api);
+                let seen = seen_events.as_ref().map(|(seen, _)| seen.clone());
                 async move {
                     // Handle events with processor
                     if let Err(e) = processor.handle_event(&event, node_api.as_ref()).await {
                         warn!("Error handling event in processor: {}", e);
+                        // Not handled, so a redelivery should be processed
+                        if let (Some(seen), Some(id)) = (seen, dedup::event_id(&event)) {
+                            seen.remove(&id);
+                        }
                     }
 
                     match event {
@@ -176,7 +279,37 @@ async fn main() -> Result<()> {
         futures::future::join_all(futures).await;
     }
 
-    warn!("Event receiver closed, module shutting down");
+    if shutdown.is_shutting_down() {
+        info!("Shutdown requested, no longer accepting events");
+    } else {
+        warn!("Event receiver closed, module shutting down");
+        shutdown.trigger();
+    }
+    if let Some(poller) = confirmation_poller {
+        poller.abort();
+    }
+    if let Some((_, cleanup)) = seen_events {
+        cleanup.abort();
+    }
+    
+    // Give payments already handed to the provider a chance to settle
+    if let Err(e) = processor.drain_inflight(processor.drain_timeout()).await {
+        warn!("Shutting down with unsettled payments: {}", e);
+    }
+    // Stop the LDK provider's background tasks and write its payment state
+    processor.shutdown_provider().await;
+    match processor.health_check().await {
+        Ok(status) => info!("Final provider health: reachable={}, latency={}ms", status.reachable, status.latency_ms),
+        Err(e) => warn!("Final health check failed: {}", e),
+    }
+    let metrics = processor.metrics();
+    info!(
+        "Final stats: payments_verified={}, payments_failed={}, invoices_created={}",
+        metrics.payments_verified_total.load(std::sync::atomic::Ordering::Relaxed),
+        metrics.payments_failed_total.load(std::sync::atomic::Ordering::Relaxed),
+        metrics.invoices_created_total.load(std::sync::atomic::Ordering::Relaxed),
+    );
+    telemetry::shutdown_tracer();
     Ok(())
 }
 
@@ -0,0 +1,88 @@
+//! Prometheus metrics for the Lightning module
+//!
+//! Counters are plain atomics shared between clones of `MetricsCollector`;
+//! `render_prometheus` produces the text exposition format served on
+//! `/metrics` when `lightning.metrics_port` is set.
+
+use crate::error::LightningError;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::info;
+
+/// Payment and invoice counters
+#[derive(Debug, Clone, Default)]
+pub struct MetricsCollector {
+    pub payments_verified_total: Arc<AtomicU64>,
+    pub payments_failed_total: Arc<AtomicU64>,
+    pub invoices_created_total: Arc<AtomicU64>,
+    pub verification_latency_ms_sum: Arc<AtomicU64>,
+    pub verification_latency_ms_count: Arc<AtomicU64>,
+}
+
+impl MetricsCollector {
+    /// Create a collector with all counters at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a payment verification and how long the provider took
+    pub fn record_verification(&self, success: bool, latency_ms: u64) {
+        if success {
+            self.payments_verified_total.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.payments_failed_total.fetch_add(1, Ordering::Relaxed);
+        }
+        self.verification_latency_ms_sum.fetch_add(latency_ms, Ordering::Relaxed);
+        self.verification_latency_ms_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a newly created invoice
+    pub fn record_invoice_created(&self) {
+        self.invoices_created_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render all counters in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let metrics = [
+            ("payments_verified_total", "counter", "Payments verified as settled", &self.payments_verified_total),
+            ("payments_failed_total", "counter", "Payment verifications that did not settle or errored", &self.payments_failed_total),
+            ("invoices_created_total", "counter", "Invoices created", &self.invoices_created_total),
+            ("verification_latency_ms_sum", "counter", "Total provider verification latency in milliseconds", &self.verification_latency_ms_sum),
+            ("verification_latency_ms_count", "counter", "Number of timed provider verifications", &self.verification_latency_ms_count),
+        ];
+
+        let mut out = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(out, "# HELP lightning_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE lightning_{} {}", name, kind);
+            let _ = writeln!(out, "lightning_{} {}", name, value.load(Ordering::Relaxed));
+        }
+        out
+    }
+
+    /// Serve `/metrics` on `addr` until the task is dropped
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), LightningError> {
+        let app = axum::Router::new().route(
+            "/metrics",
+            axum::routing::get(move || {
+                let metrics = self.clone();
+                async move {
+                    (
+                        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                        metrics.render_prometheus(),
+                    )
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| LightningError::ConfigError(format!("Failed to bind metrics endpoint {}: {}", addr, e)))?;
+        info!("Serving Prometheus metrics on http://{}/metrics", addr);
+        axum::serve(listener, app)
+            .await
+            .map_err(|e| LightningError::ProcessorError(format!("Metrics endpoint failed: {}", e)))
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! Provides NodeAPI trait implementation over IPC for the Lightning module.
 
+use crate::config_reload::ConfigSource;
+use crate::error::LightningError;
 use async_trait::async_trait;
 use blvm_node::module::ipc::client::ModuleIpcClient;
 use blvm_node::module::EventType;
@@ -66,6 +68,30 @@ impl NodeApiIpc {
             None => Err(ModuleError::OperationError("Empty response payload".to_string())),
         }
     }
+
+    /// Fetch the current value of a module config key from the node
+    ///
+    /// Unlike `ModuleContext.config`, which is fixed at startup, this asks the
+    /// node each time, so edits to the node's module config are picked up.
+    pub async fn get_module_config(&self, key: &str) -> Result<Option<String>, LightningError> {
+        Ok(self
+            .request(
+                RequestPayload::GetModuleConfig { key: key.to_string() },
+                blvm_node::module::ipc::protocol::MessageType::GetModuleConfig,
+                |payload| match payload {
+                    ResponsePayload::ModuleConfig(value) => Ok(value),
+                    _ => Err(ModuleError::OperationError("Unexpected response type".to_string())),
+                },
+            )
+            .await?)
+    }
+}
+
+#[async_trait]
+impl ConfigSource for NodeApiIpc {
+    async fn get_module_config(&self, key: &str) -> Result<Option<String>, LightningError> {
+        NodeApiIpc::get_module_config(self, key).await
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,392 @@
+//! Payment history with filtering and pagination
+//!
+//! Records are stored in one tree per day of `created_at`
+//! (`payment_history_<day>`, see `history_tree`), under a key that sorts by
+//! time: the big-endian `created_at` followed by the payment id. The
+//! `payment_history_days` tree lists the days that have records, so a query
+//! only reads the trees of the days its date range covers, one day at a
+//! time, and applies the exact bounds to the key before decoding a record.
+//! The `payment_history_index` tree maps payment ids to their history key,
+//! so recording a payment again (e.g. once it settles) replaces the earlier
+//! record.
+//!
+//! `export_csv` and `PaymentHistory::export_to_file` write records as
+//! RFC 4180 CSV for accounting.
+
+use crate::error::LightningError;
+use crate::payment_state::PaymentState;
+use crate::provider::ProviderType;
+use crate::receipt::PaymentReceipt;
+use blvm_node::module::traits::NodeAPI;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Name prefix of the per-day trees holding `PaymentRecord`s keyed by `created_at` and payment id
+pub const PAYMENT_HISTORY_TREE: &str = "payment_history";
+
+/// Storage tree listing the days (big-endian day number) that have history records
+pub const PAYMENT_HISTORY_DAYS_TREE: &str = "payment_history_days";
+
+/// Storage tree mapping payment ids to their `PAYMENT_HISTORY_TREE` key
+pub const PAYMENT_HISTORY_INDEX_TREE: &str = "payment_history_index";
+
+/// Default number of records per page
+pub const DEFAULT_PER_PAGE: u32 = 50;
+
+/// History trees each hold one UTC day of records
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Days of CSV rows queued for the export writer before decoding waits on it
+const EXPORT_CHUNKS_IN_FLIGHT: usize = 4;
+
+/// Header row of CSV exports
+pub const CSV_HEADER: &str =
+    "payment_id,payment_hash,direction,amount_msats,fee_msats,state,provider,created_at_iso,settled_at_iso";
+
+/// Which way a payment moved funds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentDirection {
+    /// Received by this node
+    Inbound,
+    /// Sent by this node
+    Outbound,
+}
+
+/// Payment as kept in the history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentRecord {
+    pub payment_id: String,
+    pub payment_hash: [u8; 32],
+    pub preimage: Option<[u8; 32]>,
+    pub amount_msats: u64,
+    pub fee_msats: u64,
+    /// Settlement time (unix seconds), once settled
+    pub settled_at: Option<u64>,
+    pub provider: ProviderType,
+    pub metadata: serde_json::Value,
+    pub direction: PaymentDirection,
+    pub state: PaymentState,
+    /// Time the payment was created (unix seconds); orders the history
+    pub created_at: u64,
+}
+
+impl PaymentRecord {
+    /// Record for a settled payment's receipt, dated at settlement
+    pub fn from_receipt(receipt: PaymentReceipt, direction: PaymentDirection) -> Self {
+        Self {
+            payment_id: receipt.payment_id,
+            payment_hash: receipt.payment_hash,
+            preimage: receipt.preimage,
+            amount_msats: receipt.amount_msats,
+            fee_msats: receipt.fee_msats,
+            settled_at: Some(receipt.settled_at),
+            provider: receipt.provider,
+            metadata: receipt.metadata,
+            direction,
+            state: PaymentState::Settled { settled_at: receipt.settled_at },
+            created_at: receipt.settled_at,
+        }
+    }
+}
+
+/// Criteria a record must meet to be returned; unset fields match anything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaymentFilter {
+    /// Earliest `created_at` (inclusive)
+    pub date_from: Option<u64>,
+    /// Latest `created_at` (inclusive)
+    pub date_to: Option<u64>,
+    pub min_amount_msats: Option<u64>,
+    pub provider: Option<ProviderType>,
+    /// State to match; only the variant is compared, not its fields
+    pub state: Option<PaymentState>,
+}
+
+impl PaymentFilter {
+    fn matches_date(&self, created_at: u64) -> bool {
+        self.date_from.map_or(true, |from| created_at >= from) && self.date_to.map_or(true, |to| created_at <= to)
+    }
+
+    /// Whether any time on `day` can match the date bounds
+    fn matches_day(&self, day: u64) -> bool {
+        self.date_from.map_or(true, |from| day >= from / SECONDS_PER_DAY)
+            && self.date_to.map_or(true, |to| day <= to / SECONDS_PER_DAY)
+    }
+
+    /// Whether `record` meets every criterion
+    pub fn matches(&self, record: &PaymentRecord) -> bool {
+        self.matches_date(record.created_at)
+            && self.min_amount_msats.map_or(true, |min| record.amount_msats >= min)
+            && self.provider.as_ref().map_or(true, |provider| &record.provider == provider)
+            && self
+                .state
+                .as_ref()
+                .map_or(true, |state| std::mem::discriminant(state) == std::mem::discriminant(&record.state))
+    }
+}
+
+/// Page to return, counting pages from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self { page: 0, per_page: DEFAULT_PER_PAGE }
+    }
+}
+
+/// One page of matching records, newest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentPage {
+    pub records: Vec<PaymentRecord>,
+    /// Number of records matching the filter across all pages
+    pub total: u64,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+/// Payment history stored through the node's storage API
+pub struct PaymentHistory {
+    node_api: Arc<dyn NodeAPI>,
+}
+
+impl PaymentHistory {
+    pub fn new(node_api: Arc<dyn NodeAPI>) -> Self {
+        Self { node_api }
+    }
+
+    /// Store `payment`, replacing any earlier record with the same payment id
+    pub async fn record(&self, payment: &PaymentRecord) -> Result<(), LightningError> {
+        let index = self.open_tree(PAYMENT_HISTORY_INDEX_TREE).await?;
+        let key = history_key(payment.created_at, &payment.payment_id);
+
+        let previous = self.node_api.storage_get(index.clone(), payment.payment_id.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment history index: {}", e)))?;
+        if let Some(previous) = previous.filter(|previous| previous != &key) {
+            if let Some(created_at) = key_created_at(&previous) {
+                let history = self.open_tree(&history_tree(created_at)).await?;
+                self.node_api.storage_remove(history, previous).await
+                    .map_err(|e| LightningError::ProcessorError(format!("Failed to remove payment history record: {}", e)))?;
+            }
+        }
+
+        let days = self.open_tree(PAYMENT_HISTORY_DAYS_TREE).await?;
+        let day = payment.created_at / SECONDS_PER_DAY;
+        self.node_api.storage_insert(days, day.to_be_bytes().to_vec(), Vec::new()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store payment history day: {}", e)))?;
+
+        let history = self.open_tree(&history_tree(payment.created_at)).await?;
+        let value = serde_json::to_vec(payment)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize payment record: {}", e)))?;
+        self.node_api.storage_insert(history, key.clone(), value).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store payment record: {}", e)))?;
+        self.node_api.storage_insert(index, payment.payment_id.as_bytes().to_vec(), key).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to update payment history index: {}", e)))
+    }
+
+    /// Records matching `filter`, newest first, paginated
+    ///
+    /// Only the days within the filter's date range are read.
+    pub async fn query(&self, filter: &PaymentFilter, page: Page) -> Result<PaymentPage, LightningError> {
+        let skip = page.page as u64 * page.per_page as u64;
+        let mut records = Vec::new();
+        let mut total = 0u64;
+        for day in self.days(filter).await?.into_iter().rev() {
+            let mut entries = self.day_entries(day).await?;
+            entries.reverse();
+            for record in matching(entries, filter) {
+                if total >= skip && records.len() < page.per_page as usize {
+                    records.push(record);
+                }
+                total += 1;
+            }
+        }
+
+        Ok(PaymentPage { records, total, page: page.page, per_page: page.per_page })
+    }
+
+    /// Write the records matching `filter` to `path` as CSV, oldest first
+    ///
+    /// Rows are written a day at a time as records are decoded rather than
+    /// collected first; the file is written on a blocking thread. Returns the
+    /// number of records written.
+    pub async fn export_to_file(&self, path: &Path, filter: &PaymentFilter) -> Result<usize, LightningError> {
+        let days = self.days(filter).await?;
+        let (chunks, mut received) = tokio::sync::mpsc::channel::<String>(EXPORT_CHUNKS_IN_FLIGHT);
+        let file_path = path.to_path_buf();
+        let writer = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&file_path)?);
+            write!(file, "{}\r\n", CSV_HEADER)?;
+            while let Some(chunk) = received.blocking_recv() {
+                file.write_all(chunk.as_bytes())?;
+            }
+            file.flush()
+        });
+
+        let mut written = 0;
+        for day in days {
+            let mut chunk = String::new();
+            for record in matching(self.day_entries(day).await?, filter) {
+                chunk.push_str(&csv_row(&record));
+                chunk.push_str("\r\n");
+                written += 1;
+            }
+            // The writer only hangs up once it has failed; its error is reported below
+            if !chunk.is_empty() && chunks.send(chunk).await.is_err() {
+                break;
+            }
+        }
+        drop(chunks);
+
+        writer.await
+            .map_err(|e| LightningError::ProcessorError(format!("Payment export task failed: {}", e)))?
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to write payment export {}: {}", path.display(), e)))?;
+        Ok(written)
+    }
+
+    /// Days with records that can match the filter's date range, oldest first
+    async fn days(&self, filter: &PaymentFilter) -> Result<Vec<u64>, LightningError> {
+        let days_tree = self.open_tree(PAYMENT_HISTORY_DAYS_TREE).await?;
+        let entries = self.node_api.storage_iter(days_tree).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment history days: {}", e)))?;
+        let mut days: Vec<u64> = entries
+            .into_iter()
+            .filter_map(|(key, _)| <[u8; 8]>::try_from(key).ok().map(u64::from_be_bytes))
+            .filter(|day| filter.matches_day(*day))
+            .collect();
+        days.sort_unstable();
+        Ok(days)
+    }
+
+    /// The history entries created on `day`, oldest first
+    async fn day_entries(&self, day: u64) -> Result<Vec<(Vec<u8>, Vec<u8>)>, LightningError> {
+        let history = self.open_tree(&history_tree(day * SECONDS_PER_DAY)).await?;
+        let mut entries = self.node_api.storage_iter(history).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment history: {}", e)))?;
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    async fn open_tree(&self, name: &str) -> Result<String, LightningError> {
+        self.node_api.storage_open_tree(name.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))
+    }
+}
+
+/// Decode the entries matching `filter`, skipping out-of-range dates by key
+fn matching(
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    filter: &PaymentFilter,
+) -> impl Iterator<Item = PaymentRecord> + '_ {
+    entries.into_iter().filter_map(move |(key, value)| {
+        if !key_created_at(&key).map_or(false, |created_at| filter.matches_date(created_at)) {
+            return None;
+        }
+        match serde_json::from_slice::<PaymentRecord>(&value) {
+            Ok(record) if filter.matches(&record) => Some(record),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Skipping corrupt payment history record {}: {}", String::from_utf8_lossy(&key[8..]), e);
+                None
+            }
+        }
+    })
+}
+
+/// Format records as RFC 4180 CSV, header row included
+///
+/// Lines end in CRLF; fields containing a comma, quote or line break are
+/// quoted, with quotes doubled. Times are ISO 8601 UTC, and `settled_at_iso`
+/// is empty for unsettled payments.
+pub fn export_csv(records: &[PaymentRecord]) -> String {
+    let mut csv = format!("{}\r\n", CSV_HEADER);
+    for record in records {
+        csv.push_str(&csv_row(record));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn csv_row(record: &PaymentRecord) -> String {
+    let direction = match record.direction {
+        PaymentDirection::Inbound => "inbound",
+        PaymentDirection::Outbound => "outbound",
+    };
+    let state = match record.state {
+        PaymentState::Pending => "pending",
+        PaymentState::InFlight => "in_flight",
+        PaymentState::Settled { .. } => "settled",
+        PaymentState::Failed { .. } => "failed",
+    };
+    [
+        record.payment_id.clone(),
+        hex::encode(record.payment_hash),
+        direction.to_string(),
+        record.amount_msats.to_string(),
+        record.fee_msats.to_string(),
+        state.to_string(),
+        record.provider.to_string(),
+        iso8601(record.created_at),
+        record.settled_at.map(iso8601).unwrap_or_default(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Unix seconds as ISO 8601 UTC (`2024-01-31T12:00:00Z`)
+fn iso8601(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let secs_of_day = unix_secs % 86_400;
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Tree holding the history records created on the same day as `created_at`
+pub fn history_tree(created_at: u64) -> String {
+    format!("{}_{}", PAYMENT_HISTORY_TREE, created_at / SECONDS_PER_DAY)
+}
+
+/// History tree key: big-endian `created_at`, then the payment id
+fn history_key(created_at: u64, payment_id: &str) -> Vec<u8> {
+    let mut key = created_at.to_be_bytes().to_vec();
+    key.extend_from_slice(payment_id.as_bytes());
+    key
+}
+
+fn key_created_at(key: &[u8]) -> Option<u64> {
+    key.get(..8).map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+}
@@ -0,0 +1,96 @@
+//! Payment state tracking
+//!
+//! Payments handled by the processor are stored in the `payment_states`
+//! storage tree, keyed by payment id, as JSON-encoded `StoredPayment` records.
+
+use crate::provider::ProviderType;
+use serde::{Deserialize, Serialize};
+
+/// Storage tree holding `StoredPayment` records keyed by payment id
+pub const PAYMENT_STATES_TREE: &str = "payment_states";
+
+/// Storage tree holding `PendingInvoice` records keyed by payment id
+pub const PENDING_INVOICES_TREE: &str = "pending_invoices";
+
+/// Lifecycle state of a payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PaymentState {
+    /// Invoice issued, no verification attempted yet
+    Pending,
+    /// Verification attempted but not yet confirmed by the provider
+    InFlight,
+    /// Payment confirmed by the provider
+    Settled { settled_at: u64 },
+    /// Payment abandoned or rejected
+    Failed { reason: String, failed_at: u64 },
+}
+
+impl PaymentState {
+    /// Whether the payment is still awaiting settlement
+    pub fn is_pending(&self) -> bool {
+        matches!(self, PaymentState::Pending | PaymentState::InFlight)
+    }
+}
+
+/// Payment record persisted by the processor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredPayment {
+    pub payment_id: String,
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    pub invoice: String,
+    pub amount_msats: Option<u64>,
+    pub fee_msats: u64,
+    /// Payment preimage (hex), if the provider reported it
+    pub preimage: Option<String>,
+    pub provider: ProviderType,
+    pub created_at: u64,
+    pub last_attempt_at: Option<u64>,
+    pub attempts: u32,
+    pub state: PaymentState,
+    /// Provider metadata from the last verification
+    pub metadata: serde_json::Value,
+}
+
+/// Payment still being processed, as listed for operators
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingPayment {
+    pub payment_id: String,
+    pub invoice: String,
+    pub amount_msats: Option<u64>,
+    pub created_at: u64,
+    pub last_attempt_at: Option<u64>,
+    pub attempts: u32,
+    pub state: PaymentState,
+}
+
+impl From<StoredPayment> for PendingPayment {
+    fn from(payment: StoredPayment) -> Self {
+        Self {
+            payment_id: payment.payment_id,
+            invoice: payment.invoice,
+            amount_msats: payment.amount_msats,
+            created_at: payment.created_at,
+            last_attempt_at: payment.last_attempt_at,
+            attempts: payment.attempts,
+            state: payment.state,
+        }
+    }
+}
+
+/// Invoice created through `LightningProcessor::create_invoice`, awaiting payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingInvoice {
+    pub payment_id: String,
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    pub bolt11: String,
+    pub amount_msats: u64,
+    pub state: PaymentState,
+    pub created_at: u64,
+    /// When the invoice expires
+    pub expires_at: u64,
+    /// When the record may be cleaned up (its TTL), a grace period after expiry
+    pub delete_after: u64,
+}
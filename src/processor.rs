@@ -1,22 +1,231 @@
 //! Lightning payment processor
 
-use crate::provider::{ProviderType, LightningProvider, create_provider};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, LightningProvider, PaymentVerificationResult, InvoiceParams, create_provider, parse_network,
+    validate_onchain_address, PPM,
+};
+use crate::provider::failover::FailoverProvider;
+use crate::provider::routing::RoutingProvider;
+use crate::provider::ldk::LDKProvider;
+use crate::provider::lnbits::{LNBitsPayment, LNBitsProvider};
+use crate::config_reload::{ConfigReload, ConfigSource, IN_PLACE_CONFIG_KEYS, RELOADABLE_CONFIG_KEYS};
 use crate::error::LightningError;
-use crate::invoice::{InvoiceData, InvoiceParser};
+use crate::invoice::{InvoiceCache, InvoiceData, InvoiceParser};
+use crate::metrics::MetricsCollector;
+use crate::rate_limiter::RateLimiter;
+use crate::payment_history::{Page, PaymentDirection, PaymentFilter, PaymentHistory};
+use crate::payment_state::{PaymentState, PendingInvoice, PendingPayment, StoredPayment, PAYMENT_STATES_TREE, PENDING_INVOICES_TREE};
+use crate::receipt::{PaymentReceipt, PAYMENT_RECEIPTS_TREE};
+use crate::webhook::{WebhookConfig, WebhookDelivery, WebhookEvent, WebhookOutcome};
 use blvm_node::module::ipc::protocol::ModuleMessage;
 use blvm_node::module::EventType;
 use blvm_node::module::ipc::protocol::EventPayload;
-use blvm_node::module::traits::NodeAPI;
+use bitcoin::{Network, Txid};
+use blvm_node::module::traits::{ModuleContext, NodeAPI};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::str::FromStr;
-use std::sync::Arc;
-use tracing::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{debug, error, info, warn};
+
+/// Storage tree recording payment hashes that have already been processed
+const PROCESSED_PAYMENTS_TREE: &str = "processed_payments";
+
+/// Storage tree holding invoices created by this module, keyed by payment hash hex
+const ISSUED_INVOICES_TREE: &str = "issued_invoices";
+
+/// Storage tree mapping invoice labels (e.g. order ids) to payment hash hex
+const INVOICE_LABELS_TREE: &str = "invoice_labels";
+
+/// Storage tree mapping invoice labels to the invoice string, so labelled
+/// invoice creation is idempotent
+const LIGHTNING_INVOICES_TREE: &str = "lightning_invoices";
+
+/// Storage tree holding provider metadata from payment verification, keyed by payment id
+const PAYMENT_METADATA_TREE: &str = "payment_metadata";
+
+/// Storage tree holding the LNBits wallet's payment history, keyed by payment hash hex
+const LNBITS_PAYMENTS_TREE: &str = "lnbits_payments";
+
+/// Storage tree holding the latest automatic sweep under `SWEEP_KEY`
+pub const SWEEPS_TREE: &str = "sweeps";
+
+/// Key of the latest sweep record in `SWEEPS_TREE`
+pub const SWEEP_KEY: &[u8] = b"latest";
+
+/// Default interval between sweep checks, and minimum time between sweeps
+const DEFAULT_SWEEP_INTERVAL_SECONDS: u64 = 600;
+
+/// Default balance left behind by a sweep to cover fees and channel reserves
+const DEFAULT_SWEEP_RESERVE_SATS: u64 = 10_000;
+
+/// How long a sweep that never recorded a txid blocks further sweeps
+const SWEEP_STALE_SECONDS: u64 = 24 * 60 * 60;
+
+/// Default duplicate detection window (24 hours)
+const DEFAULT_DEDUP_WINDOW_SECONDS: u64 = 24 * 60 * 60;
+
+/// Default address the metrics endpoint binds to
+const DEFAULT_METRICS_BIND: &str = "127.0.0.1";
+
+/// Default interval between background health checks
+const DEFAULT_HEALTH_CHECK_INTERVAL_SECONDS: u64 = 60;
+
+/// Default interval between balance checks when `lightning.min_balance_msats` is set
+const DEFAULT_BALANCE_CHECK_INTERVAL_SECONDS: u64 = 300;
+
+/// Default rate limit window
+const DEFAULT_RATE_LIMIT_WINDOW_SECONDS: u64 = 60;
+
+/// Default interval between confirmation polls of in-flight payments
+const DEFAULT_POLLER_INTERVAL_SECONDS: u64 = 30;
+
+/// Default maximum number of in-flight payments checked per poll
+const DEFAULT_POLLER_BATCH_SIZE: usize = 100;
+
+/// Default age after which an unconfirmed payment is failed instead of polled again
+const DEFAULT_POLLER_MAX_AGE_SECONDS: u64 = 24 * 60 * 60;
+
+/// An `InFlight` record whose last attempt is older than this was left by an
+/// interrupted verification, and goes back to `Pending` if it isn't confirmed
+const STALE_INFLIGHT_SECONDS: u64 = 5 * 60;
+
+/// Default time allowed for in-flight payments to settle on shutdown
+const DEFAULT_DRAIN_TIMEOUT_SECONDS: u64 = 30;
+
+/// Interval between checks of in-flight payments while draining
+const DRAIN_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Number of leading `payment_id` characters that identify a client for rate limiting
+const RATE_LIMIT_KEY_LEN: usize = 8;
+
+/// How long a pending invoice record is kept after the invoice expires
+pub const PENDING_INVOICE_GRACE_SECONDS: u64 = 60 * 60;
+
+/// Record of a processed payment, stored as JSON keyed by payment hash hex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessedPayment {
+    pub payment_id: String,
+    pub settled_at: u64,
+    /// Settled by `handle_webhook` and not yet confirmed by the payment's
+    /// own `process_payment` call
+    #[serde(default)]
+    pub by_webhook: bool,
+}
+
+/// Record of an automatic sweep, stored as JSON under `SWEEP_KEY`
+///
+/// Written without a txid before the withdrawal starts, so a sweep
+/// interrupted by a crash isn't repeated on restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SweepRecord {
+    pub amount_sats: u64,
+    pub started_at: u64,
+    pub txid: Option<String>,
+}
+
+/// Invoice created by `LightningProcessor::create_invoice`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoiceCreatedResult {
+    pub bolt11: String,
+    pub payment_hash: [u8; 32],
+    pub payment_id: String,
+    /// Unix time the invoice expires
+    pub expires_at: u64,
+}
+
+/// Payment aggregates over a period, from `LightningProcessor::statistics`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaymentStats {
+    pub total_received_msats: u64,
+    pub total_sent_msats: u64,
+    pub total_fees_paid_msats: u64,
+    pub num_received: u64,
+    pub num_sent: u64,
+    pub num_failed: u64,
+    pub num_pending: u64,
+    /// Settled payments over settled and failed ones (0 when there are neither)
+    pub success_rate: f64,
+    /// Mean time from creation to settlement of received payments
+    pub avg_settlement_time_ms: u64,
+}
+
+/// Automatic on-chain sweep configuration
+#[derive(Debug, Clone)]
+pub struct SweepConfig {
+    /// Destination address (validated against the configured network)
+    pub address: String,
+    /// Sweep once the wallet balance exceeds this many sats
+    pub threshold_sats: u64,
+    /// Sats left in the wallet after a sweep
+    pub reserve_sats: u64,
+    /// Interval between sweep checks, and minimum time between sweeps
+    pub interval_seconds: u64,
+}
 
 /// Lightning payment processor
 pub struct LightningProcessor {
-    /// Lightning provider (LNBits, LDK, or Stub)
-    provider: Box<dyn LightningProvider>,
+    /// Lightning provider (LNBits, LDK, or Stub), replaced when a config refresh changes its settings
+    provider: RwLock<Arc<dyn LightningProvider>>,
+    /// Module context the provider was built from, with reloaded config applied
+    ctx: RwLock<ModuleContext>,
     /// Node API for storage and queries
     node_api: Arc<dyn NodeAPI>,
+    /// Window during which a repeated payment hash is rejected as a duplicate
+    dedup_window_seconds: u64,
+    /// Bitcoin network used to validate on-chain addresses
+    network: Network,
+    /// Automatic sweep settings (if configured)
+    sweep: Option<SweepConfig>,
+    /// Serializes invoice creation per label, so replayed requests can't race
+    label_locks: KeyLocks,
+    /// Serializes `create_invoice` per payment id, so an id can't be used twice
+    payment_id_locks: KeyLocks,
+    /// Held by `process_payment` from the duplicate check until the hash is
+    /// recorded as processed, so concurrent replays can't both verify it
+    payment_hash_locks: KeyLocks,
+    /// Held while a sweep runs; concurrent sweeps are skipped
+    sweep_lock: tokio::sync::Mutex<()>,
+    /// Interval between background health checks (0 disables them)
+    health_check_interval_seconds: u64,
+    /// Warn when the wallet balance drops below this (balance monitoring disabled if unset)
+    min_balance_msats: Option<u64>,
+    /// Interval between background balance checks (0 disables them)
+    balance_check_interval_seconds: u64,
+    /// Payment and invoice counters
+    metrics: MetricsCollector,
+    /// Address the `/metrics` endpoint listens on (disabled if unset)
+    metrics_addr: Option<SocketAddr>,
+    /// Address the payment webhook receiver listens on (disabled if unset)
+    webhook_addr: Option<SocketAddr>,
+    /// Serializes webhook handling so duplicate deliveries verify once
+    webhook_lock: tokio::sync::Mutex<()>,
+    /// Where settled and failed payments are announced (disabled if unset)
+    outgoing_webhook: Option<WebhookConfig>,
+    /// Parsed invoices, so hot paths don't re-parse the same invoice (disabled if unset)
+    invoice_cache: Option<InvoiceCache>,
+    /// Payment request counters keyed by `payment_id` prefix
+    rate_limiter: Mutex<RateLimiter>,
+    /// Payment requests allowed per prefix per window (0 disables rate limiting)
+    rate_limit_max_per_window: AtomicU32,
+    /// Length of the rate limit window
+    rate_limit_window_seconds: AtomicU64,
+    /// Interval between config refreshes from the node (0 disables them)
+    config_refresh_interval_seconds: u64,
+    /// Interval between confirmation polls of in-flight payments (0 disables polling)
+    poller_interval_seconds: u64,
+    /// Maximum number of in-flight payments checked per poll
+    poller_batch_size: usize,
+    /// Age after which an unconfirmed payment is failed instead of polled again
+    poller_max_age_seconds: u64,
+    /// Payment id the last poll stopped at, so every payment gets its turn
+    poll_cursor: Mutex<Option<String>>,
+    /// Time allowed for in-flight payments to settle on shutdown
+    drain_timeout_seconds: u64,
+    /// How far below the invoice amount a payment may fall, in parts per million
+    amount_tolerance_ppm: u64,
 }
 
 impl LightningProcessor {
@@ -30,23 +239,130 @@ impl LightningProcessor {
         let provider_type = ProviderType::from_str(&provider_type_str)
             .map_err(|e| LightningError::ConfigError(format!("Invalid provider type: {}", e)))?;
         
-        info!("Initializing Lightning processor with provider: {:?}", provider_type);
+        info!("Initializing Lightning processor with provider: {}", provider_type);
         
         // Create provider
         let provider = create_provider(provider_type, ctx)?;
+        Self::with_provider(ctx, node_api, provider).await
+    }
+    
+    /// Create a Lightning processor around an existing provider
+    ///
+    /// Reads the same settings as `new` except `lightning.provider`, e.g. to
+    /// run the processor against a pre-programmed `StubProvider` in tests.
+    pub async fn with_provider(
+        ctx: &blvm_node::module::traits::ModuleContext,
+        node_api: Arc<dyn NodeAPI>,
+        provider: Box<dyn LightningProvider>,
+    ) -> Result<Self, LightningError> {
+        let dedup_window_seconds = ctx.get_config_or("lightning.dedup_window_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_DEDUP_WINDOW_SECONDS);
         
-        // Store provider info in module storage
-        let tree_id = node_api.storage_open_tree("lightning_config".to_string()).await
-            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let health_check_interval_seconds = ctx.get_config_or("lightning.health_check_interval_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_SECONDS);
+        
+        let min_balance_msats = ctx.get_config_or("lightning.min_balance_msats", "")
+            .parse::<u64>()
+            .ok();
+        let balance_check_interval_seconds = ctx.get_config_or("lightning.balance_check_interval_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_BALANCE_CHECK_INTERVAL_SECONDS);
+        
+        let invoice_cache = match ctx.get_config_or("lightning.invoice_cache_size", "").parse::<usize>() {
+            Ok(size) if size > 0 => Some(InvoiceCache::new(size)),
+            _ => None,
+        };
+        
+        let rate_limit_max_per_window = ctx.get_config_or("lightning.rate_limit.max_per_minute", "")
+            .parse::<u32>()
+            .unwrap_or(0);
+        let rate_limit_window_seconds = ctx.get_config_or("lightning.rate_limit.window_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_RATE_LIMIT_WINDOW_SECONDS);
+        
+        let poller_interval_seconds = ctx.get_config_or("lightning.poller.interval_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_POLLER_INTERVAL_SECONDS);
+        let poller_batch_size = match ctx.get_config_or("lightning.poller.batch_size", "").parse::<usize>() {
+            Ok(size) if size > 0 => size,
+            _ => DEFAULT_POLLER_BATCH_SIZE,
+        };
+        let poller_max_age_seconds = ctx.get_config_or("lightning.poller.max_age_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_POLLER_MAX_AGE_SECONDS);
         
-        // Store provider type
-        let provider_type_str = match provider.provider_type() {
-            ProviderType::LNBits => "lnbits",
-            ProviderType::LDK => "ldk",
-            ProviderType::Stub => "stub",
+        let config_refresh_interval_seconds = ctx.get_config_or("lightning.config_refresh_interval_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(0);
+        
+        let drain_timeout_seconds = ctx.get_config_or("lightning.shutdown.drain_timeout_seconds", "")
+            .parse::<u64>()
+            .unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECONDS);
+        
+        let amount_tolerance_ppm = ctx.get_config_or("lightning.amount_tolerance_ppm", "")
+            .parse::<u64>()
+            .unwrap_or(0)
+            .min(PPM);
+        
+        let metrics_addr = match ctx.get_config("lightning.metrics_port") {
+            Some(port) if !port.is_empty() => {
+                let port = port.trim().parse::<u16>()
+                    .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.metrics_port: {}", e)))?;
+                let bind = ctx.get_config_or("lightning.metrics_bind", DEFAULT_METRICS_BIND);
+                let ip = bind.parse::<std::net::IpAddr>()
+                    .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.metrics_bind: {}", e)))?;
+                Some(SocketAddr::new(ip, port))
+            }
+            _ => None,
+        };
+        
+        let webhook_addr = match ctx.get_config("lightning.webhook_listen") {
+            Some(addr) if !addr.is_empty() => Some(addr.trim().parse::<SocketAddr>()
+                .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.webhook_listen: {}", e)))?),
+            _ => None,
+        };
+        let outgoing_webhook = WebhookConfig::from_config(ctx)?;
+        
+        // Network for on-chain address validation
+        let network_str = ctx.get_config("lightning.network")
+            .or_else(|| ctx.get_config("lightning.ldk.network"))
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "testnet".to_string());
+        let network = parse_network(&network_str)
+            .ok_or_else(|| LightningError::ConfigError(format!("Unknown network: {}", network_str)))?;
+        
+        // Sweep settings: reject misconfigured addresses at startup rather than at sweep time
+        let sweep = match ctx.get_config("lightning.sweep.address") {
+            Some(address) if !address.is_empty() => {
+                validate_onchain_address(address, network)?;
+                let threshold_sats = ctx.get_config_or("lightning.sweep.threshold_sats", "")
+                    .parse::<u64>()
+                    .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.sweep.threshold_sats: {}", e)))?;
+                let reserve_sats = ctx.get_config_or("lightning.sweep.reserve_sats", "")
+                    .parse::<u64>()
+                    .unwrap_or(DEFAULT_SWEEP_RESERVE_SATS);
+                let interval_seconds = ctx.get_config_or("lightning.sweep.interval_seconds", "")
+                    .parse::<u64>()
+                    .unwrap_or(DEFAULT_SWEEP_INTERVAL_SECONDS);
+                info!(
+                    "Automatic sweep enabled: {} sats threshold, {} sats reserve -> {}",
+                    threshold_sats, reserve_sats, address
+                );
+                Some(SweepConfig {
+                    address: address.to_string(),
+                    threshold_sats,
+                    reserve_sats,
+                    interval_seconds,
+                })
+            }
+            _ => None,
         };
-        node_api.storage_insert(tree_id.clone(), b"provider_type".to_vec(), provider_type_str.as_bytes().to_vec()).await
-            .map_err(|e| LightningError::ProcessorError(format!("Failed to store provider_type: {}", e)))?;
+        
+        // Store provider info in module storage
+        provider.attach_storage(node_api.clone()).await?;
+        let tree_id = store_provider_info(node_api.as_ref(), provider.as_ref()).await?;
         
         // Initialize channel stats (will be updated as channels are opened/closed)
         node_api.storage_insert(tree_id.clone(), b"channel_count".to_vec(), 0u64.to_be_bytes().to_vec()).await
@@ -56,8 +372,40 @@ impl LightningProcessor {
             .map_err(|e| LightningError::ProcessorError(format!("Failed to store total_capacity_sats: {}", e)))?;
         
         Ok(Self {
-            provider,
+            provider: RwLock::new(Arc::from(provider)),
+            ctx: RwLock::new(ModuleContext {
+                module_id: ctx.module_id.clone(),
+                config: ctx.config.clone(),
+                data_dir: ctx.data_dir.clone(),
+                socket_path: ctx.socket_path.clone(),
+            }),
             node_api,
+            dedup_window_seconds,
+            network,
+            sweep,
+            label_locks: KeyLocks::default(),
+            payment_id_locks: KeyLocks::default(),
+            payment_hash_locks: KeyLocks::default(),
+            sweep_lock: tokio::sync::Mutex::new(()),
+            health_check_interval_seconds,
+            min_balance_msats,
+            balance_check_interval_seconds,
+            metrics: MetricsCollector::new(),
+            metrics_addr,
+            webhook_addr,
+            webhook_lock: tokio::sync::Mutex::new(()),
+            outgoing_webhook,
+            invoice_cache,
+            rate_limiter: Mutex::new(RateLimiter::new()),
+            rate_limit_max_per_window: AtomicU32::new(rate_limit_max_per_window),
+            rate_limit_window_seconds: AtomicU64::new(rate_limit_window_seconds),
+            config_refresh_interval_seconds,
+            poller_interval_seconds,
+            poller_batch_size,
+            poller_max_age_seconds,
+            poll_cursor: Mutex::new(None),
+            drain_timeout_seconds,
+            amount_tolerance_ppm,
         })
     }
     
@@ -73,6 +421,7 @@ impl LightningProcessor {
                     EventType::PaymentRequestCreated => {
                         if let EventPayload::PaymentRequestCreated { payment_id, invoice, .. } = &event_msg.payload {
                             debug!("Processing payment request: {}", payment_id);
+                            self.check_rate_limit(payment_id)?;
                             if let Some(invoice_str) = invoice {
                                 self.process_payment(invoice_str, payment_id, node_api).await?;
                             }
@@ -103,6 +452,27 @@ impl LightningProcessor {
         invoice: &str,
         payment_id: &str,
         node_api: &dyn NodeAPI,
+    ) -> Result<(), LightningError> {
+        self.settle_payment(invoice, payment_id, node_api, false).await
+    }
+    
+    /// Verify a payment with the provider and settle it, recording whether
+    /// the settlement came from a webhook
+    #[tracing::instrument(
+        name = "lightning.process_payment",
+        skip_all,
+        fields(
+            payment.id = %payment_id,
+            payment.provider = %self.provider_type(),
+            payment.amount_msats = tracing::field::Empty,
+        )
+    )]
+    async fn settle_payment(
+        &self,
+        invoice: &str,
+        payment_id: &str,
+        node_api: &dyn NodeAPI,
+        by_webhook: bool,
     ) -> Result<(), LightningError> {
         // Early exit: Check if invoice is empty (cheap check before expensive parsing)
         if invoice.is_empty() {
@@ -127,88 +497,1712 @@ impl LightningProcessor {
         
         // Parse invoice
         let invoice_data = self.parse_invoice(invoice)?;
+        tracing::Span::current().record("payment.amount_msats", invoice_data.amount_msats);
+        
+        // Reject payment hashes we've already processed (e.g. event replay)
+        let payment_hash_hex = invoice_data.payment_hash_hex();
+        let _guard = self.payment_hash_locks.lock(&payment_hash_hex).await;
+        if let Some(previous) = self.seen_within_last(&payment_hash_hex, self.dedup_window_seconds).await? {
+            // Settled by a webhook before this call: the first call for the
+            // payment it settled (or for any payment, if none was waiting for
+            // the invoice) confirms it, later ones are replays
+            if previous.by_webhook
+                && (previous.payment_id == payment_id || previous.payment_id == payment_hash_hex)
+            {
+                if let Some(mut settled) = self.load_payment(&previous.payment_id).await? {
+                    if matches!(settled.state, PaymentState::Settled { .. }) {
+                        if settled.payment_id != payment_id {
+                            info!("Payment {} was settled by webhook as {}", payment_id, settled.payment_id);
+                            settled.payment_id = payment_id.to_string();
+                            self.store_payment(&settled).await?;
+                        }
+                        self.record_processed(&payment_hash_hex, payment_id, false).await?;
+                        return Ok(());
+                    }
+                }
+            }
+            warn!(
+                "Duplicate payment hash {} for payment_id: {} (already processed as {} at {})",
+                payment_hash_hex, payment_id, previous.payment_id, previous.settled_at
+            );
+            return Err(LightningError::ProcessorError("duplicate payment hash detected".into()));
+        }
         
         // Check if invoice is expired
         if invoice_data.is_expired() {
             warn!("Invoice expired for payment_id: {}", payment_id);
+            self.notify_webhook(WebhookEvent::InvoiceExpired {
+                payment_id: payment_id.to_string(),
+                payment_hash: payment_hash_hex,
+            }).await;
             return Err(LightningError::InvoiceError("Invoice expired".to_string()));
         }
         
         // Get payment hash from invoice
         let payment_hash = invoice_data.payment_hash();
         
+        // Record the attempt before calling out to the provider
+        let mut payment = match self.load_payment(payment_id).await? {
+            Some(existing) => existing,
+            None => StoredPayment {
+                payment_id: payment_id.to_string(),
+                payment_hash: payment_hash_hex.clone(),
+                invoice: invoice.to_string(),
+                amount_msats: Some(invoice_data.amount_msats).filter(|amount| *amount > 0),
+                fee_msats: 0,
+                preimage: None,
+                provider: self.provider().provider_type(),
+                created_at: now_unix(),
+                last_attempt_at: None,
+                attempts: 0,
+                state: PaymentState::Pending,
+                metadata: serde_json::Value::Null,
+            },
+        };
+        payment.attempts += 1;
+        payment.last_attempt_at = Some(now_unix());
+        payment.state = PaymentState::InFlight;
+        self.store_payment(&payment).await?;
+        
         // Verify payment via provider
-        let verification_result = self.provider.verify_payment(invoice, &payment_hash, payment_id).await?;
+        let started = std::time::Instant::now();
+        let verification = self.provider().verify_payment(invoice, &payment_hash, payment_id).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        self.metrics.record_verification(
+            verification.as_ref().map(|result| result.verified).unwrap_or(false),
+            latency_ms,
+        );
+        let verification_result = verification?;
         
+        self.store_payment_metadata(payment_id, &verification_result.metadata).await?;
+        
+        payment.metadata = verification_result.metadata.clone();
         if verification_result.verified {
+            if let Err(e) = self.check_amount(&invoice_data, &verification_result) {
+                warn!("Lightning payment rejected: payment_id={}: {}", payment_id, e);
+                payment.state = PaymentState::Failed { reason: e.to_string(), failed_at: now_unix() };
+                self.store_payment(&payment).await?;
+                self.notify_webhook(WebhookEvent::PaymentFailed {
+                    payment_id: payment_id.to_string(),
+                    reason: e.to_string(),
+                }).await;
+                return Err(e);
+            }
+            
+            payment.amount_msats = verification_result.amount_msats.or(payment.amount_msats);
+            payment.preimage = verification_result.metadata.get("preimage")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            payment.state = PaymentState::Settled { settled_at: now_unix() };
+            self.store_payment(&payment).await?;
+            self.notify_webhook(WebhookEvent::PaymentReceived {
+                payment_id: payment_id.to_string(),
+                payment_hash: payment_hash_hex.clone(),
+                amount_msats: payment.amount_msats.unwrap_or(0),
+                fee_msats: payment.fee_msats,
+            }).await;
+            
             info!(
-                "Lightning payment verified via {:?}: payment_id={}, amount={:?} msats",
-                self.provider.provider_type(),
+                "Lightning payment verified via {}: payment_id={}, amount={:?} msats",
+                self.provider().provider_type(),
                 payment_id,
                 verification_result.amount_msats
             );
             
+            // Remember this payment hash so replays are rejected
+            self.record_processed(&payment_hash_hex, payment_id, by_webhook).await?;
+            
             // Check payment state via NodeAPI
             if let Ok(Some(state)) = node_api.get_payment_state(payment_id).await {
                 debug!("Payment state for {}: {:?}", payment_id, state);
             }
         } else {
+            // Not paid yet; the confirmation poller keeps checking it
+            payment.state = PaymentState::Pending;
+            self.store_payment(&payment).await?;
             warn!("Lightning payment verification failed: payment_id={}", payment_id);
         }
         
+        // Keep the channel backup current; a failed backup shouldn't fail the payment
+        let provider = self.provider();
+        if let Ok(ldk) = Self::ldk_provider(provider.as_ref()) {
+            if ldk.auto_backup() {
+                if let Err(e) = ldk.export_scb().await {
+                    warn!("Channel backup failed: {}", e);
+                }
+            }
+        }
+        
         Ok(())
     }
     
-    /// Parse Lightning invoice (BOLT11)
-    fn parse_invoice(&self, invoice: &str) -> Result<InvoiceData, LightningError> {
-        InvoiceParser::parse(invoice)
+    /// Deliver an outgoing webhook, if configured
+    ///
+    /// A receiver that is down shouldn't fail the payment, so delivery
+    /// failures are only logged.
+    async fn notify_webhook(&self, event: WebhookEvent) {
+        if let Some(config) = &self.outgoing_webhook {
+            if let Err(e) = WebhookDelivery::deliver(&event, config).await {
+                warn!("Webhook delivery for payment_id={} failed: {}", event.payment_id(), e);
+            }
+        }
     }
     
-    /// Verify multiple payments in parallel (batch operation)
+    /// Reject payments more than `lightning.amount_tolerance_ppm` below the invoice amount
     ///
-    /// Processes multiple payment verifications concurrently for better performance.
-    /// Returns a vector of verification results in the same order as inputs.
-    pub async fn verify_payments_batch(
-        &self,
-        payments: &[(&str, &str)],  // (invoice, payment_id)
-    ) -> Result<Vec<bool>, LightningError> {
-        if payments.is_empty() {
-            return Ok(Vec::new());
+    /// Overpayments are accepted. Amountless invoices, and providers that
+    /// don't report the received amount, aren't checked.
+    fn check_amount(&self, invoice_data: &InvoiceData, result: &PaymentVerificationResult) -> Result<(), LightningError> {
+        if result.is_underpaid(invoice_data.amount_msats, self.amount_tolerance_ppm) {
+            return Err(LightningError::PaymentVerificationFailed(format!(
+                "underpaid: received {} msats, invoice is for {} msats (tolerance {} ppm)",
+                result.amount_msats_or_zero(), invoice_data.amount_msats, self.amount_tolerance_ppm
+            )));
         }
+        if let Some(overpaid_msats) = result.overpaid_by_msats(invoice_data.amount_msats) {
+            info!("Payment exceeds invoice {} by {} msats", invoice_data.payment_hash_hex(), overpaid_msats);
+        }
+        Ok(())
+    }
+    
+    /// Load a payment record from the `payment_states` tree
+    pub async fn load_payment(&self, payment_id: &str) -> Result<Option<StoredPayment>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_STATES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, payment_id.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment state: {}", e)))?;
+        value
+            .map(|bytes| serde_json::from_slice::<StoredPayment>(&bytes)
+                .map_err(|e| LightningError::ProcessorError(format!("Corrupt payment state record: {}", e))))
+            .transpose()
+    }
+    
+    /// Load every payment in the `InFlight` state
+    pub async fn load_inflight_payments(&self) -> Result<Vec<StoredPayment>, LightningError> {
+        self.load_payments_where(None, usize::MAX, |payment| payment.state == PaymentState::InFlight).await
+    }
+    
+    /// Load up to `limit` payments awaiting confirmation, with payment ids after `after`
+    ///
+    /// Awaiting payments are `InFlight` ones and `Pending` ones that
+    /// `process_payment` has checked at least once; invoices that were only
+    /// created aren't included. Ordered by payment id.
+    pub async fn load_unconfirmed_payments(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<StoredPayment>, LightningError> {
+        self.load_payments_where(after, limit, |payment| match payment.state {
+            PaymentState::InFlight => true,
+            PaymentState::Pending => payment.attempts > 0,
+            _ => false,
+        })
+        .await
+    }
+    
+    async fn load_payments_where(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+        filter: impl Fn(&StoredPayment) -> bool,
+    ) -> Result<Vec<StoredPayment>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_STATES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let mut entries = self.node_api.storage_iter(tree_id).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment states: {}", e)))?;
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
         
-        // Parse all invoices first (sequential, but fast)
-        let invoice_data: Result<Vec<_>, _> = payments
-            .iter()
-            .map(|(invoice, _)| self.parse_invoice(invoice))
-            .collect();
-        let invoice_data = invoice_data?;
+        let mut payments = Vec::new();
+        for (key, value) in entries {
+            if payments.len() >= limit {
+                break;
+            }
+            if after.map_or(false, |after| key.as_slice() <= after.as_bytes()) {
+                continue;
+            }
+            match serde_json::from_slice::<StoredPayment>(&value) {
+                Ok(payment) if filter(&payment) => payments.push(payment),
+                Ok(_) => {}
+                Err(e) => warn!("Skipping corrupt payment state record {}: {}", String::from_utf8_lossy(&key), e),
+            }
+        }
+        Ok(payments)
+    }
+    
+    /// List every payment still being processed (`Pending` or `InFlight`)
+    ///
+    /// Ordered by creation time, oldest first.
+    pub async fn list_pending_payments(&self) -> Result<Vec<PendingPayment>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_STATES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let entries = self.node_api.storage_iter(tree_id).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment states: {}", e)))?;
         
-        // Verify all payments in parallel via provider
-        let futures: Vec<_> = invoice_data
-            .iter()
-            .zip(payments.iter())
-            .map(|(invoice_data, (invoice, payment_id))| {
-                let payment_hash = invoice_data.payment_hash();
-                // Clone payment_hash to avoid lifetime issues in async closure
-                let payment_hash_array = payment_hash;
-                let provider = &self.provider;
-                async move {
-                    provider.verify_payment(invoice, &payment_hash_array, payment_id).await
+        let mut payments: Vec<PendingPayment> = entries
+            .into_iter()
+            .filter_map(|(key, value)| match serde_json::from_slice::<StoredPayment>(&value) {
+                Ok(payment) if payment.state.is_pending() => Some(payment.into()),
+                Ok(_) => None,
+                Err(e) => {
+                    warn!("Skipping corrupt payment state record {}: {}", String::from_utf8_lossy(&key), e);
+                    None
                 }
             })
             .collect();
-        
-        // Wait for all verifications to complete
-        let results = futures::future::join_all(futures).await;
-        Ok(results
+        payments.sort_by(|a, b| (a.created_at, &a.payment_id).cmp(&(b.created_at, &b.payment_id)));
+        Ok(payments)
+    }
+    
+    /// One page of `list_pending_payments`, counting pages from 0
+    pub async fn list_pending_payments_page(
+        &self,
+        page: u32,
+        page_size: u32,
+    ) -> Result<Vec<PendingPayment>, LightningError> {
+        let skip = page as usize * page_size as usize;
+        Ok(self
+            .list_pending_payments()
+            .await?
             .into_iter()
-            .map(|r| r.map(|v| v.verified).unwrap_or(false))
+            .skip(skip)
+            .take(page_size as usize)
             .collect())
     }
     
-    /// Get the provider type
-    pub fn provider_type(&self) -> ProviderType {
-        self.provider.provider_type()
+    /// Abandon a payment that is still `Pending` or `InFlight`
+    ///
+    /// Cancels the invoice with the provider (skipped if the provider can't
+    /// cancel invoices) and marks the payment `Failed` with `reason`.
+    pub async fn cancel_pending_payment(&self, payment_id: &str, reason: &str) -> Result<(), LightningError> {
+        let mut payment = self.load_payment(payment_id).await?
+            .ok_or_else(|| LightningError::PaymentVerificationFailed("payment not found".into()))?;
+        match payment.state {
+            PaymentState::Pending | PaymentState::InFlight => {}
+            PaymentState::Settled { .. } => {
+                return Err(LightningError::ProcessorError("cannot cancel settled payment".into()));
+            }
+            PaymentState::Failed { .. } => {
+                return Err(LightningError::ProcessorError("cannot cancel failed payment".into()));
+            }
+        }
+        
+        let payment_hash = decode_hash(&payment.payment_hash)?;
+        match self.provider().cancel_invoice(&payment_hash).await {
+            Ok(()) => {}
+            Err(LightningError::Unsupported(e)) => debug!("Invoice for {} left open: {}", payment_id, e),
+            Err(e) => return Err(e),
+        }
+        
+        payment.state = PaymentState::Failed {
+            reason: reason.to_string(),
+            failed_at: now_unix(),
+        };
+        self.store_payment(&payment).await?;
+        warn!("Cancelled payment {}: {}", payment_id, reason);
+        self.notify_webhook(WebhookEvent::PaymentFailed {
+            payment_id: payment_id.to_string(),
+            reason: reason.to_string(),
+        }).await;
+        Ok(())
     }
-}
-
+    
+    /// Store a payment record in the `payment_states` tree
+    async fn store_payment(&self, payment: &StoredPayment) -> Result<(), LightningError> {
+        let value = serde_json::to_vec(payment)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize payment state: {}", e)))?;
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_STATES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, payment.payment_id.as_bytes().to_vec(), value).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store payment state: {}", e)))
+    }
+    
+    /// Persist provider metadata for a payment in the `payment_metadata` tree
+    pub async fn store_payment_metadata(&self, payment_id: &str, metadata: &serde_json::Value) -> Result<(), LightningError> {
+        let value = serde_json::to_vec(metadata)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize payment metadata: {}", e)))?;
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_METADATA_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, payment_id.as_bytes().to_vec(), value).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store payment metadata: {}", e)))
+    }
+    
+    /// Load provider metadata stored for a payment
+    pub async fn get_payment_metadata(&self, payment_id: &str) -> Result<Option<serde_json::Value>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_METADATA_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, payment_id.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment metadata: {}", e)))?;
+        value
+            .map(|bytes| serde_json::from_slice(&bytes)
+                .map_err(|e| LightningError::ProcessorError(format!("Corrupt payment metadata record: {}", e))))
+            .transpose()
+    }
+    
+    /// Generate a receipt for a settled payment
+    ///
+    /// The receipt is also persisted to the `payment_receipts` tree keyed by payment id.
+    pub async fn generate_receipt(&self, payment_id: &str) -> Result<PaymentReceipt, LightningError> {
+        let payment = self.load_payment(payment_id).await?
+            .ok_or_else(|| LightningError::ProcessorError(format!("Payment not found: {}", payment_id)))?;
+        
+        let settled_at = match payment.state {
+            PaymentState::Settled { settled_at } => settled_at,
+            ref state => {
+                return Err(LightningError::ProcessorError(format!(
+                    "Payment {} is not settled (state: {:?})",
+                    payment_id, state
+                )));
+            }
+        };
+        
+        let receipt = PaymentReceipt {
+            payment_id: payment.payment_id.clone(),
+            payment_hash: decode_hash(&payment.payment_hash)?,
+            preimage: payment.preimage.as_deref().map(decode_hash).transpose()?,
+            amount_msats: payment.amount_msats.unwrap_or(0),
+            fee_msats: payment.fee_msats,
+            settled_at,
+            provider: payment.provider,
+            metadata: payment.metadata,
+        };
+        
+        let tree_id = self.node_api.storage_open_tree(PAYMENT_RECEIPTS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, payment_id.as_bytes().to_vec(), receipt.to_json().into_bytes()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store payment receipt: {}", e)))?;
+        
+        Ok(receipt)
+    }
+    
+    /// Aggregate payments resolved (or, if still pending, created) since `since_unix`
+    ///
+    /// Received payments come from the `payment_states` tree, with amount and
+    /// fee taken from the payment's receipt in `payment_receipts` when it has
+    /// one; receipts whose payment state is gone still count as received.
+    /// Sent payments come from the payment history's `Outbound` records.
+    /// Settlement times have second precision and are only known for
+    /// payments with a state record.
+    pub async fn statistics(&self, since_unix: u64) -> Result<PaymentStats, LightningError> {
+        let mut receipts = HashMap::new();
+        for (key, value) in self.read_tree(PAYMENT_RECEIPTS_TREE).await? {
+            match serde_json::from_slice::<PaymentReceipt>(&value) {
+                Ok(receipt) => {
+                    receipts.insert(receipt.payment_id.clone(), receipt);
+                }
+                Err(e) => warn!("Skipping corrupt payment receipt {}: {}", String::from_utf8_lossy(&key), e),
+            }
+        }
+        
+        let mut stats = PaymentStats::default();
+        let mut timed_settlements = 0u64;
+        let mut settlement_time_ms = 0u64;
+        for (key, value) in self.read_tree(PAYMENT_STATES_TREE).await? {
+            let payment = match serde_json::from_slice::<StoredPayment>(&value) {
+                Ok(payment) => payment,
+                Err(e) => {
+                    warn!("Skipping corrupt payment state record {}: {}", String::from_utf8_lossy(&key), e);
+                    continue;
+                }
+            };
+            let receipt = receipts.remove(&payment.payment_id);
+            match payment.state {
+                PaymentState::Settled { settled_at } if settled_at >= since_unix => {
+                    stats.num_received += 1;
+                    stats.total_received_msats += receipt.as_ref().map_or(payment.amount_msats.unwrap_or(0), |r| r.amount_msats);
+                    stats.total_fees_paid_msats += receipt.as_ref().map_or(payment.fee_msats, |r| r.fee_msats);
+                    timed_settlements += 1;
+                    settlement_time_ms += settled_at.saturating_sub(payment.created_at) * 1000;
+                }
+                PaymentState::Failed { failed_at, .. } if failed_at >= since_unix => stats.num_failed += 1,
+                ref state if state.is_pending() && payment.created_at >= since_unix => stats.num_pending += 1,
+                _ => {}
+            }
+        }
+        for receipt in receipts.into_values().filter(|receipt| receipt.settled_at >= since_unix) {
+            stats.num_received += 1;
+            stats.total_received_msats += receipt.amount_msats;
+            stats.total_fees_paid_msats += receipt.fee_msats;
+        }
+        
+        let history = PaymentHistory::new(self.node_api.clone())
+            .query(&PaymentFilter::default(), Page { page: 0, per_page: u32::MAX })
+            .await?;
+        for record in history.records.into_iter().filter(|record| record.direction == PaymentDirection::Outbound) {
+            match record.state {
+                PaymentState::Settled { settled_at } if settled_at >= since_unix => {
+                    stats.num_sent += 1;
+                    stats.total_sent_msats += record.amount_msats;
+                    stats.total_fees_paid_msats += record.fee_msats;
+                }
+                PaymentState::Failed { failed_at, .. } if failed_at >= since_unix => stats.num_failed += 1,
+                ref state if state.is_pending() && record.created_at >= since_unix => stats.num_pending += 1,
+                _ => {}
+            }
+        }
+        
+        let settled = stats.num_received + stats.num_sent;
+        if settled + stats.num_failed > 0 {
+            stats.success_rate = settled as f64 / (settled + stats.num_failed) as f64;
+        }
+        if timed_settlements > 0 {
+            stats.avg_settlement_time_ms = settlement_time_ms / timed_settlements;
+        }
+        Ok(stats)
+    }
+    
+    /// `statistics` over the last `hours` hours
+    pub async fn statistics_since_hours(&self, hours: u64) -> Result<PaymentStats, LightningError> {
+        self.statistics(now_unix().saturating_sub(hours.saturating_mul(3600))).await
+    }
+    
+    /// Every entry of a storage tree
+    async fn read_tree(&self, tree: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(tree.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_iter(tree_id).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read {}: {}", tree, e)))
+    }
+    
+    /// Withdraw funds to an on-chain address after validating it against the configured network
+    pub async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        validate_onchain_address(address, self.network)?;
+        let txid = self.provider().withdraw_onchain(address, amount_sats, fee_rate).await?;
+        info!("On-chain withdrawal to {} broadcast: {}", address, txid);
+        Ok(txid)
+    }
+    
+    /// Sweep the wallet balance on-chain if it exceeds the configured threshold
+    ///
+    /// Sweeps everything above `reserve_sats`. At most one sweep runs at a
+    /// time, and none runs within `interval_seconds` of the last one. A sweep
+    /// is recorded in `SWEEPS_TREE` before the withdrawal starts; one that
+    /// never recorded a txid blocks further sweeps for `SWEEP_STALE_SECONDS`.
+    ///
+    /// Returns the sweep txid, or `None` if no sweep was made.
+    pub async fn maybe_sweep(&self) -> Result<Option<Txid>, LightningError> {
+        let sweep = match &self.sweep {
+            Some(sweep) => sweep,
+            None => return Ok(None),
+        };
+        let _guard = match self.sweep_lock.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return Ok(None),
+        };
+        
+        let now = now_unix();
+        if let Some(last) = self.last_sweep().await? {
+            let age = now.saturating_sub(last.started_at);
+            match last.txid {
+                None if age < SWEEP_STALE_SECONDS => {
+                    warn!(
+                        "Sweep of {} sats started {}s ago never recorded a txid, not sweeping again yet",
+                        last.amount_sats, age
+                    );
+                    return Ok(None);
+                }
+                Some(_) if age < sweep.interval_seconds => return Ok(None),
+                _ => {}
+            }
+        }
+        
+        let balance_sats = self.provider().balance_msats().await? / 1000;
+        let amount_sats = balance_sats.saturating_sub(sweep.reserve_sats);
+        if balance_sats <= sweep.threshold_sats || amount_sats == 0 {
+            return Ok(None);
+        }
+        
+        info!(
+            "Balance {} sats exceeds sweep threshold {} sats, sweeping {} sats",
+            balance_sats, sweep.threshold_sats, amount_sats
+        );
+        let mut record = SweepRecord { amount_sats, started_at: now, txid: None };
+        self.store_sweep(&record).await?;
+        match self.withdraw_onchain(&sweep.address, Some(amount_sats), None).await {
+            Ok(txid) => {
+                record.txid = Some(txid.to_string());
+                self.store_sweep(&record).await?;
+                Ok(Some(txid))
+            }
+            Err(e) => {
+                // Nothing was broadcast, so the next check may try again
+                self.clear_sweep().await?;
+                Err(e)
+            }
+        }
+    }
+    
+    /// The latest automatic sweep, if any
+    pub async fn last_sweep(&self) -> Result<Option<SweepRecord>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(SWEEPS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, SWEEP_KEY.to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read sweep record: {}", e)))?;
+        match value {
+            Some(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| LightningError::ProcessorError(format!("Corrupt sweep record: {}", e))),
+            None => Ok(None),
+        }
+    }
+    
+    async fn store_sweep(&self, record: &SweepRecord) -> Result<(), LightningError> {
+        let value = serde_json::to_vec(record)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize sweep record: {}", e)))?;
+        let tree_id = self.node_api.storage_open_tree(SWEEPS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, SWEEP_KEY.to_vec(), value).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store sweep record: {}", e)))
+    }
+    
+    async fn clear_sweep(&self) -> Result<(), LightningError> {
+        let tree_id = self.node_api.storage_open_tree(SWEEPS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_remove(tree_id, SWEEP_KEY.to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to remove sweep record: {}", e)))
+    }
+    
+    /// Spawn a background task that runs `maybe_sweep` every `lightning.sweep.interval_seconds`
+    ///
+    /// Returns `None` if sweeping isn't configured or the interval is 0.
+    pub fn spawn_sweeper(self: &Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        let interval_seconds = match &self.sweep {
+            Some(sweep) if sweep.interval_seconds > 0 => sweep.interval_seconds,
+            _ => return None,
+        };
+        
+        let processor = Arc::clone(self);
+        let interval = std::time::Duration::from_secs(interval_seconds);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match processor.maybe_sweep().await {
+                    Ok(Some(txid)) => info!("Automatic sweep broadcast: {}", txid),
+                    Ok(None) => {}
+                    Err(e) => warn!("Automatic sweep failed: {}", e),
+                }
+            }
+        }))
+    }
+    
+    /// Look up a processed payment hash, ignoring records older than `window_seconds`
+    pub async fn seen_within_last(
+        &self,
+        payment_hash_hex: &str,
+        window_seconds: u64,
+    ) -> Result<Option<ProcessedPayment>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PROCESSED_PAYMENTS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, payment_hash_hex.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read processed payment: {}", e)))?;
+        
+        let record = match value {
+            Some(bytes) => serde_json::from_slice::<ProcessedPayment>(&bytes)
+                .map_err(|e| LightningError::ProcessorError(format!("Corrupt processed payment record: {}", e)))?,
+            None => return Ok(None),
+        };
+        
+        if now_unix().saturating_sub(record.settled_at) > window_seconds {
+            return Ok(None);
+        }
+        Ok(Some(record))
+    }
+    
+    /// Record a payment hash as processed
+    async fn record_processed(
+        &self,
+        payment_hash_hex: &str,
+        payment_id: &str,
+        by_webhook: bool,
+    ) -> Result<(), LightningError> {
+        let record = ProcessedPayment {
+            payment_id: payment_id.to_string(),
+            settled_at: now_unix(),
+            by_webhook,
+        };
+        let value = serde_json::to_vec(&record)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize processed payment: {}", e)))?;
+        let tree_id = self.node_api.storage_open_tree(PROCESSED_PAYMENTS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, payment_hash_hex.as_bytes().to_vec(), value).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store processed payment: {}", e)))
+    }
+    
+    /// Create an invoice to be paid as `payment_id`
+    ///
+    /// The invoice is recorded like one from `create_invoice_ex` and also
+    /// stored as `Pending` in the `pending_invoices` tree, with a TTL of
+    /// `PENDING_INVOICE_GRACE_SECONDS` past its expiry after which the
+    /// confirmation poller removes it. The payment's state is initialized as
+    /// `Pending`, so `process_payment` continues the same record. A
+    /// `payment_id` that is already in use is rejected.
+    pub async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+        payment_id: &str,
+    ) -> Result<InvoiceCreatedResult, LightningError> {
+        if payment_id.is_empty() {
+            return Err(LightningError::ProcessorError("Empty payment_id".to_string()));
+        }
+        // Held until the payment is stored, so a concurrent call with the same id sees it
+        let _guard = self.payment_id_locks.lock(payment_id).await;
+        if self.load_payment(payment_id).await?.is_some() {
+            return Err(LightningError::ProcessorError(format!("Payment {} already exists", payment_id)));
+        }
+        
+        let bolt11 = self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await?;
+        let invoice_data = self.parse_invoice(&bolt11)?;
+        let payment_hash_hex = invoice_data.payment_hash_hex();
+        let created_at = now_unix();
+        let expires_at = invoice_data.expires_at();
+        
+        let pending = PendingInvoice {
+            payment_id: payment_id.to_string(),
+            payment_hash: payment_hash_hex.clone(),
+            bolt11: bolt11.clone(),
+            amount_msats,
+            state: PaymentState::Pending,
+            created_at,
+            expires_at,
+            delete_after: expires_at.saturating_add(PENDING_INVOICE_GRACE_SECONDS),
+        };
+        let value = serde_json::to_vec(&pending)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize pending invoice: {}", e)))?;
+        let tree_id = self.node_api.storage_open_tree(PENDING_INVOICES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, payment_id.as_bytes().to_vec(), value).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store pending invoice: {}", e)))?;
+        
+        self.store_payment(&StoredPayment {
+            payment_id: payment_id.to_string(),
+            payment_hash: payment_hash_hex,
+            invoice: bolt11.clone(),
+            amount_msats: Some(amount_msats).filter(|amount| *amount > 0),
+            fee_msats: 0,
+            preimage: None,
+            provider: self.provider().provider_type(),
+            created_at,
+            last_attempt_at: None,
+            attempts: 0,
+            state: PaymentState::Pending,
+            metadata: serde_json::Value::Null,
+        }).await?;
+        
+        info!("Created invoice for payment_id={}: {} msats, expires at {}", payment_id, amount_msats, expires_at);
+        Ok(InvoiceCreatedResult {
+            bolt11,
+            payment_hash: invoice_data.payment_hash(),
+            payment_id: payment_id.to_string(),
+            expires_at,
+        })
+    }
+    
+    /// Load a pending invoice record created by `create_invoice`
+    pub async fn load_pending_invoice(&self, payment_id: &str) -> Result<Option<PendingInvoice>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PENDING_INVOICES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, payment_id.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read pending invoice: {}", e)))?;
+        value
+            .map(|bytes| serde_json::from_slice::<PendingInvoice>(&bytes)
+                .map_err(|e| LightningError::ProcessorError(format!("Corrupt pending invoice record: {}", e))))
+            .transpose()
+    }
+    
+    /// Remove pending invoice records whose TTL passed by `now`
+    ///
+    /// A payment still `Pending` by then was never paid and is marked
+    /// `Failed` with an `InvoiceExpired` webhook. Returns how many records
+    /// were removed.
+    pub async fn prune_pending_invoices(&self, now: u64) -> Result<usize, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(PENDING_INVOICES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let entries = self.node_api.storage_iter(tree_id.clone()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read pending invoices: {}", e)))?;
+        
+        let mut removed = 0;
+        for (key, value) in entries {
+            let pending = match serde_json::from_slice::<PendingInvoice>(&value) {
+                Ok(pending) if pending.delete_after <= now => pending,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Skipping corrupt pending invoice record {}: {}", String::from_utf8_lossy(&key), e);
+                    continue;
+                }
+            };
+            if let Some(mut payment) = self.load_payment(&pending.payment_id).await? {
+                if payment.state == PaymentState::Pending {
+                    payment.state = PaymentState::Failed { reason: "invoice expired".to_string(), failed_at: now };
+                    self.store_payment(&payment).await?;
+                    self.notify_webhook(WebhookEvent::InvoiceExpired {
+                        payment_id: pending.payment_id.clone(),
+                        payment_hash: pending.payment_hash.clone(),
+                    }).await;
+                }
+            }
+            self.node_api.storage_remove(tree_id.clone(), key).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to remove pending invoice: {}", e)))?;
+            removed += 1;
+        }
+        if removed > 0 {
+            info!("Removed {} expired pending invoices", removed);
+        }
+        Ok(removed)
+    }
+    
+    /// Create an invoice with extended parameters
+    ///
+    /// If a label is given, creation is idempotent: a label that was already
+    /// used returns the previously created invoice. The label -> payment hash
+    /// mapping is also persisted so the payment can later be looked up with
+    /// `payment_hash_for_label`.
+    pub async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let _guard = match &params.label {
+            Some(label) => {
+                let guard = self.label_locks.lock(label).await;
+                if let Some(existing) = self.invoice_for_label(label).await? {
+                    info!("Invoice for label {} already exists, returning it", label);
+                    return Ok(existing);
+                }
+                Some(guard)
+            }
+            None => None,
+        };
+        
+        let invoice = self.provider().create_invoice_ex(params).await?;
+        self.metrics.record_invoice_created();
+        self.record_invoice(params, &invoice).await?;
+        Ok(invoice)
+    }
+    
+    /// Create multiple invoices concurrently, recording each one in storage
+    ///
+    /// Failures are reported per item so one bad request doesn't fail the batch.
+    /// Requests whose label was already used return the existing invoice, and
+    /// a label repeated within the batch is only created once.
+    pub async fn create_invoices_batch(
+        &self,
+        requests: &[InvoiceParams],
+    ) -> Result<Vec<Result<String, LightningError>>, LightningError> {
+        // Lock each label once, in sorted order so overlapping batches can't deadlock
+        let labels: std::collections::BTreeSet<&str> = requests
+            .iter()
+            .filter_map(|params| params.label.as_deref())
+            .collect();
+        let mut _guards = Vec::with_capacity(labels.len());
+        for label in labels {
+            _guards.push(self.label_locks.lock(label).await);
+        }
+        
+        /// Outcome of one request: resolved, or waiting on a request in `pending`
+        enum Slot {
+            Done(Result<String, LightningError>),
+            Pending(usize),
+        }
+        
+        // Resolve already-used labels from storage, send the rest to the provider
+        let mut slots = Vec::with_capacity(requests.len());
+        let mut pending: Vec<InvoiceParams> = Vec::new();
+        let mut pending_labels: HashMap<&str, usize> = HashMap::new();
+        for params in requests {
+            if let Some(label) = &params.label {
+                if let Some(existing) = self.invoice_for_label(label).await? {
+                    slots.push(Slot::Done(Ok(existing)));
+                    continue;
+                }
+                if let Some(&index) = pending_labels.get(label.as_str()) {
+                    slots.push(Slot::Pending(index));
+                    continue;
+                }
+                pending_labels.insert(label, pending.len());
+            }
+            slots.push(Slot::Pending(pending.len()));
+            pending.push(params.clone());
+        }
+        
+        let mut created = self.provider().create_invoices_batch(&pending).await?.into_iter();
+        let mut outcomes = Vec::with_capacity(pending.len());
+        for params in &pending {
+            outcomes.push(match created.next() {
+                Some(Ok(invoice)) => {
+                    self.metrics.record_invoice_created();
+                    self.record_invoice(params, &invoice).await.map(|_| invoice)
+                }
+                Some(Err(e)) => Err(e),
+                None => Err(LightningError::ProcessorError(
+                    "Provider returned too few batch results".to_string(),
+                )),
+            });
+        }
+        let recorded: Vec<_> = slots
+            .into_iter()
+            .map(|slot| match slot {
+                Slot::Done(result) => result,
+                Slot::Pending(index) => outcomes[index].clone(),
+            })
+            .collect();
+        
+        info!(
+            "Created {}/{} invoices in batch",
+            recorded.iter().filter(|r| r.is_ok()).count(),
+            requests.len()
+        );
+        Ok(recorded)
+    }
+    
+    /// Persist a newly created invoice (and its label, if any)
+    async fn record_invoice(&self, params: &InvoiceParams, invoice: &str) -> Result<(), LightningError> {
+        if let Some(label) = &params.label {
+            let tree_id = self.node_api.storage_open_tree(LIGHTNING_INVOICES_TREE.to_string()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+            self.node_api.storage_insert(tree_id, label.as_bytes().to_vec(), invoice.as_bytes().to_vec()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to store labelled invoice: {}", e)))?;
+        }
+        
+        let invoice_data = match self.parse_invoice(invoice) {
+            Ok(invoice_data) => invoice_data,
+            Err(e) => {
+                warn!("Created invoice could not be parsed, not recorded: {}", e);
+                return Ok(());
+            }
+        };
+        let payment_hash_hex = invoice_data.payment_hash_hex();
+        
+        let tree_id = self.node_api.storage_open_tree(ISSUED_INVOICES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, payment_hash_hex.as_bytes().to_vec(), invoice.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store invoice: {}", e)))?;
+        
+        if let Some(label) = &params.label {
+            let tree_id = self.node_api.storage_open_tree(INVOICE_LABELS_TREE.to_string()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+            self.node_api.storage_insert(tree_id, label.as_bytes().to_vec(), payment_hash_hex.as_bytes().to_vec()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to store invoice label: {}", e)))?;
+        }
+        
+        // Until a payment claims the invoice it is known by its payment hash (see `handle_webhook`)
+        self.notify_webhook(WebhookEvent::InvoiceCreated {
+            payment_id: payment_hash_hex,
+            bolt11: invoice.to_string(),
+            amount_msats: invoice_data.amount_msats,
+            expiry_at: invoice_data.expires_at(),
+        }).await;
+        
+        Ok(())
+    }
+    
+    /// Look up an invoice created by this module by its payment hash (hex)
+    pub async fn issued_invoice(&self, payment_hash_hex: &str) -> Result<Option<String>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(ISSUED_INVOICES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, payment_hash_hex.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read invoice: {}", e)))?;
+        Ok(value.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+    
+    /// Handle a provider webhook announcing that an invoice was paid
+    ///
+    /// The webhook is only a hint: the invoice must have been created by this
+    /// module, and the payment is settled through `process_payment`, i.e.
+    /// only once the provider confirms it. The payment waiting for the
+    /// invoice (from `create_invoice`, or an earlier `process_payment`) is the
+    /// one settled; if there is none, the payment hash (hex) is used as the
+    /// `payment_id`, and a later `process_payment` for the invoice takes the
+    /// settled record over. The first `process_payment` for the settled
+    /// payment afterwards returns `Ok`; replays after it are rejected as
+    /// duplicates. Duplicate deliveries are handled one at a time and report
+    /// `AlreadySettled`.
+    pub async fn handle_webhook(&self, payment_hash: &[u8; 32]) -> Result<WebhookOutcome, LightningError> {
+        let _guard = self.webhook_lock.lock().await;
+        let payment_hash_hex = hex::encode(payment_hash);
+        
+        let invoice = match self.issued_invoice(&payment_hash_hex).await? {
+            Some(invoice) => invoice,
+            None => return Ok(WebhookOutcome::UnknownInvoice),
+        };
+        if let Some(previous) = self.seen_within_last(&payment_hash_hex, u64::MAX).await? {
+            debug!("Webhook for already settled payment {}", previous.payment_id);
+            return Ok(WebhookOutcome::AlreadySettled { payment_id: previous.payment_id });
+        }
+        
+        let payment_id = match self.payment_id_for_hash(&payment_hash_hex).await? {
+            Some(payment_id) => payment_id,
+            None => payment_hash_hex,
+        };
+        self.settle_payment(&invoice, &payment_id, self.node_api.as_ref(), true).await?;
+        let settled = matches!(
+            self.load_payment(&payment_id).await?.map(|payment| payment.state),
+            Some(PaymentState::Settled { .. })
+        );
+        Ok(if settled {
+            WebhookOutcome::Settled { payment_id }
+        } else {
+            WebhookOutcome::Unverified { payment_id }
+        })
+    }
+    
+    /// Find the payment waiting for an invoice: its pending invoice record,
+    /// or an unsettled payment record for the payment hash
+    async fn payment_id_for_hash(&self, payment_hash_hex: &str) -> Result<Option<String>, LightningError> {
+        for (_, value) in self.read_tree(PENDING_INVOICES_TREE).await? {
+            if let Ok(pending) = serde_json::from_slice::<PendingInvoice>(&value) {
+                if pending.payment_hash == payment_hash_hex {
+                    return Ok(Some(pending.payment_id));
+                }
+            }
+        }
+        for (_, value) in self.read_tree(PAYMENT_STATES_TREE).await? {
+            if let Ok(payment) = serde_json::from_slice::<StoredPayment>(&value) {
+                if payment.payment_hash == payment_hash_hex && payment.state.is_pending() {
+                    return Ok(Some(payment.payment_id));
+                }
+            }
+        }
+        Ok(None)
+    }
+    
+    /// Look up the invoice previously created with the given label
+    pub async fn invoice_for_label(&self, label: &str) -> Result<Option<String>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(LIGHTNING_INVOICES_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, label.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read labelled invoice: {}", e)))?;
+        Ok(value.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+    
+    /// Look up the payment hash (hex) of an invoice created with the given label
+    pub async fn payment_hash_for_label(&self, label: &str) -> Result<Option<String>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(INVOICE_LABELS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, label.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read invoice label: {}", e)))?;
+        Ok(value.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+    
+    /// Count a payment request against its `payment_id` prefix
+    ///
+    /// Fails with "rate limit exceeded" once the prefix has used up
+    /// `lightning.rate_limit.max_per_minute` requests in the current window.
+    /// Always succeeds when rate limiting is not configured.
+    pub fn check_rate_limit(&self, payment_id: &str) -> Result<(), LightningError> {
+        let max_per_window = self.rate_limit_max_per_window.load(Ordering::Relaxed);
+        if max_per_window == 0 {
+            return Ok(());
+        }
+        let key: String = payment_id.chars().take(RATE_LIMIT_KEY_LEN).collect();
+        let window_seconds = self.rate_limit_window_seconds.load(Ordering::Relaxed);
+        let allowed = self.rate_limiter.lock().unwrap().check_and_record(&key, max_per_window, window_seconds);
+        if !allowed {
+            warn!("Rate limit exceeded for payment_id prefix {}", key);
+            return Err(LightningError::ProcessorError("rate limit exceeded".to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Parse Lightning invoice (BOLT11), through the invoice cache if enabled
+    fn parse_invoice(&self, invoice: &str) -> Result<Arc<InvoiceData>, LightningError> {
+        match &self.invoice_cache {
+            Some(cache) => cache.get_or_parse(invoice),
+            None => InvoiceParser::parse(invoice).map(Arc::new),
+        }
+    }
+    
+    /// The invoice cache, if `lightning.invoice_cache_size` is set
+    pub fn invoice_cache(&self) -> Option<&InvoiceCache> {
+        self.invoice_cache.as_ref()
+    }
+    
+    /// Verify multiple payments in parallel (batch operation)
+    ///
+    /// Processes multiple payment verifications concurrently for better performance.
+    /// Returns one result per input, in the same order, so a failure (an
+    /// unparseable invoice, an unknown payment, a network error) only affects
+    /// its own payment. Fails as a whole only if the provider can't verify
+    /// payments at all.
+    pub async fn verify_payments_batch_detailed(
+        &self,
+        payments: &[(&str, &str)],  // (invoice, payment_id)
+    ) -> Result<Vec<Result<PaymentVerificationResult, LightningError>>, LightningError> {
+        if payments.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        if !self.provider().capabilities().can_verify {
+            return Err(LightningError::ProcessorError(format!(
+                "{} provider does not support payment verification",
+                self.provider().provider_type()
+            )));
+        }
+        
+        // Verify all payments in parallel via provider
+        let provider = self.provider();
+        let futures: Vec<_> = payments
+            .iter()
+            .map(|(invoice, payment_id)| {
+                let provider = &provider;
+                async move {
+                    let payment_hash = self.parse_invoice(invoice)?.payment_hash();
+                    provider.verify_payment(invoice, &payment_hash, payment_id).await
+                }
+            })
+            .collect();
+        
+        // Wait for all verifications to complete
+        Ok(futures::future::join_all(futures).await)
+    }
+    
+    /// Verify multiple payments in parallel, reporting only whether each was verified
+    ///
+    /// Like `verify_payments_batch_detailed`, with failed verifications reported as `false`.
+    pub async fn verify_payments_batch(
+        &self,
+        payments: &[(&str, &str)],  // (invoice, payment_id)
+    ) -> Result<Vec<bool>, LightningError> {
+        Ok(self
+            .verify_payments_batch_detailed(payments)
+            .await?
+            .into_iter()
+            .map(|r| r.map(|v| v.verified).unwrap_or(false))
+            .collect())
+    }
+    
+    /// List the provider's channels, refreshing the channel stats in `lightning_config`
+    pub async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        let channels = self.provider().list_channels().await?;
+        
+        let channel_count = channels.len() as u64;
+        let total_capacity_sats: u64 = channels.iter().map(|c| c.capacity_msats / 1000).sum();
+        let tree_id = self.node_api.storage_open_tree("lightning_config".to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id.clone(), b"channel_count".to_vec(), channel_count.to_be_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store channel_count: {}", e)))?;
+        self.node_api.storage_insert(tree_id, b"total_capacity_sats".to_vec(), total_capacity_sats.to_be_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store total_capacity_sats: {}", e)))?;
+        
+        Ok(channels)
+    }
+    
+    /// Public key of the node behind the provider
+    pub async fn node_id(&self) -> Result<[u8; 33], LightningError> {
+        Ok(self.provider().get_node_info().await?.node_id)
+    }
+    
+    /// Get the LDK provider, if one is configured (directly or in a failover chain)
+    fn ldk_provider(provider: &dyn LightningProvider) -> Result<&LDKProvider, LightningError> {
+        let any = provider.as_any();
+        if let Some(ldk) = any.downcast_ref::<LDKProvider>() {
+            return Ok(ldk);
+        }
+        if let Some(failover) = any.downcast_ref::<FailoverProvider>() {
+            if let Some(ldk) = failover.providers().iter().find_map(|p| p.as_any().downcast_ref::<LDKProvider>()) {
+                return Ok(ldk);
+            }
+        }
+        if let Some(routing) = any.downcast_ref::<RoutingProvider>() {
+            if let Some(ldk) = routing.routes().iter().find_map(|route| route.provider.as_any().downcast_ref::<LDKProvider>()) {
+                return Ok(ldk);
+            }
+        }
+        Err(LightningError::ProcessorError(format!(
+            "Operation requires the LDK provider (configured: {})",
+            provider.provider_type()
+        )))
+    }
+    
+    /// LNBits provider, directly or inside a failover/routing provider
+    fn lnbits_provider(provider: &dyn LightningProvider) -> Result<&LNBitsProvider, LightningError> {
+        let any = provider.as_any();
+        if let Some(lnbits) = any.downcast_ref::<LNBitsProvider>() {
+            return Ok(lnbits);
+        }
+        if let Some(failover) = any.downcast_ref::<FailoverProvider>() {
+            if let Some(lnbits) = failover.providers().iter().find_map(|p| p.as_any().downcast_ref::<LNBitsProvider>()) {
+                return Ok(lnbits);
+            }
+        }
+        if let Some(routing) = any.downcast_ref::<RoutingProvider>() {
+            if let Some(lnbits) = routing.routes().iter().find_map(|route| route.provider.as_any().downcast_ref::<LNBitsProvider>()) {
+                return Ok(lnbits);
+            }
+        }
+        Err(LightningError::ProcessorError(format!(
+            "Payment history requires the LNBits provider (configured: {})",
+            provider.provider_type()
+        )))
+    }
+    
+    /// Copy the latest `limit` LNBits payments into the `lnbits_payments` tree
+    ///
+    /// Known payments are updated in place (e.g. once no longer pending).
+    /// Returns how many payments weren't stored before.
+    pub async fn sync_payment_history(&self, limit: u32) -> Result<u32, LightningError> {
+        let payments = Self::lnbits_provider(self.provider().as_ref())?.list_payments(limit, 0).await?;
+        
+        let tree_id = self.node_api.storage_open_tree(LNBITS_PAYMENTS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let mut synced = 0;
+        for payment in &payments {
+            let key = payment.payment_hash.as_bytes().to_vec();
+            let existing = self.node_api.storage_get(tree_id.clone(), key.clone()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment history: {}", e)))?;
+            if existing.is_none() {
+                synced += 1;
+            }
+            let value = serde_json::to_vec(payment)
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize payment: {}", e)))?;
+            self.node_api.storage_insert(tree_id.clone(), key, value).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to store payment history: {}", e)))?;
+        }
+        
+        debug!("Synced LNBits payment history: {} payments, {} new", payments.len(), synced);
+        Ok(synced)
+    }
+    
+    /// Payment synced by `sync_payment_history`
+    pub async fn synced_payment(&self, payment_hash_hex: &str) -> Result<Option<LNBitsPayment>, LightningError> {
+        let tree_id = self.node_api.storage_open_tree(LNBITS_PAYMENTS_TREE.to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        let value = self.node_api.storage_get(tree_id, payment_hash_hex.as_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read payment history: {}", e)))?;
+        value
+            .map(|bytes| serde_json::from_slice::<LNBitsPayment>(&bytes)
+                .map_err(|e| LightningError::ProcessorError(format!("Corrupt payment history record: {}", e))))
+            .transpose()
+    }
+    
+    /// Check provider health and NodeAPI connectivity
+    ///
+    /// Fails if the node can't be reached. If the provider reports a block
+    /// height but no sync status, it is compared against the node's height.
+    pub async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        let mut status = self.provider().health_check().await?;
+        
+        let node_height = self.node_api.get_block_height().await
+            .map_err(|e| LightningError::NodeConnectionError(format!("NodeAPI unreachable: {}", e)))?;
+        
+        if status.synced_to_chain.is_none() {
+            status.synced_to_chain = status.block_height.map(|height| height >= node_height);
+        }
+        Ok(status)
+    }
+    
+    /// Spawn a background task that logs a health check periodically
+    ///
+    /// Returns `None` if `lightning.health_check_interval_seconds` is 0.
+    pub fn spawn_health_monitor(self: &Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        if self.health_check_interval_seconds == 0 {
+            return None;
+        }
+        
+        let processor = Arc::clone(self);
+        let interval = std::time::Duration::from_secs(self.health_check_interval_seconds);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match processor.health_check().await {
+                    Ok(status) => info!(
+                        "Lightning health: provider={}, reachable={}, latency={}ms, block_height={:?}, synced={:?}, version={:?}",
+                        processor.provider_type(),
+                        status.reachable,
+                        status.latency_ms,
+                        status.block_height,
+                        status.synced_to_chain,
+                        status.version
+                    ),
+                    Err(e) => warn!("Lightning health check failed: {}", e),
+                }
+            }
+        }))
+    }
+    
+    /// Query the wallet balance, storing it as `balance_msats` in `lightning_config`
+    ///
+    /// Logs a warning if the balance is below `lightning.min_balance_msats`,
+    /// as payouts will start failing once the wallet runs dry.
+    pub async fn check_balance(&self) -> Result<u64, LightningError> {
+        let balance_msats = self.provider().balance_msats().await?;
+        
+        let tree_id = self.node_api.storage_open_tree("lightning_config".to_string()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+        self.node_api.storage_insert(tree_id, b"balance_msats".to_vec(), balance_msats.to_be_bytes().to_vec()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to store balance_msats: {}", e)))?;
+        
+        match self.min_balance_msats {
+            Some(min_balance_msats) if balance_msats < min_balance_msats => warn!(
+                "Lightning wallet balance low: {} msats (minimum {} msats)",
+                balance_msats, min_balance_msats
+            ),
+            _ => debug!("Lightning wallet balance: {} msats", balance_msats),
+        }
+        Ok(balance_msats)
+    }
+    
+    /// Spawn a background task that checks the wallet balance periodically
+    ///
+    /// Returns `None` unless `lightning.min_balance_msats` is set, or if
+    /// `lightning.balance_check_interval_seconds` is 0. The task stops on a
+    /// configuration error (e.g. a rejected API key), which won't fix itself.
+    pub fn spawn_balance_monitor(self: &Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        if self.min_balance_msats.is_none() || self.balance_check_interval_seconds == 0 {
+            return None;
+        }
+        
+        let processor = Arc::clone(self);
+        let interval = std::time::Duration::from_secs(self.balance_check_interval_seconds);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match processor.check_balance().await {
+                    Ok(_) => {}
+                    Err(LightningError::ConfigError(e)) => {
+                        error!("Lightning balance monitor stopped: {}", e);
+                        return;
+                    }
+                    Err(e) => warn!("Lightning balance check failed: {}", e),
+                }
+            }
+        }))
+    }
+    
+    /// Re-read the reloadable config keys from `source` and apply any changes
+    ///
+    /// Rate limits change in place. Changes to any other reloadable key
+    /// recreate the provider from the updated config, so `lightning.provider`
+    /// can name a different backend; an LDK provider ignores changes to the
+    /// LNBits keys. If the new provider can't be created the old provider and
+    /// config stay in place, still running, and the error is returned.
+    ///
+    /// An LDK provider is handed over once its replacement is in place: its
+    /// background tasks are stopped, but its payment state isn't
+    /// written again, since the replacement loaded it from the data
+    /// directory and owns it from then on.
+    pub async fn refresh_config(&self, source: &dyn ConfigSource) -> Result<ConfigReload, LightningError> {
+        let mut fetched = Vec::with_capacity(RELOADABLE_CONFIG_KEYS.len());
+        for key in RELOADABLE_CONFIG_KEYS {
+            fetched.push((*key, source.get_module_config(key).await?));
+        }
+        
+        let mut config: HashMap<String, String> = self.ctx.read().unwrap().config.clone();
+        let mut reload = ConfigReload::default();
+        for (key, value) in fetched {
+            let changed = match value {
+                Some(value) => config.insert(key.to_string(), value.clone()).as_ref() != Some(&value),
+                None => config.remove(key).is_some(),
+            };
+            if changed {
+                reload.changed.push(key.to_string());
+            }
+        }
+        if reload.changed.is_empty() {
+            return Ok(reload);
+        }
+        
+        let ctx = {
+            let current = self.ctx.read().unwrap();
+            ModuleContext {
+                module_id: current.module_id.clone(),
+                config,
+                data_dir: current.data_dir.clone(),
+                socket_path: current.socket_path.clone(),
+            }
+        };
+        
+        let current = self.provider();
+        let recreate = reload.changed.iter().any(|key| {
+            // The LDK provider doesn't read the LNBits keys; recreating it would only reload its state
+            !IN_PLACE_CONFIG_KEYS.contains(&key.as_str())
+                && !(key.starts_with("lightning.lnbits.") && current.as_any().downcast_ref::<LDKProvider>().is_some())
+        });
+        if recreate {
+            let provider_type_str = ctx.get_config_or("lightning.provider", "lnbits");
+            let provider_type = ProviderType::from_str(&provider_type_str)
+                .map_err(|e| LightningError::ConfigError(format!("Invalid provider type: {}", e)))?;
+            let provider: Arc<dyn LightningProvider> = Arc::from(create_provider(provider_type, &ctx)?);
+            provider.attach_storage(self.node_api.clone()).await?;
+            store_provider_info(self.node_api.as_ref(), provider.as_ref()).await?;
+            *self.provider.write().unwrap() = provider;
+            reload.provider_recreated = true;
+            // Two LDK providers on one data directory would overwrite each other's state
+            if let Ok(ldk) = Self::ldk_provider(current.as_ref()) {
+                ldk.hand_over().await;
+            }
+            info!("Recreated Lightning provider ({}) with reloaded config", provider_type);
+        }
+        
+        self.rate_limit_max_per_window.store(
+            ctx.get_config_or("lightning.rate_limit.max_per_minute", "").parse::<u32>().unwrap_or(0),
+            Ordering::Relaxed,
+        );
+        self.rate_limit_window_seconds.store(
+            ctx.get_config_or("lightning.rate_limit.window_seconds", "")
+                .parse::<u64>()
+                .unwrap_or(DEFAULT_RATE_LIMIT_WINDOW_SECONDS),
+            Ordering::Relaxed,
+        );
+        
+        *self.ctx.write().unwrap() = ctx;
+        info!("Reloaded config: {}", reload.changed.join(", "));
+        Ok(reload)
+    }
+    
+    /// Refresh config from `source` every `lightning.config_refresh_interval_seconds`, if set
+    ///
+    /// A failed refresh is logged and retried on the next interval.
+    pub fn spawn_config_refresh(self: &Arc<Self>, source: Arc<dyn ConfigSource>) -> Option<tokio::task::JoinHandle<()>> {
+        if self.config_refresh_interval_seconds == 0 {
+            return None;
+        }
+        
+        let processor = Arc::clone(self);
+        let interval = std::time::Duration::from_secs(self.config_refresh_interval_seconds);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick fires immediately; the config was just loaded
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = processor.refresh_config(source.as_ref()).await {
+                    warn!("Config refresh failed: {}", e);
+                }
+            }
+        }))
+    }
+    
+    /// Check unconfirmed payments with the provider once, settling confirmed ones
+    ///
+    /// Checks at most `lightning.poller.batch_size` payments, continuing
+    /// after the last payment the previous poll checked, and returns how many
+    /// were settled. A failed check leaves the payment for the next round.
+    /// Pending invoices past their TTL are cleaned up first.
+    pub async fn poll_confirmations(&self) -> Result<usize, LightningError> {
+        if let Err(e) = self.prune_pending_invoices(now_unix()).await {
+            warn!("Pending invoice cleanup failed: {}", e);
+        }
+        let cursor = self.poll_cursor.lock().unwrap().clone();
+        let mut payments = self.load_unconfirmed_payments(cursor.as_deref(), self.poller_batch_size).await?;
+        if payments.is_empty() && cursor.is_some() {
+            // Reached the end; start over from the first payment
+            payments = self.load_unconfirmed_payments(None, self.poller_batch_size).await?;
+        }
+        *self.poll_cursor.lock().unwrap() = payments.last().map(|payment| payment.payment_id.clone());
+        self.settle_confirmed(payments).await
+    }
+    
+    /// Ask the provider about each payment and settle the confirmed ones
+    ///
+    /// A confirmed payment is verified again and its amount checked before
+    /// it settles; an underpaid one fails. An unconfirmed payment older than `lightning.poller.max_age_seconds`
+    /// is failed, and a stale `InFlight` one goes back to `Pending`.
+    ///
+    /// Each payment is handled under its payment hash lock, like
+    /// `process_payment`. A payment whose hash was processed in the meantime
+    /// is skipped, and failed as a duplicate if another payment settled it.
+    async fn settle_confirmed(&self, payments: Vec<StoredPayment>) -> Result<usize, LightningError> {
+        let mut settled = 0;
+        for mut payment in payments {
+            let payment_hash = match decode_hash(&payment.payment_hash) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    warn!("Skipping payment {} with invalid hash: {}", payment.payment_id, e);
+                    continue;
+                }
+            };
+            let _guard = self.payment_hash_locks.lock(&payment.payment_hash).await;
+            if let Some(previous) = self.seen_within_last(&payment.payment_hash, u64::MAX).await? {
+                if previous.payment_id != payment.payment_id {
+                    warn!(
+                        "Duplicate payment hash {} for payment_id: {} (already processed as {})",
+                        payment.payment_hash, payment.payment_id, previous.payment_id
+                    );
+                    payment.state = PaymentState::Failed {
+                        reason: "duplicate payment hash detected".to_string(),
+                        failed_at: now_unix(),
+                    };
+                    self.store_payment(&payment).await?;
+                } else {
+                    debug!("Payment {} already settled", payment.payment_id);
+                }
+                continue;
+            }
+            match self.provider().is_payment_confirmed(&payment_hash).await {
+                Ok(true) => {
+                    // Confirmation says nothing about the amount; verify it like process_payment does
+                    let invoice_data = match self.parse_invoice(&payment.invoice) {
+                        Ok(invoice_data) => invoice_data,
+                        Err(e) => {
+                            warn!("Skipping payment {} with invalid invoice: {}", payment.payment_id, e);
+                            continue;
+                        }
+                    };
+                    let result = match self.provider().verify_payment(&payment.invoice, &payment_hash, &payment.payment_id).await {
+                        Ok(result) if result.verified => result,
+                        Ok(_) => {
+                            debug!("Payment confirmed but not verified yet: payment_id={}", payment.payment_id);
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!("Failed to verify payment {}: {}", payment.payment_id, e);
+                            continue;
+                        }
+                    };
+                    self.store_payment_metadata(&payment.payment_id, &result.metadata).await?;
+                    payment.metadata = result.metadata.clone();
+                    if let Err(e) = self.check_amount(&invoice_data, &result) {
+                        warn!("In-flight payment rejected: payment_id={}: {}", payment.payment_id, e);
+                        payment.state = PaymentState::Failed { reason: e.to_string(), failed_at: now_unix() };
+                        self.store_payment(&payment).await?;
+                        self.notify_webhook(WebhookEvent::PaymentFailed {
+                            payment_id: payment.payment_id.clone(),
+                            reason: e.to_string(),
+                        }).await;
+                        continue;
+                    }
+                    payment.amount_msats = result.amount_msats.or(payment.amount_msats);
+                    payment.preimage = result.metadata.get("preimage")
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string())
+                        .or(payment.preimage);
+                    payment.state = PaymentState::Settled { settled_at: now_unix() };
+                    self.store_payment(&payment).await?;
+                    self.record_processed(&payment.payment_hash, &payment.payment_id, false).await?;
+                    info!("In-flight payment settled: payment_id={}", payment.payment_id);
+                    self.notify_webhook(WebhookEvent::PaymentReceived {
+                        payment_id: payment.payment_id.clone(),
+                        payment_hash: payment.payment_hash.clone(),
+                        amount_msats: payment.amount_msats.unwrap_or(0),
+                        fee_msats: payment.fee_msats,
+                    }).await;
+                    settled += 1;
+                }
+                Ok(false) => {
+                    let now = now_unix();
+                    if now.saturating_sub(payment.created_at) > self.poller_max_age_seconds {
+                        let reason = format!("not confirmed within {}s", self.poller_max_age_seconds);
+                        warn!("Giving up on payment {}: {}", payment.payment_id, reason);
+                        payment.state = PaymentState::Failed { reason: reason.clone(), failed_at: now };
+                        self.store_payment(&payment).await?;
+                        self.notify_webhook(WebhookEvent::PaymentFailed {
+                            payment_id: payment.payment_id.clone(),
+                            reason,
+                        }).await;
+                    } else if payment.state == PaymentState::InFlight
+                        && now.saturating_sub(payment.last_attempt_at.unwrap_or(payment.created_at)) > STALE_INFLIGHT_SECONDS
+                    {
+                        debug!("Interrupted verification left payment {} in flight, moving it back to pending", payment.payment_id);
+                        payment.state = PaymentState::Pending;
+                        self.store_payment(&payment).await?;
+                    } else {
+                        debug!("Payment not confirmed yet: payment_id={}", payment.payment_id);
+                    }
+                }
+                Err(e) => warn!("Failed to check payment {}: {}", payment.payment_id, e),
+            }
+        }
+        Ok(settled)
+    }
+    
+    /// Give in-flight payments a window to settle before shutting down
+    ///
+    /// Polls every `InFlight` payment until none are left or `timeout`
+    /// elapses, in which case the payments still in flight are reported in
+    /// the error. They stay `InFlight` in storage and are picked up by the
+    /// confirmation poller after a restart. Payments that were verified but
+    /// not paid yet are `Pending` and aren't waited for.
+    pub async fn drain_inflight(&self, timeout: std::time::Duration) -> Result<(), LightningError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let payments = self.load_inflight_payments().await?;
+            if payments.is_empty() {
+                info!("No payments in flight");
+                return Ok(());
+            }
+            self.settle_confirmed(payments).await?;
+            let remaining = self.load_inflight_payments().await?.len();
+            if remaining == 0 {
+                info!("All in-flight payments settled");
+                return Ok(());
+            }
+            if tokio::time::Instant::now() + DRAIN_POLL_INTERVAL >= deadline {
+                return Err(LightningError::ProcessorError(format!(
+                    "{} payments still in flight after {}s drain timeout",
+                    remaining,
+                    timeout.as_secs_f64()
+                )));
+            }
+            debug!("Waiting for {} in-flight payments to settle", remaining);
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+    
+    /// Stop the LDK provider's background tasks and write its payment state on shutdown; other providers have nothing to stop
+    pub async fn shutdown_provider(&self) {
+        let provider = self.provider();
+        if let Ok(ldk) = Self::ldk_provider(provider.as_ref()) {
+            ldk.shutdown().await;
+        }
+    }
+    
+    /// Time `main` allows in-flight payments to settle on shutdown (`lightning.shutdown.drain_timeout_seconds`)
+    pub fn drain_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.drain_timeout_seconds)
+    }
+    
+    /// Spawn a background task that polls in-flight payments for confirmation
+    ///
+    /// Each poll runs in its own task, so a panic while checking one batch is
+    /// logged and the next tick carries on.
+    pub async fn start_confirmation_poller(self: Arc<Self>, poll_interval_seconds: u64) -> tokio::task::JoinHandle<()> {
+        let interval = std::time::Duration::from_secs(poll_interval_seconds.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let processor = Arc::clone(&self);
+                match tokio::spawn(async move { processor.poll_confirmations().await }).await {
+                    Ok(Ok(settled)) if settled > 0 => info!("Confirmation poll settled {} payments", settled),
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!("Confirmation poll failed: {}", e),
+                    Err(e) if e.is_panic() => warn!("Confirmation poll panicked: {}", e),
+                    Err(e) => warn!("Confirmation poll cancelled: {}", e),
+                }
+            }
+        })
+    }
+    
+    /// Interval between confirmation polls (`lightning.poller.interval_seconds`, 0 disables polling)
+    pub fn poller_interval_seconds(&self) -> u64 {
+        self.poller_interval_seconds
+    }
+    
+    /// Serve Prometheus metrics on `lightning.metrics_port`, if configured
+    pub fn spawn_metrics_server(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let addr = self.metrics_addr?;
+        let metrics = self.metrics.clone();
+        Some(tokio::spawn(async move {
+            if let Err(e) = metrics.serve(addr).await {
+                warn!("Metrics endpoint stopped: {}", e);
+            }
+        }))
+    }
+    
+    /// Receive payment webhooks on `lightning.webhook_listen`, if configured
+    pub fn spawn_webhook_server(self: &Arc<Self>) -> Option<tokio::task::JoinHandle<()>> {
+        let addr = self.webhook_addr?;
+        let processor = Arc::clone(self);
+        Some(tokio::spawn(async move {
+            if let Err(e) = crate::webhook::serve(processor, addr).await {
+                warn!("Webhook listener stopped: {}", e);
+            }
+        }))
+    }
+    
+    /// Payment and invoice counters
+    pub fn metrics(&self) -> &MetricsCollector {
+        &self.metrics
+    }
+    
+    /// Get the provider type
+    pub fn provider_type(&self) -> ProviderType {
+        self.provider().provider_type()
+    }
+    
+    /// The current provider (downcast via `as_any` for provider-specific operations)
+    ///
+    /// A config refresh may replace the provider; the returned handle keeps
+    /// working with the one it was taken from.
+    pub fn provider(&self) -> Arc<dyn LightningProvider> {
+        Arc::clone(&self.provider.read().unwrap())
+    }
+    
+    /// Get the operations supported by the configured provider
+    pub fn capabilities(&self) -> ProviderCapabilities {
+        self.provider().capabilities()
+    }
+}
+
+/// Store the provider type and capabilities in the `lightning_config` tree,
+/// so other modules can see what's supported; returns the tree id
+async fn store_provider_info(node_api: &dyn NodeAPI, provider: &dyn LightningProvider) -> Result<String, LightningError> {
+    let tree_id = node_api.storage_open_tree("lightning_config".to_string()).await
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to open storage tree: {}", e)))?;
+    
+    // Store provider type
+    let provider_type_str = provider.provider_type().as_str();
+    node_api.storage_insert(tree_id.clone(), b"provider_type".to_vec(), provider_type_str.as_bytes().to_vec()).await
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to store provider_type: {}", e)))?;
+    
+    // Store provider capabilities
+    let capabilities = provider.capabilities();
+    info!("Provider capabilities: {:?}", capabilities);
+    let capabilities_json = serde_json::to_vec(&capabilities)
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize capabilities: {}", e)))?;
+    node_api.storage_insert(tree_id.clone(), b"capabilities".to_vec(), capabilities_json).await
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to store capabilities: {}", e)))?;
+    
+    Ok(tree_id)
+}
+
+/// Async locks keyed by string, e.g. payment id
+///
+/// Calls for different keys don't wait for each other. A key's lock is
+/// dropped once nobody holds or waits for it.
+#[derive(Default)]
+struct KeyLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl KeyLocks {
+    /// Wait for the lock on `key`
+    async fn lock(&self, key: &str) -> KeyGuard<'_> {
+        let lock = self.locks.lock().unwrap().entry(key.to_string()).or_default().clone();
+        let guard = lock.lock_owned().await;
+        KeyGuard { locks: self, key: key.to_string(), guard: Some(guard) }
+    }
+}
+
+/// Lock on one key of a `KeyLocks`
+struct KeyGuard<'a> {
+    locks: &'a KeyLocks,
+    key: String,
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+}
+
+impl Drop for KeyGuard<'_> {
+    fn drop(&mut self) {
+        self.guard.take();
+        let mut locks = self.locks.locks.lock().unwrap();
+        // Only the map's own reference left: nobody holds or waits for it
+        if locks.get(&self.key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+/// Decode a hex-encoded 32-byte hash
+fn decode_hash(hex_str: &str) -> Result<[u8; 32], LightningError> {
+    let bytes = hex::decode(hex_str)
+        .map_err(|e| LightningError::ProcessorError(format!("Invalid hash hex: {}", e)))?;
+    if bytes.len() != 32 {
+        return Err(LightningError::ProcessorError("Hash must be 32 bytes".to_string()));
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&bytes);
+    Ok(hash)
+}
+
+/// Current unix time in seconds
+fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,514 @@
+//! Blink (Galoy) provider implementation
+//!
+//! Talks to Blink's GraphQL API, authenticated with an `X-API-KEY` header.
+//! Invoices are created with `lnInvoiceCreate` on a BTC wallet, or with
+//! `lnUsdInvoiceBtcDenominatedCreateOnBehalfOfRecipient` on a Stablesats
+//! (USD) wallet, and checked with `lnInvoicePaymentStatus`. Amounts are in
+//! sats, not msats.
+//!
+//! GraphQL reports failures in `errors` arrays, either at the top level or
+//! inside a mutation payload, usually with an HTTP 200; both are mapped to
+//! `LightningError` variants by code.
+
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind,
+};
+use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+/// Blink's public GraphQL endpoint
+pub const DEFAULT_BLINK_URL: &str = "https://api.blink.sv/graphql";
+
+/// Blink provider configuration
+#[derive(Debug, Clone)]
+pub struct BlinkConfig {
+    /// GraphQL endpoint
+    pub url: String,
+    /// API key (`blink_...`) with receive and read scopes
+    pub api_key: String,
+    /// Wallet to receive into; the account's BTC wallet if unset
+    pub wallet_id: Option<String>,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+impl Default for BlinkConfig {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_BLINK_URL.to_string(),
+            api_key: String::new(),
+            wallet_id: None,
+            retry_policy: ProviderRetryPolicy::default(),
+        }
+    }
+}
+
+/// Currency a Blink wallet is denominated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum WalletCurrency {
+    Btc,
+    /// Stablesats
+    Usd,
+}
+
+/// Wallet invoices are created on
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlinkWallet {
+    pub id: String,
+    pub wallet_currency: WalletCurrency,
+}
+
+/// An entry of a GraphQL `errors` array
+#[derive(Debug, Clone, Deserialize)]
+struct GraphQlError {
+    message: String,
+    /// Mutation payload errors carry the code directly
+    #[serde(default)]
+    code: Option<String>,
+    /// Top-level errors carry it in `extensions`
+    #[serde(default)]
+    extensions: Option<Value>,
+}
+
+impl GraphQlError {
+    fn code(&self) -> Option<&str> {
+        self.code
+            .as_deref()
+            .or_else(|| self.extensions.as_ref()?.get("code")?.as_str())
+    }
+}
+
+/// Map GraphQL errors to a `LightningError`, by the code of the first error
+fn map_errors(operation: &str, errors: &[GraphQlError]) -> LightningError {
+    let first = match errors.first() {
+        Some(first) => first,
+        None => return LightningError::ProcessorError(format!("Blink {} failed without an error message", operation)),
+    };
+    let message = errors.iter().map(|e| e.message.as_str()).collect::<Vec<_>>().join("; ");
+    match first.code().unwrap_or_default() {
+        "UNAUTHENTICATED" | "NOT_AUTHENTICATED" | "NOT_AUTHORIZED" | "FORBIDDEN" | "INVALID_AUTHORIZATION" => {
+            LightningError::ConfigError(format!("Blink rejected the API key: {}", message))
+        }
+        "TOO_MANY_REQUEST" | "TOO_MANY_REQUESTS" | "RATE_LIMITED" => LightningError::HttpError {
+            status_code: 429,
+            body: message,
+        },
+        "INVALID_INPUT" | "INVALID_COIN_AMOUNT" | "INVALID_PAYMENT_REQUEST" | "INVOICE_AMOUNT_TOO_LARGE"
+        | "INVALID_WALLET_ID" => LightningError::InvoiceError(format!("Blink {}: {}", operation, message)),
+        "NOT_FOUND" | "INVOICE_NOT_FOUND" | "PAYMENT_NOT_FOUND" => {
+            LightningError::PaymentVerificationFailed(format!("Blink {}: {}", operation, message))
+        }
+        "SERVICE_UNAVAILABLE" | "DEALER_OFFLINE" => {
+            LightningError::NodeConnectionError(format!("Blink {}: {}", operation, message))
+        }
+        _ => LightningError::ProcessorError(format!("Blink {} error: {}", operation, message)),
+    }
+}
+
+/// GraphQL response envelope
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+/// Payload of `lnInvoiceCreate` and its USD-wallet counterpart
+#[derive(Debug, Deserialize)]
+struct InvoiceCreatePayload {
+    #[serde(default)]
+    invoice: Option<LnInvoice>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LnInvoice {
+    payment_request: String,
+    payment_hash: String,
+    #[serde(default)]
+    satoshis: Option<u64>,
+}
+
+/// Payload of `lnInvoicePaymentStatus`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentStatusPayload {
+    #[serde(default)]
+    status: Option<InvoicePaymentStatus>,
+    #[serde(default)]
+    payment_preimage: Option<String>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum InvoicePaymentStatus {
+    Pending,
+    Paid,
+    Expired,
+}
+
+const WALLETS_QUERY: &str = "query Wallets { me { defaultAccount { wallets { id walletCurrency } } } }";
+
+const BTC_INVOICE_MUTATION: &str = "mutation LnInvoiceCreate($input: LnInvoiceCreateInput!) { \
+    lnInvoiceCreate(input: $input) { invoice { paymentRequest paymentHash satoshis } errors { message code path } } }";
+
+const USD_INVOICE_MUTATION: &str = "mutation LnUsdInvoiceBtcDenominatedCreate($input: LnUsdInvoiceBtcDenominatedCreateOnBehalfOfRecipientInput!) { \
+    lnUsdInvoiceBtcDenominatedCreateOnBehalfOfRecipient(input: $input) { invoice { paymentRequest paymentHash satoshis } errors { message code path } } }";
+
+const PAYMENT_STATUS_QUERY: &str = "query LnInvoicePaymentStatus($input: LnInvoicePaymentStatusInput!) { \
+    lnInvoicePaymentStatus(input: $input) { status paymentPreimage errors { message code path } } }";
+
+const PAYMENT_STATUS_BY_HASH_QUERY: &str = "query LnInvoicePaymentStatusByHash($input: LnInvoicePaymentStatusByHashInput!) { \
+    lnInvoicePaymentStatusByHash(input: $input) { status paymentPreimage errors { message code path } } }";
+
+const HEALTH_QUERY: &str = "query Me { me { id } }";
+
+/// Blink provider implementation
+pub struct BlinkProvider {
+    config: BlinkConfig,
+    http_client: Client,
+    /// Receiving wallet, looked up on first use
+    wallet: OnceCell<BlinkWallet>,
+}
+
+impl BlinkProvider {
+    /// Create a new Blink provider
+    pub fn new(config: BlinkConfig) -> Result<Self, LightningError> {
+        if config.api_key.is_empty() {
+            return Err(LightningError::ConfigError("lightning.blink.api_key is required".to_string()));
+        }
+        let http_client = Client::builder()
+            .timeout(config.retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            config,
+            http_client,
+            wallet: OnceCell::new(),
+        })
+    }
+
+    /// Run a GraphQL operation and return its `data`
+    ///
+    /// Top-level GraphQL errors are mapped to `LightningError`. Idempotent
+    /// operations (queries) are retried according to the retry policy.
+    async fn graphql<T: DeserializeOwned>(
+        &self,
+        operation: &str,
+        query: &str,
+        variables: Value,
+        idempotent: bool,
+    ) -> Result<T, LightningError> {
+        let policy = &self.config.retry_policy;
+        let body = json!({ "query": query, "variables": variables });
+        let mut attempt: u32 = 0;
+
+        loop {
+            let response = match self
+                .http_client
+                .post(&self.config.url)
+                .header("X-API-KEY", &self.config.api_key)
+                .json(&body)
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if idempotent && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("Blink {} failed (attempt {}/{}): {}", operation, attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("Blink request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && idempotent && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("Blink {} returned {} (attempt {}/{})", operation, status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+                return Err(LightningError::ConfigError(format!("Blink rejected the API key ({})", status)));
+            }
+
+            let text = response.text().await.unwrap_or_default();
+            // GraphQL servers may report errors with a 4xx and an `errors` body
+            let parsed = serde_json::from_str::<GraphQlResponse<T>>(&text);
+            return match parsed {
+                Ok(GraphQlResponse { errors, .. }) if !errors.is_empty() => Err(map_errors(operation, &errors)),
+                Ok(GraphQlResponse { data: Some(data), .. }) if status.is_success() => Ok(data),
+                _ if !status.is_success() => Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: text,
+                }),
+                Ok(_) => Err(LightningError::ProcessorError(format!("Blink {} returned no data", operation))),
+                Err(e) => Err(LightningError::ProcessorError(format!("Failed to parse Blink response: {}", e))),
+            };
+        }
+    }
+
+    /// The wallet invoices are created on
+    ///
+    /// The configured `wallet_id` if set (it must belong to the account),
+    /// otherwise the account's BTC wallet.
+    pub async fn wallet(&self) -> Result<&BlinkWallet, LightningError> {
+        self.wallet
+            .get_or_try_init(|| async {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "camelCase")]
+                struct Account {
+                    wallets: Vec<BlinkWallet>,
+                }
+                #[derive(Deserialize)]
+                #[serde(rename_all = "camelCase")]
+                struct Me {
+                    default_account: Account,
+                }
+                #[derive(Deserialize)]
+                struct Data {
+                    me: Me,
+                }
+
+                let data: Data = self.graphql("wallet lookup", WALLETS_QUERY, json!({}), true).await?;
+                let wallets = data.me.default_account.wallets;
+                let wallet = match &self.config.wallet_id {
+                    Some(wallet_id) => wallets.into_iter().find(|wallet| &wallet.id == wallet_id).ok_or_else(|| {
+                        LightningError::ConfigError(format!("Blink wallet {} not found on this account", wallet_id))
+                    })?,
+                    None => wallets
+                        .into_iter()
+                        .find(|wallet| wallet.wallet_currency == WalletCurrency::Btc)
+                        .ok_or_else(|| LightningError::ConfigError("Blink account has no BTC wallet".to_string()))?,
+                };
+                debug!("Using Blink {:?} wallet {}", wallet.wallet_currency, wallet.id);
+                Ok(wallet)
+            })
+            .await
+    }
+
+    /// Look up an invoice's payment status, by invoice if we have one and by hash otherwise
+    async fn payment_status(&self, invoice: &str, payment_hash: &[u8; 32]) -> Result<PaymentStatusPayload, LightningError> {
+        let payload = if invoice.is_empty() {
+            #[derive(Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            struct Data {
+                ln_invoice_payment_status_by_hash: PaymentStatusPayload,
+            }
+            let variables = json!({ "input": { "paymentHash": hex::encode(payment_hash) } });
+            let data: Data = self.graphql("payment status", PAYMENT_STATUS_BY_HASH_QUERY, variables, true).await?;
+            data.ln_invoice_payment_status_by_hash
+        } else {
+            #[derive(Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            struct Data {
+                ln_invoice_payment_status: PaymentStatusPayload,
+            }
+            let variables = json!({ "input": { "paymentRequest": invoice } });
+            let data: Data = self.graphql("payment status", PAYMENT_STATUS_QUERY, variables, true).await?;
+            data.ln_invoice_payment_status
+        };
+        if !payload.errors.is_empty() {
+            return Err(map_errors("payment status", &payload.errors));
+        }
+        Ok(payload)
+    }
+}
+
+#[async_trait]
+impl LightningProvider for BlinkProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "blink", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via Blink: payment_id={}", payment_id);
+
+        let payload = match self.payment_status(invoice, payment_hash).await {
+            Ok(payload) => payload,
+            Err(LightningError::PaymentVerificationFailed(message)) => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: json!({
+                        "provider": "blink",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "invoice_not_found",
+                        "message": message,
+                    }),
+                });
+            }
+            Err(e) => return Err(e),
+        };
+
+        let status = payload.status.unwrap_or(InvoicePaymentStatus::Pending);
+        let paid = status == InvoicePaymentStatus::Paid;
+        debug!("Blink payment {}: {:?}", hex::encode(payment_hash), status);
+
+        // The status payload has no amount; a paid invoice was paid in full
+        let amount_msats = InvoiceParser::parse(invoice)
+            .ok()
+            .map(|data| data.amount_msats)
+            .filter(|amount| paid && *amount > 0);
+
+        Ok(PaymentVerificationResult {
+            verified: paid,
+            amount_msats,
+            timestamp: None,
+            metadata: json!({
+                "provider": "blink",
+                "payment_hash": hex::encode(payment_hash),
+                "status": format!("{:?}", status).to_lowercase(),
+                "preimage": payload.payment_preimage,
+                "expired": status == InvoicePaymentStatus::Expired,
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "blink", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let memo = match &params.description {
+            DescriptionKind::Direct(description) => description.clone(),
+            DescriptionKind::Hash(_) => {
+                return Err(LightningError::InvoiceError(
+                    "Blink does not support description hashes".to_string(),
+                ));
+            }
+        };
+        // Blink only takes whole sats; round up so the payer never pays less than requested
+        let amount_sats = params.amount_msats.div_ceil(1000);
+        // Blink's expiry is in minutes
+        let expires_in = params.expiry_seconds.div_ceil(60).max(1);
+
+        let wallet = self.wallet().await?;
+        debug!("Creating invoice via Blink: amount={} sats, wallet={:?}", amount_sats, wallet.wallet_currency);
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Data {
+            #[serde(alias = "lnUsdInvoiceBtcDenominatedCreateOnBehalfOfRecipient")]
+            ln_invoice_create: InvoiceCreatePayload,
+        }
+
+        // Invoice creation has no idempotency key, so it is never retried
+        let data: Data = match wallet.wallet_currency {
+            WalletCurrency::Btc => {
+                let variables = json!({ "input": {
+                    "walletId": wallet.id,
+                    "amount": amount_sats,
+                    "memo": memo,
+                    "expiresIn": expires_in,
+                }});
+                self.graphql("invoice creation", BTC_INVOICE_MUTATION, variables, false).await?
+            }
+            WalletCurrency::Usd => {
+                // Priced in sats so the amount doesn't move with the exchange rate
+                let variables = json!({ "input": {
+                    "recipientWalletId": wallet.id,
+                    "amount": amount_sats,
+                    "memo": memo,
+                    "expiresIn": expires_in,
+                }});
+                self.graphql("invoice creation", USD_INVOICE_MUTATION, variables, false).await?
+            }
+        };
+
+        let payload = data.ln_invoice_create;
+        if !payload.errors.is_empty() {
+            return Err(map_errors("invoice creation", &payload.errors));
+        }
+        let invoice = payload
+            .invoice
+            .ok_or_else(|| LightningError::ProcessorError("Blink returned no invoice".to_string()))?;
+
+        if let Some(satoshis) = invoice.satoshis {
+            if satoshis != amount_sats {
+                warn!("Blink invoice is for {} sats, requested {}", satoshis, amount_sats);
+            }
+        }
+        debug!("Blink invoice created: payment_hash={}", invoice.payment_hash);
+        Ok(invoice.payment_request)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        match self.payment_status("", payment_hash).await {
+            Ok(payload) => Ok(payload.status == Some(InvoicePaymentStatus::Paid)),
+            Err(LightningError::PaymentVerificationFailed(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        let started = std::time::Instant::now();
+        let result = self.graphql::<Value>("health check", HEALTH_QUERY, json!({}), true).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        if let Err(e) = &result {
+            warn!("Blink health check failed: {}", e);
+        }
+        Ok(HealthStatus {
+            reachable: result.is_ok(),
+            latency_ms,
+            block_height: None,
+            synced_to_chain: None,
+            version: None,
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Blink
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,219 @@
+//! Chain sources for the LDK provider
+//!
+//! The LDK provider follows the chain through a `ChainSource`: the best
+//! block, to know when it is synced, and fee rates for on-chain
+//! transactions. `BitcoindChainSource` reads both from a bitcoind JSON-RPC
+//! interface (`lightning.ldk.chain_source = "bitcoind"`).
+
+use crate::error::LightningError;
+use crate::provider::retry::{retry_idempotent, ProviderRetryPolicy};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Chain source configuration
+#[derive(Debug, Clone)]
+pub enum ChainSourceConfig {
+    /// bitcoind JSON-RPC
+    Bitcoind(BitcoindConfig),
+}
+
+/// bitcoind JSON-RPC connection
+#[derive(Debug, Clone, Default)]
+pub struct BitcoindConfig {
+    /// RPC URL (e.g. `http://127.0.0.1:8332`)
+    pub url: String,
+    /// RPC user (`rpcuser`/`rpcauth`)
+    pub user: Option<String>,
+    /// RPC password
+    pub pass: Option<String>,
+    /// `.cookie` file, used when no user is configured
+    pub cookie_file: Option<PathBuf>,
+}
+
+/// Create the chain source for a configuration
+pub fn create_chain_source(
+    config: &ChainSourceConfig,
+    retry_policy: ProviderRetryPolicy,
+) -> Result<Box<dyn ChainSource>, LightningError> {
+    Ok(match config {
+        ChainSourceConfig::Bitcoind(config) => Box::new(BitcoindChainSource::new(config.clone(), retry_policy)?),
+    })
+}
+
+/// Chain tip as seen by a chain source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BestBlock {
+    pub height: u32,
+    /// Block hash (hex, as displayed by block explorers)
+    pub hash: String,
+}
+
+/// Source of chain data for the LDK provider
+#[async_trait]
+pub trait ChainSource: Send + Sync {
+    /// Current chain tip
+    async fn best_block(&self) -> Result<BestBlock, LightningError>;
+
+    /// Fee rate expected to confirm within `target_blocks`, in sat/vB
+    async fn fee_rate_sat_per_vb(&self, target_blocks: u32) -> Result<f64, LightningError>;
+}
+
+/// bitcoind JSON-RPC chain source
+pub struct BitcoindChainSource {
+    config: BitcoindConfig,
+    http_client: Client,
+    retry_policy: ProviderRetryPolicy,
+}
+
+/// JSON-RPC 1.0 response envelope
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: serde_json::Value,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Fields read from `getblock <hash> 1`
+#[derive(Debug, Deserialize)]
+struct RpcBlock {
+    hash: String,
+    height: u32,
+}
+
+/// `estimatesmartfee` result; `feerate` is in BTC/kvB and absent without enough data
+#[derive(Debug, Deserialize)]
+struct RpcFeeEstimate {
+    #[serde(default)]
+    feerate: Option<f64>,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+impl BitcoindChainSource {
+    /// Create a chain source for a bitcoind RPC interface
+    pub fn new(config: BitcoindConfig, retry_policy: ProviderRetryPolicy) -> Result<Self, LightningError> {
+        let parsed = reqwest::Url::parse(&config.url)
+            .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.ldk.bitcoind.url {}: {}", config.url, e)))?;
+        if !matches!(parsed.scheme(), "https" | "http") {
+            return Err(LightningError::ConfigError(format!(
+                "Invalid lightning.ldk.bitcoind.url {}: not http(s)",
+                config.url
+            )));
+        }
+        if config.user.is_none() && config.cookie_file.is_none() {
+            return Err(LightningError::ConfigError(
+                "bitcoind chain source requires lightning.ldk.bitcoind.user or cookie_file".to_string(),
+            ));
+        }
+        let http_client = Client::builder()
+            .timeout(retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            config,
+            http_client,
+            retry_policy,
+        })
+    }
+
+    /// RPC credentials, reading the cookie file on every call since bitcoind rewrites it on restart
+    fn credentials(&self) -> Result<(String, String), LightningError> {
+        if let Some(user) = &self.config.user {
+            return Ok((user.clone(), self.config.pass.clone().unwrap_or_default()));
+        }
+        let cookie_file = self.config.cookie_file.as_ref().ok_or_else(|| {
+            LightningError::ConfigError("No bitcoind RPC credentials configured".to_string())
+        })?;
+        let cookie = std::fs::read_to_string(cookie_file).map_err(|e| {
+            LightningError::NodeConnectionError(format!("Failed to read bitcoind cookie {}: {}", cookie_file.display(), e))
+        })?;
+        cookie
+            .trim()
+            .split_once(':')
+            .map(|(user, pass)| (user.to_string(), pass.to_string()))
+            .ok_or_else(|| LightningError::ConfigError(format!("Malformed bitcoind cookie {}", cookie_file.display())))
+    }
+
+    /// Call an RPC method, retrying transient failures
+    async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, LightningError> {
+        let body = json!({"jsonrpc": "1.0", "id": "blvm-lightning", "method": method, "params": params});
+        let response = retry_idempotent(&self.retry_policy, "bitcoind RPC", || async {
+            let (user, pass) = self.credentials()?;
+            let response = self.http_client
+                .post(&self.config.url)
+                .basic_auth(user, Some(pass))
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| LightningError::NodeConnectionError(format!("bitcoind {} failed: {}", method, e)))?;
+            let status = response.status();
+            if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+                return Err(LightningError::ConfigError(format!("bitcoind rejected the RPC credentials ({})", status)));
+            }
+            // RPC errors come back as 500 with the error in the envelope
+            let text = response.text().await.unwrap_or_default();
+            match serde_json::from_str::<RpcResponse>(&text) {
+                Ok(response) => Ok(response),
+                Err(_) if !status.is_success() => Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: text,
+                }),
+                Err(e) => Err(LightningError::ProcessorError(format!("Failed to parse bitcoind {} response: {}", method, e))),
+            }
+        })
+        .await?;
+
+        if let Some(error) = response.error {
+            return Err(LightningError::ProcessorError(format!(
+                "bitcoind {} failed: {} (code {})",
+                method, error.message, error.code
+            )));
+        }
+        serde_json::from_value(response.result)
+            .map_err(|e| LightningError::ProcessorError(format!("Unexpected bitcoind {} result: {}", method, e)))
+    }
+}
+
+#[async_trait]
+impl ChainSource for BitcoindChainSource {
+    async fn best_block(&self) -> Result<BestBlock, LightningError> {
+        let height: u32 = self.call("getblockcount", json!([])).await?;
+        let hash: String = self.call("getblockhash", json!([height])).await?;
+        let block: RpcBlock = self.call("getblock", json!([hash, 1])).await?;
+        Ok(BestBlock {
+            height: block.height,
+            hash: block.hash,
+        })
+    }
+
+    async fn fee_rate_sat_per_vb(&self, target_blocks: u32) -> Result<f64, LightningError> {
+        // estimatesmartfee accepts targets between 1 and 1008
+        let estimate: RpcFeeEstimate = self.call("estimatesmartfee", json!([target_blocks.clamp(1, 1008)])).await?;
+        // BTC/kvB -> sat/vB
+        estimate
+            .feerate
+            .map(|feerate| feerate * 100_000_000.0 / 1000.0)
+            .ok_or_else(|| {
+                LightningError::ProcessorError(format!(
+                    "bitcoind has no fee estimate for {} blocks: {}",
+                    target_blocks,
+                    estimate.errors.join("; ")
+                ))
+            })
+    }
+}
@@ -0,0 +1,164 @@
+//! Inbound channel acceptance policy for the LDK provider
+//!
+//! Every channel a peer tries to open is checked against
+//! `lightning.ldk.channel_policy.*` before it is accepted: its size, whether
+//! it is announced, how many channels the peer already has with us, and
+//! whether it may be used before the funding transaction confirms
+//! (zero-conf, which means trusting the peer not to double-spend it).
+
+use crate::error::LightningError;
+use blvm_node::module::traits::ModuleContext;
+use std::str::FromStr;
+
+/// Announced (public) or unannounced (private) channels the policy accepts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelVisibility {
+    #[default]
+    Any,
+    Public,
+    Private,
+}
+
+impl FromStr for ChannelVisibility {
+    type Err = LightningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "any" => Ok(ChannelVisibility::Any),
+            "public" => Ok(ChannelVisibility::Public),
+            "private" => Ok(ChannelVisibility::Private),
+            other => Err(LightningError::ConfigError(format!(
+                "Invalid lightning.ldk.channel_policy.visibility '{}': expected any, public or private",
+                other
+            ))),
+        }
+    }
+}
+
+/// Which inbound channel opens are accepted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelPolicy {
+    /// Smallest channel accepted
+    pub min_channel_size_sats: u64,
+    /// Largest channel accepted (no limit if unset)
+    pub max_channel_size_sats: Option<u64>,
+    /// Accept channels as zero-conf
+    pub accept_zero_conf: bool,
+    /// Peers zero-conf channels are accepted from (any peer if empty)
+    pub zero_conf_allowlist: Vec<[u8; 33]>,
+    /// Channels a single peer may have with us (no limit if unset)
+    pub max_channels_per_peer: Option<usize>,
+    pub visibility: ChannelVisibility,
+}
+
+/// A peer's request to open a channel to us
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelOpenRequest {
+    pub counterparty_node_id: [u8; 33],
+    pub funding_satoshis: u64,
+    /// The channel would be announced to the network
+    pub is_public: bool,
+    /// The opener wants to use the channel before the funding transaction confirms
+    pub requires_zero_conf: bool,
+}
+
+/// Outcome of checking a channel open against the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelDecision {
+    Accept { zero_conf: bool },
+    Reject { reason: String },
+}
+
+impl ChannelPolicy {
+    /// Read `lightning.ldk.channel_policy.*`
+    ///
+    /// Keys: `min_channel_size_sats`, `max_channel_size_sats`,
+    /// `accept_zero_conf`, `zero_conf_allowlist` (node ids, comma-separated
+    /// or a JSON array), `max_channels_per_peer` and `visibility` (`any`,
+    /// `public` or `private`).
+    pub fn from_config(ctx: &ModuleContext) -> Result<Self, LightningError> {
+        let key = |name: &str| format!("lightning.ldk.channel_policy.{}", name);
+        let parse_u64 = |name: &str| -> Result<Option<u64>, LightningError> {
+            match ctx.get_config(&key(name)).map(str::trim).filter(|s| !s.is_empty()) {
+                Some(value) => value.parse::<u64>().map(Some).map_err(|e| {
+                    LightningError::ConfigError(format!("Invalid {}: {}", key(name), e))
+                }),
+                None => Ok(None),
+            }
+        };
+        let zero_conf_allowlist = match ctx.get_config(&key("zero_conf_allowlist")).map(str::trim).filter(|s| !s.is_empty()) {
+            Some(json) if json.starts_with('[') => serde_json::from_str::<Vec<String>>(json)
+                .map_err(|e| LightningError::ConfigError(format!("Invalid {}: {}", key("zero_conf_allowlist"), e)))?,
+            Some(list) => list.split(',').map(|s| s.trim().to_string()).collect(),
+            None => Vec::new(),
+        };
+        let zero_conf_allowlist = zero_conf_allowlist
+            .iter()
+            .map(|node_id| {
+                hex::decode(node_id)
+                    .ok()
+                    .and_then(|bytes| <[u8; 33]>::try_from(bytes).ok())
+                    .ok_or_else(|| {
+                        LightningError::ConfigError(format!("Invalid node id in {}: {}", key("zero_conf_allowlist"), node_id))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let accept_zero_conf = match ctx.get_config(&key("accept_zero_conf")).map(str::trim).filter(|s| !s.is_empty()) {
+            Some(value) => value.parse::<bool>()
+                .map_err(|e| LightningError::ConfigError(format!("Invalid {}: {}", key("accept_zero_conf"), e)))?,
+            None => false,
+        };
+
+        let policy = Self {
+            min_channel_size_sats: parse_u64("min_channel_size_sats")?.unwrap_or(0),
+            max_channel_size_sats: parse_u64("max_channel_size_sats")?,
+            accept_zero_conf,
+            zero_conf_allowlist,
+            max_channels_per_peer: parse_u64("max_channels_per_peer")?.map(|n| n as usize),
+            visibility: ctx.get_config(&key("visibility")).unwrap_or_default().parse()?,
+        };
+        if policy.max_channel_size_sats.is_some_and(|max| max < policy.min_channel_size_sats) {
+            return Err(LightningError::ConfigError(format!(
+                "{} is below {}",
+                key("max_channel_size_sats"),
+                key("min_channel_size_sats")
+            )));
+        }
+        Ok(policy)
+    }
+
+    /// Whether channels from `node_id` may be used zero-conf
+    pub fn trusts_zero_conf(&self, node_id: &[u8; 33]) -> bool {
+        self.accept_zero_conf && (self.zero_conf_allowlist.is_empty() || self.zero_conf_allowlist.contains(node_id))
+    }
+
+    /// Check a channel open, given how many channels the peer already has with us
+    pub fn evaluate(&self, request: &ChannelOpenRequest, peer_channels: usize) -> ChannelDecision {
+        let reject = |reason: String| ChannelDecision::Reject { reason };
+        if request.funding_satoshis < self.min_channel_size_sats {
+            return reject(format!(
+                "channel size {} sats is below the minimum of {} sats",
+                request.funding_satoshis, self.min_channel_size_sats
+            ));
+        }
+        if let Some(max) = self.max_channel_size_sats.filter(|max| request.funding_satoshis > *max) {
+            return reject(format!(
+                "channel size {} sats is above the maximum of {} sats",
+                request.funding_satoshis, max
+            ));
+        }
+        match (self.visibility, request.is_public) {
+            (ChannelVisibility::Public, false) => return reject("only public channels are accepted".to_string()),
+            (ChannelVisibility::Private, true) => return reject("only private channels are accepted".to_string()),
+            _ => {}
+        }
+        if let Some(max) = self.max_channels_per_peer.filter(|max| peer_channels >= *max) {
+            return reject(format!("peer already has {} of at most {} channels", peer_channels, max));
+        }
+        let zero_conf = self.trusts_zero_conf(&request.counterparty_node_id);
+        if request.requires_zero_conf && !zero_conf {
+            return reject("zero-conf channels are not accepted from this peer".to_string());
+        }
+        ChannelDecision::Accept { zero_conf }
+    }
+}
@@ -0,0 +1,290 @@
+//! Circuit breaker wrapper for Lightning providers
+//!
+//! Stops calling an unhealthy provider after repeated failures so an outage
+//! doesn't turn into a stream of slow, failing requests and error logs.
+
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, PaymentStream, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use bitcoin::Txid;
+use blvm_node::module::traits::NodeAPI;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Default consecutive failures before the circuit opens
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+/// Default window in which failures must occur to count as consecutive
+pub const DEFAULT_WINDOW_SECONDS: u64 = 60;
+/// Default time the circuit stays open before allowing a trial request
+pub const DEFAULT_RESET_TIMEOUT_SECONDS: u64 = 30;
+
+/// Circuit breaker configuration
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures (within `window_seconds`) that open the circuit
+    pub failure_threshold: u32,
+    /// Failures further apart than this restart the count
+    pub window_seconds: u64,
+    /// Time the circuit stays open before a trial request is allowed
+    pub reset_timeout_seconds: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            window_seconds: DEFAULT_WINDOW_SECONDS,
+            reset_timeout_seconds: DEFAULT_RESET_TIMEOUT_SECONDS,
+        }
+    }
+}
+
+/// Circuit state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests pass through
+    Closed,
+    /// Requests are rejected immediately
+    Open,
+    /// A single trial request is allowed through
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    consecutive_failures: u32,
+    first_failure_at: Option<Instant>,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+/// Provider wrapper implementing the circuit breaker pattern
+pub struct CircuitBreakerProvider {
+    inner: Box<dyn LightningProvider>,
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreakerProvider {
+    /// Wrap a provider with a circuit breaker
+    pub fn new(inner: Box<dyn LightningProvider>, config: CircuitBreakerConfig) -> Self {
+        Self {
+            inner,
+            config,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                first_failure_at: None,
+                opened_at: None,
+                trial_in_flight: false,
+            }),
+        }
+    }
+
+    /// Current circuit state
+    pub fn state(&self) -> CircuitState {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        state.state
+    }
+
+    /// Move from Open to HalfOpen once the reset timeout has elapsed
+    fn refresh(&self, state: &mut BreakerState) {
+        if state.state == CircuitState::Open {
+            let reset_timeout = Duration::from_secs(self.config.reset_timeout_seconds);
+            if state.opened_at.map_or(true, |opened| opened.elapsed() >= reset_timeout) {
+                info!("Circuit breaker for {} half-open, allowing trial request", self.inner.provider_type());
+                state.state = CircuitState::HalfOpen;
+                state.trial_in_flight = false;
+            }
+        }
+    }
+
+    /// Check whether a request may proceed
+    ///
+    /// The returned guard records a failure if it's dropped before
+    /// `CallGuard::finish`, so a cancelled half-open trial reopens the circuit
+    /// instead of blocking every later request.
+    fn before_call(&self) -> Result<CallGuard<'_>, LightningError> {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        match state.state {
+            CircuitState::Closed => {}
+            CircuitState::HalfOpen if !state.trial_in_flight => state.trial_in_flight = true,
+            _ => return Err(LightningError::NodeConnectionError("circuit open".to_string())),
+        }
+        Ok(CallGuard { breaker: self, finished: false })
+    }
+
+    /// Record the outcome of a request
+    fn after_call(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            if state.state != CircuitState::Closed {
+                info!("Circuit breaker for {} closed", self.inner.provider_type());
+            }
+            state.state = CircuitState::Closed;
+            state.consecutive_failures = 0;
+            state.first_failure_at = None;
+            state.opened_at = None;
+            state.trial_in_flight = false;
+            return;
+        }
+
+        if state.state == CircuitState::HalfOpen {
+            warn!("Circuit breaker trial request failed, reopening for {}", self.inner.provider_type());
+            state.state = CircuitState::Open;
+            state.opened_at = Some(Instant::now());
+            state.trial_in_flight = false;
+            return;
+        }
+
+        let window = Duration::from_secs(self.config.window_seconds);
+        match state.first_failure_at {
+            Some(first) if first.elapsed() <= window => state.consecutive_failures += 1,
+            _ => {
+                state.first_failure_at = Some(Instant::now());
+                state.consecutive_failures = 1;
+            }
+        }
+
+        if state.state == CircuitState::Closed && state.consecutive_failures >= self.config.failure_threshold {
+            warn!(
+                "Circuit breaker opened for {} after {} consecutive failures",
+                self.inner.provider_type(),
+                state.consecutive_failures
+            );
+            state.state = CircuitState::Open;
+            state.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Run a provider call through the breaker
+    async fn call<T, F>(&self, f: F) -> Result<T, LightningError>
+    where
+        F: Future<Output = Result<T, LightningError>>,
+    {
+        let guard = self.before_call()?;
+        let result = f.await;
+        // Errors from a provider that answered (e.g. unknown payment) don't count against it
+        guard.finish(result.as_ref().map_or_else(|e| !e.is_retryable(), |_| true));
+        result
+    }
+}
+
+/// An admitted request; counts as a failure unless finished
+struct CallGuard<'a> {
+    breaker: &'a CircuitBreakerProvider,
+    finished: bool,
+}
+
+impl CallGuard<'_> {
+    /// Record the outcome of the request
+    fn finish(mut self, success: bool) {
+        self.finished = true;
+        self.breaker.after_call(success);
+    }
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.breaker.after_call(false);
+        }
+    }
+}
+
+#[async_trait]
+impl LightningProvider for CircuitBreakerProvider {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        self.call(self.inner.verify_payment(invoice, payment_hash, payment_id)).await
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.call(self.inner.create_invoice(amount_msats, description, expiry_seconds)).await
+    }
+
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        self.call(self.inner.create_invoice_ex(params)).await
+    }
+
+    async fn create_invoices_batch(
+        &self,
+        requests: &[InvoiceParams],
+    ) -> Result<Vec<Result<String, LightningError>>, LightningError> {
+        self.call(self.inner.create_invoices_batch(requests)).await
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        self.call(self.inner.is_payment_confirmed(payment_hash)).await
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        self.call(self.inner.cancel_invoice(payment_hash)).await
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        self.call(self.inner.balance_msats()).await
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        self.call(self.inner.withdraw_onchain(address, amount_sats, fee_rate)).await
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        self.call(self.inner.subscribe_payments()).await
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        self.call(self.inner.estimate_fee(invoice, amount_msats)).await
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        self.call(self.inner.list_channels()).await
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        self.call(self.inner.get_node_info()).await
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        self.inner.attach_storage(node_api).await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        self.call(self.inner.health_check()).await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.inner.provider_type()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        // The breaker is transparent; expose the wrapped provider
+        self.inner.as_any()
+    }
+}
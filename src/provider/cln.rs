@@ -0,0 +1,318 @@
+//! Core Lightning (CLN) provider implementation
+//!
+//! Talks to CLN's REST plugin (`clnrest`), authenticating with a rune.
+//! Every RPC method is exposed as `POST /v1/<method>` with JSON parameters.
+
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind, MAX_DESCRIPTION_BYTES, load_ca_certificate, socks5_proxy,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// CLN provider configuration
+#[derive(Debug, Clone, Default)]
+pub struct CLNConfig {
+    /// clnrest URL (e.g., "https://127.0.0.1:3010")
+    pub url: String,
+    /// Rune authorizing the invoice/listinvoices/getinfo methods
+    pub rune: String,
+    /// clnrest's self-signed CA certificate (PEM)
+    pub ca_cert_path: Option<PathBuf>,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+    /// SOCKS5 proxy for all requests, e.g. `socks5h://127.0.0.1:9050` to reach an onion service over Tor
+    pub socks5_proxy: Option<String>,
+}
+
+impl CLNConfig {
+    /// Check settings that can be rejected before any request is made
+    pub fn validate(&self) -> Result<(), LightningError> {
+        if let Some(proxy) = &self.socks5_proxy {
+            socks5_proxy(proxy)?;
+        }
+        Ok(())
+    }
+}
+
+/// Invoice as returned by `listinvoices`
+#[derive(Debug, Deserialize)]
+struct ListedInvoice {
+    label: String,
+    status: String,
+    #[serde(default)]
+    amount_msat: Option<u64>,
+    #[serde(default)]
+    amount_received_msat: Option<u64>,
+    #[serde(default)]
+    paid_at: Option<u64>,
+    #[serde(default)]
+    payment_preimage: Option<String>,
+}
+
+/// CLN provider implementation
+pub struct CLNProvider {
+    config: CLNConfig,
+    http_client: Client,
+}
+
+impl CLNProvider {
+    /// Create a new CLN provider
+    pub fn new(config: CLNConfig) -> Result<Self, LightningError> {
+        config.validate()?;
+        let mut builder = Client::builder().timeout(config.retry_policy.request_timeout);
+        if let Some(proxy) = &config.socks5_proxy {
+            debug!("Routing clnrest requests through a SOCKS5 proxy");
+            builder = builder.proxy(socks5_proxy(proxy)?);
+        }
+        if let Some(path) = &config.ca_cert_path {
+            builder = builder.add_root_certificate(load_ca_certificate(path)?);
+        }
+        let http_client = builder
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self { config, http_client })
+    }
+
+    /// Call a CLN RPC method via clnrest
+    ///
+    /// Idempotent calls are retried according to the retry policy.
+    async fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        idempotent: bool,
+    ) -> Result<T, LightningError> {
+        let url = format!("{}/v1/{}", self.config.url.trim_end_matches('/'), method);
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let response = match self
+                .http_client
+                .post(&url)
+                .header("Rune", &self.config.rune)
+                .json(&params)
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if idempotent && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("CLN {} failed (attempt {}/{}): {}", method, attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("CLN request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && idempotent && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("CLN {} returned {} (attempt {}/{})", method, status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            if !status.is_success() {
+                let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                return Err(LightningError::ProcessorError(format!("CLN {} error: {} - {}", method, status, error_text)));
+            }
+
+            return response
+                .json::<T>()
+                .await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse CLN response: {}", e)));
+        }
+    }
+
+    /// Look up an invoice by payment hash
+    async fn find_invoice(&self, payment_hash: &[u8; 32]) -> Result<Option<ListedInvoice>, LightningError> {
+        #[derive(Deserialize)]
+        struct ListInvoicesResponse {
+            invoices: Vec<ListedInvoice>,
+        }
+
+        let response: ListInvoicesResponse = self
+            .call("listinvoices", serde_json::json!({ "payment_hash": hex::encode(payment_hash) }), true)
+            .await?;
+        Ok(response.invoices.into_iter().next())
+    }
+}
+
+#[async_trait]
+impl LightningProvider for CLNProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "cln", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via CLN: payment_id={}", payment_id);
+
+        let invoice = match self.find_invoice(payment_hash).await? {
+            Some(invoice) => invoice,
+            None => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "cln",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        let verified = invoice.status == "paid";
+        debug!("CLN invoice {} status: {}", invoice.label, invoice.status);
+
+        Ok(PaymentVerificationResult {
+            verified,
+            amount_msats: invoice.amount_received_msat.or(invoice.amount_msat),
+            timestamp: invoice.paid_at,
+            metadata: serde_json::json!({
+                "provider": "cln",
+                "payment_hash": hex::encode(payment_hash),
+                "label": invoice.label,
+                "status": invoice.status,
+                "preimage": invoice.payment_preimage,
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "cln", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        debug!("Creating invoice via CLN: amount={} msats", params.amount_msats);
+
+        // CLN can commit to the hash of a description it is given, but can't
+        // take a bare hash
+        let (description, deschashonly) = match &params.description {
+            DescriptionKind::Direct(description) => (description.clone(), description.len() > MAX_DESCRIPTION_BYTES),
+            DescriptionKind::Hash(_) => {
+                return Err(LightningError::InvoiceError(
+                    "CLN does not support precomputed description hashes".to_string(),
+                ));
+            }
+        };
+
+        // CLN requires a unique label per invoice
+        let label = params
+            .label
+            .clone()
+            .unwrap_or_else(|| format!("blvm-{}", hex::encode(rand::random::<[u8; 16]>())));
+
+        #[derive(Deserialize)]
+        struct InvoiceResponse {
+            bolt11: String,
+        }
+
+        let response: InvoiceResponse = self
+            .call(
+                "invoice",
+                serde_json::json!({
+                    "amount_msat": params.amount_msats,
+                    "label": label,
+                    "description": description,
+                    "expiry": params.expiry_seconds,
+                    "deschashonly": deschashonly,
+                }),
+                // CLN rejects duplicate labels, so a retry can never create a second invoice
+                params.label.is_some(),
+            )
+            .await?;
+
+        debug!("CLN invoice created: label={}", label);
+        Ok(response.bolt11)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        Ok(self
+            .find_invoice(payment_hash)
+            .await?
+            .map(|invoice| invoice.status == "paid")
+            .unwrap_or(false))
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        #[derive(Deserialize)]
+        struct GetInfoResponse {
+            blockheight: u64,
+            version: String,
+            #[serde(default)]
+            warning_bitcoind_sync: Option<String>,
+            #[serde(default)]
+            warning_lightningd_sync: Option<String>,
+        }
+
+        let started = std::time::Instant::now();
+        let result = self.call::<GetInfoResponse>("getinfo", serde_json::json!({}), true).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(info) => Ok(HealthStatus {
+                reachable: true,
+                latency_ms,
+                block_height: Some(info.blockheight),
+                synced_to_chain: Some(info.warning_bitcoind_sync.is_none() && info.warning_lightningd_sync.is_none()),
+                version: Some(info.version),
+            }),
+            Err(e) => {
+                warn!("CLN health check failed: {}", e);
+                Ok(HealthStatus {
+                    reachable: false,
+                    latency_ms,
+                    block_height: None,
+                    synced_to_chain: None,
+                    version: None,
+                })
+            }
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::CLN
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
@@ -0,0 +1,324 @@
+//! Composite provider
+//!
+//! Spreads invoices over several providers at once: by amount between an
+//! LNBits wallet (small payments) and an LDK node (large ones), or round
+//! robin over every member. Unlike the routing provider nothing is recorded
+//! about which member created an invoice, so payments are verified by
+//! asking each member in turn.
+
+use crate::invoice::InvoiceParser;
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, PaymentStream, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use bitcoin::Txid;
+use blvm_node::module::traits::NodeAPI;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Default `lightning.composite.lnbits_max_msats` (100k sats)
+pub const DEFAULT_LNBITS_MAX_MSATS: u64 = 100_000_000;
+
+/// How the composite provider picks a member for a new invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    /// LNBits up to `lnbits_max_msats` (inclusive), LDK above
+    Threshold { lnbits_max_msats: u64 },
+    /// Each member in turn
+    RoundRobin,
+}
+
+impl FromStr for RoutingStrategy {
+    type Err = LightningError;
+
+    /// Parse a strategy name; `"threshold"` starts at `DEFAULT_LNBITS_MAX_MSATS`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "threshold" => Ok(RoutingStrategy::Threshold { lnbits_max_msats: DEFAULT_LNBITS_MAX_MSATS }),
+            "round_robin" | "roundrobin" => Ok(RoutingStrategy::RoundRobin),
+            other => Err(LightningError::ConfigError(format!(
+                "Invalid lightning.composite.strategy '{}': expected threshold or round_robin",
+                other
+            ))),
+        }
+    }
+}
+
+/// Provider that creates invoices through one of several members
+pub struct CompositeProvider {
+    providers: Vec<Box<dyn LightningProvider>>,
+    strategy: RoutingStrategy,
+    /// Member the next round robin invoice goes to
+    next: AtomicUsize,
+}
+
+impl CompositeProvider {
+    /// Create a composite provider
+    ///
+    /// `Threshold` needs an LNBits and an LDK member.
+    pub fn new(providers: Vec<Box<dyn LightningProvider>>, strategy: RoutingStrategy) -> Result<Self, LightningError> {
+        if providers.is_empty() {
+            return Err(LightningError::ConfigError("Composite provider needs at least one provider".to_string()));
+        }
+        if let RoutingStrategy::Threshold { .. } = strategy {
+            for provider_type in [ProviderType::LNBits, ProviderType::LDK] {
+                if !providers.iter().any(|provider| provider.provider_type() == provider_type) {
+                    return Err(LightningError::ConfigError(format!(
+                        "Threshold routing needs an {} provider in lightning.composite.providers",
+                        provider_type
+                    )));
+                }
+            }
+        }
+        Ok(Self {
+            providers,
+            strategy,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Members, in configured order
+    pub fn providers(&self) -> &[Box<dyn LightningProvider>] {
+        &self.providers
+    }
+
+    pub fn strategy(&self) -> RoutingStrategy {
+        self.strategy
+    }
+
+    /// The member a new invoice for `amount_msats` goes to
+    ///
+    /// Advances the round robin position.
+    pub fn route(&self, amount_msats: u64) -> &dyn LightningProvider {
+        match self.strategy {
+            RoutingStrategy::Threshold { lnbits_max_msats } => self.threshold_member(amount_msats, lnbits_max_msats),
+            RoutingStrategy::RoundRobin => {
+                let index = self.next.fetch_add(1, Ordering::Relaxed) % self.providers.len();
+                self.providers[index].as_ref()
+            }
+        }
+    }
+
+    fn threshold_member(&self, amount_msats: u64, lnbits_max_msats: u64) -> &dyn LightningProvider {
+        let provider_type = if amount_msats <= lnbits_max_msats { ProviderType::LNBits } else { ProviderType::LDK };
+        self.providers
+            .iter()
+            .find(|provider| provider.provider_type() == provider_type)
+            .map(|provider| provider.as_ref())
+            // Checked in `new`
+            .unwrap_or(self.providers[0].as_ref())
+    }
+
+    async fn create_routed(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let provider = self.route(params.amount_msats);
+        debug!("Creating {} msats invoice through {}", params.amount_msats, provider.provider_type());
+        provider.create_invoice_ex(params).await
+    }
+}
+
+#[async_trait]
+impl LightningProvider for CompositeProvider {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        // The first member that saw the payment; otherwise the last answer
+        let mut unverified = None;
+        let mut last_error = None;
+        for provider in &self.providers {
+            match provider.verify_payment(invoice, payment_hash, payment_id).await {
+                Ok(mut result) if result.verified => {
+                    if let Some(metadata) = result.metadata.as_object_mut() {
+                        metadata.insert("verified_by".to_string(), serde_json::Value::String(provider.provider_type().to_string()));
+                    }
+                    return Ok(result);
+                }
+                Ok(result) => unverified = Some(result),
+                Err(e) => {
+                    debug!("{} could not verify payment {}: {}", provider.provider_type(), hex::encode(payment_hash), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match (unverified, last_error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(LightningError::ConfigError("Composite provider has no providers".to_string())),
+        }
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_routed(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        self.create_routed(params).await
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        let mut answered = false;
+        let mut last_error = None;
+        for provider in &self.providers {
+            match provider.is_payment_confirmed(payment_hash).await {
+                Ok(true) => return Ok(true),
+                Ok(false) => answered = true,
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            // Unknown to every member that answered is unconfirmed; no answer at all is an error
+            Some(e) if !answered => Err(e),
+            _ => Ok(false),
+        }
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        // Only the member that created the invoice knows it
+        let mut last_error = None;
+        for provider in &self.providers {
+            match provider.cancel_invoice(payment_hash).await {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| LightningError::ConfigError("Composite provider has no providers".to_string())))
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        let mut total = 0u64;
+        for provider in &self.providers {
+            total = total.saturating_add(provider.balance_msats().await?);
+        }
+        Ok(total)
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        let provider = self.providers
+            .iter()
+            .find(|provider| provider.capabilities().can_withdraw_onchain)
+            .ok_or_else(|| LightningError::ProcessorError("No composite member supports on-chain withdrawals".to_string()))?;
+        provider.withdraw_onchain(address, amount_sats, fee_rate).await
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        let mut streams = Vec::new();
+        for provider in &self.providers {
+            match provider.subscribe_payments().await {
+                Ok(stream) => streams.push(stream),
+                Err(e) => debug!("{} has no payment subscription: {}", provider.provider_type(), e),
+            }
+        }
+        if streams.is_empty() {
+            return Err(LightningError::ProcessorError("No composite member supports payment subscriptions".to_string()));
+        }
+        Ok(Box::pin(futures::stream::select_all(streams)))
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        match self.strategy {
+            RoutingStrategy::Threshold { lnbits_max_msats } => {
+                let amount = match amount_msats {
+                    Some(amount) => amount,
+                    None => InvoiceParser::parse(invoice)?.amount_msats,
+                };
+                self.threshold_member(amount, lnbits_max_msats).estimate_fee(invoice, amount_msats).await
+            }
+            // Any member could pay it; the first that can estimate
+            RoutingStrategy::RoundRobin => {
+                let mut last_error = None;
+                for provider in &self.providers {
+                    match provider.estimate_fee(invoice, amount_msats).await {
+                        Ok(estimate) => return Ok(estimate),
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+                Err(last_error.unwrap_or_else(|| LightningError::ConfigError("Composite provider has no providers".to_string())))
+            }
+        }
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        let mut channels = Vec::new();
+        let mut last_error = None;
+        for provider in &self.providers {
+            match provider.list_channels().await {
+                Ok(mut list) => channels.append(&mut list),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if channels.is_empty() => Err(e),
+            _ => Ok(channels),
+        }
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        let mut last_error = None;
+        for provider in &self.providers {
+            match provider.get_node_info().await {
+                Ok(info) => return Ok(info),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| LightningError::ConfigError("Composite provider has no providers".to_string())))
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        for provider in &self.providers {
+            provider.attach_storage(node_api.clone()).await?;
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // Any member may be handed the next invoice
+        let mut first = None;
+        for provider in &self.providers {
+            let status = provider.health_check().await?;
+            if !status.reachable {
+                return Ok(status);
+            }
+            first.get_or_insert(status);
+        }
+        first.ok_or_else(|| LightningError::ConfigError("Composite provider has no providers".to_string()))
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.providers.iter().fold(ProviderCapabilities::default(), |acc, provider| {
+            let caps = provider.capabilities();
+            ProviderCapabilities {
+                can_verify: acc.can_verify || caps.can_verify,
+                can_create_invoices: acc.can_create_invoices || caps.can_create_invoices,
+                can_pay: acc.can_pay || caps.can_pay,
+                can_keysend: acc.can_keysend || caps.can_keysend,
+                can_hold_invoices: acc.can_hold_invoices || caps.can_hold_invoices,
+                can_manage_channels: acc.can_manage_channels || caps.can_manage_channels,
+                can_list_payments: acc.can_list_payments || caps.can_list_payments,
+                can_withdraw_onchain: acc.can_withdraw_onchain || caps.can_withdraw_onchain,
+            }
+        })
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Composite
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,322 @@
+//! Eclair provider implementation
+//!
+//! Talks to Eclair's HTTP API: every method is `POST /<method>` with
+//! form-encoded parameters, authenticated with HTTP basic auth (empty user,
+//! API password). Amounts are already in msats.
+
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind, MAX_DESCRIPTION_BYTES, load_ca_certificate,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use bitcoin::hashes::{sha256, Hash};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// Eclair provider configuration
+#[derive(Debug, Clone, Default)]
+pub struct EclairConfig {
+    /// Eclair API URL (e.g., "http://127.0.0.1:8080")
+    pub url: String,
+    /// API password (`eclair.api.password`)
+    pub password: String,
+    /// Additional trusted root certificate (PEM) when Eclair is behind TLS
+    pub ca_cert_path: Option<PathBuf>,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+/// Timestamp as reported by Eclair (`{"iso": ..., "unix": ...}`)
+#[derive(Debug, Deserialize)]
+struct Timestamp {
+    unix: u64,
+}
+
+/// Invoice as returned by `createinvoice` and `parseinvoice`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EclairInvoice {
+    serialized: String,
+    payment_hash: String,
+    #[serde(default)]
+    amount: Option<u64>,
+}
+
+/// Incoming payment status from `getreceivedinfo`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReceivedStatus {
+    #[serde(rename = "type")]
+    status_type: String,
+    #[serde(default)]
+    amount: Option<u64>,
+    #[serde(default)]
+    received_at: Option<Timestamp>,
+}
+
+/// Response of `getreceivedinfo`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReceivedInfo {
+    payment_request: EclairInvoice,
+    #[serde(default)]
+    payment_preimage: Option<String>,
+    status: ReceivedStatus,
+}
+
+/// Eclair provider implementation
+pub struct EclairProvider {
+    config: EclairConfig,
+    http_client: Client,
+}
+
+impl EclairProvider {
+    /// Create a new Eclair provider
+    pub fn new(config: EclairConfig) -> Result<Self, LightningError> {
+        let mut builder = Client::builder().timeout(config.retry_policy.request_timeout);
+        if let Some(path) = &config.ca_cert_path {
+            builder = builder.add_root_certificate(load_ca_certificate(path)?);
+        }
+        let http_client = builder
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self { config, http_client })
+    }
+
+    /// Call an Eclair API method
+    ///
+    /// Returns `Ok(None)` for 404 responses (unknown payment hash).
+    /// Idempotent calls are retried according to the retry policy.
+    async fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: &[(&str, String)],
+        idempotent: bool,
+    ) -> Result<Option<T>, LightningError> {
+        let url = format!("{}/{}", self.config.url.trim_end_matches('/'), method);
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let response = match self
+                .http_client
+                .post(&url)
+                .basic_auth("", Some(&self.config.password))
+                .form(params)
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if idempotent && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("Eclair {} failed (attempt {}/{}): {}", method, attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("Eclair request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && idempotent && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("Eclair {} returned {} (attempt {}/{})", method, status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            if status == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+
+            if !status.is_success() {
+                let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                return Err(LightningError::ProcessorError(format!("Eclair {} error: {} - {}", method, status, error_text)));
+            }
+
+            return response
+                .json::<T>()
+                .await
+                .map(Some)
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse Eclair response: {}", e)));
+        }
+    }
+
+    /// Look up an incoming payment by payment hash
+    async fn received_info(&self, payment_hash: &[u8; 32]) -> Result<Option<ReceivedInfo>, LightningError> {
+        self.call("getreceivedinfo", &[("paymentHash", hex::encode(payment_hash))], true).await
+    }
+}
+
+#[async_trait]
+impl LightningProvider for EclairProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "eclair", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via Eclair: payment_id={}", payment_id);
+
+        // Make sure the invoice actually commits to the hash being verified
+        if !invoice.is_empty() {
+            let parsed: Option<EclairInvoice> = self.call("parseinvoice", &[("invoice", invoice.to_string())], true).await?;
+            let matches = parsed
+                .map(|parsed| parsed.payment_hash.eq_ignore_ascii_case(&hex::encode(payment_hash)))
+                .unwrap_or(false);
+            if !matches {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "eclair",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "payment_hash_mismatch",
+                    }),
+                });
+            }
+        }
+
+        let info = match self.received_info(payment_hash).await? {
+            Some(info) => info,
+            None => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "eclair",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        let verified = info.status.status_type == "received";
+        debug!("Eclair payment {} status: {}", info.payment_request.payment_hash, info.status.status_type);
+
+        Ok(PaymentVerificationResult {
+            verified,
+            amount_msats: info.status.amount.or(info.payment_request.amount),
+            timestamp: info.status.received_at.map(|t| t.unix),
+            metadata: serde_json::json!({
+                "provider": "eclair",
+                "payment_hash": hex::encode(payment_hash),
+                "status": info.status.status_type,
+                "preimage": info.payment_preimage,
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "eclair", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        debug!("Creating invoice via Eclair: amount={} msats", params.amount_msats);
+
+        let mut form = vec![
+            ("amountMsat", params.amount_msats.to_string()),
+            ("expireIn", params.expiry_seconds.to_string()),
+        ];
+        match &params.description {
+            DescriptionKind::Direct(description) if description.len() > MAX_DESCRIPTION_BYTES => {
+                let hash = sha256::Hash::hash(description.as_bytes());
+                form.push(("descriptionHash", hex::encode(hash.to_byte_array())));
+            }
+            DescriptionKind::Direct(description) => form.push(("description", description.clone())),
+            DescriptionKind::Hash(hash) => form.push(("descriptionHash", hex::encode(hash))),
+        }
+
+        // createinvoice has no idempotency key, so it is never retried
+        let invoice: Option<EclairInvoice> = self.call("createinvoice", &form, false).await?;
+        let invoice = invoice
+            .ok_or_else(|| LightningError::ProcessorError("Eclair createinvoice endpoint not found".to_string()))?;
+
+        debug!("Eclair invoice created: payment_hash={}", invoice.payment_hash);
+        Ok(invoice.serialized)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        Ok(self
+            .received_info(payment_hash)
+            .await?
+            .map(|info| info.status.status_type == "received")
+            .unwrap_or(false))
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GetInfoResponse {
+            version: String,
+            block_height: u64,
+        }
+
+        let started = std::time::Instant::now();
+        let result = self.call::<GetInfoResponse>("getinfo", &[], true).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(Some(info)) => Ok(HealthStatus {
+                reachable: true,
+                latency_ms,
+                block_height: Some(info.block_height),
+                synced_to_chain: None,
+                version: Some(info.version),
+            }),
+            result => {
+                if let Err(e) = result {
+                    warn!("Eclair health check failed: {}", e);
+                }
+                Ok(HealthStatus {
+                    reachable: false,
+                    latency_ms,
+                    block_height: None,
+                    synced_to_chain: None,
+                    version: None,
+                })
+            }
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Eclair
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,226 @@
+//! Failover provider
+//!
+//! Tries a chain of providers in order, falling back to the next one when a
+//! provider can't be reached (connection errors, timeouts, 5xx). Errors that
+//! come from a provider that did answer, such as an unknown payment, are
+//! returned as-is: the next provider would not know any better.
+
+use crate::provider::circuit_breaker::{CircuitBreakerConfig, CircuitBreakerProvider};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, PaymentStream, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use bitcoin::Txid;
+use blvm_node::module::traits::NodeAPI;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Provider that falls back through a list of providers
+pub struct FailoverProvider {
+    providers: Vec<Box<dyn LightningProvider>>,
+    /// Index of the provider that answered the most recent call
+    active: AtomicUsize,
+}
+
+impl FailoverProvider {
+    /// Create a failover chain; providers are tried in the given order
+    pub fn new(providers: Vec<Box<dyn LightningProvider>>) -> Result<Self, LightningError> {
+        if providers.is_empty() {
+            return Err(LightningError::ConfigError("Failover chain needs at least one provider".to_string()));
+        }
+        Ok(Self {
+            providers,
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// Create a failover chain with a circuit breaker around each provider
+    ///
+    /// A provider whose breaker is open is skipped without a request until
+    /// the breaker's reset timeout lets a trial call through.
+    pub fn with_circuit_breakers(
+        providers: Vec<Box<dyn LightningProvider>>,
+        config: CircuitBreakerConfig,
+    ) -> Result<Self, LightningError> {
+        Self::new(
+            providers
+                .into_iter()
+                .map(|provider| Box::new(CircuitBreakerProvider::new(provider, config.clone())) as Box<dyn LightningProvider>)
+                .collect(),
+        )
+    }
+
+    /// Providers in the chain, in order
+    pub fn providers(&self) -> &[Box<dyn LightningProvider>] {
+        &self.providers
+    }
+
+    /// Type of the provider that answered the most recent call
+    pub fn active_provider_type(&self) -> ProviderType {
+        self.providers[self.active.load(Ordering::Relaxed)].provider_type()
+    }
+
+    /// Call each provider in turn until one answers
+    ///
+    /// Moves on only when a provider is unreachable (`is_retryable`); any
+    /// other error is returned immediately. Returns the index of the provider
+    /// that answered along with its result.
+    async fn try_each_indexed<'a, T, F, Fut>(&'a self, operation: &str, mut f: F) -> Result<(usize, T), LightningError>
+    where
+        F: FnMut(&'a dyn LightningProvider) -> Fut,
+        Fut: Future<Output = Result<T, LightningError>>,
+    {
+        let mut last_error = None;
+        for (index, provider) in self.providers.iter().enumerate() {
+            match f(provider.as_ref()).await {
+                Err(e) if e.is_retryable() => {
+                    warn!("{} failed via {}, trying next provider: {}", operation, provider.provider_type(), e);
+                    last_error = Some(e);
+                }
+                result => {
+                    let previous = self.active.swap(index, Ordering::Relaxed);
+                    if previous != index {
+                        info!("{} answered by {}", operation, provider.provider_type());
+                    }
+                    return result.map(|value| (index, value));
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| LightningError::ConfigError("Failover chain is empty".to_string())))
+    }
+
+    /// Call each provider in turn until one answers
+    async fn try_each<'a, T, F, Fut>(&'a self, operation: &str, f: F) -> Result<T, LightningError>
+    where
+        F: FnMut(&'a dyn LightningProvider) -> Fut,
+        Fut: Future<Output = Result<T, LightningError>>,
+    {
+        self.try_each_indexed(operation, f).await.map(|(_, value)| value)
+    }
+}
+
+#[async_trait]
+impl LightningProvider for FailoverProvider {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        let (index, mut result) = self
+            .try_each_indexed("Payment verification", |p| p.verify_payment(invoice, payment_hash, payment_id))
+            .await?;
+        if let Some(metadata) = result.metadata.as_object_mut() {
+            metadata.insert(
+                "answered_by".to_string(),
+                serde_json::to_value(self.providers[index].provider_type()).unwrap_or_default(),
+            );
+        }
+        Ok(result)
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.try_each("Invoice creation", |p| p.create_invoice(amount_msats, description, expiry_seconds)).await
+    }
+
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        self.try_each("Invoice creation", |p| p.create_invoice_ex(params)).await
+    }
+
+    async fn create_invoices_batch(
+        &self,
+        requests: &[InvoiceParams],
+    ) -> Result<Vec<Result<String, LightningError>>, LightningError> {
+        self.try_each("Batch invoice creation", |p| p.create_invoices_batch(requests)).await
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        self.try_each("Payment confirmation", |p| p.is_payment_confirmed(payment_hash)).await
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        self.try_each("Invoice cancellation", |p| p.cancel_invoice(payment_hash)).await
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        self.try_each("Balance query", |p| p.balance_msats()).await
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        self.try_each("On-chain withdrawal", |p| p.withdraw_onchain(address, amount_sats, fee_rate)).await
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        self.try_each("Payment subscription", |p| p.subscribe_payments()).await
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        self.try_each("Fee estimation", |p| p.estimate_fee(invoice, amount_msats)).await
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        self.try_each("Channel listing", |p| p.list_channels()).await
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        self.try_each("Node info", |p| p.get_node_info()).await
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        for provider in &self.providers {
+            provider.attach_storage(node_api.clone()).await?;
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // Healthy if any provider in the chain is reachable
+        let mut last = None;
+        for provider in &self.providers {
+            match provider.health_check().await {
+                Ok(status) if status.reachable => return Ok(status),
+                result => last = Some(result),
+            }
+        }
+        last.unwrap_or_else(|| Err(LightningError::ConfigError("Failover chain is empty".to_string())))
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // A call can succeed through any provider in the chain
+        self.providers.iter().fold(ProviderCapabilities::default(), |acc, p| {
+            let caps = p.capabilities();
+            ProviderCapabilities {
+                can_verify: acc.can_verify || caps.can_verify,
+                can_create_invoices: acc.can_create_invoices || caps.can_create_invoices,
+                can_pay: acc.can_pay || caps.can_pay,
+                can_keysend: acc.can_keysend || caps.can_keysend,
+                can_hold_invoices: acc.can_hold_invoices || caps.can_hold_invoices,
+                can_manage_channels: acc.can_manage_channels || caps.can_manage_channels,
+                can_list_payments: acc.can_list_payments || caps.can_list_payments,
+                can_withdraw_onchain: acc.can_withdraw_onchain || caps.can_withdraw_onchain,
+            }
+        })
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        // The backend currently answering calls, so callers can tell when the chain has failed over
+        self.active_provider_type()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
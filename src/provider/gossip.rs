@@ -0,0 +1,215 @@
+//! Rapid Gossip Sync for the LDK provider
+//!
+//! Pathfinding needs a view of the public network that a fresh node has no
+//! way to build quickly from peer gossip. With `lightning.ldk.rgs_url` set,
+//! the provider downloads a Rapid Gossip Sync snapshot at startup and every
+//! `lightning.ldk.rgs_sync_interval_secs`, asking the server only for changes
+//! since the last snapshot it applied. The graph is persisted to
+//! `network_graph.bin` in the data dir so a restart picks up where it left
+//! off. An unreachable server or a rejected (e.g. stale) snapshot leaves the
+//! last good graph in place.
+
+use crate::error::LightningError;
+use crate::provider::retry::{retry_idempotent, ProviderRetryPolicy};
+use bitcoin::constants::ChainHash;
+use bitcoin::Network;
+use lightning::routing::gossip::NetworkGraph;
+use lightning::util::logger::{Level, Logger, Record};
+use lightning::util::ser::{ReadableArgs, Writeable};
+use lightning_rapid_gossip_sync::RapidGossipSync;
+use reqwest::Client;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, error, info, trace, warn};
+
+/// Persisted network graph, relative to the data dir
+pub const NETWORK_GRAPH_FILE: &str = "network_graph.bin";
+
+/// Default seconds between Rapid Gossip Sync downloads
+pub const DEFAULT_RGS_SYNC_INTERVAL_SECS: u64 = 3600;
+
+/// Forwards LDK log records to `tracing`
+pub struct LdkLogger;
+
+impl Logger for LdkLogger {
+    fn log(&self, record: Record) {
+        match record.level {
+            Level::Gossip | Level::Trace => trace!(target: "ldk", "{}", record.args),
+            Level::Debug => debug!(target: "ldk", "{}", record.args),
+            Level::Info => info!(target: "ldk", "{}", record.args),
+            Level::Warn => warn!(target: "ldk", "{}", record.args),
+            Level::Error => error!(target: "ldk", "{}", record.args),
+        }
+    }
+}
+
+type Graph = NetworkGraph<Arc<LdkLogger>>;
+
+/// Size and freshness of the network graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkGraphStats {
+    pub node_count: usize,
+    pub channel_count: usize,
+    /// Timestamp of the last applied snapshot (unix seconds), if any
+    pub last_sync_timestamp: Option<u32>,
+}
+
+/// Network graph kept current from a Rapid Gossip Sync server
+pub struct GossipSync {
+    graph: Arc<Graph>,
+    rgs: RapidGossipSync<Arc<Graph>, Arc<LdkLogger>>,
+    graph_path: PathBuf,
+    /// Snapshot server base URL (no downloads if unset)
+    rgs_url: Option<String>,
+    http_client: Client,
+    retry_policy: ProviderRetryPolicy,
+    /// Serializes snapshot application and graph writes
+    sync_lock: tokio::sync::Mutex<()>,
+}
+
+impl GossipSync {
+    /// Load the graph persisted in `data_dir`, or start an empty one
+    pub fn new(
+        network: Network,
+        data_dir: &Path,
+        rgs_url: Option<String>,
+        retry_policy: ProviderRetryPolicy,
+    ) -> Result<Self, LightningError> {
+        if let Some(url) = &rgs_url {
+            let parsed = reqwest::Url::parse(url)
+                .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.ldk.rgs_url {}: {}", url, e)))?;
+            if !matches!(parsed.scheme(), "https" | "http") {
+                return Err(LightningError::ConfigError(format!(
+                    "Invalid lightning.ldk.rgs_url {}: not http(s)",
+                    url
+                )));
+            }
+        }
+        let http_client = Client::builder()
+            .timeout(retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        let logger = Arc::new(LdkLogger);
+        let graph_path = data_dir.join(NETWORK_GRAPH_FILE);
+        let graph = Arc::new(load_graph(&graph_path, network, logger.clone()));
+        let rgs = RapidGossipSync::new(graph.clone(), logger);
+
+        Ok(Self {
+            graph,
+            rgs,
+            graph_path,
+            rgs_url: rgs_url.map(|url| url.trim_end_matches('/').to_string()),
+            http_client,
+            retry_policy,
+            sync_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Whether snapshots are downloaded (`lightning.ldk.rgs_url` is set)
+    pub fn is_enabled(&self) -> bool {
+        self.rgs_url.is_some()
+    }
+
+    /// Node and channel counts and the last sync time
+    pub fn stats(&self) -> NetworkGraphStats {
+        let graph = self.graph.read_only();
+        NetworkGraphStats {
+            node_count: graph.nodes().len(),
+            channel_count: graph.channels().len(),
+            last_sync_timestamp: self.graph.get_last_rapid_gossip_sync_timestamp(),
+        }
+    }
+
+    /// Apply a snapshot and persist the graph; returns the snapshot's timestamp
+    ///
+    /// A rejected snapshot (malformed, for another chain, or older than two
+    /// weeks) is not persisted.
+    pub async fn apply_snapshot(&self, snapshot: &[u8]) -> Result<u32, LightningError> {
+        let _guard = self.sync_lock.lock().await;
+        let timestamp = self.rgs.update_network_graph(snapshot).map_err(|e| {
+            LightningError::ProcessorError(format!("Rapid Gossip Sync snapshot rejected: {:?}", e))
+        })?;
+        self.persist()?;
+        Ok(timestamp)
+    }
+
+    /// Download the changes since the last sync and apply them
+    pub async fn sync(&self) -> Result<NetworkGraphStats, LightningError> {
+        let base_url = self.rgs_url.as_ref().ok_or_else(|| {
+            LightningError::ConfigError("Rapid Gossip Sync requires lightning.ldk.rgs_url".to_string())
+        })?;
+        let since = self.graph.get_last_rapid_gossip_sync_timestamp().unwrap_or(0);
+        let url = format!("{}/{}", base_url, since);
+        let snapshot = retry_idempotent(&self.retry_policy, "Rapid Gossip Sync download", || async {
+            let response = self.http_client.get(&url).send().await
+                .map_err(|e| LightningError::NodeConnectionError(format!("Rapid Gossip Sync request failed: {}", e)))?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                });
+            }
+            response.bytes().await
+                .map_err(|e| LightningError::NodeConnectionError(format!("Rapid Gossip Sync download failed: {}", e)))
+        })
+        .await?;
+
+        let timestamp = self.apply_snapshot(&snapshot).await?;
+        let stats = self.stats();
+        info!(
+            "Applied Rapid Gossip Sync snapshot {}: {} nodes, {} channels",
+            timestamp, stats.node_count, stats.channel_count
+        );
+        Ok(stats)
+    }
+
+    /// Write the graph to `NETWORK_GRAPH_FILE` (via a temp file, so a crash
+    /// mid-write leaves the previous graph intact)
+    fn persist(&self) -> Result<(), LightningError> {
+        let tmp_path = self.graph_path.with_extension("bin.tmp");
+        std::fs::write(&tmp_path, self.graph.encode())
+            .and_then(|_| std::fs::rename(&tmp_path, &self.graph_path))
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to persist network graph: {}", e)))
+    }
+}
+
+/// Read a persisted graph; an unreadable file or one for another network
+/// is logged and replaced by an empty graph
+fn load_graph(path: &Path, network: Network, logger: Arc<LdkLogger>) -> Graph {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return NetworkGraph::new(network, logger),
+        Err(e) => {
+            warn!("Failed to read network graph {}: {}; starting empty", path.display(), e);
+            return NetworkGraph::new(network, logger);
+        }
+    };
+    match Graph::read(&mut &data[..], logger.clone()) {
+        Ok(graph) if graph.get_chain_hash() == ChainHash::using_genesis_block(network) => {
+            debug!("Loaded network graph from {}", path.display());
+            graph
+        }
+        Ok(_) => {
+            warn!("Network graph {} is for another network; starting empty", path.display());
+            NetworkGraph::new(network, logger)
+        }
+        Err(e) => {
+            warn!("Network graph {} is corrupt ({:?}); starting empty", path.display(), e);
+            NetworkGraph::new(network, logger)
+        }
+    }
+}
+
+/// Download snapshots until aborted
+pub(crate) async fn poll_rgs(gossip: Arc<GossipSync>, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = gossip.sync().await {
+            warn!("Rapid Gossip Sync failed, keeping the last network graph: {}", e);
+        }
+    }
+}
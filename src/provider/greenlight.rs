@@ -0,0 +1,364 @@
+//! Greenlight (Blockstream) provider implementation
+//!
+//! Runs a non-custodial CLN node hosted by Greenlight. The node seed lives in
+//! the module data dir, encrypted via `keystore`, and the signer runs in this
+//! process. Registration (or recovery, if the node already exists) happens on
+//! first use, after which the device credentials are stored encrypted
+//! alongside the seed.
+
+use crate::provider::keystore;
+use crate::provider::retry::{retry_idempotent, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind, MAX_DESCRIPTION_BYTES,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use gl_client::bitcoin::Network;
+use gl_client::credentials::{Device, Nobody};
+use gl_client::node::ClnClient;
+use gl_client::pb::cln;
+use gl_client::scheduler::Scheduler;
+use gl_client::signer::Signer;
+use std::path::PathBuf;
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
+
+/// Seed file, relative to the data dir
+const SEED_FILE: &str = "greenlight/seed.enc";
+/// Device credentials file, relative to the data dir
+const DEVICE_CREDS_FILE: &str = "greenlight/device_creds.enc";
+
+/// Greenlight provider configuration
+#[derive(Debug, Clone)]
+pub struct GreenlightConfig {
+    /// Module data dir; the seed and device credentials are stored beneath it
+    pub data_dir: PathBuf,
+    /// Network (mainnet, testnet, regtest, signet)
+    pub network: String,
+    /// Passphrase the seed and device credentials are encrypted with
+    pub seed_passphrase: String,
+    /// Invite code for registering a new node
+    pub invite_code: Option<String>,
+    /// Developer certificate and key (PEM) used to register/recover the node
+    pub developer_cert_path: Option<PathBuf>,
+    pub developer_key_path: Option<PathBuf>,
+    /// Timeout and retry policy for payment lookups
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+/// Authenticated node connection plus the running signer
+struct Connection {
+    node: ClnClient,
+    /// Dropping the sender stops the signer
+    _signer_shutdown: tokio::sync::mpsc::Sender<()>,
+}
+
+/// Greenlight provider implementation
+pub struct GreenlightProvider {
+    config: GreenlightConfig,
+    network: Network,
+    seed: Vec<u8>,
+    connection: OnceCell<Connection>,
+}
+
+impl GreenlightProvider {
+    /// Create a new Greenlight provider
+    ///
+    /// Loads the encrypted seed from the data dir, generating one on first
+    /// start. The node itself is contacted lazily.
+    pub fn new(config: GreenlightConfig) -> Result<Self, LightningError> {
+        if config.seed_passphrase.is_empty() {
+            return Err(LightningError::ConfigError(
+                "Greenlight requires lightning.greenlight.seed_passphrase; the seed is never stored in plaintext".to_string(),
+            ));
+        }
+        let network = match config.network.to_lowercase().as_str() {
+            "mainnet" | "bitcoin" => Network::Bitcoin,
+            "testnet" => Network::Testnet,
+            "regtest" => Network::Regtest,
+            "signet" => Network::Signet,
+            other => return Err(LightningError::ConfigError(format!("Invalid network: {}", other))),
+        };
+
+        let seed_path = config.data_dir.join(SEED_FILE);
+        let seed = match keystore::load_encrypted(&seed_path, &config.seed_passphrase)? {
+            Some(seed) => seed,
+            None => {
+                let seed = rand::random::<[u8; 32]>().to_vec();
+                keystore::save_encrypted(&seed_path, &seed, &config.seed_passphrase)?;
+                info!("Generated new Greenlight seed, saved to {:?}", seed_path);
+                seed
+            }
+        };
+
+        Ok(Self {
+            config,
+            network,
+            seed,
+            connection: OnceCell::new(),
+        })
+    }
+
+    fn developer_credentials(&self) -> Result<Nobody, LightningError> {
+        match (&self.config.developer_cert_path, &self.config.developer_key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                let read = |path: &PathBuf| {
+                    std::fs::read(path).map_err(|e| {
+                        LightningError::ConfigError(format!("Failed to read {}: {}", path.display(), e))
+                    })
+                };
+                Ok(Nobody::with(read(cert_path)?, read(key_path)?))
+            }
+            _ => Ok(Nobody::new()),
+        }
+    }
+
+    /// Load stored device credentials, or register/recover the node to obtain them
+    async fn device_credentials(&self) -> Result<Device, LightningError> {
+        let creds_path = self.config.data_dir.join(DEVICE_CREDS_FILE);
+        if let Some(creds) = keystore::load_encrypted(&creds_path, &self.config.seed_passphrase)? {
+            return Ok(Device::from_bytes(creds));
+        }
+
+        let nobody = self.developer_credentials()?;
+        let signer = Signer::new(self.seed.clone(), self.network, nobody.clone())
+            .map_err(|e| LightningError::ConfigError(format!("Failed to create Greenlight signer: {}", e)))?;
+        let scheduler = Scheduler::new(self.network, nobody)
+            .await
+            .map_err(|e| LightningError::NodeConnectionError(format!("Greenlight scheduler unreachable: {}", e)))?;
+
+        // Recover first so an existing node is never re-registered
+        let creds = match scheduler.recover(&signer).await {
+            Ok(response) => {
+                info!("Recovered existing Greenlight node");
+                response.creds
+            }
+            Err(e) => {
+                debug!("Greenlight recovery failed ({}), registering a new node", e);
+                scheduler
+                    .register(&signer, self.config.invite_code.clone())
+                    .await
+                    .map_err(|e| LightningError::ProcessorError(format!("Greenlight registration failed: {}", e)))?
+                    .creds
+            }
+        };
+        keystore::save_encrypted(&creds_path, &creds, &self.config.seed_passphrase)?;
+        Ok(Device::from_bytes(creds))
+    }
+
+    async fn connect(&self) -> Result<Connection, LightningError> {
+        let device = self.device_credentials().await?;
+
+        let signer = Signer::new(self.seed.clone(), self.network, device.clone())
+            .map_err(|e| LightningError::ConfigError(format!("Failed to create Greenlight signer: {}", e)))?;
+        let (shutdown_tx, shutdown_rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            if let Err(e) = signer.run_forever(shutdown_rx).await {
+                warn!("Greenlight signer stopped: {}", e);
+            }
+        });
+
+        let node = Scheduler::new(self.network, device)
+            .await
+            .map_err(|e| LightningError::NodeConnectionError(format!("Greenlight scheduler unreachable: {}", e)))?
+            .node()
+            .await
+            .map_err(|e| LightningError::NodeConnectionError(format!("Failed to schedule Greenlight node: {}", e)))?;
+
+        Ok(Connection {
+            node,
+            _signer_shutdown: shutdown_tx,
+        })
+    }
+
+    /// Client for the scheduled node, connecting on first use
+    async fn node(&self) -> Result<ClnClient, LightningError> {
+        let connection = self.connection.get_or_try_init(|| self.connect()).await?;
+        Ok(connection.node.clone())
+    }
+
+    async fn find_invoice(
+        &self,
+        payment_hash: &[u8; 32],
+    ) -> Result<Option<cln::ListinvoicesInvoices>, LightningError> {
+        let request = cln::ListinvoicesRequest {
+            payment_hash: Some(payment_hash.to_vec()),
+            ..Default::default()
+        };
+        let response = self
+            .node()
+            .await?
+            .list_invoices(request)
+            .await
+            .map_err(|e| LightningError::NodeConnectionError(format!("Greenlight listinvoices failed: {}", e)))?;
+        Ok(response.into_inner().invoices.into_iter().next())
+    }
+}
+
+fn is_paid(invoice: &cln::ListinvoicesInvoices) -> bool {
+    invoice.status == cln::listinvoices_invoices::ListinvoicesInvoicesStatus::Paid as i32
+}
+
+#[async_trait]
+impl LightningProvider for GreenlightProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "greenlight", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via Greenlight: payment_id={}", payment_id);
+
+        let invoice = retry_idempotent(&self.config.retry_policy, "Greenlight invoice lookup", || {
+            self.find_invoice(payment_hash)
+        })
+        .await?;
+
+        let invoice = match invoice {
+            Some(invoice) => invoice,
+            None => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "greenlight",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        let verified = is_paid(&invoice);
+        Ok(PaymentVerificationResult {
+            verified,
+            amount_msats: invoice
+                .amount_received_msat
+                .as_ref()
+                .or(invoice.amount_msat.as_ref())
+                .map(|amount| amount.msat),
+            timestamp: invoice.paid_at,
+            metadata: serde_json::json!({
+                "provider": "greenlight",
+                "payment_hash": hex::encode(payment_hash),
+                "label": invoice.label,
+                "preimage": invoice.payment_preimage.as_ref().map(hex::encode),
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "greenlight", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        debug!("Creating invoice via Greenlight: amount={} msats", params.amount_msats);
+
+        let (description, deschashonly) = match &params.description {
+            DescriptionKind::Direct(description) => (description.clone(), description.len() > MAX_DESCRIPTION_BYTES),
+            DescriptionKind::Hash(_) => {
+                return Err(LightningError::InvoiceError(
+                    "Greenlight does not support precomputed description hashes".to_string(),
+                ));
+            }
+        };
+        let label = params
+            .label
+            .clone()
+            .unwrap_or_else(|| format!("blvm-{}", hex::encode(rand::random::<[u8; 16]>())));
+
+        let request = cln::InvoiceRequest {
+            amount_msat: Some(cln::AmountOrAny {
+                value: Some(cln::amount_or_any::Value::Amount(cln::Amount { msat: params.amount_msats })),
+            }),
+            description,
+            label: label.clone(),
+            expiry: Some(params.expiry_seconds),
+            deschashonly: Some(deschashonly),
+            ..Default::default()
+        };
+        let response = self
+            .node()
+            .await?
+            .invoice(request)
+            .await
+            .map_err(|e| LightningError::ProcessorError(format!("Greenlight invoice failed: {}", e)))?;
+
+        debug!("Greenlight invoice created: label={}", label);
+        Ok(response.into_inner().bolt11)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        let invoice = retry_idempotent(&self.config.retry_policy, "Greenlight invoice lookup", || {
+            self.find_invoice(payment_hash)
+        })
+        .await?;
+        Ok(invoice.as_ref().map(is_paid).unwrap_or(false))
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        let started = std::time::Instant::now();
+        let result = match self.node().await {
+            Ok(mut node) => node
+                .getinfo(cln::GetinfoRequest {})
+                .await
+                .map(|response| response.into_inner())
+                .map_err(|e| LightningError::NodeConnectionError(format!("Greenlight getinfo failed: {}", e))),
+            Err(e) => Err(e),
+        };
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(info) => Ok(HealthStatus {
+                reachable: true,
+                latency_ms,
+                block_height: Some(info.blockheight as u64),
+                synced_to_chain: Some(info.warning_bitcoind_sync.is_none() && info.warning_lightningd_sync.is_none()),
+                version: Some(info.version),
+            }),
+            Err(e) => {
+                warn!("Greenlight health check failed: {}", e);
+                Ok(HealthStatus {
+                    reachable: false,
+                    latency_ms,
+                    block_height: None,
+                    synced_to_chain: None,
+                    version: None,
+                })
+            }
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Greenlight
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
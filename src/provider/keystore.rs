@@ -0,0 +1,124 @@
+//! Encrypted storage for provider key material
+//!
+//! Secrets (node keys, seeds, device credentials) are sealed with
+//! ChaCha20-Poly1305 under a key derived from a passphrase with scrypt.
+//!
+//! File format (version 1):
+//! `magic (4) | version (1) | salt (16) | nonce (12) | ciphertext + tag`
+
+use crate::error::LightningError;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::path::Path;
+
+/// Magic bytes identifying an encrypted key file
+pub const KEYSTORE_MAGIC: &[u8; 4] = b"BLVK";
+/// Current file format version
+pub const KEYSTORE_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = KEYSTORE_MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+/// scrypt cost parameters (N = 2^15, r = 8, p = 1)
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, LightningError> {
+    let params = scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, 32)
+        .map_err(|e| LightningError::ConfigError(format!("Invalid key derivation parameters: {}", e)))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| LightningError::ConfigError(format!("Key derivation failed: {}", e)))?;
+    Ok(Key::from(key))
+}
+
+/// Whether `data` looks like an encrypted key file
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(KEYSTORE_MAGIC)
+}
+
+/// Encrypt a secret under `passphrase`
+pub fn encrypt(secret: &[u8], passphrase: &str) -> Result<Vec<u8>, LightningError> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret)
+        .map_err(|e| LightningError::ConfigError(format!("Failed to encrypt key material: {}", e)))?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    out.extend_from_slice(KEYSTORE_MAGIC);
+    out.push(KEYSTORE_VERSION);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt a secret produced by `encrypt`
+///
+/// A wrong passphrase fails authentication and yields a `ConfigError`.
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, LightningError> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        return Err(LightningError::ConfigError("Not an encrypted key file".to_string()));
+    }
+    let version = data[KEYSTORE_MAGIC.len()];
+    if version != KEYSTORE_VERSION {
+        return Err(LightningError::ConfigError(format!("Unsupported key file version {}", version)));
+    }
+
+    let salt = &data[KEYSTORE_MAGIC.len() + 1..KEYSTORE_MAGIC.len() + 1 + SALT_LEN];
+    let nonce = &data[HEADER_LEN - NONCE_LEN..HEADER_LEN];
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), &data[HEADER_LEN..])
+        .map_err(|_| LightningError::ConfigError("Wrong passphrase or corrupt key file".to_string()))
+}
+
+/// Encrypt a secret and write it to `path`
+///
+/// Written to a temporary file and renamed into place so a crash never
+/// leaves a truncated key file; the file is only readable by the owner.
+pub fn save_encrypted(path: &Path, secret: &[u8], passphrase: &str) -> Result<(), LightningError> {
+    let data = encrypt(secret, passphrase)?;
+    write_private(path, &data)
+}
+
+/// Write `data` to `path` so only the owner can read it
+///
+/// Written to a temporary file and renamed into place so a crash never
+/// leaves a truncated file.
+pub fn write_private(path: &Path, data: &[u8]) -> Result<(), LightningError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| LightningError::ConfigError(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, data)
+        .map_err(|e| LightningError::ConfigError(format!("Failed to write {}: {}", tmp_path.display(), e)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| LightningError::ConfigError(format!("Failed to restrict {}: {}", tmp_path.display(), e)))?;
+    }
+    std::fs::rename(&tmp_path, path)
+        .map_err(|e| LightningError::ConfigError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Read and decrypt a secret from `path`; `None` if the file doesn't exist
+pub fn load_encrypted(path: &Path, passphrase: &str) -> Result<Option<Vec<u8>>, LightningError> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(LightningError::ConfigError(format!("Failed to read {}: {}", path.display(), e)));
+        }
+    };
+    decrypt(&data, passphrase)
+        .map(Some)
+        .map_err(|e| LightningError::ConfigError(format!("{}: {}", path.display(), e)))
+}
@@ -1,19 +1,360 @@
 //! LDK (Lightning Development Kit) provider implementation
 //!
-//! Full LDK integration for Rust-native Lightning payments.
-//! Provides channel management, peer connections, and payment processing.
+//! Rust-native Lightning invoices signed with the node key, with chain tip
+//! sync and payment tracking. There is no LDK `ChannelManager`,
+//! `ChainMonitor`, `KeysManager`, persister or background processor yet:
+//! channels and received payments are only known from what the caller
+//! feeds in (`mark_payment_received`), and no HTLCs are claimed.
 
-use crate::provider::{ProviderType, LightningProvider, PaymentVerificationResult};
+use crate::provider::chain::{create_chain_source, BestBlock, ChainSource, ChainSourceConfig};
+use crate::provider::channel_policy::{ChannelDecision, ChannelOpenRequest, ChannelPolicy};
+use crate::provider::gossip::{poll_rgs, GossipSync, NetworkGraphStats};
+use crate::provider::keystore;
+use crate::provider::retry::{retry_idempotent, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams, DescriptionKind,
+    MAX_DESCRIPTION_BYTES, parse_network, payment_amount_msats,
+};
 use crate::error::LightningError;
+use crate::invoice::address_to_fallback;
 use async_trait::async_trait;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn, error};
-use lightning_invoice::Invoice;
+use lightning_invoice::{Bolt11Invoice, Currency, InvoiceBuilder, PaymentSecret};
 use bitcoin::Network;
-use secp256k1::{SecretKey, PublicKey, Secp256k1};
+use secp256k1::{SecretKey, PublicKey, Secp256k1, Message};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use base64::Engine;
+use bitcoin::hashes::{sha256, sha256d, Hash as _};
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Prefix committed to by node message signatures
+const SIGNED_MESSAGE_PREFIX: &[u8] = b"Lightning Signed Message:";
+
+/// Header byte of a recoverable signature by a compressed key, before the recovery id
+const SIGNED_MESSAGE_HEADER: u8 = 31;
+
+/// Digest signed by `sign_message`
+fn signed_message_digest(message: &[u8]) -> Result<Message, LightningError> {
+    let mut data = Vec::with_capacity(SIGNED_MESSAGE_PREFIX.len() + message.len());
+    data.extend_from_slice(SIGNED_MESSAGE_PREFIX);
+    data.extend_from_slice(message);
+    Message::from_digest_slice(&sha256d::Hash::hash(&data).to_byte_array())
+        .map_err(|e| LightningError::ProcessorError(format!("Invalid message digest: {}", e)))
+}
+
+/// Plaintext node key in the data directory (hex), used without a key passphrase
+pub const NODE_KEY_FILE: &str = "node_key.hex";
+
+/// Node key encrypted with `keystore` under `lightning.ldk.key_passphrase`
+pub const ENCRYPTED_NODE_KEY_FILE: &str = "node_key.enc";
+
+/// Static channel backup file in the data directory
+pub const SCB_FILE: &str = "channel.bak";
+
+/// Magic bytes identifying a static channel backup
+const SCB_MAGIC: &[u8; 4] = b"BLVS";
+/// Current backup format version
+const SCB_VERSION: u8 = 1;
+const SCB_NONCE_LEN: usize = 12;
+const SCB_HEADER_LEN: usize = SCB_MAGIC.len() + 1 + SCB_NONCE_LEN;
+
+/// Tracked payments and issued invoices, persisted in the data directory
+pub const PAYMENT_STATE_FILE: &str = "ldk_payments.json";
+
+/// Plaintext contents of a static channel backup (and of `PAYMENT_STATE_FILE`)
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ChannelBackup {
+    /// Tracked payments (hex payment hash -> state)
+    payments: HashMap<String, BackupPayment>,
+    /// Issued invoices (hex payment hash -> BOLT11)
+    invoices: HashMap<String, String>,
+    /// Preimages of issued invoices (hex payment hash -> hex preimage)
+    #[serde(default)]
+    preimages: HashMap<String, String>,
+    /// Payment secrets of issued invoices (hex payment hash -> hex secret)
+    #[serde(default)]
+    payment_secrets: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupPayment {
+    amount_msats: u64,
+    timestamp: u64,
+    confirmed: bool,
+}
+
+fn decode_backup_hash(hash_hex: &str) -> Result<[u8; 32], LightningError> {
+    let mut hash = [0u8; 32];
+    hex::decode_to_slice(hash_hex, &mut hash)
+        .map_err(|e| LightningError::ConfigError(format!("Corrupt channel backup payment hash {}: {}", hash_hex, e)))?;
+    Ok(hash)
+}
+
+/// Payment state loaded from `PAYMENT_STATE_FILE`
+#[derive(Default)]
+struct LoadedPaymentState {
+    payments: HashMap<[u8; 32], (u64, u64, bool)>,
+    invoices: HashMap<[u8; 32], String>,
+    preimages: HashMap<[u8; 32], [u8; 32]>,
+    payment_secrets: HashMap<[u8; 32], [u8; 32]>,
+}
+
+/// Load `PAYMENT_STATE_FILE`, if there is one
+///
+/// A corrupt file is moved aside (to `<file>.corrupt`) and the node starts
+/// with no tracked payments rather than refusing to start.
+fn load_payment_state(data_dir: &std::path::Path) -> LoadedPaymentState {
+    let path = data_dir.join(PAYMENT_STATE_FILE);
+    let contents = match std::fs::read(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return LoadedPaymentState::default(),
+        Err(e) => {
+            warn!("Failed to read LDK payment state {}: {}", path.display(), e);
+            return LoadedPaymentState::default();
+        }
+    };
+
+    let parsed = serde_json::from_slice::<ChannelBackup>(&contents)
+        .map_err(|e| LightningError::ConfigError(format!("Corrupt LDK payment state: {}", e)))
+        .and_then(|state| {
+            let payments = state.payments
+                .into_iter()
+                .map(|(hash_hex, payment)| {
+                    Ok((decode_backup_hash(&hash_hex)?, (payment.amount_msats, payment.timestamp, payment.confirmed)))
+                })
+                .collect::<Result<HashMap<_, _>, LightningError>>()?;
+            let invoices = state.invoices
+                .into_iter()
+                .map(|(hash_hex, invoice)| Ok((decode_backup_hash(&hash_hex)?, invoice)))
+                .collect::<Result<HashMap<_, _>, LightningError>>()?;
+            let preimages = state.preimages
+                .into_iter()
+                .map(|(hash_hex, preimage_hex)| Ok((decode_backup_hash(&hash_hex)?, decode_backup_hash(&preimage_hex)?)))
+                .collect::<Result<HashMap<_, _>, LightningError>>()?;
+            let payment_secrets = state.payment_secrets
+                .into_iter()
+                .map(|(hash_hex, secret_hex)| Ok((decode_backup_hash(&hash_hex)?, decode_backup_hash(&secret_hex)?)))
+                .collect::<Result<HashMap<_, _>, LightningError>>()?;
+            Ok(LoadedPaymentState { payments, invoices, preimages, payment_secrets })
+        });
+    match parsed {
+        Ok(state) => {
+            info!("Loaded LDK payment state: {} payments, {} invoices", state.payments.len(), state.invoices.len());
+            state
+        }
+        Err(e) => {
+            let corrupt_path = path.with_extension("json.corrupt");
+            error!("{} ({}); moving it to {} and starting empty", e, path.display(), corrupt_path.display());
+            if let Err(e) = std::fs::rename(&path, &corrupt_path) {
+                warn!("Failed to move corrupt LDK payment state aside: {}", e);
+            }
+            LoadedPaymentState::default()
+        }
+    }
+}
+
+/// Payment state of the provider, shared with the cleanup task
+#[derive(Clone)]
+struct PaymentStore {
+    /// `PAYMENT_STATE_FILE` in the data directory
+    path: PathBuf,
+    /// Payment hash tracking (payment_hash -> (amount_msats, timestamp, confirmed))
+    payment_tracker: Arc<RwLock<HashMap<[u8; 32], (u64, u64, bool)>>>,
+    /// Invoice storage (payment_hash -> invoice_string)
+    invoice_storage: Arc<RwLock<HashMap<[u8; 32], String>>>,
+    /// Preimages of issued invoices (payment_hash -> preimage), needed to claim payments
+    preimages: Arc<RwLock<HashMap<[u8; 32], [u8; 32]>>>,
+    /// Payment secrets of issued invoices (payment_hash -> payment_secret), checked before claiming
+    payment_secrets: Arc<RwLock<HashMap<[u8; 32], [u8; 32]>>>,
+    /// Caller metadata stored alongside invoices (payment_hash -> metadata)
+    invoice_metadata: Arc<RwLock<HashMap<[u8; 32], serde_json::Value>>>,
+    /// Invoice labels (label -> payment_hash), used to make labelled creation idempotent
+    invoice_labels: Arc<RwLock<HashMap<String, [u8; 32]>>>,
+    /// Serializes writes of `PAYMENT_STATE_FILE`
+    write_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl PaymentStore {
+    /// Tracked payments and issued invoices, keyed by payment hash hex
+    async fn snapshot(&self) -> ChannelBackup {
+        let mut backup = ChannelBackup::default();
+        for (hash, (amount_msats, timestamp, confirmed)) in self.payment_tracker.read().await.iter() {
+            backup.payments.insert(hex::encode(hash), BackupPayment {
+                amount_msats: *amount_msats,
+                timestamp: *timestamp,
+                confirmed: *confirmed,
+            });
+        }
+        for (hash, invoice) in self.invoice_storage.read().await.iter() {
+            backup.invoices.insert(hex::encode(hash), invoice.clone());
+        }
+        for (hash, preimage) in self.preimages.read().await.iter() {
+            backup.preimages.insert(hex::encode(hash), hex::encode(preimage));
+        }
+        for (hash, secret) in self.payment_secrets.read().await.iter() {
+            backup.payment_secrets.insert(hex::encode(hash), hex::encode(secret));
+        }
+        backup
+    }
+
+    /// Write the tracked payments and issued invoices to `PAYMENT_STATE_FILE`
+    ///
+    /// Called after every change. The file is serialized and written on a
+    /// blocking thread. A failed write is logged and the change kept in
+    /// memory, so it is only lost if the node restarts first.
+    async fn persist(&self) {
+        let _guard = self.write_lock.lock().await;
+        let snapshot = self.snapshot().await;
+        let path = self.path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let tmp_path = path.with_extension("tmp");
+            let contents = serde_json::to_vec(&snapshot).map_err(|e| e.to_string())?;
+            std::fs::write(&tmp_path, contents)
+                .and_then(|_| std::fs::rename(&tmp_path, &path))
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+        if let Err(e) = result {
+            warn!("Failed to persist LDK payment state {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Drop tracker entries past their retention, then expired invoices
+    ///
+    /// An expired invoice goes with its preimage, payment secret, metadata
+    /// and label, but not while the tracker still holds a payment for it.
+    async fn prune(&self, now: u64, retention: &RetentionPolicy) -> PrunedEntries {
+        let mut pruned = PrunedEntries::default();
+        let mut tracker = self.payment_tracker.write().await;
+        let before = tracker.len();
+        tracker.retain(|_, (_, timestamp, confirmed)| {
+            let retention_secs = if *confirmed { retention.confirmed_retention_secs } else { retention.retention_secs };
+            now.saturating_sub(*timestamp) <= retention_secs
+        });
+        pruned.payments = before - tracker.len();
+
+        let mut expired = Vec::new();
+        let mut storage = self.invoice_storage.write().await;
+        storage.retain(|hash, invoice| {
+            let is_expired = invoice.parse::<Bolt11Invoice>().is_ok_and(|invoice| {
+                (invoice.duration_since_epoch() + invoice.expiry_time()).as_secs() <= now
+            });
+            if is_expired && !tracker.contains_key(hash) {
+                expired.push(*hash);
+                return false;
+            }
+            true
+        });
+        drop(storage);
+        drop(tracker);
+        pruned.invoices = expired.len();
+
+        if !expired.is_empty() {
+            let mut preimages = self.preimages.write().await;
+            let mut payment_secrets = self.payment_secrets.write().await;
+            let mut metadata = self.invoice_metadata.write().await;
+            for hash in &expired {
+                preimages.remove(hash);
+                payment_secrets.remove(hash);
+                metadata.remove(hash);
+            }
+            drop((preimages, payment_secrets, metadata));
+            self.invoice_labels.write().await.retain(|_, hash| !expired.contains(hash));
+        }
+        if pruned.payments > 0 || pruned.invoices > 0 {
+            self.persist().await;
+        }
+        pruned
+    }
+}
+
+/// How long tracked payments are kept before cleanup removes them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Seconds to keep payments that never confirmed
+    pub retention_secs: u64,
+    /// Seconds to keep confirmed payments
+    pub confirmed_retention_secs: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            retention_secs: DEFAULT_RETENTION_SECS,
+            confirmed_retention_secs: DEFAULT_CONFIRMED_RETENTION_SECS,
+        }
+    }
+}
+
+/// Default seconds between cleanup passes
+pub const DEFAULT_CLEANUP_INTERVAL_SECS: u64 = 3600;
+
+/// Default retention of unconfirmed payments (7 days)
+pub const DEFAULT_RETENTION_SECS: u64 = 7 * 24 * 3600;
+
+/// Default retention of confirmed payments (90 days)
+pub const DEFAULT_CONFIRMED_RETENTION_SECS: u64 = 90 * 24 * 3600;
+
+/// Entries removed by a cleanup pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrunedEntries {
+    /// Expired invoices (with their preimages, metadata and labels)
+    pub invoices: usize,
+    /// Payment tracker entries past their retention
+    pub payments: usize,
+}
+
+/// Prune the payment store every `interval` until aborted
+async fn poll_cleanup(store: PaymentStore, retention: RetentionPolicy, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let pruned = store.prune(now_secs(), &retention).await;
+        if pruned.payments > 0 || pruned.invoices > 0 {
+            info!("Pruned {} expired LDK invoices and {} old tracked payments", pruned.invoices, pruned.payments);
+        }
+    }
+}
+
+/// Poll the chain source for the tip until aborted
+async fn poll_chain_tip(
+    chain_source: Arc<dyn ChainSource>,
+    best_block: Arc<RwLock<Option<BestBlock>>>,
+    interval: std::time::Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match chain_source.best_block().await {
+            Ok(tip) => {
+                let mut best_block = best_block.write().await;
+                if best_block.as_ref() != Some(&tip) {
+                    debug!("Chain tip now {} ({})", tip.height, tip.hash);
+                    *best_block = Some(tip);
+                }
+            }
+            Err(e) => warn!("LDK chain sync failed: {}", e),
+        }
+    }
+}
+
+/// Current unix time in seconds
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
 
 /// LDK provider configuration
 #[derive(Debug, Clone)]
@@ -24,8 +365,50 @@ pub struct LDKConfig {
     pub network: String,
     /// Node private key (optional, will generate if not provided)
     pub node_private_key: Option<Vec<u8>>,
+    /// Passphrase the stored node key is encrypted under (stored in plaintext if unset)
+    pub key_passphrase: Option<String>,
+    /// Timeout and retry policy for payment lookups
+    pub retry_policy: ProviderRetryPolicy,
+    /// Base fee assumed per routing hop when estimating fees
+    pub default_fee_estimate_msats: u64,
+    /// Export a channel backup after every processed payment
+    pub auto_backup: bool,
+    /// CLTV delta required for the final hop of incoming payments (network default if unset)
+    pub min_final_cltv_expiry: Option<u32>,
+    /// Chain source to follow the chain with (no chain sync if unset)
+    pub chain_source: Option<ChainSourceConfig>,
+    /// Seconds between chain tip polls
+    pub chain_sync_interval_secs: u64,
+    /// Rapid Gossip Sync server to fetch the network graph from (no sync if unset)
+    pub rgs_url: Option<String>,
+    /// Seconds between Rapid Gossip Sync downloads
+    pub rgs_sync_interval_secs: u64,
+    /// Seconds between cleanup passes over the payment store (0 disables cleanup)
+    pub cleanup_interval_secs: u64,
+    /// How long cleanup keeps tracked payments
+    pub retention: RetentionPolicy,
+    /// Which inbound channels are accepted
+    pub channel_policy: ChannelPolicy,
+}
+
+/// Default seconds between chain tip polls
+pub const DEFAULT_CHAIN_SYNC_INTERVAL_SECS: u64 = 30;
+
+/// Default final hop CLTV delta for a network
+///
+/// Mainnet uses the conservative 144 blocks (a day); test networks, where
+/// blocks come quickly and nothing is at stake, need far fewer.
+pub fn default_cltv_for_network(network: &Network) -> u32 {
+    match network {
+        Network::Bitcoin => 144,
+        Network::Regtest => 6,
+        _ => 18,
+    }
 }
 
+/// Proportional fee assumed per routing hop (LDK's default forwarding fee)
+const ESTIMATE_HOP_FEE_RATE_PPM: u64 = 1_000;
+
 /// LDK provider implementation
 pub struct LDKProvider {
     config: LDKConfig,
@@ -35,10 +418,22 @@ pub struct LDKProvider {
     node_public_key: PublicKey,
     /// Network (mainnet, testnet, regtest)
     network: Network,
-    /// Payment hash tracking (payment_hash -> (amount_msats, timestamp, confirmed))
-    payment_tracker: Arc<RwLock<HashMap<[u8; 32], (u64, u64, bool)>>>,
-    /// Invoice storage (payment_hash -> invoice_string)
-    invoice_storage: Arc<RwLock<HashMap<[u8; 32], String>>>,
+    /// Tracked payments and issued invoices
+    store: PaymentStore,
+    /// Background pruning of expired invoices and old payments
+    cleanup_task: Option<tokio::task::JoinHandle<()>>,
+    /// Open channels (channel_id -> channel), populated by channel event handling
+    channels: Arc<RwLock<HashMap<[u8; 32], ChannelInfo>>>,
+    /// Chain source, if chain sync is configured
+    chain_source: Option<Arc<dyn ChainSource>>,
+    /// Latest chain tip seen by the chain source
+    best_block: Arc<RwLock<Option<BestBlock>>>,
+    /// Background chain tip poller
+    sync_task: Option<tokio::task::JoinHandle<()>>,
+    /// Network graph for pathfinding
+    gossip: Arc<GossipSync>,
+    /// Background Rapid Gossip Sync downloader
+    gossip_task: Option<tokio::task::JoinHandle<()>>,
     /// Secp256k1 context
     secp: Secp256k1<secp256k1::All>,
 }
@@ -53,16 +448,10 @@ impl LDKProvider {
             .map_err(|e| LightningError::ConfigError(format!("Failed to create data directory: {}", e)))?;
         
         // Determine network
-        let network = match config.network.to_lowercase().as_str() {
-            "mainnet" => Network::Bitcoin,
-            "testnet" => Network::Testnet,
-            "regtest" => Network::Regtest,
-            "signet" => Network::Signet,
-            _ => {
-                warn!("Unknown network '{}', defaulting to testnet", config.network);
-                Network::Testnet
-            }
-        };
+        let network = parse_network(&config.network).unwrap_or_else(|| {
+            warn!("Unknown network '{}', defaulting to testnet", config.network);
+            Network::Testnet
+        });
         
         // Initialize or load node keys
         let secp = Secp256k1::new();
@@ -76,6 +465,9 @@ impl LDKProvider {
                 .map_err(|e| LightningError::ConfigError(format!("Invalid private key: {}", e)))?;
             let public_key = PublicKey::from_secret_key(&secp, &secret_key);
             (secret_key, public_key)
+        } else if let Some(keys) = Self::load_keys(&config.data_dir, config.key_passphrase.as_deref())? {
+            // Reuse the identity from previous runs
+            keys
         } else {
             // Generate new keys
             let secret_key = SecretKey::from_slice(&rand::random::<[u8; 32]>())
@@ -83,39 +475,173 @@ impl LDKProvider {
             let public_key = PublicKey::from_secret_key(&secp, &secret_key);
             
             // Save keys to disk for persistence
-            let key_path = config.data_dir.join("node_key.hex");
-            // secp256k1 0.12: serialize the key
-            // In 0.12, SecretKey implements Display or can be serialized via its inner bytes
-            // Use the key's serialization method
-            let mut key_bytes = [0u8; 32];
-            key_bytes.copy_from_slice(&secret_key[..]);
-            std::fs::write(&key_path, hex::encode(key_bytes))
-                .map_err(|e| LightningError::ConfigError(format!("Failed to save node key: {}", e)))?;
-            
+            let key_path = Self::save_keys(&config.data_dir, &secret_key, config.key_passphrase.as_deref())?;
             info!("Generated new node keys, saved to {:?}", key_path);
             (secret_key, public_key)
         };
         
         info!("LDK provider initialized: node_id={}", hex::encode(node_public_key.serialize()));
         
+        let state = load_payment_state(&config.data_dir);
+        let store = PaymentStore {
+            path: config.data_dir.join(PAYMENT_STATE_FILE),
+            payment_tracker: Arc::new(RwLock::new(state.payments)),
+            invoice_storage: Arc::new(RwLock::new(state.invoices)),
+            preimages: Arc::new(RwLock::new(state.preimages)),
+            payment_secrets: Arc::new(RwLock::new(state.payment_secrets)),
+            invoice_metadata: Arc::new(RwLock::new(HashMap::new())),
+            invoice_labels: Arc::new(RwLock::new(HashMap::new())),
+            write_lock: Arc::new(tokio::sync::Mutex::new(())),
+        };
+        let cleanup_task = match (config.cleanup_interval_secs, tokio::runtime::Handle::try_current()) {
+            (0, _) => None,
+            (interval, Ok(runtime)) => Some(runtime.spawn(poll_cleanup(
+                store.clone(),
+                config.retention.clone(),
+                std::time::Duration::from_secs(interval),
+            ))),
+            (_, Err(_)) => {
+                warn!("No Tokio runtime; expired LDK invoices and payments won't be pruned");
+                None
+            }
+        };
+        
+        let chain_source: Option<Arc<dyn ChainSource>> = match &config.chain_source {
+            Some(chain_source) => Some(Arc::from(create_chain_source(chain_source, config.retry_policy.clone())?)),
+            None => None,
+        };
+        let best_block = Arc::new(RwLock::new(None));
+        let sync_task = match &chain_source {
+            Some(chain_source) => {
+                let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+                    LightningError::ConfigError("LDK chain sync requires a Tokio runtime".to_string())
+                })?;
+                Some(runtime.spawn(poll_chain_tip(
+                    chain_source.clone(),
+                    best_block.clone(),
+                    std::time::Duration::from_secs(config.chain_sync_interval_secs.max(1)),
+                )))
+            }
+            None => None,
+        };
+        
+        let gossip = Arc::new(GossipSync::new(
+            network,
+            &config.data_dir,
+            config.rgs_url.clone(),
+            config.retry_policy.clone(),
+        )?);
+        let gossip_task = if gossip.is_enabled() {
+            let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+                LightningError::ConfigError("LDK Rapid Gossip Sync requires a Tokio runtime".to_string())
+            })?;
+            Some(runtime.spawn(poll_rgs(
+                gossip.clone(),
+                std::time::Duration::from_secs(config.rgs_sync_interval_secs.max(1)),
+            )))
+        } else {
+            None
+        };
+        
         Ok(Self {
             config,
             node_secret_key,
             node_public_key,
             network,
-            payment_tracker: Arc::new(RwLock::new(HashMap::new())),
-            invoice_storage: Arc::new(RwLock::new(HashMap::new())),
+            store,
+            cleanup_task,
+            channels: Arc::new(RwLock::new(HashMap::new())),
+            chain_source,
+            best_block,
+            sync_task,
+            gossip,
+            gossip_task,
             secp,
         })
     }
     
-    /// Load node keys from disk
-    fn load_keys(data_dir: &PathBuf) -> Result<(SecretKey, PublicKey), LightningError> {
-        let key_path = data_dir.join("node_key.hex");
-        let key_hex = std::fs::read_to_string(&key_path)
+    /// Invoice currency for the network (testnet4 invoices use the testnet prefix)
+    fn currency(&self) -> Currency {
+        match self.network {
+            Network::Testnet4 => Currency::BitcoinTestnet,
+            network => Currency::from(network),
+        }
+    }
+    
+    /// Final hop CLTV delta put in invoices that don't ask for one
+    pub fn min_final_cltv_expiry(&self) -> u32 {
+        self.config.min_final_cltv_expiry.unwrap_or_else(|| default_cltv_for_network(&self.network))
+    }
+    
+    /// Fetch the chain tip from the chain source now
+    pub async fn sync_chain(&self) -> Result<BestBlock, LightningError> {
+        let chain_source = self.chain_source.as_ref().ok_or_else(|| {
+            LightningError::ConfigError("LDK chain sync requires lightning.ldk.chain_source".to_string())
+        })?;
+        let tip = chain_source.best_block().await?;
+        *self.best_block.write().await = Some(tip.clone());
+        Ok(tip)
+    }
+    
+    /// Latest chain tip seen, if chain sync has run
+    pub async fn best_block(&self) -> Option<BestBlock> {
+        self.best_block.read().await.clone()
+    }
+    
+    /// Node and channel counts of the network graph and when it was last synced
+    pub fn network_graph_stats(&self) -> NetworkGraphStats {
+        self.gossip.stats()
+    }
+    
+    /// Download and apply a Rapid Gossip Sync snapshot now
+    pub async fn sync_gossip(&self) -> Result<NetworkGraphStats, LightningError> {
+        self.gossip.sync().await
+    }
+    
+    /// On-chain fee rate (sat/vB) to confirm within `target_blocks`
+    pub async fn fee_rate_sat_per_vb(&self, target_blocks: u32) -> Result<f64, LightningError> {
+        let chain_source = self.chain_source.as_ref().ok_or_else(|| {
+            LightningError::ConfigError("LDK fee estimation requires lightning.ldk.chain_source".to_string())
+        })?;
+        chain_source.fee_rate_sat_per_vb(target_blocks).await
+    }
+    
+    /// Load node keys from disk; `None` if no key has been stored yet
+    ///
+    /// Reads `node_key.enc`, or the legacy `node_key.hex`. Whether a file is
+    /// encrypted is decided by its `keystore` magic header, not its name. A
+    /// plaintext key found once a passphrase is configured is encrypted to
+    /// `node_key.enc` and the plaintext file removed.
+    fn load_keys(data_dir: &PathBuf, passphrase: Option<&str>) -> Result<Option<(SecretKey, PublicKey)>, LightningError> {
+        let encrypted_path = data_dir.join(ENCRYPTED_NODE_KEY_FILE);
+        let plaintext_path = data_dir.join(NODE_KEY_FILE);
+        let key_path = if encrypted_path.exists() {
+            &encrypted_path
+        } else if plaintext_path.exists() {
+            &plaintext_path
+        } else {
+            return Ok(None);
+        };
+        
+        let data = std::fs::read(key_path)
             .map_err(|e| LightningError::ConfigError(format!("Failed to read node key: {}", e)))?;
-        let key_bytes = hex::decode(key_hex.trim())
-            .map_err(|e| LightningError::ConfigError(format!("Invalid key hex: {}", e)))?;
+        let encrypted = keystore::is_encrypted(&data);
+        let key_bytes = if encrypted {
+            let passphrase = passphrase.ok_or_else(|| {
+                LightningError::ConfigError(format!(
+                    "{} is encrypted; set lightning.ldk.key_passphrase or lightning.ldk.key_passphrase_file",
+                    key_path.display()
+                ))
+            })?;
+            keystore::decrypt(&data, passphrase)
+                .map_err(|e| LightningError::ConfigError(format!("{}: {}", key_path.display(), e)))?
+        } else {
+            let key_hex = String::from_utf8_lossy(&data);
+            hex::decode(key_hex.trim())
+                .map_err(|e| LightningError::ConfigError(format!("Invalid key hex: {}", e)))?
+        };
+        info!("Loaded {} node keys from {:?}", if encrypted { "encrypted" } else { "plaintext" }, key_path);
+        
         if key_bytes.len() != 32 {
             return Err(LightningError::ConfigError("Node key must be 32 bytes".to_string()));
         }
@@ -125,13 +651,230 @@ impl LDKProvider {
         let secret_key = SecretKey::from_slice(&key_array)
             .map_err(|e| LightningError::ConfigError(format!("Invalid key: {}", e)))?;
         let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-        Ok((secret_key, public_key))
+        
+        // Migrate a plaintext key now that it can be encrypted
+        if passphrase.is_some() && !encrypted {
+            Self::save_keys(data_dir, &secret_key, passphrase)?;
+            if plaintext_path.exists() {
+                std::fs::remove_file(&plaintext_path)
+                    .map_err(|e| LightningError::ConfigError(format!("Failed to remove plaintext node key: {}", e)))?;
+            }
+            info!("Encrypted node key to {:?}", encrypted_path);
+        }
+        Ok(Some((secret_key, public_key)))
     }
-}
+    
+    /// Save the node key, encrypted if a passphrase is set; returns the file written
+    fn save_keys(data_dir: &PathBuf, secret_key: &SecretKey, passphrase: Option<&str>) -> Result<PathBuf, LightningError> {
+        match passphrase {
+            Some(passphrase) => {
+                let key_path = data_dir.join(ENCRYPTED_NODE_KEY_FILE);
+                keystore::save_encrypted(&key_path, &secret_key.secret_bytes(), passphrase)?;
+                Ok(key_path)
+            }
+            None => {
+                let key_path = data_dir.join(NODE_KEY_FILE);
+                keystore::write_private(&key_path, hex::encode(secret_key.secret_bytes()).as_bytes())?;
+                Ok(key_path)
+            }
+        }
+    }
+    
+    /// Stop the background tasks and write the payment state
+    pub async fn shutdown(&self) {
+        self.abort_tasks();
+        self.store.persist().await;
+        info!("LDK provider shut down");
+    }
+    
+    /// Stop the background tasks without writing the payment state, for a
+    /// provider a replacement has taken over from
+    ///
+    /// Every change is persisted as it's made, so the replacement already
+    /// loaded this provider's state; writing it again could overwrite what
+    /// the replacement stored since.
+    pub async fn hand_over(&self) {
+        self.abort_tasks();
+        info!("LDK provider handed over to its replacement");
+    }
+    
+    fn abort_tasks(&self) {
+        for task in [&self.sync_task, &self.gossip_task, &self.cleanup_task].into_iter().flatten() {
+            task.abort();
+        }
+    }
+    
+    /// Sign a message with the node key
+    ///
+    /// Follows the Lightning node message signing convention: the
+    /// double-SHA256 of `"Lightning Signed Message:" || message` is signed
+    /// recoverably, and the 65-byte signature (header byte `31 + recovery id`,
+    /// then `r || s`) is returned base64-encoded.
+    pub fn sign_message(&self, message: &[u8]) -> Result<String, LightningError> {
+        let digest = signed_message_digest(message)?;
+        let (recovery_id, compact) = self.secp.sign_ecdsa_recoverable(&digest, &self.node_secret_key).serialize_compact();
 
-#[async_trait]
-impl LightningProvider for LDKProvider {
-    async fn verify_payment(
+        let mut signature = Vec::with_capacity(65);
+        signature.push(SIGNED_MESSAGE_HEADER + recovery_id.to_i32() as u8);
+        signature.extend_from_slice(&compact);
+        Ok(base64::engine::general_purpose::STANDARD.encode(signature))
+    }
+
+    /// Check a `sign_message` signature against the expected node public key
+    ///
+    /// Returns `Ok(false)` if the signature is well-formed but was made by a
+    /// different key or over a different message.
+    pub fn verify_message(&self, message: &[u8], signature: &str, expected_pubkey: &[u8; 33]) -> Result<bool, LightningError> {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(signature.trim())
+            .map_err(|e| LightningError::ProcessorError(format!("Invalid signature encoding: {}", e)))?;
+        if bytes.len() != 65 {
+            return Err(LightningError::ProcessorError(format!("Signature must be 65 bytes, got {}", bytes.len())));
+        }
+        let recovery_id = bytes[0].checked_sub(SIGNED_MESSAGE_HEADER)
+            .and_then(|id| RecoveryId::from_i32(id as i32).ok())
+            .ok_or_else(|| LightningError::ProcessorError(format!("Invalid signature header byte: {}", bytes[0])))?;
+        let signature = RecoverableSignature::from_compact(&bytes[1..], recovery_id)
+            .map_err(|e| LightningError::ProcessorError(format!("Invalid signature: {}", e)))?;
+
+        let digest = signed_message_digest(message)?;
+        match self.secp.recover_ecdsa(&digest, &signature) {
+            Ok(pubkey) => Ok(pubkey.serialize() == *expected_pubkey),
+            // Recovery can fail for a signature that doesn't belong to this message
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Node public key (the node id), hex-encoded
+    pub fn node_public_key_hex(&self) -> String {
+        hex::encode(self.node_public_key.serialize())
+    }
+    
+    /// Whether a channel backup should be exported after every processed payment
+    pub fn auto_backup(&self) -> bool {
+        self.config.auto_backup
+    }
+
+    /// Backup encryption key, derived from the node key
+    fn scb_cipher(&self) -> Result<Aes256Gcm, LightningError> {
+        let mut data = b"blvm-lightning channel backup".to_vec();
+        data.extend_from_slice(&self.node_secret_key.secret_bytes());
+        Aes256Gcm::new_from_slice(&sha256::Hash::hash(&data).to_byte_array())
+            .map_err(|e| LightningError::ProcessorError(format!("Invalid backup key: {}", e)))
+    }
+
+    /// Remove expired invoices and tracked payments past their retention now
+    ///
+    /// The cleanup task does this every `cleanup_interval_secs`. `now` is
+    /// unix seconds.
+    pub async fn prune_expired(&self, now: u64) -> PrunedEntries {
+        self.store.prune(now, &self.config.retention).await
+    }
+    
+    /// Invoice this node created for a payment hash
+    pub async fn get_invoice(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.store.invoice_storage.read().await.get(payment_hash).cloned()
+    }
+
+    /// Export a static channel backup
+    ///
+    /// Serializes the tracked payments and issued invoices, encrypts them
+    /// with AES-256-GCM under a key derived from the node key, writes the
+    /// blob to `SCB_FILE` in the data directory and returns it.
+    ///
+    /// Format: `magic (4) | version (1) | nonce (12) | ciphertext + tag`
+    pub async fn export_scb(&self) -> Result<Vec<u8>, LightningError> {
+        let backup = self.store.snapshot().await;
+        let plaintext = serde_json::to_vec(&backup)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize channel backup: {}", e)))?;
+
+        let nonce: [u8; SCB_NONCE_LEN] = rand::random();
+        let ciphertext = self.scb_cipher()?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to encrypt channel backup: {}", e)))?;
+
+        let mut blob = Vec::with_capacity(SCB_HEADER_LEN + ciphertext.len());
+        blob.extend_from_slice(SCB_MAGIC);
+        blob.push(SCB_VERSION);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+
+        let path = self.config.data_dir.join(SCB_FILE);
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, &blob)
+            .and_then(|_| std::fs::rename(&tmp_path, &path))
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to write channel backup {}: {}", path.display(), e)))?;
+
+        debug!("Exported channel backup: {} payments, {} invoices", backup.payments.len(), backup.invoices.len());
+        Ok(blob)
+    }
+
+    /// Restore a backup produced by `export_scb`
+    ///
+    /// Entries already known to the node are kept. Returns the number of
+    /// payments (invoices or payment states) restored. A backup made with a different node key fails
+    /// authentication and yields a `ConfigError`.
+    pub async fn restore_scb(&self, backup_bytes: &[u8]) -> Result<usize, LightningError> {
+        if !backup_bytes.starts_with(SCB_MAGIC) || backup_bytes.len() < SCB_HEADER_LEN {
+            return Err(LightningError::ConfigError("Not a channel backup".to_string()));
+        }
+        let version = backup_bytes[SCB_MAGIC.len()];
+        if version != SCB_VERSION {
+            return Err(LightningError::ConfigError(format!("Unsupported channel backup version {}", version)));
+        }
+
+        let nonce = &backup_bytes[SCB_MAGIC.len() + 1..SCB_HEADER_LEN];
+        let plaintext = self.scb_cipher()?
+            .decrypt(Nonce::from_slice(nonce), &backup_bytes[SCB_HEADER_LEN..])
+            .map_err(|_| LightningError::ConfigError("Channel backup is corrupt or belongs to another node".to_string()))?;
+        let backup: ChannelBackup = serde_json::from_slice(&plaintext)
+            .map_err(|e| LightningError::ConfigError(format!("Corrupt channel backup: {}", e)))?;
+
+        // Payment hashes with a restored invoice or payment state
+        let mut restored = HashSet::new();
+        let mut tracker = self.store.payment_tracker.write().await;
+        for (hash_hex, payment) in backup.payments {
+            let hash = decode_backup_hash(&hash_hex)?;
+            if !tracker.contains_key(&hash) {
+                tracker.insert(hash, (payment.amount_msats, payment.timestamp, payment.confirmed));
+                restored.insert(hash);
+            }
+        }
+        drop(tracker);
+
+        let mut storage = self.store.invoice_storage.write().await;
+        for (hash_hex, invoice) in backup.invoices {
+            let hash = decode_backup_hash(&hash_hex)?;
+            if !storage.contains_key(&hash) {
+                storage.insert(hash, invoice);
+                restored.insert(hash);
+            }
+        }
+        drop(storage);
+
+        let mut preimages = self.store.preimages.write().await;
+        for (hash_hex, preimage_hex) in backup.preimages {
+            let hash = decode_backup_hash(&hash_hex)?;
+            preimages.entry(hash).or_insert(decode_backup_hash(&preimage_hex)?);
+        }
+        drop(preimages);
+
+        let mut payment_secrets = self.store.payment_secrets.write().await;
+        for (hash_hex, secret_hex) in backup.payment_secrets {
+            let hash = decode_backup_hash(&hash_hex)?;
+            payment_secrets.entry(hash).or_insert(decode_backup_hash(&secret_hex)?);
+        }
+        drop(payment_secrets);
+
+        if !restored.is_empty() {
+            self.store.persist().await;
+        }
+
+        info!("Restored {} payments from channel backup", restored.len());
+        Ok(restored.len())
+    }
+
+    /// Look up a payment in the tracker after checking the invoice matches the hash
+    async fn lookup_payment(
         &self,
         invoice: &str,
         payment_hash: &[u8; 32],
@@ -140,19 +883,11 @@ impl LightningProvider for LDKProvider {
         debug!("Verifying payment via LDK: payment_id={}, payment_hash={}", payment_id, hex::encode(payment_hash));
 
         // 1. Parse invoice using lightning-invoice
-        let parsed_invoice: Invoice = invoice.parse()
-            .map_err(|e| LightningError::InvoiceError(format!("Failed to parse invoice: {:?}", e)))?;
+        let parsed_invoice: Bolt11Invoice = invoice.parse()
+            .map_err(|e| LightningError::InvoiceError(format!("Failed to parse invoice: {}", e)))?;
         
         // 2. Verify payment hash matches invoice
-        // lightning-invoice 0.2: payment_hash() returns &Sha256, convert to bytes
-        let invoice_payment_hash = parsed_invoice.payment_hash();
-        // Convert hash to bytes via hex string (sha256::Hash Display outputs hex)
-        let hash_str = format!("{}", invoice_payment_hash.0);
-        let hash_bytes = hex::decode(hash_str)
-            .map_err(|e| LightningError::InvoiceError(format!("Failed to decode payment hash: {}", e)))?;
-        let mut invoice_hash_bytes = [0u8; 32];
-        invoice_hash_bytes.copy_from_slice(&hash_bytes[..32]);
-        if invoice_hash_bytes != *payment_hash {
+        if parsed_invoice.payment_hash().to_byte_array() != *payment_hash {
             return Ok(PaymentVerificationResult {
                 verified: false,
                 amount_msats: None,
@@ -165,9 +900,35 @@ impl LightningProvider for LDKProvider {
             });
         }
         
+        // An invoice for one of our payment hashes must carry the secret we issued it with
+        let issued_invoice = self.store.invoice_storage.read().await.get(payment_hash).cloned();
+        if let Some(issued_invoice) = issued_invoice {
+            if self.payment_secret_for(payment_hash, &issued_invoice).await != Some(parsed_invoice.payment_secret().0) {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "ldk",
+                        "error": "payment_secret_mismatch",
+                        "payment_hash": hex::encode(payment_hash),
+                    }),
+                });
+            }
+        }
+        
+        // Metadata attached when the invoice was created (if it was ours)
+        let invoice_metadata = self.store.invoice_metadata.read().await
+            .get(payment_hash)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        
         // 3. Check payment tracker for payment status
-        let tracker = self.payment_tracker.read().await;
+        let tracker = self.store.payment_tracker.read().await;
         if let Some((amount_msats, timestamp, confirmed)) = tracker.get(payment_hash) {
+            // Overpayments are claimed, but flagged
+            let invoice_amount_msats = parsed_invoice.amount_milli_satoshis().unwrap_or(0);
+            let overpaid_msats = amount_msats.checked_sub(invoice_amount_msats).filter(|overpaid| invoice_amount_msats > 0 && *overpaid > 0);
             return Ok(PaymentVerificationResult {
                 verified: *confirmed,
                 amount_msats: Some(*amount_msats),
@@ -176,43 +937,137 @@ impl LightningProvider for LDKProvider {
                     "provider": "ldk",
                     "payment_hash": hex::encode(payment_hash),
                     "network": format!("{:?}", self.network),
+                    "overpaid_msats": overpaid_msats,
+                    "invoice_metadata": invoice_metadata,
                 }),
             });
         }
         
-        // 4. Payment not found in tracker - check if invoice is valid
-        // lightning-invoice 0.2: use amount_pico_btc() and convert to msats
-        // 1 BTC = 10^12 pico BTC = 10^11 msats, so 1 pico BTC = 0.1 msats
-        // For integer math: (pico_btc + 5) / 10 rounds to nearest msat
-        let amount_msats = parsed_invoice.amount_pico_btc()
-            .map(|pico_btc| (pico_btc + 5) / 10)
-            .unwrap_or(0);
-        
-        // For now, if invoice is valid and payment_hash matches, we consider it verified
-        // In a full implementation, this would query the channel manager for HTLC status
-        let verified = true; // Simplified: assume payment is verified if invoice is valid
-        
-        // Store in tracker
+        // 4. Payment not found in tracker: nothing has been received for it yet
+        // A valid invoice alone proves nothing; only a claimed HTLC (see
+        // `mark_payment_received`) marks a payment verified
         drop(tracker);
-        let mut tracker = self.payment_tracker.write().await;
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        tracker.insert(*payment_hash, (amount_msats, timestamp, verified));
-        
+        debug!("No payment received for {} yet", hex::encode(payment_hash));
         Ok(PaymentVerificationResult {
-            verified,
-            amount_msats: Some(amount_msats),
-            timestamp: Some(timestamp),
+            verified: false,
+            amount_msats: None,
+            timestamp: None,
             metadata: serde_json::json!({
                 "provider": "ldk",
                 "payment_hash": hex::encode(payment_hash),
                 "network": format!("{:?}", self.network),
-                "node_id": hex::encode(self.node_public_key.serialize()),
+                "status": "pending",
+                "invoice_metadata": invoice_metadata,
             }),
         })
     }
+    
+    /// Check a peer's request to open a channel against `config.channel_policy`
+    ///
+    /// Nothing calls this in the module yet: without an LDK `ChannelManager`
+    /// no `OpenChannelRequest` events arrive.
+    pub async fn handle_channel_open_request(&self, request: ChannelOpenRequest) -> ChannelDecision {
+        let node_id_hex = hex::encode(request.counterparty_node_id);
+        let peer_channels = self.channels.read().await
+            .values()
+            .filter(|channel| channel.counterparty_node_id == request.counterparty_node_id)
+            .count();
+        let decision = self.config.channel_policy.evaluate(&request, peer_channels);
+        match &decision {
+            ChannelDecision::Accept { zero_conf: true } => {
+                info!("Accepting zero-conf channel of {} sats from {}", request.funding_satoshis, node_id_hex);
+            }
+            ChannelDecision::Accept { zero_conf: false } => {}
+            ChannelDecision::Reject { reason } => {
+                warn!("Rejecting channel of {} sats from {}: {}", request.funding_satoshis, node_id_hex, reason);
+            }
+        }
+        decision
+    }
+    
+    /// Payment secret of an invoice this node issued
+    ///
+    /// Taken from the invoice itself for invoices issued before secrets
+    /// were stored alongside them.
+    async fn payment_secret_for(&self, payment_hash: &[u8; 32], invoice: &str) -> Option<[u8; 32]> {
+        if let Some(secret) = self.store.payment_secrets.read().await.get(payment_hash) {
+            return Some(*secret);
+        }
+        invoice.parse::<Bolt11Invoice>().ok().map(|invoice| invoice.payment_secret().0)
+    }
+    
+    /// Record an incoming payment as received
+    ///
+    /// Meant for channel event handling once the HTLCs for `payment_hash`
+    /// are claimed; with no LDK `ChannelManager` in the module yet, only
+    /// tests call it, to simulate a payment arriving.
+    pub async fn mark_payment_received(&self, payment_hash: &[u8; 32], amount_msats: u64) {
+        self.store.payment_tracker.write().await.insert(*payment_hash, (amount_msats, now_secs(), true));
+        self.store.persist().await;
+        info!("Payment received: payment_hash={}, amount={} msats", hex::encode(payment_hash), amount_msats);
+    }
+    
+    /// Put a payment straight into the payment tracker, as if channel events had recorded it
+    #[cfg(any(test, feature = "test-utils"))]
+    pub async fn inject_payment_for_test(&self, payment_hash: [u8; 32], amount_msats: u64, confirmed: bool) {
+        self.store.payment_tracker.write().await.insert(payment_hash, (amount_msats, now_secs(), confirmed));
+        self.store.persist().await;
+    }
+    
+    /// Add a channel, as if the channel manager had opened it
+    #[cfg(any(test, feature = "test-utils"))]
+    pub async fn inject_channel_for_test(&self, channel: ChannelInfo) {
+        self.channels.write().await.insert(channel.channel_id, channel);
+    }
+    
+    /// Check the payment tracker for a confirmed payment
+    async fn lookup_confirmation(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        debug!("Checking payment confirmation via LDK: payment_hash={}", hex::encode(payment_hash));
+        
+        // Check payment tracker
+        let tracker = self.store.payment_tracker.read().await;
+        if let Some((_amount, _timestamp, confirmed)) = tracker.get(payment_hash) {
+            return Ok(*confirmed);
+        }
+        
+        // Payment not found - return false
+        // In a full implementation, this would query the channel manager
+        Ok(false)
+    }
+}
+
+impl Drop for LDKProvider {
+    fn drop(&mut self) {
+        if let Some(task) = &self.sync_task {
+            task.abort();
+        }
+        if let Some(task) = &self.gossip_task {
+            task.abort();
+        }
+        if let Some(task) = &self.cleanup_task {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait]
+impl LightningProvider for LDKProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "ldk", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        retry_idempotent(&self.config.retry_policy, "LDK payment verification", || {
+            self.lookup_payment(invoice, payment_hash, payment_id)
+        })
+        .await
+    }
 
     async fn create_invoice(
         &self,
@@ -220,59 +1075,91 @@ impl LightningProvider for LDKProvider {
         description: &str,
         expiry_seconds: u64,
     ) -> Result<String, LightningError> {
-        debug!("Creating invoice via LDK: amount={} msats, description={}", amount_msats, description);
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "ldk", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let amount_msats = params.amount_msats;
+        let expiry_seconds = params.expiry_seconds;
+        debug!("Creating invoice via LDK: amount={} msats, description={:?}", amount_msats, params.description);
 
-        use lightning_invoice::{Currency, InvoiceBuilder};
-        use bitcoin_hashes::sha256;
-        use bitcoin_hashes::Hash;
+        // A label that was already used returns the existing invoice
+        if let Some(label) = &params.label {
+            if let Some(payment_hash) = self.store.invoice_labels.read().await.get(label) {
+                if let Some(existing) = self.store.invoice_storage.read().await.get(payment_hash) {
+                    debug!("Returning existing LDK invoice for label {}", label);
+                    return Ok(existing.clone());
+                }
+            }
+        }
+
+        // 1. Generate payment preimage and hash
+        let payment_preimage: [u8; 32] = rand::random();
+        // Proves the sender got the invoice, so probes with the bare hash are failed
+        let payment_secret: [u8; 32] = rand::random();
+        let payment_hash = sha256::Hash::hash(&payment_preimage);
+        let payment_hash_bytes = payment_hash.to_byte_array();
         
-        // 1. Generate payment hash and secret
-        let payment_secret_bytes: [u8; 32] = rand::random();
-        let payment_hash = sha256::Hash::hash(&payment_secret_bytes);
-        // Convert hash to bytes via hex string (works across bitcoin_hashes versions)
-        let hash_str = format!("{}", payment_hash);
-        let hash_bytes = hex::decode(hash_str)
-            .map_err(|e| LightningError::ProcessorError(format!("Failed to decode hash: {}", e)))?;
-        let mut payment_hash_bytes = [0u8; 32];
-        payment_hash_bytes.copy_from_slice(&hash_bytes[..32]);
+        // 2. Build invoice
+        let builder = InvoiceBuilder::new(self.currency())
+            .amount_milli_satoshis(amount_msats);
         
-        // 2. Determine currency based on network
-        // Note: lightning-invoice 0.2 only supports Bitcoin and BitcoinTestnet
-        let currency = match self.network {
-            Network::Bitcoin => Currency::Bitcoin,
-            Network::Testnet => Currency::BitcoinTestnet,
-            Network::Regtest => Currency::BitcoinTestnet, // Use testnet for regtest
-            Network::Signet => Currency::BitcoinTestnet, // Use testnet for signet
-            Network::Testnet4 => Currency::BitcoinTestnet, // Use testnet for testnet4
+        // Long descriptions don't fit in the `d` field, so commit to their hash instead
+        let builder = match &params.description {
+            DescriptionKind::Direct(description) if description.len() <= MAX_DESCRIPTION_BYTES => {
+                builder.description(description.clone())
+            }
+            DescriptionKind::Direct(description) => {
+                builder.description_hash(sha256::Hash::hash(description.as_bytes()))
+            }
+            DescriptionKind::Hash(description_hash) => {
+                builder.description_hash(sha256::Hash::from_byte_array(*description_hash))
+            }
         };
         
-        // 3. Build invoice using lightning-invoice 0.2 API
-        // Convert msats to pico BTC: 1 msat = 10 pico BTC (since 1 pico BTC = 0.1 msats)
-        let amount_pico_btc = amount_msats * 10;
+        let builder = match &params.fallback_address {
+            Some(address) => builder.fallback(address_to_fallback(address, self.network)?),
+            None => builder,
+        };
         
-        // Build invoice with all required fields
-        // lightning-invoice 0.2 requires: description, payment_hash, timestamp, and signature
-        // bitcoin_hashes 0.3 is aligned with lightning-invoice 0.2 dependencies (see Cargo.toml)
-        // The sha256::Hash type from bitcoin_hashes 0.3 is compatible with InvoiceBuilder
-        let invoice = InvoiceBuilder::new(currency)
-            .amount_pico_btc(amount_pico_btc)
-            .description(description.to_string())
+        let invoice = builder
             .payment_hash(payment_hash)
+            .payment_secret(PaymentSecret(payment_secret))
             .expiry_time(std::time::Duration::from_secs(expiry_seconds))
-            .min_final_cltv_expiry(144) // Standard 144 blocks
+            .min_final_cltv_expiry_delta(params.min_final_cltv_expiry.unwrap_or_else(|| self.min_final_cltv_expiry().into()))
             .current_timestamp()
             .build_signed(|hash| {
                 // Use the node's actual private key for signing
-                self.secp.sign_recoverable(hash, &self.node_secret_key)
+                self.secp.sign_ecdsa_recoverable(hash, &self.node_secret_key)
             })
-            .map_err(|e| LightningError::ProcessorError(format!("Failed to build invoice: {:?}", e)))?;
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to build invoice: {}", e)))?;
         
-        // 4. Convert to BOLT11 string
+        // 3. Convert to BOLT11 string
         let invoice_string = invoice.to_string();
         
-        // 5. Store invoice in storage
-        let mut storage = self.invoice_storage.write().await;
+        // 4. Store invoice (and any caller metadata) in storage
+        let mut storage = self.store.invoice_storage.write().await;
         storage.insert(payment_hash_bytes, invoice_string.clone());
+        drop(storage);
+        self.store.preimages.write().await.insert(payment_hash_bytes, payment_preimage);
+        self.store.payment_secrets.write().await.insert(payment_hash_bytes, payment_secret);
+        self.store.persist().await;
+        
+        if !params.metadata.is_null() || params.label.is_some() {
+            let metadata = serde_json::json!({
+                "label": params.label,
+                "metadata": params.metadata,
+            });
+            self.store.invoice_metadata.write().await.insert(payment_hash_bytes, metadata);
+        }
+        if let Some(label) = &params.label {
+            self.store.invoice_labels.write().await.insert(label.clone(), payment_hash_bytes);
+        }
         
         info!("Created LDK invoice: payment_hash={}, amount={} msats", hex::encode(payment_hash_bytes), amount_msats);
         
@@ -280,21 +1167,84 @@ impl LightningProvider for LDKProvider {
     }
 
     async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
-        debug!("Checking payment confirmation via LDK: payment_hash={}", hex::encode(payment_hash));
-        
-        // Check payment tracker
-        let tracker = self.payment_tracker.read().await;
-        if let Some((_amount, _timestamp, confirmed)) = tracker.get(payment_hash) {
-            return Ok(*confirmed);
+        retry_idempotent(&self.config.retry_policy, "LDK payment confirmation", || {
+            self.lookup_confirmation(payment_hash)
+        })
+        .await
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        let amount_msats = payment_amount_msats(invoice, amount_msats)?;
+
+        // Mock route-finding: pay through the active channel with the most
+        // outbound liquidity, assuming one forwarding hop beyond it
+        // In a full implementation, this would run LDK's router over the network graph
+        let channels = self.channels.read().await;
+        let channel = channels
+            .values()
+            .filter(|c| c.is_active && c.local_balance_msats >= amount_msats)
+            .max_by_key(|c| c.local_balance_msats)
+            .ok_or_else(|| {
+                LightningError::RoutingError(format!("No channel with {} msats outbound liquidity", amount_msats))
+            })?;
+
+        // Less headroom in the first-hop channel means a less certain route
+        let headroom = 1.0 - amount_msats as f32 / channel.local_balance_msats.max(1) as f32;
+        Ok(FeeEstimate::from_rate(
+            self.config.default_fee_estimate_msats,
+            ESTIMATE_HOP_FEE_RATE_PPM,
+            amount_msats,
+            (0.2 + 0.6 * headroom).clamp(0.2, 0.8),
+        ))
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        Ok(self.channels.read().await.values().cloned().collect())
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        let channels = self.channels.read().await;
+        let num_active_channels = channels.values().filter(|channel| channel.is_active).count() as u32;
+        Ok(NodeInfo {
+            node_id: self.node_public_key.serialize(),
+            alias: None,
+            color: None,
+            num_peers: 0,
+            num_active_channels,
+            num_pending_channels: channels.len() as u32 - num_active_channels,
+            block_height: self.best_block().await.map(|tip| tip.height as u64).unwrap_or(0),
+            version: Some(format!("blvm-lightning-ldk/{}", env!("CARGO_PKG_VERSION"))),
+        })
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // LDK runs in-process, so it is always reachable; chain sync status
+        // is only known with a chain source
+        let best_block = self.best_block().await;
+        Ok(HealthStatus {
+            reachable: true,
+            latency_ms: 0,
+            block_height: best_block.as_ref().map(|tip| tip.height as u64),
+            synced_to_chain: self.chain_source.as_ref().map(|_| best_block.is_some()),
+            version: Some(format!("blvm-lightning-ldk/{}", env!("CARGO_PKG_VERSION"))),
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            can_manage_channels: true,
+            ..Default::default()
         }
-        
-        // Payment not found - return false
-        // In a full implementation, this would query the channel manager
-        Ok(false)
     }
 
     fn provider_type(&self) -> ProviderType {
         ProviderType::LDK
     }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
 }
 
@@ -1,89 +1,969 @@
 //! LNBits provider implementation
 //!
 //! Integrates with LNBits REST API for Lightning payments.
+//!
+//! One instance can serve several tenant wallets (`lightning.lnbits.wallets`):
+//! invoices are created in the wallet named by `InvoiceParams::wallet`, and
+//! the issuing wallet is remembered per payment hash in module storage so
+//! verification uses that wallet's key.
 
-use crate::provider::{ProviderType, LightningProvider, PaymentVerificationResult};
+use crate::provider::payment_index::PaymentIndex;
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams, DescriptionKind,
+    PaymentStream, PaymentUpdate,
+    MAX_CONCURRENT_INVOICE_REQUESTS, MAX_DESCRIPTION_BYTES, load_ca_certificate, payment_amount_msats, socks5_proxy,
+};
 use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
 use async_trait::async_trait;
+use blvm_node::module::traits::NodeAPI;
+use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{debug, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
 use hex;
 
+/// Default TCP connect timeout
+pub const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 5_000;
+
+/// Default connect timeout through a SOCKS5 proxy, which includes building a Tor circuit
+pub const DEFAULT_PROXY_CONNECT_TIMEOUT_MS: u64 = 30_000;
+
+/// Default request timeout through a SOCKS5 proxy
+pub const DEFAULT_PROXY_REQUEST_TIMEOUT_SECS: u64 = 90;
+
+/// Default number of idle connections kept open to the LNBits host
+pub const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 32;
+
+/// Default time an idle pooled connection is kept open
+pub const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// Default time an unpaid payment lookup is reused
+pub const DEFAULT_STATUS_CACHE_MS: u64 = 2_000;
+
+/// Default number of payment lookups kept
+pub const DEFAULT_STATUS_CACHE_CAPACITY: usize = 10_000;
+
+/// Storage tree holding the payment hash -> tenant wallet index
+pub const LNBITS_WALLETS_TREE: &str = "lnbits_wallets";
+
+/// TCP and HTTP/2 keepalive interval for pooled connections
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest wait between websocket reconnection attempts
+const WEBSOCKET_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Settlements buffered for slow `subscribe_payments` consumers
+const SETTLEMENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Websocket settlements kept for `verify_payment`, least recently used evicted first
+const SETTLEMENT_CACHE_CAPACITY: usize = 10_000;
+
+/// Default synthetic fee rate (1%, matching LNBits' default fee reserve)
+pub const DEFAULT_FEE_RATE_PPM: u64 = 10_000;
+
 /// LNBits provider configuration
 #[derive(Debug, Clone)]
 pub struct LNBitsConfig {
     /// LNBits API URL (e.g., "https://lnbits.example.com")
     pub api_url: String,
-    /// LNBits API key (admin or invoice key)
-    pub api_key: String,
+    /// Invoice/read key, used for verification and invoice creation
+    pub invoice_key: String,
+    /// Admin key, required for wallet management and spending
+    pub admin_key: Option<String>,
     /// Wallet ID (optional, for specific wallet operations)
     pub wallet_id: Option<String>,
+    /// TCP connect timeout in milliseconds
+    pub connect_timeout_ms: u64,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+    /// Additional trusted root certificate (PEM), e.g. for a self-signed LNBits instance
+    pub tls_ca_cert_path: Option<PathBuf>,
+    /// Disable TLS certificate verification entirely (insecure)
+    pub tls_accept_invalid_certs: bool,
+    /// Fee rate for synthetic estimates, in parts per million
+    pub fee_rate_ppm: u64,
+    /// Base fee for synthetic estimates
+    pub default_fee_estimate_msats: u64,
+    /// SOCKS5 proxy for all requests, e.g. `socks5h://127.0.0.1:9050` to reach an onion service over Tor
+    pub socks5_proxy: Option<String>,
+    /// Listen for payment notifications on the wallet websocket
+    pub websocket: bool,
+    /// Have LNBits decode the invoice during verification and compare it with local parsing
+    pub cross_check_decode: bool,
+    /// Idle connections kept open to the LNBits host (0 disables pooling)
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle pooled connection is kept open
+    pub pool_idle_timeout_secs: u64,
+    /// Speak HTTP/2 without negotiation, multiplexing requests over one connection
+    pub http2: bool,
+    /// URL LNBits POSTs to when an invoice created here is paid
+    pub webhook_url: Option<String>,
+    /// How long an unpaid payment lookup is reused (0 disables; paid lookups are kept until evicted)
+    pub status_cache_ms: u64,
+    /// Payment lookups kept, least recently used evicted first (0 disables the cache)
+    pub status_cache_capacity: usize,
+    /// Tenant wallets on the same instance, by tenant name
+    pub wallets: HashMap<String, LNBitsWallet>,
+}
+
+/// A tenant wallet on a shared LNBits instance
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LNBitsWallet {
+    pub wallet_id: String,
+    /// Invoice/read key of the wallet
+    pub invoice_key: String,
+}
+
+impl Default for LNBitsConfig {
+    fn default() -> Self {
+        Self {
+            api_url: String::new(),
+            invoice_key: String::new(),
+            admin_key: None,
+            wallet_id: None,
+            connect_timeout_ms: DEFAULT_CONNECT_TIMEOUT_MS,
+            retry_policy: ProviderRetryPolicy::default(),
+            tls_ca_cert_path: None,
+            tls_accept_invalid_certs: false,
+            fee_rate_ppm: DEFAULT_FEE_RATE_PPM,
+            default_fee_estimate_msats: crate::provider::DEFAULT_FEE_ESTIMATE_MSATS,
+            socks5_proxy: None,
+            websocket: false,
+            cross_check_decode: false,
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST,
+            pool_idle_timeout_secs: DEFAULT_POOL_IDLE_TIMEOUT_SECS,
+            http2: false,
+            webhook_url: None,
+            status_cache_ms: DEFAULT_STATUS_CACHE_MS,
+            status_cache_capacity: DEFAULT_STATUS_CACHE_CAPACITY,
+            wallets: HashMap::new(),
+        }
+    }
+}
+
+impl LNBitsConfig {
+    /// Check settings that can be rejected before any request is made
+    pub fn validate(&self) -> Result<(), LightningError> {
+        if let Some(proxy) = &self.socks5_proxy {
+            socks5_proxy(proxy)?;
+            if self.websocket {
+                return Err(LightningError::ConfigError(
+                    "lightning.lnbits.websocket can't be used with a SOCKS5 proxy".to_string(),
+                ));
+            }
+        }
+        if let Some(url) = &self.webhook_url {
+            let parsed = reqwest::Url::parse(url)
+                .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.lnbits.webhook_url {}: {}", url, e)))?;
+            if !matches!(parsed.scheme(), "https" | "http") {
+                return Err(LightningError::ConfigError(format!(
+                    "Invalid lightning.lnbits.webhook_url {}: not http(s)",
+                    url
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Key for invoice and read operations
+    ///
+    /// An admin key can do everything the invoice key can, so it is used
+    /// when no invoice key is configured.
+    pub fn invoice_api_key(&self) -> &str {
+        match self.admin_key.as_deref() {
+            Some(admin_key) if self.invoice_key.is_empty() => admin_key,
+            _ => &self.invoice_key,
+        }
+    }
+
+    /// Key for wallet management and spending
+    pub fn admin_api_key(&self) -> Result<&str, LightningError> {
+        self.admin_key
+            .as_deref()
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| LightningError::ConfigError("LNBits admin key required (lightning.lnbits.admin_key)".to_string()))
+    }
+}
+
+/// Which LNBits key a request is authenticated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ApiKey<'a> {
+    /// Invoice/read key
+    Invoice,
+    /// Admin key
+    Admin,
+    /// Invoice key of a tenant wallet
+    Wallet(&'a LNBitsWallet),
+}
+
+/// LNBits wallet, as returned by the wallet management API
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletDetails {
+    pub id: String,
+    pub name: String,
+    pub balance_msats: u64,
+    /// Invoice/read key
+    pub inkey: String,
+    pub adminkey: String,
+}
+
+impl std::fmt::Debug for WalletDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WalletDetails")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("balance_msats", &self.balance_msats)
+            .field("inkey", &"<redacted>")
+            .field("adminkey", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Invoice as decoded by LNBits
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DecodedInvoice {
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    #[serde(rename = "amount_msat", default)]
+    pub amount_msats: Option<u64>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub expiry: Option<u64>,
+}
+
+/// Wallet as LNBits serializes it (keys are omitted by some endpoints)
+#[derive(Deserialize)]
+struct WalletResponse {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    name: String,
+    /// msats; LNBits allows slightly negative balances after fees
+    #[serde(default)]
+    balance: i64,
+    #[serde(default)]
+    inkey: String,
+    #[serde(default)]
+    adminkey: String,
+}
+
+impl From<WalletResponse> for WalletDetails {
+    fn from(wallet: WalletResponse) -> Self {
+        Self {
+            id: wallet.id,
+            name: wallet.name,
+            balance_msats: wallet.balance.max(0) as u64,
+            inkey: wallet.inkey,
+            adminkey: wallet.adminkey,
+        }
+    }
+}
+
+/// Payment in the wallet's history (`GET /api/v1/payments`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LNBitsPayment {
+    /// Payment hash (hex)
+    pub payment_hash: String,
+    #[serde(default)]
+    pub bolt11: String,
+    /// msats, negative for outgoing payments
+    pub amount: i64,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub memo: String,
+    /// Unix timestamp
+    pub time: u64,
+    /// msats (LNBits reports outgoing fees as negative)
+    #[serde(default, deserialize_with = "fee_msats")]
+    pub fee: u64,
+    pub pending: bool,
+}
+
+fn null_as_empty<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+fn fee_msats<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Ok(i64::deserialize(deserializer)?.unsigned_abs())
+}
+
+/// Payment record returned by `GET /api/v1/payments/{payment_hash}`
+#[derive(Debug, Clone, Deserialize)]
+struct PaymentStatus {
+    paid: bool,
+    /// msats, negative for outgoing payments
+    #[serde(rename = "amount")]
+    amount_msats: Option<i64>,
+    #[serde(rename = "time")]
+    timestamp: Option<u64>,
+    #[serde(default)]
+    preimage: Option<String>,
+    #[serde(default)]
+    details: Option<serde_json::Value>,
+}
+
+/// Per payment hash cache, bounded by capacity and TTL
+///
+/// Used for recent payment lookups, so back-to-back checks of one hash make
+/// one request (`None` records a payment LNBits doesn't know), and for
+/// settlements pushed on the websocket. Final entries (paid lookups,
+/// settlements) are kept until evicted; others expire after the TTL. Evicts
+/// the least recently used entry once the capacity is reached.
+struct StatusCache<V> {
+    capacity: usize,
+    ttl: Duration,
+    /// Whether an entry can no longer change, and so never expires
+    is_final: fn(&V) -> bool,
+    state: std::sync::Mutex<StatusCacheState<V>>,
+}
+
+struct StatusCacheState<V> {
+    /// Payment hash -> (entry, fetched at, last use tick)
+    entries: HashMap<[u8; 32], (V, Instant, u64)>,
+    /// Last use tick -> payment hash, oldest first
+    recency: BTreeMap<u64, [u8; 32]>,
+    tick: u64,
+}
+
+impl<V: Clone> StatusCache<V> {
+    fn new(capacity: usize, ttl: Duration, is_final: fn(&V) -> bool) -> Self {
+        Self {
+            capacity,
+            ttl,
+            is_final,
+            state: std::sync::Mutex::new(StatusCacheState {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    /// Cached entry, unless missing or expired
+    fn get(&self, payment_hash: &[u8; 32]) -> Option<V> {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let (value, fetched_at, last_used) = state.entries.get_mut(payment_hash)?;
+        state.recency.remove(last_used);
+        if !(self.is_final)(value) && fetched_at.elapsed() >= self.ttl {
+            state.entries.remove(payment_hash);
+            return None;
+        }
+        state.tick += 1;
+        *last_used = state.tick;
+        state.recency.insert(state.tick, *payment_hash);
+        Some(value.clone())
+    }
+
+    fn insert(&self, payment_hash: &[u8; 32], value: V) {
+        if self.capacity == 0 || (!(self.is_final)(&value) && self.ttl.is_zero()) {
+            return;
+        }
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        if let Some((_, _, last_used)) = state.entries.remove(payment_hash) {
+            state.recency.remove(&last_used);
+        }
+        while state.entries.len() >= self.capacity {
+            match state.recency.pop_first() {
+                Some((_, oldest)) => state.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        state.tick += 1;
+        state.entries.insert(*payment_hash, (value, Instant::now(), state.tick));
+        state.recency.insert(state.tick, *payment_hash);
+    }
+}
+
+/// A paid lookup is final
+fn status_is_final(status: &Option<PaymentStatus>) -> bool {
+    status.as_ref().is_some_and(|status| status.paid)
 }
 
 /// LNBits provider implementation
 pub struct LNBitsProvider {
     config: LNBitsConfig,
     http_client: Arc<Client>,
+    /// Recent payment lookups
+    status_cache: StatusCache<Option<PaymentStatus>>,
+    /// Payment hash -> tenant wallet that issued the invoice
+    wallet_index: PaymentIndex,
+    /// Settlements seen on the websocket (payment_hash -> settlement)
+    settled: Arc<StatusCache<PaymentUpdate>>,
+    /// Feeds `subscribe_payments`
+    settlements: broadcast::Sender<PaymentUpdate>,
+    /// Websocket listener, if enabled
+    websocket_task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl LNBitsProvider {
     /// Create a new LNBits provider
+    ///
+    /// All requests, including concurrent verifications, share one pooled
+    /// HTTP client.
     pub fn new(config: LNBitsConfig) -> Result<Self, LightningError> {
-        let http_client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+        config.validate()?;
+        let mut builder = Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .timeout(config.retry_policy.request_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs))
+            .tcp_keepalive(KEEPALIVE_INTERVAL);
+
+        if config.http2 {
+            builder = builder
+                .http2_prior_knowledge()
+                .http2_keep_alive_interval(KEEPALIVE_INTERVAL)
+                .http2_keep_alive_while_idle(true)
+                .http2_adaptive_window(true);
+        }
+
+        if let Some(proxy) = &config.socks5_proxy {
+            debug!("Routing LNBits requests through a SOCKS5 proxy");
+            builder = builder.proxy(socks5_proxy(proxy)?);
+        }
+
+        if let Some(path) = &config.tls_ca_cert_path {
+            builder = builder.add_root_certificate(load_ca_certificate(path)?);
+        }
+
+        if config.tls_accept_invalid_certs {
+            warn!(
+                "!!! TLS certificate verification is DISABLED for LNBits at {} - connections can be intercepted. \
+                 Use lightning.lnbits.tls_ca_cert instead where possible !!!",
+                config.api_url
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        let http_client = builder
             .build()
             .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
 
+        let settled = Arc::new(StatusCache::new(SETTLEMENT_CACHE_CAPACITY, Duration::ZERO, |_| true));
+        let (settlements, _) = broadcast::channel(SETTLEMENT_CHANNEL_CAPACITY);
+        let websocket_task = if config.websocket {
+            let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+                LightningError::ConfigError("lightning.lnbits.websocket requires a Tokio runtime".to_string())
+            })?;
+            Some(runtime.spawn(listen_websocket(
+                websocket_url(&config)?,
+                websocket_connector(&config)?,
+                config.retry_policy.clone(),
+                settled.clone(),
+                settlements.clone(),
+            )))
+        } else {
+            None
+        };
+
+        let status_cache = StatusCache::new(
+            config.status_cache_capacity,
+            Duration::from_millis(config.status_cache_ms),
+            status_is_final,
+        );
+
         Ok(Self {
             config,
             http_client: Arc::new(http_client),
+            status_cache,
+            wallet_index: PaymentIndex::in_tree("LNBits wallet index", LNBITS_WALLETS_TREE),
+            settled,
+            settlements,
+            websocket_task,
         })
     }
 
+    /// Configuration the provider was built with
+    pub fn config(&self) -> &LNBitsConfig {
+        &self.config
+    }
+
+    /// Settlement already pushed over the websocket, if any
+    pub async fn cached_settlement(&self, payment_hash: &[u8; 32]) -> Option<PaymentUpdate> {
+        self.settled.get(payment_hash)
+    }
+
+    /// Tenant wallet configured under `name`
+    fn wallet(&self, name: &str) -> Result<&LNBitsWallet, LightningError> {
+        self.config
+            .wallets
+            .get(name)
+            .ok_or_else(|| LightningError::ConfigError(format!("Unknown LNBits wallet: {}", name)))
+    }
+
+    /// Tenant wallet that issued the invoice for a payment hash, if any
+    pub fn issuing_wallet(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.wallet_index.get(payment_hash)
+    }
+
+    /// Key to look up a payment with: the issuing wallet's, or the default invoice key
+    fn key_for_payment(&self, payment_hash: &[u8; 32]) -> ApiKey<'_> {
+        let name = match self.issuing_wallet(payment_hash) {
+            Some(name) => name,
+            None => return ApiKey::Invoice,
+        };
+        match self.wallet(&name) {
+            Ok(wallet) => ApiKey::Wallet(wallet),
+            Err(e) => {
+                warn!("{}, checking payment {} with the default key", e, hex::encode(payment_hash));
+                ApiKey::Invoice
+            }
+        }
+    }
+
+    /// Remember which tenant wallet issued an invoice
+    async fn record_wallet(&self, name: &str, invoice: &str) {
+        let payment_hash = match InvoiceParser::parse(invoice) {
+            Ok(data) => data.payment_hash_hex(),
+            Err(e) => {
+                warn!("Can't index invoice from LNBits wallet {}, it will be verified with the default key: {}", name, e);
+                return;
+            }
+        };
+        if let Err(e) = self.wallet_index.insert(payment_hash, name.to_string()).await {
+            warn!("{}", e);
+        }
+    }
+
+    /// Look up a payment by hash, `None` if LNBits doesn't know it
+    ///
+    /// Served from the status cache when a recent enough lookup exists.
+    async fn payment_status(&self, payment_hash: &[u8; 32]) -> Result<Option<PaymentStatus>, LightningError> {
+        if let Some(status) = self.status_cache.get(payment_hash) {
+            debug!("LNBits payment status for {} served from cache", hex::encode(payment_hash));
+            return Ok(status);
+        }
+
+        // LNBits API: Check payment status
+        // GET /api/v1/payments/{payment_hash}
+        let endpoint = format!("/payments/{}", hex::encode(payment_hash));
+        let key = self.key_for_payment(payment_hash);
+        let status = match self.request::<PaymentStatus>(reqwest::Method::GET, &endpoint, None, true, key).await {
+            Ok(status) => Some(status),
+            // Not found means not paid yet; anything else (an unreachable instance,
+            // a rejected key) says nothing about the payment and must not read as unpaid
+            Err(LightningError::PaymentVerificationFailed(_)) => None,
+            Err(e) => return Err(e),
+        };
+        self.status_cache.insert(payment_hash, status.clone());
+        Ok(status)
+    }
+
     /// Make an authenticated request to LNBits API
+    ///
+    /// Idempotent requests are retried according to the retry policy;
+    /// non-idempotent requests (e.g. unlabelled invoice creation) are sent once.
     async fn request<T: for<'de> Deserialize<'de>>(
         &self,
         method: reqwest::Method,
         endpoint: &str,
         body: Option<serde_json::Value>,
+        idempotent: bool,
+        key: ApiKey<'_>,
     ) -> Result<T, LightningError> {
-        let url = format!("{}/api/v1{}", self.config.api_url.trim_end_matches('/'), endpoint);
+        self.request_path(method, &format!("/api/v1{}", endpoint), body, idempotent, key).await
+    }
+
+    /// Make an authenticated request to any path on the LNBits instance (e.g. an extension API)
+    async fn request_path<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+        idempotent: bool,
+        key: ApiKey<'_>,
+    ) -> Result<T, LightningError> {
+        let api_key = match key {
+            ApiKey::Invoice => self.config.invoice_api_key(),
+            ApiKey::Admin => self.config.admin_api_key()?,
+            ApiKey::Wallet(wallet) => &wallet.invoice_key,
+        };
+        let url = format!("{}{}", self.config.api_url.trim_end_matches('/'), path);
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
         
-        let mut request = self
-            .http_client
-            .request(method, &url)
-            .header("X-Api-Key", &self.config.api_key)
-            .header("Content-Type", "application/json");
+        loop {
+            let mut request = self
+                .http_client
+                .request(method.clone(), &url)
+                .header("X-Api-Key", api_key)
+                .header("Content-Type", "application/json");
 
-        if let Some(body) = body {
-            request = request.json(&body);
-        }
+            if let Some(body) = &body {
+                request = request.json(body);
+            }
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| LightningError::ProcessorError(format!("LNBits API request failed: {}", e)))?;
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    // A failed connect never sent the request, so even a POST is safe to repeat
+                    let safe = idempotent || e.is_connect();
+                    if safe && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("LNBits API request failed (attempt {}/{}): {}", attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.jittered_backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("LNBits API request failed: {}", e)));
+                }
+            };
 
-        if !response.status().is_success() {
             let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LightningError::ProcessorError(format!(
-                "LNBits API error: {} - {}",
-                status, error_text
-            )));
+            // A 429 means the request was turned away unprocessed
+            let class = match status.as_u16() {
+                429 => Some(ErrorClass::RateLimited),
+                _ if status.is_server_error() && idempotent => Some(ErrorClass::ServerError),
+                _ => None,
+            };
+            if let Some(class) = class.filter(|class| policy.should_retry(*class, attempt + 1)) {
+                attempt += 1;
+                warn!("LNBits API returned {} (attempt {}/{})", status, attempt, policy.max_retries);
+                // Honour Retry-After, but never wait longer than a request may take
+                let delay = retry_after(&response)
+                    .map(|delay| delay.min(policy.request_timeout))
+                    .unwrap_or_else(|| policy.jittered_backoff(attempt));
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+
+            if !status.is_success() {
+                let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                return Err(status_error(status, error_text, path));
+            }
+
+            return response
+                .json::<T>()
+                .await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse LNBits response: {}", e)));
+        }
+    }
+
+    /// Balance of the wallet the invoice key belongs to, in msats
+    ///
+    /// A rejected key is reported as `ConfigError`.
+    pub async fn get_wallet_balance(&self) -> Result<u64, LightningError> {
+        #[derive(Deserialize)]
+        struct BalanceResponse {
+            /// msats; integral, but not every LNBits version serializes it as an integer
+            balance: serde_json::Number,
+        }
+
+        // GET /api/v1/wallet
+        let wallet: BalanceResponse = self.request(reqwest::Method::GET, "/wallet", None, true, ApiKey::Invoice).await?;
+        // A negative balance (overdrawn fee reserve) has nothing spendable
+        Ok(wallet.balance.as_u64()
+            .or_else(|| wallet.balance.as_f64().filter(|balance| *balance >= 0.0).map(|balance| balance as u64))
+            .unwrap_or(0))
+    }
+
+    /// Decode a BOLT11 invoice with LNBits
+    pub async fn decode_invoice(&self, invoice: &str) -> Result<DecodedInvoice, LightningError> {
+        // POST /api/v1/payments/decode (read-only, so safe to retry)
+        let body = serde_json::json!({ "data": invoice });
+        self.request(reqwest::Method::POST, "/payments/decode", Some(body), true, ApiKey::Invoice).await
+    }
+
+    /// Compare LNBits' decoding of an invoice with ours
+    ///
+    /// Returns metadata describing the first diverging field, or `None` if
+    /// both agree. An invoice LNBits refuses to decode counts as diverging.
+    async fn cross_check_decode(&self, invoice: &str) -> Result<Option<serde_json::Value>, LightningError> {
+        let local = InvoiceParser::parse(invoice)?;
+        let decoded = match self.decode_invoice(invoice).await {
+            Ok(decoded) => decoded,
+            Err(e) if e.is_retryable() => return Err(e),
+            Err(e) => {
+                return Ok(Some(serde_json::json!({
+                    "field": "invoice",
+                    "local": "parsed",
+                    "lnbits": e.to_string(),
+                })))
+            }
+        };
+
+        let local_hash = hex::encode(local.payment_hash());
+        if !decoded.payment_hash.eq_ignore_ascii_case(&local_hash) {
+            return Ok(Some(serde_json::json!({
+                "field": "payment_hash",
+                "local": local_hash,
+                "lnbits": decoded.payment_hash,
+            })));
+        }
+        // Amountless invoices decode to no amount or 0
+        let decoded_amount = decoded.amount_msats.unwrap_or(0);
+        if decoded_amount != local.amount_msats {
+            return Ok(Some(serde_json::json!({
+                "field": "amount_msats",
+                "local": local.amount_msats,
+                "lnbits": decoded_amount,
+            })));
         }
+        Ok(None)
+    }
+
+    /// Create a wallet for an LNBits user (requires the User Manager extension)
+    pub async fn create_wallet(&self, user_id: &str, wallet_name: &str) -> Result<WalletDetails, LightningError> {
+        debug!("Creating LNBits wallet {} for user {}", wallet_name, user_id);
+
+        // POST /usermanager/api/v1/wallets
+        let body = serde_json::json!({
+            "user_id": user_id,
+            "wallet_name": wallet_name,
+            "admin_id": user_id,
+        });
+        // Not idempotent: a retry after a lost response would create a second wallet
+        let wallet: WalletResponse = self
+            .request_path(reqwest::Method::POST, "/usermanager/api/v1/wallets", Some(body), false, ApiKey::Admin)
+            .await?;
+        Ok(wallet.into())
+    }
+
+    /// Delete the wallet the configured admin key belongs to
+    pub async fn delete_wallet(&self) -> Result<(), LightningError> {
+        debug!("Deleting LNBits wallet {:?}", self.config.wallet_id);
+
+        // DELETE /api/v1/wallet
+        self.request::<serde_json::Value>(reqwest::Method::DELETE, "/wallet", None, false, ApiKey::Admin).await?;
+        Ok(())
+    }
 
-        response
-            .json::<T>()
-            .await
-            .map_err(|e| LightningError::ProcessorError(format!("Failed to parse LNBits response: {}", e)))
+    /// Wallet payment history, newest first
+    pub async fn list_payments(&self, limit: u32, offset: u32) -> Result<Vec<LNBitsPayment>, LightningError> {
+        // GET /api/v1/payments?limit={limit}&offset={offset}
+        let endpoint = format!("/payments?limit={}&offset={}", limit, offset);
+        self.request(reqwest::Method::GET, &endpoint, None, true, ApiKey::Invoice).await
+    }
+
+    /// Details of the wallet the configured admin key belongs to
+    pub async fn get_wallet_details(&self) -> Result<WalletDetails, LightningError> {
+        // GET /api/v1/wallet
+        let wallet: WalletResponse = self.request(reqwest::Method::GET, "/wallet", None, true, ApiKey::Admin).await?;
+        let mut details = WalletDetails::from(wallet);
+        // Older LNBits versions don't echo the admin key back
+        if details.adminkey.is_empty() {
+            details.adminkey = self.config.admin_api_key()?.to_string();
+        }
+        Ok(details)
+    }
+}
+
+impl Drop for LNBitsProvider {
+    fn drop(&mut self) {
+        if let Some(task) = &self.websocket_task {
+            task.abort();
+        }
+    }
+}
+
+/// Wallet websocket URL (`/api/v1/ws/{wallet_key}`)
+fn websocket_url(config: &LNBitsConfig) -> Result<String, LightningError> {
+    let base = config.api_url.trim_end_matches('/');
+    let base = if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        return Err(LightningError::ConfigError(format!("Invalid LNBits API URL for websocket: {}", config.api_url)));
+    };
+    Ok(format!("{}/api/v1/ws/{}", base, config.invoice_api_key()))
+}
+
+/// TLS connector for the websocket honouring `tls_ca_cert_path` and `tls_accept_invalid_certs`
+///
+/// `None` uses the default connector, which trusts the system roots only.
+fn websocket_connector(config: &LNBitsConfig) -> Result<Option<tokio_tungstenite::Connector>, LightningError> {
+    if config.tls_ca_cert_path.is_none() && !config.tls_accept_invalid_certs {
+        return Ok(None);
+    }
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(path) = &config.tls_ca_cert_path {
+        let pem = std::fs::read(path).map_err(|e| {
+            LightningError::ConfigError(format!("Failed to read TLS CA cert {}: {}", path.display(), e))
+        })?;
+        let certificate = native_tls::Certificate::from_pem(&pem).map_err(|e| {
+            LightningError::ConfigError(format!("Invalid TLS CA cert {}: {}", path.display(), e))
+        })?;
+        builder.add_root_certificate(certificate);
+    }
+    if config.tls_accept_invalid_certs {
+        builder.danger_accept_invalid_certs(true);
+    }
+    let connector = builder
+        .build()
+        .map_err(|e| LightningError::ConfigError(format!("Failed to create websocket TLS connector: {}", e)))?;
+    Ok(Some(tokio_tungstenite::Connector::NativeTls(connector)))
+}
+
+/// Payment notification pushed on the wallet websocket
+#[derive(Deserialize)]
+struct WebsocketEvent {
+    payment: Option<WebsocketPayment>,
+}
+
+#[derive(Deserialize)]
+struct WebsocketPayment {
+    payment_hash: String,
+    /// msats, negative for outgoing payments
+    #[serde(default)]
+    amount: i64,
+    #[serde(default)]
+    pending: Option<bool>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    preimage: Option<String>,
+    #[serde(default)]
+    time: Option<u64>,
+}
+
+impl WebsocketPayment {
+    /// The settlement this event reports, if it is a settled incoming payment
+    fn settlement(&self) -> Option<PaymentUpdate> {
+        let settled = match self.status.as_deref() {
+            Some(status) => status == "success",
+            None => self.pending == Some(false),
+        };
+        if !settled || self.amount <= 0 {
+            return None;
+        }
+        let mut payment_hash = [0u8; 32];
+        hex::decode_to_slice(&self.payment_hash, &mut payment_hash).ok()?;
+        let preimage = self.preimage.as_deref().and_then(|preimage| {
+            let mut bytes = [0u8; 32];
+            hex::decode_to_slice(preimage, &mut bytes).ok().map(|_| bytes)
+        });
+        Some(PaymentUpdate {
+            payment_hash,
+            amount_msats: Some(self.amount as u64),
+            settled_at: self.time,
+            preimage,
+        })
+    }
+}
+
+/// Keep the wallet websocket open, recording settlements as they arrive
+///
+/// Reconnects with exponential backoff (capped at `WEBSOCKET_MAX_BACKOFF`)
+/// whenever the connection drops; LNBits resubscribes a connection to its
+/// wallet on connect, so nothing else has to be sent.
+async fn listen_websocket(
+    url: String,
+    connector: Option<tokio_tungstenite::Connector>,
+    policy: ProviderRetryPolicy,
+    settled: Arc<StatusCache<PaymentUpdate>>,
+    settlements: broadcast::Sender<PaymentUpdate>,
+) {
+    let mut attempt: u32 = 0;
+    loop {
+        match tokio_tungstenite::connect_async_tls_with_config(url.as_str(), None, false, connector.clone()).await {
+            Ok((mut socket, _)) => {
+                info!("LNBits websocket connected");
+                attempt = 0;
+                while let Some(message) = socket.next().await {
+                    let text = match message {
+                        Ok(tokio_tungstenite::tungstenite::Message::Text(text)) => text,
+                        Ok(tokio_tungstenite::tungstenite::Message::Close(_)) => break,
+                        Ok(_) => continue,
+                        Err(e) => {
+                            warn!("LNBits websocket error: {}", e);
+                            break;
+                        }
+                    };
+                    let event: WebsocketEvent = match serde_json::from_str(&text) {
+                        Ok(event) => event,
+                        Err(e) => {
+                            debug!("Ignoring unrecognised LNBits websocket message: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Some(update) = event.payment.as_ref().and_then(WebsocketPayment::settlement) {
+                        debug!("LNBits websocket settlement: payment_hash={}", hex::encode(update.payment_hash));
+                        settled.insert(&update.payment_hash, update.clone());
+                        // No subscribers is fine; the cache still serves verify_payment
+                        let _ = settlements.send(update);
+                    }
+                }
+                warn!("LNBits websocket disconnected");
+            }
+            Err(e) => warn!("LNBits websocket connection failed (attempt {}): {}", attempt + 1, e),
+        }
+
+        attempt = attempt.saturating_add(1);
+        tokio::time::sleep(policy.jittered_backoff(attempt).min(WEBSOCKET_MAX_BACKOFF)).await;
+    }
+}
+
+/// Delay requested by a `Retry-After` header given in seconds
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Map an unsuccessful LNBits response to an error
+fn status_error(status: reqwest::StatusCode, body: String, endpoint: &str) -> LightningError {
+    let detail = error_detail(&body);
+    let lowercase = detail.to_lowercase();
+    match status.as_u16() {
+        401 | 403 => LightningError::ConfigError("invalid API key".to_string()),
+        429 => LightningError::NodeConnectionError("rate limited".to_string()),
+        code if code >= 500 => LightningError::NodeConnectionError(format!("LNBits API error: {} - {}", status, detail)),
+        _ if lowercase.contains("insufficient balance") => LightningError::InsufficientBalance(detail),
+        _ if lowercase.contains("expired") => LightningError::InvoiceExpired(detail),
+        _ if lowercase.contains("wallet") && (lowercase.contains("not found") || lowercase.contains("does not exist")) => {
+            LightningError::WalletNotFound(detail)
+        }
+        404 => LightningError::PaymentVerificationFailed("payment not found".to_string()),
+        code => LightningError::LNBitsApiError {
+            status: code,
+            detail,
+            endpoint: endpoint.to_string(),
+        },
+    }
+}
+
+/// Message of an LNBits error response
+///
+/// LNBits answers errors with FastAPI's `{"detail": ...}`, where `detail` is
+/// a message or, for rejected request bodies, a list of validation errors.
+/// Anything else is returned as is.
+fn error_detail(body: &str) -> String {
+    #[derive(Deserialize)]
+    struct ErrorResponse {
+        detail: serde_json::Value,
+    }
+
+    match serde_json::from_str::<ErrorResponse>(body).map(|response| response.detail) {
+        Ok(serde_json::Value::String(detail)) => detail,
+        Ok(serde_json::Value::Array(errors)) => errors
+            .iter()
+            .filter_map(|error| error.get("msg").and_then(|msg| msg.as_str()))
+            .collect::<Vec<_>>()
+            .join("; "),
+        _ => body.trim().to_string(),
     }
 }
 
 #[async_trait]
 impl LightningProvider for LNBitsProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "lnbits", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
     async fn verify_payment(
         &self,
         invoice: &str,
@@ -92,48 +972,74 @@ impl LightningProvider for LNBitsProvider {
     ) -> Result<PaymentVerificationResult, LightningError> {
         debug!("Verifying payment via LNBits: payment_id={}", payment_id);
 
-        // LNBits API: Check payment status
-        // GET /api/v1/payments/{payment_hash}
-        let payment_hash_hex = hex::encode(payment_hash);
-        let endpoint = format!("/payments/{}", payment_hash_hex);
+        if self.config.cross_check_decode && !invoice.is_empty() {
+            if let Some(mismatch) = self.cross_check_decode(invoice).await? {
+                warn!("LNBits decodes invoice for payment {} differently: {}", payment_id, mismatch);
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "lnbits",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "decode_mismatch",
+                        "mismatch": mismatch,
+                    }),
+                });
+            }
+        }
 
-        #[derive(Deserialize)]
-        struct PaymentResponse {
-            paid: bool,
-            #[serde(rename = "amount")]
-            amount_msats: Option<u64>,
-            #[serde(rename = "time")]
-            timestamp: Option<u64>,
+        if let Some(update) = self.cached_settlement(payment_hash).await {
+            return Ok(PaymentVerificationResult {
+                verified: true,
+                amount_msats: update.amount_msats,
+                timestamp: update.settled_at,
+                metadata: serde_json::json!({
+                    "provider": "lnbits",
+                    "payment_hash": hex::encode(payment_hash),
+                    "preimage": update.preimage.map(hex::encode),
+                    "source": "websocket",
+                }),
+            });
         }
 
-        match self.request::<PaymentResponse>(reqwest::Method::GET, &endpoint, None).await {
-            Ok(payment) => {
-                let verified = payment.paid;
+        let payment_hash_hex = hex::encode(payment_hash);
+        match self.payment_status(payment_hash).await? {
+            Some(payment) => {
+                // Newer LNBits versions only report the amount inside `details`
+                let amount = payment.amount_msats.or_else(|| {
+                    payment.details.as_ref().and_then(|d| d.get("amount")).and_then(|a| a.as_i64())
+                });
+                // A paid outgoing payment with this hash is not a payment to us
+                let outgoing = amount.is_some_and(|amount| amount < 0);
+                let verified = payment.paid && !outgoing;
                 debug!(
                     "LNBits payment check: payment_id={}, verified={}, amount={:?}",
-                    payment_id, verified, payment.amount_msats
+                    payment_id, verified, amount
                 );
 
                 Ok(PaymentVerificationResult {
                     verified,
-                    amount_msats: payment.amount_msats,
+                    amount_msats: amount.map(|amount| amount.unsigned_abs()),
                     timestamp: payment.timestamp,
                     metadata: serde_json::json!({
                         "provider": "lnbits",
                         "payment_hash": payment_hash_hex,
+                        "preimage": payment.preimage,
+                        "outgoing": outgoing,
+                        "extra": payment.details.as_ref().and_then(|d| d.get("extra")).cloned(),
                     }),
                 })
             }
-            Err(e) => {
-                // If payment not found, it might not be paid yet
-                warn!("LNBits payment check failed: payment_id={}, error={}", payment_id, e);
+            None => {
+                debug!("LNBits payment not found: payment_id={}", payment_id);
                 Ok(PaymentVerificationResult {
                     verified: false,
                     amount_msats: None,
                     timestamp: None,
                     metadata: serde_json::json!({
                         "provider": "lnbits",
-                        "error": e.to_string(),
+                        "error": LightningError::PaymentVerificationFailed("payment not found".to_string()).to_string(),
                     }),
                 })
             }
@@ -146,11 +1052,24 @@ impl LightningProvider for LNBitsProvider {
         description: &str,
         expiry_seconds: u64,
     ) -> Result<String, LightningError> {
-        debug!("Creating invoice via LNBits: amount={} msats", amount_msats);
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "lnbits", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        // LNBits takes the amount in sats; round up so the payer never pays less than requested
+        let amount_sats = params.amount_msats.div_ceil(1000);
+        debug!("Creating invoice via LNBits: amount={} sats", amount_sats);
 
         // LNBits API: Create invoice
         // POST /api/v1/payments
-        let endpoint = if let Some(wallet_id) = &self.config.wallet_id {
+        let wallet = params.wallet.as_deref().map(|name| self.wallet(name)).transpose()?;
+        let wallet_id = wallet.map(|wallet| &wallet.wallet_id).or(self.config.wallet_id.as_ref());
+        let endpoint = if let Some(wallet_id) = wallet_id {
             format!("/payments?wallet={}", wallet_id)
         } else {
             "/payments".to_string()
@@ -159,9 +1078,20 @@ impl LightningProvider for LNBitsProvider {
         #[derive(Serialize)]
         struct InvoiceRequest {
             out: bool, // false = invoice (receive payment)
+            /// Sats (the default `unit`)
             amount: u64,
-            memo: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            memo: Option<String>,
+            /// Hex-encoded description; LNBits commits to its hash in the invoice
+            #[serde(skip_serializing_if = "Option::is_none")]
+            unhashed_description: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            description_hash: Option<String>,
             expiry: u64,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            extra: Option<serde_json::Value>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            webhook: Option<String>,
         }
 
         #[derive(Deserialize)]
@@ -169,39 +1099,199 @@ impl LightningProvider for LNBitsProvider {
             payment_request: String,
         }
 
+        let (memo, unhashed_description, description_hash) = match &params.description {
+            DescriptionKind::Direct(description) if description.len() <= MAX_DESCRIPTION_BYTES => {
+                (Some(description.clone()), None, None)
+            }
+            DescriptionKind::Direct(description) => (None, Some(hex::encode(description.as_bytes())), None),
+            DescriptionKind::Hash(hash) => (None, None, Some(hex::encode(hash))),
+        };
+
+        // LNBits keeps `extra` with the payment record, so label/metadata come back on lookup
+        let extra = if params.label.is_some() || !params.metadata.is_null() {
+            Some(serde_json::json!({
+                "label": params.label,
+                "metadata": params.metadata,
+            }))
+        } else {
+            None
+        };
+
         let request_body = InvoiceRequest {
             out: false,
-            amount: amount_msats,
-            memo: description.to_string(),
-            expiry: expiry_seconds,
+            amount: amount_sats,
+            memo,
+            unhashed_description,
+            description_hash,
+            expiry: params.expiry_seconds,
+            extra,
+            webhook: self.config.webhook_url.clone(),
         };
 
+        // Only safe to repeat when a label identifies the request
+        let idempotent = params.label.is_some();
+        let body = serde_json::to_value(request_body)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize request: {}", e)))?;
+        let key = wallet.map(ApiKey::Wallet).unwrap_or(ApiKey::Invoice);
         let response: InvoiceResponse = self
-            .request(reqwest::Method::POST, &endpoint, Some(serde_json::to_value(request_body)
-                .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize request: {}", e)))?))
+            .request(reqwest::Method::POST, &endpoint, Some(body), idempotent, key)
             .await?;
 
         debug!("LNBits invoice created: {}", response.payment_request);
+        if let Some(name) = &params.wallet {
+            self.record_wallet(name, &response.payment_request).await;
+        }
         Ok(response.payment_request)
     }
 
+    async fn create_invoices_batch(
+        &self,
+        requests: &[InvoiceParams],
+    ) -> Result<Vec<Result<String, LightningError>>, LightningError> {
+        let (first, rest) = match requests.split_first() {
+            Some(split) => split,
+            None => return Ok(Vec::new()),
+        };
+
+        // Issue the first request on its own so the pooled connection (and TLS
+        // session) is established once, then pipeline the rest over it.
+        let mut results = Vec::with_capacity(requests.len());
+        results.push(self.create_invoice_ex(first).await);
+
+        let rest_results: Vec<_> = futures::stream::iter(rest)
+            .map(|params| self.create_invoice_ex(params))
+            .buffered(MAX_CONCURRENT_INVOICE_REQUESTS)
+            .collect()
+            .await;
+        results.extend(rest_results);
+
+        debug!("LNBits batch created {} invoices", results.iter().filter(|r| r.is_ok()).count());
+        Ok(results)
+    }
+
     async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
-        let payment_hash_hex = hex::encode(payment_hash);
-        let endpoint = format!("/payments/{}", payment_hash_hex);
+        if self.cached_settlement(payment_hash).await.is_some() {
+            return Ok(true);
+        }
+        // Payment not found = not confirmed
+        Ok(self.payment_status(payment_hash).await?.is_some_and(|payment| payment.paid))
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        self.get_wallet_balance().await
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        // LNBits doesn't expose its funding source's node; report the wallet instead
+        // GET /api/v1/wallet
+        let wallet: WalletResponse = self.request(reqwest::Method::GET, "/wallet", None, true, ApiKey::Invoice).await?;
+        debug!("LNBits wallet {} ({})", wallet.id, wallet.name);
+        Ok(NodeInfo {
+            node_id: [0u8; 33],
+            alias: Some(wallet.name).filter(|name| !name.is_empty()),
+            color: None,
+            num_peers: 0,
+            num_active_channels: 0,
+            num_pending_channels: 0,
+            block_height: 0,
+            version: None,
+        })
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        let amount_msats = payment_amount_msats(invoice, amount_msats)?;
 
+        // LNBits API: Fee reserve the funding source would hold back for this invoice
+        // GET /api/v1/payments/fee-reserve?invoice=<bolt11>
         #[derive(Deserialize)]
-        struct PaymentResponse {
-            paid: bool,
+        struct FeeReserveResponse {
+            fee_reserve: u64,
         }
 
-        match self.request::<PaymentResponse>(reqwest::Method::GET, &endpoint, None).await {
-            Ok(payment) => Ok(payment.paid),
-            Err(_) => Ok(false), // Payment not found = not confirmed
+        let endpoint = format!("/payments/fee-reserve?invoice={}", invoice);
+        match self.request::<FeeReserveResponse>(reqwest::Method::GET, &endpoint, None, true, ApiKey::Invoice).await {
+            Ok(reserve) => Ok(FeeEstimate {
+                fee_base_msats: reserve.fee_reserve,
+                fee_proportional: 0.0,
+                estimated_total_fee_msats: reserve.fee_reserve,
+                // A reserve is an upper bound rather than the fee actually paid
+                confidence: 0.7,
+            }),
+            Err(e) => {
+                debug!("LNBits fee reserve unavailable, using configured fee rate: {}", e);
+                Ok(FeeEstimate::from_rate(
+                    self.config.default_fee_estimate_msats,
+                    self.config.fee_rate_ppm,
+                    amount_msats,
+                    0.3,
+                ))
+            }
+        }
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        if self.websocket_task.is_none() {
+            return Err(LightningError::ProcessorError(
+                "LNBits payment subscriptions require lightning.lnbits.websocket".to_string(),
+            ));
+        }
+        let receiver = self.settlements.subscribe();
+        Ok(futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(update) => return Some((update, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("LNBits payment subscriber fell behind, {} settlements skipped", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed())
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        // LNBits wallets don't expose the funding source's channels
+        Err(LightningError::ProcessorError("not supported".into()))
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        self.wallet_index.attach(node_api).await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // LNBits API: Wallet details, the cheapest authenticated call
+        // GET /api/v1/wallet
+        let started = std::time::Instant::now();
+        let result = self.request::<serde_json::Value>(reqwest::Method::GET, "/wallet", None, true, ApiKey::Invoice).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        if let Err(e) = &result {
+            warn!("LNBits health check failed: {}", e);
+        }
+        Ok(HealthStatus {
+            reachable: result.is_ok(),
+            latency_ms,
+            block_height: None,
+            synced_to_chain: None,
+            version: None,
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
         }
     }
 
     fn provider_type(&self) -> ProviderType {
         ProviderType::LNBits
     }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
 }
 
@@ -0,0 +1,172 @@
+//! LND provider support
+//!
+//! Credential loading (macaroon + TLS certificate) is shared by every LND
+//! transport; the gRPC transport lives in `grpc`.
+//!
+//! The last `settle_index` delivered by the invoice subscription is kept in
+//! the `lnd_subscription` storage tree, so settlements that happen while the
+//! module is down are replayed when it subscribes again.
+
+pub mod grpc;
+pub mod proto;
+
+use crate::error::LightningError;
+use crate::provider::payment_index::PaymentIndex;
+use crate::provider::PaymentUpdate;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Delay before the first resubscription attempt
+pub const RESUBSCRIBE_BACKOFF_BASE_MS: u64 = 500;
+/// Upper bound on the resubscription delay
+pub const RESUBSCRIBE_BACKOFF_MAX_MS: u64 = 30_000;
+
+/// Storage tree holding invoice subscription progress
+pub const LND_SUBSCRIPTION_TREE: &str = "lnd_subscription";
+
+/// Key of the last delivered `settle_index` in `LND_SUBSCRIPTION_TREE`
+pub const SETTLE_INDEX_KEY: &str = "settle_index";
+
+/// Subscription progress kept in `LND_SUBSCRIPTION_TREE` once storage is attached
+pub fn subscription_progress() -> PaymentIndex {
+    PaymentIndex::in_tree("LND subscription progress", LND_SUBSCRIPTION_TREE)
+}
+
+/// The last delivered `settle_index` recorded in `progress` (0 if none)
+pub fn saved_settle_index(progress: &PaymentIndex) -> u64 {
+    progress
+        .get_key(SETTLE_INDEX_KEY)
+        .and_then(|index| index.parse().ok())
+        .unwrap_or(0)
+}
+
+/// LND authentication material
+#[derive(Clone)]
+pub struct LndCredentials {
+    /// Hex-encoded macaroon, sent as the `macaroon` header/metadata
+    pub macaroon_hex: String,
+    /// LND's TLS certificate (PEM), usually self-signed
+    pub tls_cert_pem: Option<Vec<u8>>,
+}
+
+impl std::fmt::Debug for LndCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LndCredentials")
+            .field("macaroon_hex", &"<redacted>")
+            .field("tls_cert_pem", &self.tls_cert_pem.as_ref().map(|pem| pem.len()))
+            .finish()
+    }
+}
+
+impl LndCredentials {
+    /// Load the macaroon (binary file) and optional TLS certificate from disk
+    pub fn load(macaroon_path: &Path, tls_cert_path: Option<&Path>) -> Result<Self, LightningError> {
+        let macaroon = std::fs::read(macaroon_path).map_err(|e| {
+            LightningError::ConfigError(format!("Failed to read LND macaroon {}: {}", macaroon_path.display(), e))
+        })?;
+        let tls_cert_pem = tls_cert_path
+            .map(|path| {
+                std::fs::read(path).map_err(|e| {
+                    LightningError::ConfigError(format!("Failed to read LND TLS cert {}: {}", path.display(), e))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            macaroon_hex: hex::encode(macaroon),
+            tls_cert_pem,
+        })
+    }
+}
+
+/// An LND invoice event relevant to settlement tracking
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvoiceEvent {
+    pub add_index: u64,
+    pub settle_index: u64,
+    pub settled: bool,
+    pub update: PaymentUpdate,
+}
+
+/// Source of invoice subscriptions that can be resumed from an index
+///
+/// Implemented by the gRPC client; abstracted so resume behavior can be
+/// exercised without a live node.
+#[async_trait]
+pub trait InvoiceSubscriber: Send + Sync + 'static {
+    /// Subscribe to invoice updates after the given add/settle indices
+    async fn subscribe(
+        &self,
+        add_index: u64,
+        settle_index: u64,
+    ) -> Result<BoxStream<'static, Result<InvoiceEvent, LightningError>>, LightningError>;
+}
+
+/// Follow an invoice subscription, resubscribing after failures
+///
+/// The highest `add_index`/`settle_index` seen is passed on resubscription so
+/// LND replays anything that happened while disconnected. Settlements at or
+/// below the last seen `settle_index` are dropped, so replays never produce
+/// duplicates. The stream ends when the receiver is dropped.
+///
+/// With `progress`, each settlement's `settle_index` is recorded under
+/// `SETTLE_INDEX_KEY` once it has been delivered, to resume from after a
+/// restart (see `saved_settle_index`).
+pub fn resumable_settlements(
+    subscriber: Arc<dyn InvoiceSubscriber>,
+    add_index: u64,
+    settle_index: u64,
+    progress: Option<Arc<PaymentIndex>>,
+) -> BoxStream<'static, PaymentUpdate> {
+    let (tx, rx) = tokio::sync::mpsc::channel(64);
+
+    tokio::spawn(async move {
+        let mut add_index = add_index;
+        let mut settle_index = settle_index;
+        let mut failures: u32 = 0;
+
+        while !tx.is_closed() {
+            match subscriber.subscribe(add_index, settle_index).await {
+                Ok(mut stream) => {
+                    debug!("LND invoice subscription active (add_index={}, settle_index={})", add_index, settle_index);
+                    while let Some(item) = stream.next().await {
+                        let event = match item {
+                            Ok(event) => event,
+                            Err(e) => {
+                                warn!("LND invoice subscription failed: {}", e);
+                                break;
+                            }
+                        };
+                        failures = 0;
+                        add_index = add_index.max(event.add_index);
+                        if event.settled && event.settle_index > settle_index {
+                            settle_index = event.settle_index;
+                            if tx.send(event.update).await.is_err() {
+                                return;
+                            }
+                            if let Some(progress) = &progress {
+                                if let Err(e) = progress.insert(SETTLE_INDEX_KEY.to_string(), settle_index.to_string()).await {
+                                    warn!("{}", e);
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!("LND invoice subscription could not be opened: {}", e),
+            }
+
+            failures = failures.saturating_add(1);
+            let delay_ms = RESUBSCRIBE_BACKOFF_BASE_MS
+                .saturating_mul(1u64 << failures.min(16).saturating_sub(1))
+                .min(RESUBSCRIBE_BACKOFF_MAX_MS);
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        }
+    });
+
+    futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|update| (update, rx)) }).boxed()
+}
@@ -0,0 +1,96 @@
+//! Subset of LND's `lightning.proto` used by the gRPC provider
+//!
+//! Hand-written prost messages so the build doesn't need `protoc`. Field
+//! numbers match `lnrpc/lightning.proto`; unused fields are omitted, which
+//! protobuf decoding tolerates.
+
+/// `lnrpc.Invoice`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Invoice {
+    #[prost(string, tag = "1")]
+    pub memo: String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub r_preimage: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub r_hash: Vec<u8>,
+    #[prost(int64, tag = "7")]
+    pub creation_date: i64,
+    #[prost(int64, tag = "8")]
+    pub settle_date: i64,
+    #[prost(string, tag = "9")]
+    pub payment_request: String,
+    #[prost(bytes = "vec", tag = "10")]
+    pub description_hash: Vec<u8>,
+    #[prost(int64, tag = "11")]
+    pub expiry: i64,
+    #[prost(uint64, tag = "16")]
+    pub add_index: u64,
+    #[prost(uint64, tag = "17")]
+    pub settle_index: u64,
+    #[prost(int64, tag = "20")]
+    pub amt_paid_msat: i64,
+    #[prost(enumeration = "InvoiceState", tag = "21")]
+    pub state: i32,
+    #[prost(int64, tag = "23")]
+    pub value_msat: i64,
+}
+
+/// `lnrpc.Invoice.InvoiceState`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum InvoiceState {
+    Open = 0,
+    Settled = 1,
+    Canceled = 2,
+    Accepted = 3,
+}
+
+/// `lnrpc.AddInvoiceResponse`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AddInvoiceResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub r_hash: Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub payment_request: String,
+    #[prost(uint64, tag = "16")]
+    pub add_index: u64,
+    #[prost(bytes = "vec", tag = "17")]
+    pub payment_addr: Vec<u8>,
+}
+
+/// `lnrpc.PaymentHash`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PaymentHash {
+    #[prost(bytes = "vec", tag = "2")]
+    pub r_hash: Vec<u8>,
+}
+
+/// `lnrpc.InvoiceSubscription`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InvoiceSubscription {
+    /// Replay invoices added after this index
+    #[prost(uint64, tag = "1")]
+    pub add_index: u64,
+    /// Replay invoices settled after this index
+    #[prost(uint64, tag = "2")]
+    pub settle_index: u64,
+}
+
+/// `lnrpc.GetInfoRequest`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetInfoRequest {}
+
+/// `lnrpc.GetInfoResponse`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetInfoResponse {
+    #[prost(string, tag = "1")]
+    pub identity_pubkey: String,
+    #[prost(string, tag = "2")]
+    pub alias: String,
+    #[prost(uint32, tag = "6")]
+    pub block_height: u32,
+    #[prost(bool, tag = "9")]
+    pub synced_to_chain: bool,
+    #[prost(string, tag = "14")]
+    pub version: String,
+}
@@ -3,42 +3,383 @@
 //! Supports multiple providers:
 //! - LNBits (REST API)
 //! - LDK (Lightning Development Kit)
+//! - CLN (Core Lightning REST)
+//! - Eclair (HTTP API)
+//! - phoenixd (HTTP API)
+//! - LNDhub (BlueWallet/Alby accounts)
+//! - Blink (Galoy GraphQL API)
+//! - OpenNode (REST API, fiat settlement)
+//! - Strike (REST API)
+//! - Watch-only (verification via LNURL-verify or a read-only LNBits key)
+//! - LND (gRPC, behind the `lnd-grpc` feature)
+//! - Greenlight (hosted CLN, behind the `greenlight` feature)
+//! - Routing (dispatches to other providers by amount)
+//! - Composite (spreads invoices over LNBits and LDK by amount, or round robin)
 //! - Stub (for testing)
 
 use crate::error::LightningError;
 use async_trait::async_trait;
-use blvm_node::module::traits::ModuleContext;
+use bitcoin::{Address, Network, Txid};
+use blvm_node::module::traits::{ModuleContext, NodeAPI};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
 
 // Define types first, then submodules can import them
 pub mod lnbits;
+pub mod chain;
+pub mod channel_policy;
+pub mod gossip;
 pub mod ldk;
 pub mod stub;
+pub mod cln;
+pub mod eclair;
+pub mod phoenixd;
+pub mod lndhub;
+pub mod blink;
+pub mod opennode;
+pub mod strike;
+pub mod watch_only;
+pub mod circuit_breaker;
+pub mod record;
+pub mod payment_index;
+pub mod routing;
+pub mod failover;
+pub mod composite;
+pub mod retry;
+pub mod keystore;
+#[cfg(feature = "lnd-grpc")]
+pub mod lnd;
+#[cfg(feature = "greenlight")]
+pub mod greenlight;
 
 /// Lightning provider type
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// Displays and serializes as the lowercase name accepted by `FromStr` and
+/// `lightning.provider` (e.g. `"lnbits"`). Records written with the older
+/// variant names (e.g. `"LNBits"`) still deserialize.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProviderType {
+    #[serde(alias = "LNBits")]
     LNBits,
+    #[serde(alias = "LDK")]
     LDK,
+    #[serde(alias = "Stub")]
     Stub,
+    #[serde(alias = "CLN")]
+    CLN,
+    #[serde(alias = "Eclair")]
+    Eclair,
+    #[serde(alias = "Phoenixd")]
+    Phoenixd,
+    /// LNDhub account (BlueWallet, Alby)
+    #[serde(alias = "LndHub")]
+    LndHub,
+    /// Blink (Galoy) wallet
+    #[serde(alias = "Blink")]
+    Blink,
+    /// OpenNode merchant account
+    #[serde(alias = "OpenNode")]
+    OpenNode,
+    /// Strike account
+    #[serde(alias = "Strike")]
+    Strike,
+    /// Verification only, for invoices created elsewhere
+    #[serde(rename = "watch_only", alias = "WatchOnly")]
+    WatchOnly,
+    /// LND over gRPC (requires the `lnd-grpc` feature)
+    #[serde(alias = "LND")]
+    LND,
+    /// Greenlight hosted node (requires the `greenlight` feature)
+    #[serde(alias = "Greenlight")]
+    Greenlight,
+    /// Providers tried in order until one is reachable
+    ///
+    /// An empty chain (plain `"failover"`) is read from
+    /// `lightning.failover.primary` and `lightning.failover.secondary`.
+    #[serde(alias = "Failover")]
+    Failover(Vec<ProviderType>),
+    /// Providers chosen by invoice amount, per `lightning.routing.rules`
+    #[serde(alias = "Routing")]
+    Routing,
+    /// LNBits and LDK side by side, per `lightning.composite.strategy`
+    #[serde(alias = "Composite")]
+    Composite,
+}
+
+/// Every provider type, with `Failover` as the configured (empty) chain
+static ALL_PROVIDER_TYPES: [ProviderType; 16] = [
+    ProviderType::LNBits,
+    ProviderType::LDK,
+    ProviderType::Stub,
+    ProviderType::CLN,
+    ProviderType::Eclair,
+    ProviderType::Phoenixd,
+    ProviderType::LndHub,
+    ProviderType::Blink,
+    ProviderType::OpenNode,
+    ProviderType::Strike,
+    ProviderType::WatchOnly,
+    ProviderType::LND,
+    ProviderType::Greenlight,
+    ProviderType::Failover(Vec::new()),
+    ProviderType::Routing,
+    ProviderType::Composite,
+];
+
+impl ProviderType {
+    /// Every provider type
+    ///
+    /// Types behind a cargo feature (`LND`, `Greenlight`) are listed even if
+    /// this build can't create them.
+    pub fn all() -> &'static [ProviderType] {
+        &ALL_PROVIDER_TYPES
+    }
+
+    /// Name as accepted by `FromStr` (`"failover"` for any failover chain)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderType::LNBits => "lnbits",
+            ProviderType::LDK => "ldk",
+            ProviderType::Stub => "stub",
+            ProviderType::CLN => "cln",
+            ProviderType::Eclair => "eclair",
+            ProviderType::Phoenixd => "phoenixd",
+            ProviderType::LndHub => "lndhub",
+            ProviderType::Blink => "blink",
+            ProviderType::OpenNode => "opennode",
+            ProviderType::Strike => "strike",
+            ProviderType::WatchOnly => "watch_only",
+            ProviderType::LND => "lnd",
+            ProviderType::Greenlight => "greenlight",
+            ProviderType::Failover(_) => "failover",
+            ProviderType::Routing => "routing",
+            ProviderType::Composite => "composite",
+        }
+    }
+}
+
+impl fmt::Display for ProviderType {
+    /// A failover chain displays as its comma-separated providers (`"lnbits,stub"`)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderType::Failover(chain) if !chain.is_empty() => {
+                let names: Vec<String> = chain.iter().map(|provider| provider.to_string()).collect();
+                f.write_str(&names.join(","))
+            }
+            _ => f.write_str(self.as_str()),
+        }
+    }
 }
 
 impl FromStr for ProviderType {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
+        // "lnbits,stub" -> failover chain
+        if s.contains(',') {
+            let chain = s
+                .split(',')
+                .map(|part| part.trim().parse::<ProviderType>())
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(ProviderType::Failover(chain));
+        }
+        
+        match s.trim().to_lowercase().as_str() {
             "lnbits" => Ok(ProviderType::LNBits),
             "ldk" => Ok(ProviderType::LDK),
             "stub" => Ok(ProviderType::Stub),
+            "cln" => Ok(ProviderType::CLN),
+            "eclair" => Ok(ProviderType::Eclair),
+            "phoenixd" => Ok(ProviderType::Phoenixd),
+            "lndhub" => Ok(ProviderType::LndHub),
+            "blink" => Ok(ProviderType::Blink),
+            "opennode" => Ok(ProviderType::OpenNode),
+            "strike" => Ok(ProviderType::Strike),
+            "watch_only" | "watchonly" => Ok(ProviderType::WatchOnly),
+            "lnd" => Ok(ProviderType::LND),
+            "greenlight" => Ok(ProviderType::Greenlight),
+            "failover" => Ok(ProviderType::Failover(Vec::new())),
+            "routing" => Ok(ProviderType::Routing),
+            "composite" => Ok(ProviderType::Composite),
             _ => Err(format!("Unknown provider type: {}", s)),
         }
     }
 }
 
-/// Payment verification result
+/// Maximum length of a BOLT11 `d` field; longer descriptions must be hashed
+pub const MAX_DESCRIPTION_BYTES: usize = 639;
+
+/// Maximum number of concurrent invoice creations in a batch
+pub const MAX_CONCURRENT_INVOICE_REQUESTS: usize = 16;
+
+/// Whether failover chains, routing rules and composites may contain the
+/// stub provider, which verifies every payment; only in test builds
+const STUB_MEMBERS_ALLOWED: bool = cfg!(any(test, feature = "test-utils"));
+
+/// Invoice description, either embedded directly or committed to by hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptionKind {
+    /// Plaintext description (hashed automatically if longer than `MAX_DESCRIPTION_BYTES`)
+    Direct(String),
+    /// SHA256 hash of a description held out-of-band
+    Hash([u8; 32]),
+}
+
+/// Extended invoice creation parameters
 #[derive(Debug, Clone)]
+pub struct InvoiceParams {
+    pub amount_msats: u64,
+    pub description: DescriptionKind,
+    pub expiry_seconds: u64,
+    /// Caller-supplied label (e.g. order id)
+    pub label: Option<String>,
+    /// Arbitrary metadata stored alongside the invoice by the provider
+    pub metadata: Value,
+    /// On-chain fallback address embedded in the invoice
+    pub fallback_address: Option<String>,
+    /// Final hop CLTV delta (provider default if unset)
+    pub min_final_cltv_expiry: Option<u64>,
+    /// Tenant wallet to create the invoice in (LNBits `lightning.lnbits.wallets`)
+    pub wallet: Option<String>,
+}
+
+impl InvoiceParams {
+    /// Create parameters for a plain invoice with a direct description
+    pub fn new(amount_msats: u64, description: &str, expiry_seconds: u64) -> Self {
+        Self {
+            amount_msats,
+            description: DescriptionKind::Direct(description.to_string()),
+            expiry_seconds,
+            label: None,
+            metadata: Value::Null,
+            fallback_address: None,
+            min_final_cltv_expiry: None,
+            wallet: None,
+        }
+    }
+}
+
+/// Operations supported by a provider
+///
+/// Lets callers check support up front instead of relying on "not supported"
+/// errors from individual calls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    /// Can verify incoming payments
+    pub can_verify: bool,
+    /// Can create invoices
+    pub can_create_invoices: bool,
+    /// Can pay BOLT11 invoices
+    pub can_pay: bool,
+    /// Can send spontaneous (keysend) payments
+    pub can_keysend: bool,
+    /// Can create hold invoices
+    pub can_hold_invoices: bool,
+    /// Manages its own channels; `list_channels` is supported when set
+    pub can_manage_channels: bool,
+    /// Can list historical payments
+    pub can_list_payments: bool,
+    /// Can withdraw funds on-chain
+    pub can_withdraw_onchain: bool,
+}
+
+/// Provider health as reported by `health_check`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthStatus {
+    /// Whether the provider answered
+    pub reachable: bool,
+    /// Round-trip time of the health probe
+    pub latency_ms: u64,
+    /// Chain height seen by the provider, if known
+    pub block_height: Option<u64>,
+    /// Whether the provider is synced to the chain tip, if known
+    pub synced_to_chain: Option<bool>,
+    /// Provider/backend version, if known
+    pub version: Option<String>,
+}
+
+/// Identity and status of the node behind a provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeInfo {
+    /// Node public key (compressed); all zeros if the backend doesn't expose it
+    pub node_id: [u8; 33],
+    pub alias: Option<String>,
+    /// Node color as hex, e.g. `#3399ff`
+    pub color: Option<String>,
+    pub num_peers: u32,
+    pub num_active_channels: u32,
+    pub num_pending_channels: u32,
+    /// Chain height seen by the node; 0 if unknown
+    pub block_height: u64,
+    pub version: Option<String>,
+}
+
+/// Lightning channel summary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub channel_id: [u8; 32],
+    /// Counterparty node public key (compressed)
+    pub counterparty_node_id: [u8; 33],
+    pub capacity_msats: u64,
+    pub local_balance_msats: u64,
+    pub remote_balance_msats: u64,
+    /// Channel is usable (peer connected, channel funded)
+    pub is_active: bool,
+    pub short_channel_id: Option<u64>,
+    /// Channel is announced to the network
+    pub is_public: bool,
+}
+
+/// Settlement pushed by a provider's payment subscription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentUpdate {
+    pub payment_hash: [u8; 32],
+    pub amount_msats: Option<u64>,
+    pub settled_at: Option<u64>,
+    pub preimage: Option<[u8; 32]>,
+}
+
+/// Stream of settlements from `subscribe_payments`
+pub type PaymentStream = futures::stream::BoxStream<'static, PaymentUpdate>;
+
+/// Routing fee estimate for paying an invoice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    /// Flat part of the fee
+    pub fee_base_msats: u64,
+    /// Proportional part of the fee (fraction of the amount, e.g. 0.001 = 0.1%)
+    pub fee_proportional: f64,
+    /// Expected total fee for the amount
+    pub estimated_total_fee_msats: u64,
+    /// How reliable the estimate is (0.0–1.0)
+    pub confidence: f32,
+}
+
+impl FeeEstimate {
+    /// Build an estimate from a base fee and a rate in parts per million
+    pub fn from_rate(fee_base_msats: u64, fee_rate_ppm: u64, amount_msats: u64, confidence: f32) -> Self {
+        let proportional_msats = (amount_msats as u128 * fee_rate_ppm as u128 / 1_000_000) as u64;
+        Self {
+            fee_base_msats,
+            fee_proportional: fee_rate_ppm as f64 / 1_000_000.0,
+            estimated_total_fee_msats: fee_base_msats.saturating_add(proportional_msats),
+            confidence: confidence.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Fee assumed by providers that can't estimate fees dynamically
+pub const DEFAULT_FEE_ESTIMATE_MSATS: u64 = 1_000;
+
+/// Parts per million in one whole (a tolerance of this accepts any amount)
+pub(crate) const PPM: u64 = 1_000_000;
+
+/// Payment verification result
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaymentVerificationResult {
     pub verified: bool,
     pub amount_msats: Option<u64>,
@@ -46,6 +387,48 @@ pub struct PaymentVerificationResult {
     pub metadata: Value,
 }
 
+impl PaymentVerificationResult {
+    /// Whether the received amount is more than `tolerance_ppm` below `invoiced_msats`
+    ///
+    /// Amountless invoices (`invoiced_msats` of 0) and results without an
+    /// amount are never underpaid; a tolerance above 1,000,000 counts as
+    /// 1,000,000 (any amount).
+    pub fn is_underpaid(&self, invoiced_msats: u64, tolerance_ppm: u64) -> bool {
+        match self.amount_msats {
+            Some(received) if invoiced_msats > 0 => {
+                let tolerance_ppm = tolerance_ppm.min(PPM);
+                let minimum = invoiced_msats as u128 * (PPM - tolerance_ppm) as u128 / PPM as u128;
+                (received as u128) < minimum
+            }
+            _ => false,
+        }
+    }
+
+    /// How much more than `invoiced_msats` was received, if anything
+    ///
+    /// `None` for amountless invoices and results without an amount.
+    pub fn overpaid_by_msats(&self, invoiced_msats: u64) -> Option<u64> {
+        match self.amount_msats {
+            Some(received) if invoiced_msats > 0 && received > invoiced_msats => Some(received - invoiced_msats),
+            _ => None,
+        }
+    }
+
+    /// Received amount, 0 if the provider didn't report one
+    pub fn amount_msats_or_zero(&self) -> u64 {
+        self.amount_msats.unwrap_or(0)
+    }
+
+    /// Seconds since the provider's payment timestamp (0 if it is in the future)
+    pub fn age_seconds(&self) -> Option<u64> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.timestamp.map(|timestamp| now.saturating_sub(timestamp))
+    }
+}
+
 /// Lightning provider trait
 #[async_trait]
 pub trait LightningProvider: Send + Sync {
@@ -65,11 +448,158 @@ pub trait LightningProvider: Send + Sync {
         expiry_seconds: u64,
     ) -> Result<String, LightningError>;
 
+    /// Create a Lightning invoice with extended parameters
+    ///
+    /// The default implementation only supports direct descriptions and
+    /// drops label, metadata, fallback address and CLTV delta.
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        match &params.description {
+            DescriptionKind::Direct(description) => {
+                self.create_invoice(params.amount_msats, description, params.expiry_seconds).await
+            }
+            DescriptionKind::Hash(_) => Err(LightningError::InvoiceError(
+                "Provider does not support description hashes".to_string(),
+            )),
+        }
+    }
+
+    /// Create a Lightning invoice committing to a description by its SHA256 hash
+    ///
+    /// Used when the description is held out-of-band (e.g. LNURL-pay
+    /// metadata). The default implementation goes through `create_invoice_ex`,
+    /// so providers without description hash support return an error.
+    async fn create_invoice_with_description_hash(
+        &self,
+        amount_msats: u64,
+        description_hash: &[u8; 32],
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        let params = InvoiceParams {
+            description: DescriptionKind::Hash(*description_hash),
+            ..InvoiceParams::new(amount_msats, "", expiry_seconds)
+        };
+        self.create_invoice_ex(&params).await
+    }
+
+    /// Create multiple invoices concurrently
+    ///
+    /// Failures are reported per item; the outer error is reserved for
+    /// failures that affect the whole batch. The default implementation fans
+    /// out over `create_invoice_ex` with at most `MAX_CONCURRENT_INVOICE_REQUESTS`
+    /// requests in flight.
+    async fn create_invoices_batch(
+        &self,
+        requests: &[InvoiceParams],
+    ) -> Result<Vec<Result<String, LightningError>>, LightningError> {
+        let semaphore = tokio::sync::Semaphore::new(MAX_CONCURRENT_INVOICE_REQUESTS);
+        let futures: Vec<_> = requests
+            .iter()
+            .map(|params| {
+                let semaphore = &semaphore;
+                async move {
+                    let _permit = semaphore.acquire().await
+                        .map_err(|e| LightningError::ProcessorError(format!("Batch semaphore closed: {}", e)))?;
+                    self.create_invoice_ex(params).await
+                }
+            })
+            .collect();
+        
+        Ok(futures::future::join_all(futures).await)
+    }
+
     /// Check if a payment is confirmed
     async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError>;
 
+    /// Cancel an unpaid invoice so it can no longer be paid
+    async fn cancel_invoice(&self, _payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        Err(LightningError::Unsupported(format!(
+            "Invoice cancellation not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// Get the spendable wallet balance
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        Err(LightningError::ProcessorError(format!(
+            "Balance query not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// Withdraw funds to an on-chain address
+    ///
+    /// `amount_sats` of `None` withdraws the full balance; `fee_rate` is in sat/vB.
+    /// Callers are expected to validate the address network first
+    /// (see `validate_onchain_address`).
+    async fn withdraw_onchain(
+        &self,
+        _address: &str,
+        _amount_sats: Option<u64>,
+        _fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        Err(LightningError::ProcessorError(format!(
+            "On-chain withdrawal not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// Subscribe to incoming payment settlements
+    ///
+    /// Lets callers react to settlements as they happen instead of polling
+    /// `verify_payment`. The stream ends when the provider shuts down.
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        Err(LightningError::ProcessorError(format!(
+            "Payment subscriptions not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// Estimate the routing fee for paying `invoice` without sending anything
+    ///
+    /// `amount_msats` is required for amountless invoices and overrides the
+    /// invoice amount otherwise.
+    async fn estimate_fee(&self, _invoice: &str, _amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        Err(LightningError::ProcessorError(format!(
+            "Fee estimation not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// List the node's channels
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        Err(LightningError::ProcessorError(format!(
+            "Channel listing not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// Identify the node the provider is connected to
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        Err(LightningError::ProcessorError(format!(
+            "Node info not supported by {} provider",
+            self.provider_type()
+        )))
+    }
+
+    /// Give the provider the module's storage for state it must keep across restarts
+    ///
+    /// Providers are created before a `NodeAPI` is available; the processor
+    /// calls this once it has one. Providers that keep no state ignore it.
+    async fn attach_storage(&self, _node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        Ok(())
+    }
+
+    /// Check that the provider is reachable and report its health
+    async fn health_check(&self) -> Result<HealthStatus, LightningError>;
+
+    /// Operations supported by this provider
+    fn capabilities(&self) -> ProviderCapabilities;
+
     /// Get the provider type
     fn provider_type(&self) -> ProviderType;
+
+    /// Access the concrete provider, for provider-specific operations
+    fn as_any(&self) -> &dyn std::any::Any;
 }
 
 /// Create a Lightning provider based on type and context
@@ -77,19 +607,126 @@ pub fn create_provider(
     provider_type: ProviderType,
     ctx: &ModuleContext,
 ) -> Result<Box<dyn LightningProvider>, LightningError> {
-    match provider_type {
+    // Failover chains, routes and composites put a breaker on each member instead
+    let wrap = !matches!(provider_type, ProviderType::Failover(_) | ProviderType::Routing | ProviderType::Composite)
+        && config_bool(ctx, "lightning.circuit_breaker.enabled", false);
+    // A replayed session stands in for the configured backend
+    let provider: Box<dyn LightningProvider> = match ctx.get_config("lightning.replay_path").filter(|s| !s.is_empty()) {
+        Some(path) => Box::new(record::ReplayProvider::from_file(&data_dir_path(ctx, path))?),
+        None => create_backend(provider_type, ctx)?,
+    };
+    let provider: Box<dyn LightningProvider> = match ctx.get_config("lightning.record_path").filter(|s| !s.is_empty()) {
+        Some(path) => Box::new(record::RecordingProvider::new(
+            provider,
+            data_dir_path(ctx, path),
+            config_bool(ctx, "lightning.record_redact", false),
+        )),
+        None => provider,
+    };
+    
+    if wrap {
+        return Ok(Box::new(circuit_breaker::CircuitBreakerProvider::new(provider, circuit_breaker_config(ctx))));
+    }
+    
+    Ok(provider)
+}
+
+/// Circuit breaker settings from `lightning.circuit_breaker.*`
+fn circuit_breaker_config(ctx: &ModuleContext) -> circuit_breaker::CircuitBreakerConfig {
+    circuit_breaker::CircuitBreakerConfig {
+        failure_threshold: config_u64(ctx, "lightning.circuit_breaker.failure_threshold", circuit_breaker::DEFAULT_FAILURE_THRESHOLD as u64) as u32,
+        window_seconds: config_u64(ctx, "lightning.circuit_breaker.window_seconds", circuit_breaker::DEFAULT_WINDOW_SECONDS),
+        reset_timeout_seconds: config_u64(ctx, "lightning.circuit_breaker.reset_timeout_seconds", circuit_breaker::DEFAULT_RESET_TIMEOUT_SECONDS),
+    }
+}
+
+/// Create a provider without the optional circuit breaker
+fn create_backend(
+    provider_type: ProviderType,
+    ctx: &ModuleContext,
+) -> Result<Box<dyn LightningProvider>, LightningError> {
+    let provider: Box<dyn LightningProvider> = match provider_type {
         ProviderType::LNBits => {
             let api_url = ctx.get_config_or("lightning.lnbits.api_url", "");
-            let api_key = ctx.get_config_or("lightning.lnbits.api_key", "");
+            // Each request uses the least-privileged key that works; the legacy
+            // `api_key` stands in for whichever of the two isn't configured
+            let config_key = |key: &str| ctx.get_config(key).filter(|s| !s.is_empty()).map(|s| s.to_string());
+            let legacy_key = config_key("lightning.lnbits.api_key");
+            let invoice_key = config_key("lightning.lnbits.invoice_key")
+                .or_else(|| legacy_key.clone())
+                .unwrap_or_default();
+            let admin_key = config_key("lightning.lnbits.admin_key").or(legacy_key);
             let wallet_id = ctx.get_config("lightning.lnbits.wallet_id").map(|s| s.to_string());
+            let socks5_proxy = ctx.get_config("lightning.lnbits.socks5_proxy")
+                .or_else(|| ctx.get_config("lightning.lnbits.proxy"))
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string());
+            
+            // Tor circuits are slow to build; give proxied requests more time unless told otherwise
+            let mut retry_policy = retry::ProviderRetryPolicy::from_config(ctx, "lnbits");
+            let timeout_configured = [
+                "lightning.lnbits.timeout_secs",
+                "lightning.lnbits.request_timeout_secs",
+                "lightning.lnbits.request_timeout_ms",
+                "lightning.request_timeout_secs",
+                "lightning.request_timeout_ms",
+            ]
+                .iter()
+                .any(|key| ctx.get_config(key).is_some());
+            if socks5_proxy.is_some() && !timeout_configured {
+                retry_policy.request_timeout = std::time::Duration::from_secs(lnbits::DEFAULT_PROXY_REQUEST_TIMEOUT_SECS);
+            }
+            let default_connect_timeout_ms = if socks5_proxy.is_some() {
+                lnbits::DEFAULT_PROXY_CONNECT_TIMEOUT_MS
+            } else {
+                lnbits::DEFAULT_CONNECT_TIMEOUT_MS
+            };
             
             let config = lnbits::LNBitsConfig {
                 api_url: api_url.to_string(),
-                api_key: api_key.to_string(),
+                invoice_key,
+                admin_key,
                 wallet_id,
+                connect_timeout_ms: config_u64(ctx, "lightning.lnbits.connect_timeout_ms", default_connect_timeout_ms),
+                retry_policy,
+                tls_ca_cert_path: ctx.get_config("lightning.lnbits.tls_ca_cert")
+                    .or_else(|| ctx.get_config("lightning.lnbits.ca_cert_path"))
+                    .map(std::path::PathBuf::from),
+                tls_accept_invalid_certs: config_bool(ctx, "lightning.lnbits.tls_accept_invalid", false)
+                    || config_bool(ctx, "lightning.lnbits.accept_invalid_certs", false),
+                fee_rate_ppm: config_u64(ctx, "lightning.lnbits.fee_rate_ppm", lnbits::DEFAULT_FEE_RATE_PPM),
+                default_fee_estimate_msats: config_u64(ctx, "lightning.default_fee_estimate_msats", DEFAULT_FEE_ESTIMATE_MSATS),
+                socks5_proxy,
+                websocket: config_bool(ctx, "lightning.lnbits.websocket", false),
+                cross_check_decode: config_bool(ctx, "lightning.lnbits.cross_check_decode", false),
+                pool_max_idle_per_host: config_u64(
+                    ctx,
+                    "lightning.lnbits.pool_max_idle_per_host",
+                    lnbits::DEFAULT_POOL_MAX_IDLE_PER_HOST as u64,
+                ) as usize,
+                pool_idle_timeout_secs: config_u64(
+                    ctx,
+                    "lightning.lnbits.pool_idle_timeout_secs",
+                    lnbits::DEFAULT_POOL_IDLE_TIMEOUT_SECS,
+                ),
+                http2: config_bool(ctx, "lightning.lnbits.http2", false),
+                wallets: match ctx.get_config("lightning.lnbits.wallets").filter(|s| !s.is_empty()) {
+                    Some(json) => serde_json::from_str(json)
+                        .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.lnbits.wallets: {}", e)))?,
+                    None => std::collections::HashMap::new(),
+                },
+                status_cache_ms: config_u64(ctx, "lightning.lnbits.status_cache_ms", lnbits::DEFAULT_STATUS_CACHE_MS),
+                status_cache_capacity: config_u64(
+                    ctx,
+                    "lightning.lnbits.status_cache_capacity",
+                    lnbits::DEFAULT_STATUS_CACHE_CAPACITY as u64,
+                ) as usize,
+                webhook_url: ctx.get_config("lightning.lnbits.webhook_url")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string()),
             };
             
-            Ok(Box::new(lnbits::LNBitsProvider::new(config)?))
+            Box::new(lnbits::LNBitsProvider::new(config)?)
         }
         ProviderType::LDK => {
             let data_dir = ctx.data_dir.clone();
@@ -102,13 +739,404 @@ pub fn create_provider(
                 data_dir: std::path::PathBuf::from(data_dir),
                 network: network.to_string(),
                 node_private_key,
+                key_passphrase: ldk_key_passphrase(ctx)?,
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "ldk"),
+                default_fee_estimate_msats: config_u64(ctx, "lightning.default_fee_estimate_msats", DEFAULT_FEE_ESTIMATE_MSATS),
+                auto_backup: config_bool(ctx, "lightning.ldk.auto_backup", false),
+                min_final_cltv_expiry: ctx.get_config("lightning.ldk.min_final_cltv_expiry")
+                    .and_then(|s| s.parse::<u32>().ok()),
+                chain_source: ldk_chain_source(ctx)?,
+                chain_sync_interval_secs: config_u64(
+                    ctx,
+                    "lightning.ldk.chain_sync_interval_secs",
+                    ldk::DEFAULT_CHAIN_SYNC_INTERVAL_SECS,
+                ),
+                rgs_url: ctx.get_config("lightning.ldk.rgs_url")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string()),
+                rgs_sync_interval_secs: config_u64(
+                    ctx,
+                    "lightning.ldk.rgs_sync_interval_secs",
+                    gossip::DEFAULT_RGS_SYNC_INTERVAL_SECS,
+                ),
+                cleanup_interval_secs: config_u64(
+                    ctx,
+                    "lightning.ldk.cleanup_interval_secs",
+                    ldk::DEFAULT_CLEANUP_INTERVAL_SECS,
+                ),
+                retention: ldk::RetentionPolicy {
+                    retention_secs: config_u64(ctx, "lightning.ldk.retention_secs", ldk::DEFAULT_RETENTION_SECS),
+                    confirmed_retention_secs: config_u64(
+                        ctx,
+                        "lightning.ldk.confirmed_retention_secs",
+                        ldk::DEFAULT_CONFIRMED_RETENTION_SECS,
+                    ),
+                },
+                channel_policy: channel_policy::ChannelPolicy::from_config(ctx)?,
             };
             
-            Ok(Box::new(ldk::LDKProvider::new(config)?))
+            Box::new(ldk::LDKProvider::new(config)?)
         }
         ProviderType::Stub => {
-            Ok(Box::new(stub::StubProvider::new()))
+            let config = stub::StubConfig {
+                failure_rate: ctx.get_config_or("lightning.stub.failure_rate", "").parse::<f64>().unwrap_or(0.0),
+                latency_ms: config_u64(ctx, "lightning.stub.latency_ms", 0),
+                fixed_amount_msats: config_u64(ctx, "lightning.stub.amount_msats", stub::STUB_AMOUNT_MSATS),
+                ..Default::default()
+            };
+            let provider = stub::StubProvider::new_with_config(config);
+            match ctx.get_config("lightning.stub.scenario").filter(|s| !s.is_empty()) {
+                Some(scenario) => Box::new(provider.scenario(stub::StubScenario::from_config_value(scenario)?)),
+                None => Box::new(provider),
+            }
+        }
+        ProviderType::CLN => {
+            let config = cln::CLNConfig {
+                url: ctx.get_config_or("lightning.cln.url", "").to_string(),
+                rune: ctx.get_config_or("lightning.cln.rune", "").to_string(),
+                ca_cert_path: ctx.get_config("lightning.cln.ca_cert").map(std::path::PathBuf::from),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "cln"),
+                socks5_proxy: ctx.get_config("lightning.cln.socks5_proxy")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string()),
+            };
+            
+            Box::new(cln::CLNProvider::new(config)?)
+        }
+        ProviderType::Eclair => {
+            let config = eclair::EclairConfig {
+                url: ctx.get_config_or("lightning.eclair.url", "").to_string(),
+                password: ctx.get_config_or("lightning.eclair.password", "").to_string(),
+                ca_cert_path: ctx.get_config("lightning.eclair.ca_cert").map(std::path::PathBuf::from),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "eclair"),
+            };
+            
+            Box::new(eclair::EclairProvider::new(config)?)
+        }
+        ProviderType::Phoenixd => {
+            let config = phoenixd::PhoenixdConfig {
+                url: ctx.get_config_or("lightning.phoenixd.url", "").to_string(),
+                password: ctx.get_config_or("lightning.phoenixd.password", "").to_string(),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "phoenixd"),
+            };
+            
+            Box::new(phoenixd::PhoenixdProvider::new(config)?)
+        }
+        ProviderType::LndHub => {
+            let config = lndhub::LndHubConfig {
+                account: lndhub::LndHubUri::parse(ctx.get_config_or("lightning.lndhub.uri", ""))?,
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "lndhub"),
+            };
+            
+            Box::new(lndhub::LndHubProvider::new(config)?)
+        }
+        ProviderType::Blink => {
+            let config = blink::BlinkConfig {
+                url: ctx.get_config_or("lightning.blink.url", blink::DEFAULT_BLINK_URL).to_string(),
+                api_key: ctx.get_config_or("lightning.blink.api_key", "").to_string(),
+                wallet_id: ctx.get_config("lightning.blink.wallet_id")
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string()),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "blink"),
+            };
+            
+            Box::new(blink::BlinkProvider::new(config)?)
+        }
+        ProviderType::OpenNode => {
+            let env = ctx.get_config_or("lightning.opennode.env", "dev").parse::<opennode::OpenNodeEnv>()?;
+            let config = opennode::OpenNodeConfig {
+                url: ctx.get_config_or("lightning.opennode.url", env.url()).to_string(),
+                api_key: ctx.get_config_or("lightning.opennode.api_key", "").to_string(),
+                auto_settle: config_bool(ctx, "lightning.opennode.auto_settle", false),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "opennode"),
+            };
+            
+            Box::new(opennode::OpenNodeProvider::new(config)?)
+        }
+        ProviderType::Strike => {
+            let config = strike::StrikeConfig {
+                url: ctx.get_config_or("lightning.strike.url", strike::DEFAULT_STRIKE_URL).to_string(),
+                api_key: ctx.get_config_or("lightning.strike.api_key", "").to_string(),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "strike"),
+            };
+            
+            Box::new(strike::StrikeProvider::new(config)?)
+        }
+        ProviderType::WatchOnly => {
+            let lnbits_url = ctx.get_config_or("lightning.watch_only.lnbits_url", "");
+            let lnbits = if lnbits_url.is_empty() {
+                None
+            } else {
+                Some(lnbits::LNBitsConfig {
+                    api_url: lnbits_url.to_string(),
+                    invoice_key: ctx.get_config_or("lightning.watch_only.lnbits_invoice_key", "").to_string(),
+                    retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "watch_only"),
+                    ..Default::default()
+                })
+            };
+            let config = watch_only::WatchOnlyConfig {
+                lnbits,
+                verify_url_index_path: Some(data_dir_path(ctx, watch_only::WATCH_ONLY_VERIFY_URL_FILE)),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "watch_only"),
+            };
+            
+            Box::new(watch_only::WatchOnlyProvider::new(config)?)
+        }
+        #[cfg(feature = "lnd-grpc")]
+        ProviderType::LND => {
+            let config = lnd::grpc::LndGrpcConfig {
+                url: ctx.get_config_or("lightning.lnd.url", "").to_string(),
+                macaroon_path: std::path::PathBuf::from(ctx.get_config_or("lightning.lnd.macaroon_path", "")),
+                tls_cert_path: ctx.get_config("lightning.lnd.tls_cert_path").map(std::path::PathBuf::from),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "lnd"),
+            };
+            
+            Box::new(lnd::grpc::LndGrpcProvider::new(config)?)
+        }
+        #[cfg(not(feature = "lnd-grpc"))]
+        ProviderType::LND => {
+            return Err(LightningError::ConfigError(
+                "LND provider requires building with the `lnd-grpc` feature".to_string(),
+            ));
+        }
+        #[cfg(feature = "greenlight")]
+        ProviderType::Greenlight => {
+            let config = greenlight::GreenlightConfig {
+                data_dir: std::path::PathBuf::from(&ctx.data_dir),
+                network: ctx.get_config_or("lightning.greenlight.network", "testnet").to_string(),
+                seed_passphrase: ctx.get_config_or("lightning.greenlight.seed_passphrase", "").to_string(),
+                invite_code: ctx.get_config("lightning.greenlight.invite_code").map(|s| s.to_string()),
+                developer_cert_path: ctx.get_config("lightning.greenlight.developer_cert").map(std::path::PathBuf::from),
+                developer_key_path: ctx.get_config("lightning.greenlight.developer_key").map(std::path::PathBuf::from),
+                retry_policy: retry::ProviderRetryPolicy::from_config(ctx, "greenlight"),
+            };
+            
+            Box::new(greenlight::GreenlightProvider::new(config)?)
+        }
+        #[cfg(not(feature = "greenlight"))]
+        ProviderType::Greenlight => {
+            return Err(LightningError::ConfigError(
+                "Greenlight provider requires building with the `greenlight` feature".to_string(),
+            ));
+        }
+        ProviderType::Failover(chain) => {
+            // "failover" on its own: primary and secondary come from [lightning.failover]
+            let chain = if chain.is_empty() {
+                ["primary", "secondary"]
+                    .iter()
+                    .map(|role| {
+                        let key = format!("lightning.failover.{}", role);
+                        ctx.get_config_or(&key, "")
+                            .parse::<ProviderType>()
+                            .map_err(|e| LightningError::ConfigError(format!("Invalid {}: {}", key, e)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            } else {
+                chain
+            };
+            if chain.iter().any(|member| matches!(member, ProviderType::Failover(_))) {
+                return Err(LightningError::ConfigError("Failover chains can't be nested".to_string()));
+            }
+            // Invoices would be created through it whenever it is the reachable member
+            if chain.contains(&ProviderType::WatchOnly) {
+                return Err(LightningError::ConfigError("Watch-only provider can't be part of a failover chain".to_string()));
+            }
+            // It would verify every payment whenever the real backends are down
+            if chain.contains(&ProviderType::Stub) && !STUB_MEMBERS_ALLOWED {
+                return Err(LightningError::ConfigError("Stub provider can't be part of a failover chain".to_string()));
+            }
+            
+            // Each provider gets its own circuit breaker so a dead backend is
+            // skipped instead of being retried on every call
+            let providers = chain
+                .into_iter()
+                .map(|provider_type| create_backend(provider_type, ctx))
+                .collect::<Result<Vec<_>, _>>()?;
+            let config = circuit_breaker::CircuitBreakerConfig {
+                reset_timeout_seconds: config_u64(ctx, "lightning.failover.cooldown_seconds", circuit_breaker::DEFAULT_RESET_TIMEOUT_SECONDS),
+                ..circuit_breaker_config(ctx)
+            };
+            Box::new(failover::FailoverProvider::with_circuit_breakers(providers, config)?)
+        }
+        ProviderType::Routing => {
+            let rules = routing::RoutingRule::parse_rules(ctx.get_config_or("lightning.routing.rules", ""))?;
+            let routes = rules
+                .into_iter()
+                .map(|rule| {
+                    let provider_type = rule.provider.parse::<ProviderType>()
+                        .map_err(|e| LightningError::ConfigError(format!("Invalid routing rule provider: {}", e)))?;
+                    if provider_type == ProviderType::Routing {
+                        return Err(LightningError::ConfigError("Routing rules can't route to another routing provider".to_string()));
+                    }
+                    if provider_type == ProviderType::WatchOnly {
+                        return Err(LightningError::ConfigError("Routing rules can't route invoice creation to the watch-only provider".to_string()));
+                    }
+                    if provider_type == ProviderType::Stub && !STUB_MEMBERS_ALLOWED {
+                        return Err(LightningError::ConfigError("Routing rules can't route to the stub provider".to_string()));
+                    }
+                    Ok(routing::Route {
+                        rule,
+                        provider: create_provider(provider_type, ctx)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, LightningError>>()?;
+            Box::new(routing::RoutingProvider::new(routes)?)
+        }
+        ProviderType::Composite => {
+            let strategy = match ctx.get_config_or("lightning.composite.strategy", "threshold").parse::<composite::RoutingStrategy>()? {
+                composite::RoutingStrategy::Threshold { .. } => composite::RoutingStrategy::Threshold {
+                    lnbits_max_msats: config_u64(ctx, "lightning.composite.lnbits_max_msats", composite::DEFAULT_LNBITS_MAX_MSATS),
+                },
+                strategy => strategy,
+            };
+            // Not parsed as a whole: "lnbits,ldk" would be a failover chain
+            let providers = ctx.get_config_or("lightning.composite.providers", "lnbits,ldk")
+                .split(',')
+                .map(|name| {
+                    let provider_type = name.parse::<ProviderType>()
+                        .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.composite.providers: {}", e)))?;
+                    if matches!(provider_type, ProviderType::Failover(_) | ProviderType::Routing | ProviderType::Composite) {
+                        return Err(LightningError::ConfigError(format!("Composite provider can't contain a {} provider", provider_type)));
+                    }
+                    if provider_type == ProviderType::WatchOnly {
+                        return Err(LightningError::ConfigError("Composite provider can't create invoices through the watch-only provider".to_string()));
+                    }
+                    if provider_type == ProviderType::Stub && !STUB_MEMBERS_ALLOWED {
+                        return Err(LightningError::ConfigError("Composite provider can't contain the stub provider".to_string()));
+                    }
+                    create_provider(provider_type, ctx)
+                })
+                .collect::<Result<Vec<_>, LightningError>>()?;
+            Box::new(composite::CompositeProvider::new(providers, strategy)?)
+        }
+    };
+    
+    Ok(provider)
+}
+
+/// Node key passphrase from `lightning.ldk.key_passphrase` (or its alias
+/// `key_encryption_password`), or read from `lightning.ldk.key_passphrase_file`
+/// (trailing newline ignored)
+fn ldk_key_passphrase(ctx: &ModuleContext) -> Result<Option<String>, LightningError> {
+    if let Some(passphrase) = ctx.get_config("lightning.ldk.key_passphrase")
+        .or_else(|| ctx.get_config("lightning.ldk.key_encryption_password"))
+        .filter(|s| !s.is_empty())
+    {
+        return Ok(Some(passphrase.to_string()));
+    }
+    match ctx.get_config("lightning.ldk.key_passphrase_file").filter(|s| !s.is_empty()) {
+        Some(path) => {
+            let path = data_dir_path(ctx, path);
+            let passphrase = std::fs::read_to_string(&path)
+                .map_err(|e| LightningError::ConfigError(format!("Failed to read {}: {}", path.display(), e)))?;
+            let passphrase = passphrase.trim_end_matches(['\r', '\n']);
+            if passphrase.is_empty() {
+                return Err(LightningError::ConfigError(format!("{} is empty", path.display())));
+            }
+            Ok(Some(passphrase.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// LDK chain source from `lightning.ldk.chain_source` ("bitcoind"; unset for none)
+fn ldk_chain_source(ctx: &ModuleContext) -> Result<Option<chain::ChainSourceConfig>, LightningError> {
+    let config_str = |key: &str| ctx.get_config(key).filter(|s| !s.is_empty()).map(|s| s.to_string());
+    match ctx.get_config_or("lightning.ldk.chain_source", "").trim().to_lowercase().as_str() {
+        "" => Ok(None),
+        "bitcoind" => {
+            let url = config_str("lightning.ldk.bitcoind.url").ok_or_else(|| {
+                LightningError::ConfigError("lightning.ldk.chain_source = \"bitcoind\" requires lightning.ldk.bitcoind.url".to_string())
+            })?;
+            Ok(Some(chain::ChainSourceConfig::Bitcoind(chain::BitcoindConfig {
+                url,
+                user: config_str("lightning.ldk.bitcoind.user"),
+                pass: config_str("lightning.ldk.bitcoind.pass"),
+                cookie_file: config_str("lightning.ldk.bitcoind.cookie_file").map(std::path::PathBuf::from),
+            })))
         }
+        other => Err(LightningError::ConfigError(format!("Unknown lightning.ldk.chain_source: {}", other))),
     }
 }
 
+/// Resolve a configured path relative to the module data directory
+fn data_dir_path(ctx: &ModuleContext, path: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from(&ctx.data_dir).join(path)
+}
+
+/// Read a numeric config value, falling back to `default` if unset or invalid
+pub(crate) fn config_u64(ctx: &ModuleContext, key: &str, default: u64) -> u64 {
+    ctx.get_config(key)
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+/// Read a boolean config value, falling back to `default` if unset or invalid
+pub(crate) fn config_bool(ctx: &ModuleContext, key: &str, default: bool) -> bool {
+    ctx.get_config(key)
+        .and_then(|s| s.trim().parse::<bool>().ok())
+        .unwrap_or(default)
+}
+
+/// Load a PEM root certificate to trust in addition to the system roots
+pub(crate) fn load_ca_certificate(path: &std::path::Path) -> Result<reqwest::Certificate, LightningError> {
+    let pem = std::fs::read(path).map_err(|e| {
+        LightningError::ConfigError(format!("Failed to read TLS CA cert {}: {}", path.display(), e))
+    })?;
+    reqwest::Certificate::from_pem(&pem).map_err(|e| {
+        LightningError::ConfigError(format!("Invalid TLS CA cert {}: {}", path.display(), e))
+    })
+}
+
+/// Parse a SOCKS5 proxy URL, e.g. `socks5h://127.0.0.1:9050` for Tor
+///
+/// `socks5h` resolves host names through the proxy, which `.onion`
+/// addresses need; `socks5` resolves them locally.
+pub(crate) fn socks5_proxy(url: &str) -> Result<reqwest::Proxy, LightningError> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| LightningError::ConfigError(format!("Invalid SOCKS5 proxy URL {}: {}", url, e)))?;
+    if !matches!(parsed.scheme(), "socks5" | "socks5h") {
+        return Err(LightningError::ConfigError(format!(
+            "Invalid SOCKS5 proxy URL {}: scheme must be socks5h:// or socks5://",
+            url
+        )));
+    }
+    if parsed.host_str().unwrap_or("").is_empty() || parsed.port().is_none() {
+        return Err(LightningError::ConfigError(format!(
+            "Invalid SOCKS5 proxy URL {}: expected host:port, e.g. socks5h://127.0.0.1:9050",
+            url
+        )));
+    }
+    reqwest::Proxy::all(url)
+        .map_err(|e| LightningError::ConfigError(format!("Invalid SOCKS5 proxy URL {}: {}", url, e)))
+}
+
+/// Amount being paid: the explicit amount, else the invoice's own amount
+pub(crate) fn payment_amount_msats(invoice: &str, amount_msats: Option<u64>) -> Result<u64, LightningError> {
+    if let Some(amount) = amount_msats {
+        return Ok(amount);
+    }
+    match crate::invoice::InvoiceParser::parse(invoice)?.amount_msats {
+        0 => Err(LightningError::InvoiceError("Amountless invoice requires amount_msats".to_string())),
+        amount => Ok(amount),
+    }
+}
+
+/// Parse a network name as used in config ("mainnet", "testnet", "regtest", "signet")
+pub fn parse_network(name: &str) -> Option<Network> {
+    match name.to_lowercase().as_str() {
+        "mainnet" | "bitcoin" => Some(Network::Bitcoin),
+        "testnet" => Some(Network::Testnet),
+        "regtest" => Some(Network::Regtest),
+        "signet" => Some(Network::Signet),
+        _ => None,
+    }
+}
+
+/// Validate that an on-chain address is well-formed and belongs to `network`
+pub fn validate_onchain_address(address: &str, network: Network) -> Result<Address, LightningError> {
+    Address::from_str(address)
+        .map_err(|e| LightningError::ConfigError(format!("Invalid address {}: {}", address, e)))?
+        .require_network(network)
+        .map_err(|e| LightningError::ConfigError(format!("Address {} is not valid for {:?}: {}", address, network, e)))
+}
@@ -0,0 +1,405 @@
+//! OpenNode provider implementation
+//!
+//! Talks to OpenNode's REST API, authenticated with an `Authorization`
+//! header carrying the API key. Invoices are OpenNode charges
+//! (`POST /v1/charges`), which OpenNode can settle to the merchant's fiat
+//! balance. Amounts are in sats, not msats.
+//!
+//! OpenNode looks charges up by its own id, not by payment hash, so the
+//! charge id of every invoice created here is recorded in a payment hash
+//! index in module storage. Charges created outside the module
+//! aren't in the index and can't be verified.
+
+use crate::provider::payment_index::PaymentIndex;
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind,
+};
+use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
+use async_trait::async_trait;
+use blvm_node::module::traits::NodeAPI;
+use reqwest::{Client, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Storage tree holding the payment hash -> charge id index
+pub const OPENNODE_CHARGES_TREE: &str = "opennode_charges";
+
+/// OpenNode environment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OpenNodeEnv {
+    /// Testnet sandbox
+    #[default]
+    Dev,
+    Live,
+}
+
+impl OpenNodeEnv {
+    /// API base URL for the environment
+    pub fn url(&self) -> &'static str {
+        match self {
+            OpenNodeEnv::Dev => "https://dev-api.opennode.com",
+            OpenNodeEnv::Live => "https://api.opennode.com",
+        }
+    }
+}
+
+impl FromStr for OpenNodeEnv {
+    type Err = LightningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dev" => Ok(OpenNodeEnv::Dev),
+            "live" => Ok(OpenNodeEnv::Live),
+            _ => Err(LightningError::ConfigError(format!(
+                "Invalid lightning.opennode.env: {} (expected dev or live)",
+                s
+            ))),
+        }
+    }
+}
+
+/// OpenNode provider configuration
+#[derive(Debug, Clone, Default)]
+pub struct OpenNodeConfig {
+    /// API base URL (see `OpenNodeEnv::url`)
+    pub url: String,
+    /// API key with invoice permission
+    pub api_key: String,
+    /// Convert received payments to the merchant's fiat balance
+    pub auto_settle: bool,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+/// OpenNode wraps every response body in `data`
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    data: T,
+}
+
+/// Lightning part of a charge
+#[derive(Debug, Deserialize)]
+struct LightningInvoice {
+    payreq: String,
+    /// Unix time in seconds
+    #[serde(default)]
+    settled_at: Option<u64>,
+}
+
+/// Charge from `POST /v1/charges` or `GET /v1/charge/{id}`
+#[derive(Debug, Deserialize)]
+struct Charge {
+    id: String,
+    /// unpaid, processing, paid, underpaid, refunded or expired
+    status: String,
+    /// Amount in sats
+    #[serde(default)]
+    amount: u64,
+    #[serde(default)]
+    fiat_value: Option<f64>,
+    #[serde(default)]
+    lightning_invoice: Option<LightningInvoice>,
+}
+
+/// OpenNode provider implementation
+pub struct OpenNodeProvider {
+    config: OpenNodeConfig,
+    http_client: Client,
+    /// Payment hash -> charge id of invoices created through this provider
+    charges: PaymentIndex,
+}
+
+impl OpenNodeProvider {
+    /// Create a new OpenNode provider
+    ///
+    /// The charge index is kept in memory until module storage is attached.
+    pub fn new(config: OpenNodeConfig) -> Result<Self, LightningError> {
+        if config.api_key.is_empty() {
+            return Err(LightningError::ConfigError("lightning.opennode.api_key is required".to_string()));
+        }
+        let http_client = Client::builder()
+            .timeout(config.retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            config,
+            http_client,
+            charges: PaymentIndex::in_tree("OpenNode charge index", OPENNODE_CHARGES_TREE),
+        })
+    }
+
+    /// Charge id recorded for a payment hash, if the invoice was created through this provider
+    pub fn charge_id(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.charges.get(payment_hash)
+    }
+
+    /// Make an API request and return its `data`
+    ///
+    /// Returns `Ok(None)` for 404 responses (unknown charge).
+    /// Idempotent requests are retried according to the retry policy.
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+        idempotent: bool,
+    ) -> Result<Option<T>, LightningError> {
+        let url = format!("{}{}", self.config.url.trim_end_matches('/'), path);
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let mut request = self
+                .http_client
+                .request(method.clone(), &url)
+                .header("Authorization", &self.config.api_key);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if idempotent && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("OpenNode {} failed (attempt {}/{}): {}", path, attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("OpenNode request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && idempotent && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("OpenNode {} returned {} (attempt {}/{})", path, status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            match status {
+                StatusCode::NOT_FOUND => return Ok(None),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    return Err(LightningError::ConfigError(format!("OpenNode rejected the API key ({})", status)));
+                }
+                StatusCode::BAD_REQUEST => {
+                    #[derive(Deserialize)]
+                    struct ErrorResponse {
+                        message: String,
+                    }
+                    let text = response.text().await.unwrap_or_default();
+                    let message = serde_json::from_str::<ErrorResponse>(&text)
+                        .map(|e| e.message)
+                        .unwrap_or(text);
+                    return Err(LightningError::InvoiceError(format!("OpenNode rejected {}: {}", path, message)));
+                }
+                _ => {}
+            }
+
+            if !status.is_success() {
+                return Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                });
+            }
+
+            return response
+                .json::<Envelope<T>>()
+                .await
+                .map(|envelope| Some(envelope.data))
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse OpenNode response: {}", e)));
+        }
+    }
+
+    /// Look up the charge for a payment hash
+    ///
+    /// Returns `Ok(None)` if the charge id isn't recorded or OpenNode doesn't know it.
+    async fn charge(&self, payment_hash: &[u8; 32]) -> Result<Option<Charge>, LightningError> {
+        let charge_id = match self.charge_id(payment_hash) {
+            Some(charge_id) => charge_id,
+            None => return Ok(None),
+        };
+        self.request(Method::GET, &format!("/v1/charge/{}", charge_id), None, true).await
+    }
+}
+
+#[async_trait]
+impl LightningProvider for OpenNodeProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "opennode", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via OpenNode: payment_id={}", payment_id);
+
+        let charge_id = match self.charge_id(payment_hash) {
+            Some(charge_id) => charge_id,
+            None => {
+                warn!("No OpenNode charge recorded for payment hash {}", hex::encode(payment_hash));
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: json!({
+                        "provider": "opennode",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "charge_not_found",
+                    }),
+                });
+            }
+        };
+
+        let charge = match self.charge(payment_hash).await? {
+            Some(charge) => charge,
+            None => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: json!({
+                        "provider": "opennode",
+                        "payment_hash": hex::encode(payment_hash),
+                        "charge_id": charge_id,
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        let paid = charge.status == "paid";
+        debug!("OpenNode charge {}: {}", charge.id, charge.status);
+
+        Ok(PaymentVerificationResult {
+            verified: paid,
+            // OpenNode reports sats
+            amount_msats: Some(charge.amount.saturating_mul(1000)).filter(|_| paid),
+            timestamp: charge.lightning_invoice.as_ref().and_then(|ln| ln.settled_at),
+            metadata: json!({
+                "provider": "opennode",
+                "payment_hash": hex::encode(payment_hash),
+                "charge_id": charge.id,
+                "status": charge.status,
+                "fiat_value": charge.fiat_value,
+                "expired": charge.status == "expired",
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "opennode", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let description = match &params.description {
+            DescriptionKind::Direct(description) => description.clone(),
+            DescriptionKind::Hash(_) => {
+                return Err(LightningError::InvoiceError(
+                    "OpenNode does not support description hashes".to_string(),
+                ));
+            }
+        };
+        // OpenNode only takes whole sats; round up so the payer never pays less than requested
+        let amount_sats = params.amount_msats.div_ceil(1000);
+        debug!("Creating charge via OpenNode: amount={} sats", amount_sats);
+
+        let mut body = json!({
+            "amount": amount_sats,
+            "description": description,
+            // OpenNode's ttl is in minutes
+            "ttl": params.expiry_seconds.div_ceil(60).max(1),
+            "auto_settle": self.config.auto_settle,
+        });
+        if let Some(label) = &params.label {
+            body["order_id"] = json!(label);
+        }
+
+        // Charge creation has no idempotency key, so it is never retried
+        let charge: Option<Charge> = self.request(Method::POST, "/v1/charges", Some(&body), false).await?;
+        let charge = charge
+            .ok_or_else(|| LightningError::ProcessorError("OpenNode charges endpoint not found".to_string()))?;
+        let invoice = charge
+            .lightning_invoice
+            .map(|ln| ln.payreq)
+            .ok_or_else(|| LightningError::ProcessorError(format!("OpenNode charge {} has no lightning invoice", charge.id)))?;
+
+        // Without the charge id the payment can't be verified later, so fail the invoice
+        let payment_hash = InvoiceParser::parse(&invoice)?.payment_hash_hex();
+        self.charges.insert(payment_hash.clone(), charge.id.clone()).await?;
+
+        debug!("OpenNode charge created: id={}, payment_hash={}", charge.id, payment_hash);
+        Ok(invoice)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        Ok(self
+            .charge(payment_hash)
+            .await?
+            .map(|charge| charge.status == "paid")
+            .unwrap_or(false))
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        self.charges.attach(node_api).await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // Needs a key with read permission; invoice-only keys report unreachable
+        let started = std::time::Instant::now();
+        let result = self.request::<Value>(Method::GET, "/v1/account/balance", None, true).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let reachable = matches!(result, Ok(Some(_)));
+        if let Err(e) = &result {
+            warn!("OpenNode health check failed: {}", e);
+        }
+        Ok(HealthStatus {
+            reachable,
+            latency_ms,
+            block_height: None,
+            synced_to_chain: None,
+            version: None,
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::OpenNode
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,159 @@
+//! Persisted payment hash index
+//!
+//! Some providers need to remember something per invoice that the backend
+//! can't look up by payment hash: which provider created it (routing), or
+//! the backend's own id for it (OpenNode charges). `PaymentIndex` maps a
+//! payment hash to such a value so it survives restarts.
+//!
+//! An index opened with `in_tree` lives in a module storage tree. Providers
+//! are created before the processor has a `NodeAPI` to give them, so the
+//! index is held in memory until `attach` loads the tree (see
+//! `LightningProvider::attach_storage`); entries recorded before that are
+//! written to the tree then. An index opened with `open` is kept in a JSON
+//! file in the module data directory instead.
+
+use crate::error::LightningError;
+use blvm_node::module::traits::NodeAPI;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Payment hash (hex) -> value map, persisted to a storage tree or a JSON file
+pub struct PaymentIndex {
+    /// Used in error messages, e.g. "routing index"
+    name: String,
+    entries: Mutex<HashMap<String, String>>,
+    /// Where the index is persisted as a file (kept in memory only if unset)
+    path: Option<PathBuf>,
+    /// Module storage tree the index is persisted in
+    tree: Option<String>,
+    /// Node API and tree id, once attached
+    storage: Mutex<Option<(Arc<dyn NodeAPI>, String)>>,
+}
+
+impl PaymentIndex {
+    /// Open an index, loading `path` if it exists
+    pub fn open(name: &str, path: Option<PathBuf>) -> Result<Self, LightningError> {
+        let entries = match &path {
+            Some(path) if path.exists() => load(name, path)?,
+            _ => HashMap::new(),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            entries: Mutex::new(entries),
+            path,
+            tree: None,
+            storage: Mutex::new(None),
+        })
+    }
+
+    /// Create an index persisted in the module storage tree `tree` once attached
+    pub fn in_tree(name: &str, tree: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            entries: Mutex::new(HashMap::new()),
+            path: None,
+            tree: Some(tree.to_string()),
+            storage: Mutex::new(None),
+        }
+    }
+
+    /// Load the index from its storage tree and persist later entries there
+    ///
+    /// Entries recorded before attaching are written to the tree. Does
+    /// nothing for an index that isn't kept in a tree.
+    pub async fn attach(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        let tree = match &self.tree {
+            Some(tree) => tree.clone(),
+            None => return Ok(()),
+        };
+        let tree_id = node_api.storage_open_tree(tree).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to open {} tree: {}", self.name, e)))?;
+        let stored = node_api.storage_iter(tree_id.clone()).await
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to read {}: {}", self.name, e)))?;
+
+        let unsaved: Vec<(String, String)> = self.entries.lock().unwrap().drain().collect();
+        for (key, value) in &unsaved {
+            node_api.storage_insert(tree_id.clone(), key.as_bytes().to_vec(), value.as_bytes().to_vec()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to store {} entry: {}", self.name, e)))?;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        for (key, value) in stored {
+            entries.insert(String::from_utf8_lossy(&key).into_owned(), String::from_utf8_lossy(&value).into_owned());
+        }
+        entries.extend(unsaved);
+        *self.storage.lock().unwrap() = Some((node_api, tree_id));
+        Ok(())
+    }
+
+    /// Value recorded for a payment hash
+    pub fn get(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.get_key(&hex::encode(payment_hash))
+    }
+
+    /// Value recorded under `key`, for indexes keyed by something other than a payment hash
+    pub fn get_key(&self, key: &str) -> Option<String> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    /// Record a value for a payment hash (hex), or another key, and persist it
+    ///
+    /// The entry is kept in memory even if persisting it fails.
+    pub async fn insert(&self, key: String, value: String) -> Result<(), LightningError> {
+        let storage = self.storage.lock().unwrap().clone();
+        if let Some((node_api, tree_id)) = storage {
+            self.entries.lock().unwrap().insert(key.clone(), value.clone());
+            return node_api.storage_insert(tree_id, key.into_bytes(), value.into_bytes()).await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to store {} entry: {}", self.name, e)));
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, value);
+        match &self.path {
+            Some(path) => save(&self.name, path, &entries),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of recorded payments
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether no payments are recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+}
+
+impl std::fmt::Debug for PaymentIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PaymentIndex")
+            .field("name", &self.name)
+            .field("entries", &self.len())
+            .field("path", &self.path)
+            .field("tree", &self.tree)
+            .finish()
+    }
+}
+
+fn load(name: &str, path: &Path) -> Result<HashMap<String, String>, LightningError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| LightningError::ConfigError(format!("Failed to read {} {}: {}", name, path.display(), e)))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| LightningError::ConfigError(format!("Corrupt {} {}: {}", name, path.display(), e)))
+}
+
+fn save(name: &str, path: &Path, entries: &HashMap<String, String>) -> Result<(), LightningError> {
+    let contents = serde_json::to_vec(entries)
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize {}: {}", name, e)))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, contents)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to write {} {}: {}", name, path.display(), e)))
+}
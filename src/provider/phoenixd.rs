@@ -0,0 +1,309 @@
+//! phoenixd provider implementation
+//!
+//! Talks to phoenixd's HTTP API, authenticated with HTTP basic auth (empty
+//! user, `http-password` from phoenix.conf). Amounts are in sats, not msats.
+
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind, MAX_DESCRIPTION_BYTES,
+};
+use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
+use async_trait::async_trait;
+use bitcoin::hashes::{sha256, Hash};
+use reqwest::{Client, Method, StatusCode};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+/// phoenixd provider configuration
+#[derive(Debug, Clone, Default)]
+pub struct PhoenixdConfig {
+    /// phoenixd URL (e.g., "http://127.0.0.1:9740")
+    pub url: String,
+    /// `http-password` from phoenix.conf
+    pub password: String,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+/// Incoming payment from `GET /payments/incoming/{paymentHash}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IncomingPayment {
+    #[serde(default)]
+    invoice: Option<String>,
+    is_paid: bool,
+    #[serde(default)]
+    received_sat: u64,
+    #[serde(default)]
+    fees: u64,
+    #[serde(default)]
+    preimage: Option<String>,
+    #[serde(default)]
+    external_id: Option<String>,
+    /// Unix time in milliseconds
+    #[serde(default)]
+    completed_at: Option<u64>,
+}
+
+impl IncomingPayment {
+    /// Whether the invoice expired unpaid (unknown if the invoice can't be parsed)
+    fn is_expired(&self) -> Option<bool> {
+        if self.is_paid {
+            return Some(false);
+        }
+        let invoice = self.invoice.as_deref()?;
+        InvoiceParser::parse(invoice).ok().map(|data| data.is_expired())
+    }
+}
+
+/// phoenixd provider implementation
+pub struct PhoenixdProvider {
+    config: PhoenixdConfig,
+    http_client: Client,
+}
+
+impl PhoenixdProvider {
+    /// Create a new phoenixd provider
+    pub fn new(config: PhoenixdConfig) -> Result<Self, LightningError> {
+        let http_client = Client::builder()
+            .timeout(config.retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self { config, http_client })
+    }
+
+    /// Make an API request
+    ///
+    /// Returns `Ok(None)` for 404 responses (unknown payment hash).
+    /// Idempotent requests are retried according to the retry policy.
+    async fn request<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: Method,
+        path: &str,
+        form: Option<&[(&str, String)]>,
+        idempotent: bool,
+    ) -> Result<Option<T>, LightningError> {
+        let url = format!("{}{}", self.config.url.trim_end_matches('/'), path);
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let mut request = self
+                .http_client
+                .request(method.clone(), &url)
+                .basic_auth("", Some(&self.config.password));
+            if let Some(form) = form {
+                request = request.form(form);
+            }
+
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if idempotent && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("phoenixd {} failed (attempt {}/{}): {}", path, attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("phoenixd request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && idempotent && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("phoenixd {} returned {} (attempt {}/{})", path, status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            if status == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+
+            if !status.is_success() {
+                let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                return Err(LightningError::ProcessorError(format!("phoenixd {} error: {} - {}", path, status, error_text)));
+            }
+
+            return response
+                .json::<T>()
+                .await
+                .map(Some)
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse phoenixd response: {}", e)));
+        }
+    }
+
+    /// Look up an incoming payment by payment hash
+    async fn incoming_payment(&self, payment_hash: &[u8; 32]) -> Result<Option<IncomingPayment>, LightningError> {
+        let path = format!("/payments/incoming/{}", hex::encode(payment_hash));
+        self.request(Method::GET, &path, None, true).await
+    }
+}
+
+#[async_trait]
+impl LightningProvider for PhoenixdProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "phoenixd", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via phoenixd: payment_id={}", payment_id);
+
+        let payment = match self.incoming_payment(payment_hash).await? {
+            Some(payment) => payment,
+            None => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: serde_json::json!({
+                        "provider": "phoenixd",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        debug!("phoenixd payment {}: paid={}", hex::encode(payment_hash), payment.is_paid);
+
+        Ok(PaymentVerificationResult {
+            verified: payment.is_paid,
+            // phoenixd reports sats
+            amount_msats: Some(payment.received_sat.saturating_mul(1000)).filter(|_| payment.is_paid),
+            timestamp: payment.completed_at.map(|ms| ms / 1000),
+            metadata: serde_json::json!({
+                "provider": "phoenixd",
+                "payment_hash": hex::encode(payment_hash),
+                "preimage": payment.preimage,
+                "external_id": payment.external_id,
+                "fees_sats": payment.fees,
+                "expired": payment.is_expired(),
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "phoenixd", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        // phoenixd only takes whole sats; round up so the payer never pays less than requested
+        let amount_sats = params.amount_msats.div_ceil(1000);
+        debug!("Creating invoice via phoenixd: amount={} sats", amount_sats);
+
+        let mut form = vec![
+            ("amountSat", amount_sats.to_string()),
+            ("expirySeconds", params.expiry_seconds.to_string()),
+        ];
+        match &params.description {
+            DescriptionKind::Direct(description) if description.len() > MAX_DESCRIPTION_BYTES => {
+                let hash = sha256::Hash::hash(description.as_bytes());
+                form.push(("descriptionHash", hex::encode(hash.to_byte_array())));
+            }
+            DescriptionKind::Direct(description) => form.push(("description", description.clone())),
+            DescriptionKind::Hash(hash) => form.push(("descriptionHash", hex::encode(hash))),
+        }
+        if let Some(label) = &params.label {
+            form.push(("externalId", label.clone()));
+        }
+
+        #[derive(Deserialize)]
+        struct CreateInvoiceResponse {
+            serialized: String,
+            #[serde(rename = "paymentHash")]
+            payment_hash: String,
+        }
+
+        // createinvoice has no idempotency key, so it is never retried
+        let response: Option<CreateInvoiceResponse> =
+            self.request(Method::POST, "/createinvoice", Some(&form), false).await?;
+        let response = response
+            .ok_or_else(|| LightningError::ProcessorError("phoenixd createinvoice endpoint not found".to_string()))?;
+
+        debug!("phoenixd invoice created: payment_hash={}", response.payment_hash);
+        Ok(response.serialized)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        Ok(self
+            .incoming_payment(payment_hash)
+            .await?
+            .map(|payment| payment.is_paid)
+            .unwrap_or(false))
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct GetInfoResponse {
+            #[serde(default)]
+            block_height: Option<u64>,
+            #[serde(default)]
+            version: Option<String>,
+        }
+
+        let started = std::time::Instant::now();
+        let result = self.request::<GetInfoResponse>(Method::GET, "/getinfo", None, true).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(Some(info)) => Ok(HealthStatus {
+                reachable: true,
+                latency_ms,
+                block_height: info.block_height,
+                synced_to_chain: None,
+                version: info.version,
+            }),
+            result => {
+                if let Err(e) = result {
+                    warn!("phoenixd health check failed: {}", e);
+                }
+                Ok(HealthStatus {
+                    reachable: false,
+                    latency_ms,
+                    block_height: None,
+                    synced_to_chain: None,
+                    version: None,
+                })
+            }
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Phoenixd
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
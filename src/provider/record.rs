@@ -0,0 +1,801 @@
+//! Record-and-replay provider wrappers
+//!
+//! `RecordingProvider` passes calls through to a real provider and writes
+//! each call (method, arguments, result) to a JSON Lines file: a header line
+//! describing the provider, then one line per call, appended as calls
+//! complete. `ReplayProvider` serves the same results from that file, so a
+//! session captured once against a live backend can be replayed in CI
+//! without network access.
+//!
+//! With redaction on, invoices, payment hashes and preimages in call
+//! arguments and results are stored as SHA256 hashes; replay hashes incoming
+//! arguments the same way before matching them, and returns the redacted
+//! results as recorded.
+
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, PaymentStream, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::Txid;
+use blvm_node::module::traits::NodeAPI;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Result keys holding payment hashes or preimages, redacted wherever they appear
+const REDACTED_KEYS: &[&str] = &["payment_hash", "preimage", "payment_preimage"];
+
+/// A recorded provider session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// Type of the provider that was recorded
+    pub provider_type: ProviderType,
+    /// Capabilities of the provider that was recorded
+    pub capabilities: ProviderCapabilities,
+    /// Whether invoices, payment hashes and preimages are hashed
+    pub redacted: bool,
+    /// Calls in the order they completed
+    pub calls: Vec<RecordedCall>,
+}
+
+/// First line of a recording file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordingHeader {
+    provider_type: ProviderType,
+    capabilities: ProviderCapabilities,
+    redacted: bool,
+}
+
+impl Recording {
+    /// Load a recording file
+    ///
+    /// Recordings written as a single JSON document, before calls were
+    /// appended line by line, load as well.
+    pub fn load(path: &Path) -> Result<Self, LightningError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| LightningError::ConfigError(format!("Failed to read recording {}: {}", path.display(), e)))?;
+        if let Ok(recording) = serde_json::from_str::<Recording>(&contents) {
+            return Ok(recording);
+        }
+
+        let invalid = |e: String| LightningError::ConfigError(format!("Invalid recording {}: {}", path.display(), e));
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+        let header: RecordingHeader = match lines.next() {
+            Some(line) => serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?,
+            None => return Err(invalid("empty file".to_string())),
+        };
+        let calls = lines
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<RecordedCall>, _>>()
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Recording {
+            provider_type: header.provider_type,
+            capabilities: header.capabilities,
+            redacted: header.redacted,
+            calls,
+        })
+    }
+
+    /// Write the recording to a file, replacing it atomically
+    pub fn save(&self, path: &Path) -> Result<(), LightningError> {
+        let mut contents = self.header_line()?;
+        for call in &self.calls {
+            contents.push_str(&call_line(call)?);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, contents)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to write recording {}: {}", path.display(), e)))
+    }
+
+    /// The header line of the recording file, newline included
+    fn header_line(&self) -> Result<String, LightningError> {
+        let header = RecordingHeader {
+            provider_type: self.provider_type.clone(),
+            capabilities: self.capabilities,
+            redacted: self.redacted,
+        };
+        serde_json::to_string(&header)
+            .map(|line| line + "\n")
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize recording: {}", e)))
+    }
+}
+
+/// A call as a line of the recording file, newline included
+fn call_line(call: &RecordedCall) -> Result<String, LightningError> {
+    serde_json::to_string(call)
+        .map(|line| line + "\n")
+        .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize recorded call: {}", e)))
+}
+
+/// A single provider call and its result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    /// Trait method name, e.g. `verify_payment`
+    pub method: String,
+    /// Arguments, redacted if the recording is
+    pub args: Value,
+    /// What the provider returned
+    pub result: RecordedResult,
+}
+
+/// Result of a recorded call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedResult {
+    Ok(Value),
+    Err(RecordedError),
+}
+
+/// Serializable form of `LightningError`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum RecordedError {
+    ModuleError(String),
+    InvoiceParseError(String),
+    InvoiceError(String),
+    ProcessorError(String),
+    PaymentVerificationFailed(String),
+    NodeConnectionError(String),
+    RoutingError(String),
+    ConfigError(String),
+    HttpError { status_code: u16, body: String },
+    Unsupported(String),
+    #[serde(rename = "lnbits_api_error")]
+    LNBitsApiError { status: u16, detail: String, endpoint: String },
+    InsufficientBalance(String),
+    InvoiceExpired(String),
+    WalletNotFound(String),
+}
+
+impl From<&LightningError> for RecordedError {
+    fn from(err: &LightningError) -> Self {
+        match err {
+            LightningError::ModuleError(msg) => RecordedError::ModuleError(msg.clone()),
+            LightningError::InvoiceParseError(msg) => RecordedError::InvoiceParseError(msg.clone()),
+            LightningError::InvoiceError(msg) => RecordedError::InvoiceError(msg.clone()),
+            LightningError::ProcessorError(msg) => RecordedError::ProcessorError(msg.clone()),
+            LightningError::PaymentVerificationFailed(msg) => RecordedError::PaymentVerificationFailed(msg.clone()),
+            LightningError::NodeConnectionError(msg) => RecordedError::NodeConnectionError(msg.clone()),
+            LightningError::RoutingError(msg) => RecordedError::RoutingError(msg.clone()),
+            LightningError::ConfigError(msg) => RecordedError::ConfigError(msg.clone()),
+            LightningError::HttpError { status_code, body } => RecordedError::HttpError {
+                status_code: *status_code,
+                body: body.clone(),
+            },
+            LightningError::Unsupported(msg) => RecordedError::Unsupported(msg.clone()),
+            LightningError::LNBitsApiError { status, detail, endpoint } => RecordedError::LNBitsApiError {
+                status: *status,
+                detail: detail.clone(),
+                endpoint: endpoint.clone(),
+            },
+            LightningError::InsufficientBalance(msg) => RecordedError::InsufficientBalance(msg.clone()),
+            LightningError::InvoiceExpired(msg) => RecordedError::InvoiceExpired(msg.clone()),
+            LightningError::WalletNotFound(msg) => RecordedError::WalletNotFound(msg.clone()),
+        }
+    }
+}
+
+impl From<RecordedError> for LightningError {
+    fn from(err: RecordedError) -> Self {
+        match err {
+            RecordedError::ModuleError(msg) => LightningError::ModuleError(msg),
+            RecordedError::InvoiceParseError(msg) => LightningError::InvoiceParseError(msg),
+            RecordedError::InvoiceError(msg) => LightningError::InvoiceError(msg),
+            RecordedError::ProcessorError(msg) => LightningError::ProcessorError(msg),
+            RecordedError::PaymentVerificationFailed(msg) => LightningError::PaymentVerificationFailed(msg),
+            RecordedError::NodeConnectionError(msg) => LightningError::NodeConnectionError(msg),
+            RecordedError::RoutingError(msg) => LightningError::RoutingError(msg),
+            RecordedError::ConfigError(msg) => LightningError::ConfigError(msg),
+            RecordedError::HttpError { status_code, body } => LightningError::HttpError { status_code, body },
+            RecordedError::Unsupported(msg) => LightningError::Unsupported(msg),
+            RecordedError::LNBitsApiError { status, detail, endpoint } => {
+                LightningError::LNBitsApiError { status, detail, endpoint }
+            }
+            RecordedError::InsufficientBalance(msg) => LightningError::InsufficientBalance(msg),
+            RecordedError::InvoiceExpired(msg) => LightningError::InvoiceExpired(msg),
+            RecordedError::WalletNotFound(msg) => LightningError::WalletNotFound(msg),
+        }
+    }
+}
+
+/// Builds call arguments, hashing sensitive values when redacting
+#[derive(Debug, Clone, Copy)]
+struct ArgEncoder {
+    redact: bool,
+}
+
+impl ArgEncoder {
+    fn invoice(&self, invoice: &str) -> Value {
+        if self.redact {
+            json!(format!("sha256:{}", sha256::Hash::hash(invoice.as_bytes())))
+        } else {
+            json!(invoice)
+        }
+    }
+
+    fn payment_hash(&self, payment_hash: &[u8; 32]) -> Value {
+        if self.redact {
+            json!(format!("sha256:{}", sha256::Hash::hash(payment_hash)))
+        } else {
+            json!(hex::encode(payment_hash))
+        }
+    }
+
+    fn verify_payment(&self, invoice: &str, payment_hash: &[u8; 32], payment_id: &str) -> Value {
+        json!({
+            "invoice": self.invoice(invoice),
+            "payment_hash": self.payment_hash(payment_hash),
+            "payment_id": payment_id,
+        })
+    }
+
+    fn create_invoice(&self, amount_msats: u64, description: &str, expiry_seconds: u64) -> Value {
+        json!({
+            "amount_msats": amount_msats,
+            "description": description,
+            "expiry_seconds": expiry_seconds,
+        })
+    }
+
+    fn create_invoice_ex(&self, params: &InvoiceParams) -> Value {
+        let description = match &params.description {
+            DescriptionKind::Direct(description) => json!({ "direct": description }),
+            DescriptionKind::Hash(hash) => json!({ "hash": hex::encode(hash) }),
+        };
+        let mut request = json!({
+            "amount_msats": params.amount_msats,
+            "description": description,
+            "expiry_seconds": params.expiry_seconds,
+            "label": params.label,
+            "metadata": params.metadata,
+        });
+        // Only present when set, so recordings made before these options existed still match
+        if let Some(address) = &params.fallback_address {
+            request["fallback_address"] = json!(address);
+        }
+        if let Some(cltv) = params.min_final_cltv_expiry {
+            request["min_final_cltv_expiry"] = json!(cltv);
+        }
+        if let Some(wallet) = &params.wallet {
+            request["wallet"] = json!(wallet);
+        }
+        request
+    }
+
+    fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Value {
+        json!({ "payment_hash": self.payment_hash(payment_hash) })
+    }
+
+    fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Value {
+        json!({ "payment_hash": self.payment_hash(payment_hash) })
+    }
+
+    fn withdraw_onchain(&self, address: &str, amount_sats: Option<u64>, fee_rate: Option<f64>) -> Value {
+        json!({
+            "address": address,
+            "amount_sats": amount_sats,
+            "fee_rate": fee_rate,
+        })
+    }
+
+    fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Value {
+        json!({
+            "invoice": self.invoice(invoice),
+            "amount_msats": amount_msats,
+        })
+    }
+
+    /// A call result, with invoices, payment hashes and preimages hashed when redacting
+    fn result(&self, value: Value) -> Value {
+        if self.redact {
+            redact(value, false)
+        } else {
+            value
+        }
+    }
+}
+
+/// Hash invoices anywhere in `value`, and payment hashes and preimages under `REDACTED_KEYS`
+///
+/// Hex payment hashes are hashed as bytes, so they redact to the same value
+/// as the arguments do. Byte arrays are replaced by their hash as a byte
+/// array, so the result still decodes on replay.
+fn redact(value: Value, secret: bool) -> Value {
+    match value {
+        Value::String(s) if secret || is_invoice(&s) => {
+            let mut bytes = [0u8; 32];
+            match hex::decode_to_slice(&s, &mut bytes) {
+                Ok(()) => json!(format!("sha256:{}", sha256::Hash::hash(&bytes))),
+                Err(_) => json!(format!("sha256:{}", sha256::Hash::hash(s.as_bytes()))),
+            }
+        }
+        Value::Array(items) if secret => {
+            let bytes: Option<Vec<u8>> = items
+                .iter()
+                .map(|item| item.as_u64().and_then(|n| u8::try_from(n).ok()))
+                .collect();
+            match bytes {
+                Some(bytes) => json!(sha256::Hash::hash(&bytes).to_byte_array()),
+                None => Value::Array(items.into_iter().map(|item| redact(item, true)).collect()),
+            }
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| redact(item, false)).collect()),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let secret = REDACTED_KEYS.contains(&key.as_str());
+                    (key, redact(value, secret))
+                })
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Whether a string looks like a BOLT11 invoice
+fn is_invoice(s: &str) -> bool {
+    let lower = s.to_ascii_lowercase();
+    lower.starts_with("lnbc") || lower.starts_with("lntb") || lower.starts_with("lnsb")
+}
+
+fn verification_to_json(result: &PaymentVerificationResult) -> Value {
+    json!({
+        "verified": result.verified,
+        "amount_msats": result.amount_msats,
+        "timestamp": result.timestamp,
+        "metadata": result.metadata,
+    })
+}
+
+fn verification_from_json(value: Value) -> Result<PaymentVerificationResult, LightningError> {
+    #[derive(Deserialize)]
+    struct Recorded {
+        verified: bool,
+        amount_msats: Option<u64>,
+        timestamp: Option<u64>,
+        #[serde(default)]
+        metadata: Value,
+    }
+    let recorded: Recorded = decode(value)?;
+    Ok(PaymentVerificationResult {
+        verified: recorded.verified,
+        amount_msats: recorded.amount_msats,
+        timestamp: recorded.timestamp,
+        metadata: recorded.metadata,
+    })
+}
+
+fn channel_to_json(channel: &ChannelInfo) -> Value {
+    json!({
+        "channel_id": hex::encode(channel.channel_id),
+        "counterparty_node_id": hex::encode(channel.counterparty_node_id),
+        "capacity_msats": channel.capacity_msats,
+        "local_balance_msats": channel.local_balance_msats,
+        "remote_balance_msats": channel.remote_balance_msats,
+        "is_active": channel.is_active,
+        "short_channel_id": channel.short_channel_id,
+        "is_public": channel.is_public,
+    })
+}
+
+fn node_info_to_json(info: &NodeInfo) -> Value {
+    json!({
+        "node_id": hex::encode(info.node_id),
+        "alias": info.alias,
+        "color": info.color,
+        "num_peers": info.num_peers,
+        "num_active_channels": info.num_active_channels,
+        "num_pending_channels": info.num_pending_channels,
+        "block_height": info.block_height,
+        "version": info.version,
+    })
+}
+
+fn node_info_from_json(value: Value) -> Result<NodeInfo, LightningError> {
+    #[derive(Deserialize)]
+    struct Recorded {
+        node_id: String,
+        alias: Option<String>,
+        color: Option<String>,
+        num_peers: u32,
+        num_active_channels: u32,
+        num_pending_channels: u32,
+        block_height: u64,
+        version: Option<String>,
+    }
+    let recorded: Recorded = decode(value)?;
+    let mut node_id = [0u8; 33];
+    hex::decode_to_slice(&recorded.node_id, &mut node_id)
+        .map_err(|e| LightningError::ProcessorError(format!("Invalid recorded node_id: {}", e)))?;
+    Ok(NodeInfo {
+        node_id,
+        alias: recorded.alias,
+        color: recorded.color,
+        num_peers: recorded.num_peers,
+        num_active_channels: recorded.num_active_channels,
+        num_pending_channels: recorded.num_pending_channels,
+        block_height: recorded.block_height,
+        version: recorded.version,
+    })
+}
+
+fn channel_from_json(value: Value) -> Result<ChannelInfo, LightningError> {
+    #[derive(Deserialize)]
+    struct Recorded {
+        channel_id: String,
+        counterparty_node_id: String,
+        capacity_msats: u64,
+        local_balance_msats: u64,
+        remote_balance_msats: u64,
+        is_active: bool,
+        short_channel_id: Option<u64>,
+        is_public: bool,
+    }
+    let recorded: Recorded = decode(value)?;
+    let mut channel_id = [0u8; 32];
+    hex::decode_to_slice(&recorded.channel_id, &mut channel_id)
+        .map_err(|e| LightningError::ProcessorError(format!("Invalid recorded channel_id: {}", e)))?;
+    let mut counterparty_node_id = [0u8; 33];
+    hex::decode_to_slice(&recorded.counterparty_node_id, &mut counterparty_node_id)
+        .map_err(|e| LightningError::ProcessorError(format!("Invalid recorded counterparty_node_id: {}", e)))?;
+    Ok(ChannelInfo {
+        channel_id,
+        counterparty_node_id,
+        capacity_msats: recorded.capacity_msats,
+        local_balance_msats: recorded.local_balance_msats,
+        remote_balance_msats: recorded.remote_balance_msats,
+        is_active: recorded.is_active,
+        short_channel_id: recorded.short_channel_id,
+        is_public: recorded.is_public,
+    })
+}
+
+fn decode<T: DeserializeOwned>(value: Value) -> Result<T, LightningError> {
+    serde_json::from_value(value)
+        .map_err(|e| LightningError::ProcessorError(format!("Invalid recorded result: {}", e)))
+}
+
+fn encode<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Provider wrapper that records every call to a JSON Lines file
+pub struct RecordingProvider<P: LightningProvider + ?Sized> {
+    inner: Box<P>,
+    path: PathBuf,
+    args: ArgEncoder,
+    recording: Mutex<Recording>,
+    /// Recording file, opened for appending (unset if it couldn't be created)
+    file: tokio::sync::Mutex<Option<tokio::fs::File>>,
+}
+
+impl<P: LightningProvider + ?Sized> RecordingProvider<P> {
+    /// Record calls to `inner` into `path`, overwriting any previous recording
+    ///
+    /// The file is created with its header line here; failing to create it
+    /// is logged and calls are then only recorded in memory.
+    pub fn new(inner: Box<P>, path: impl Into<PathBuf>, redact: bool) -> Self {
+        let path = path.into();
+        info!("Recording {} provider calls to {}", inner.provider_type(), path.display());
+        let recording = Recording {
+            provider_type: inner.provider_type(),
+            capabilities: inner.capabilities(),
+            redacted: redact,
+            calls: Vec::new(),
+        };
+        let file = match recording.save(&path) {
+            Ok(()) => std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .map(tokio::fs::File::from_std)
+                .map_err(|e| warn!("Failed to open recording {}: {}", path.display(), e))
+                .ok(),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        };
+        Self {
+            inner,
+            path,
+            args: ArgEncoder { redact },
+            recording: Mutex::new(recording),
+            file: tokio::sync::Mutex::new(file),
+        }
+    }
+
+    /// Path the recording is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Calls recorded so far
+    pub fn recording(&self) -> Recording {
+        self.recording.lock().unwrap().clone()
+    }
+
+    /// Record a call and append it to the file
+    ///
+    /// Failing to write the recording never fails the call itself.
+    async fn record<T>(
+        &self,
+        method: &str,
+        args: Value,
+        result: Result<T, LightningError>,
+        to_json: impl FnOnce(&T) -> Value,
+    ) -> Result<T, LightningError> {
+        let recorded = match &result {
+            Ok(value) => RecordedResult::Ok(self.args.result(to_json(value))),
+            Err(e) => RecordedResult::Err(RecordedError::from(e)),
+        };
+        let call = RecordedCall {
+            method: method.to_string(),
+            args,
+            result: recorded,
+        };
+        let line = call_line(&call);
+
+        // Held while appending, so the file lists calls in the same order as `recording()`
+        let mut file = self.file.lock().await;
+        self.recording.lock().unwrap().calls.push(call);
+        if let Some(file) = file.as_mut() {
+            let written = match line {
+                Ok(line) => file.write_all(line.as_bytes()).await.and(file.flush().await)
+                    .map_err(|e| LightningError::ProcessorError(format!(
+                        "Failed to write recording {}: {}", self.path.display(), e
+                    ))),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                warn!("{}", e);
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<P: LightningProvider + ?Sized> LightningProvider for RecordingProvider<P> {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        let result = self.inner.verify_payment(invoice, payment_hash, payment_id).await;
+        self.record("verify_payment", self.args.verify_payment(invoice, payment_hash, payment_id), result, verification_to_json).await
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        let result = self.inner.create_invoice(amount_msats, description, expiry_seconds).await;
+        self.record("create_invoice", self.args.create_invoice(amount_msats, description, expiry_seconds), result, encode).await
+    }
+
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let result = self.inner.create_invoice_ex(params).await;
+        self.record("create_invoice_ex", self.args.create_invoice_ex(params), result, encode).await
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        let result = self.inner.is_payment_confirmed(payment_hash).await;
+        self.record("is_payment_confirmed", self.args.is_payment_confirmed(payment_hash), result, encode).await
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        let result = self.inner.cancel_invoice(payment_hash).await;
+        self.record("cancel_invoice", self.args.cancel_invoice(payment_hash), result, encode).await
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        let result = self.inner.balance_msats().await;
+        self.record("balance_msats", json!({}), result, encode).await
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        let result = self.inner.withdraw_onchain(address, amount_sats, fee_rate).await;
+        self.record("withdraw_onchain", self.args.withdraw_onchain(address, amount_sats, fee_rate), result, |txid| {
+            json!(txid.to_string())
+        }).await
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        // Streams can't be replayed; pass them through unrecorded
+        self.inner.subscribe_payments().await
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        let result = self.inner.estimate_fee(invoice, amount_msats).await;
+        self.record("estimate_fee", self.args.estimate_fee(invoice, amount_msats), result, encode).await
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        let result = self.inner.list_channels().await;
+        self.record("list_channels", json!({}), result, |channels| {
+            Value::Array(channels.iter().map(channel_to_json).collect())
+        }).await
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        let result = self.inner.get_node_info().await;
+        self.record("get_node_info", json!({}), result, node_info_to_json).await
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        self.inner.attach_storage(node_api).await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        let result = self.inner.health_check().await;
+        self.record("health_check", json!({}), result, encode).await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.inner.provider_type()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        // Recording is transparent; expose the wrapped provider
+        self.inner.as_any()
+    }
+}
+
+/// Provider that serves results from a recording
+///
+/// Each call is matched against the first unused recorded call with the
+/// same method and arguments, so concurrent callers don't have to arrive in
+/// the recorded order. Calls with no match fail with a `ProcessorError`
+/// naming the method and arguments.
+pub struct ReplayProvider {
+    provider_type: ProviderType,
+    capabilities: ProviderCapabilities,
+    args: ArgEncoder,
+    /// Recorded calls not yet replayed
+    remaining: Mutex<Vec<RecordedCall>>,
+}
+
+impl ReplayProvider {
+    /// Replay a recording
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            provider_type: recording.provider_type,
+            capabilities: recording.capabilities,
+            args: ArgEncoder { redact: recording.redacted },
+            remaining: Mutex::new(recording.calls),
+        }
+    }
+
+    /// Replay a recording file written by `RecordingProvider`
+    pub fn from_file(path: &Path) -> Result<Self, LightningError> {
+        let recording = Recording::load(path)?;
+        info!("Replaying {} recorded {} provider calls from {}", recording.calls.len(), recording.provider_type, path.display());
+        Ok(Self::new(recording))
+    }
+
+    /// Recorded calls that haven't been replayed yet
+    pub fn remaining(&self) -> Vec<RecordedCall> {
+        self.remaining.lock().unwrap().clone()
+    }
+
+    /// Take the recorded result for a call
+    fn replay(&self, method: &str, args: Value) -> Result<Value, LightningError> {
+        let mut remaining = self.remaining.lock().unwrap();
+        let position = remaining
+            .iter()
+            .position(|call| call.method == method && call.args == args)
+            .ok_or_else(|| {
+                LightningError::ProcessorError(format!("Unexpected {} call during replay: {}", method, args))
+            })?;
+        match remaining.remove(position).result {
+            RecordedResult::Ok(value) => Ok(value),
+            RecordedResult::Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl LightningProvider for ReplayProvider {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        verification_from_json(self.replay("verify_payment", self.args.verify_payment(invoice, payment_hash, payment_id))?)
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        decode(self.replay("create_invoice", self.args.create_invoice(amount_msats, description, expiry_seconds))?)
+    }
+
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        decode(self.replay("create_invoice_ex", self.args.create_invoice_ex(params))?)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        decode(self.replay("is_payment_confirmed", self.args.is_payment_confirmed(payment_hash))?)
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        decode(self.replay("cancel_invoice", self.args.cancel_invoice(payment_hash))?)
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        decode(self.replay("balance_msats", json!({}))?)
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        let txid: String = decode(self.replay("withdraw_onchain", self.args.withdraw_onchain(address, amount_sats, fee_rate))?)?;
+        Txid::from_str(&txid)
+            .map_err(|e| LightningError::ProcessorError(format!("Invalid recorded txid: {}", e)))
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        Err(LightningError::ProcessorError("Payment subscriptions can't be replayed".to_string()))
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        decode(self.replay("estimate_fee", self.args.estimate_fee(invoice, amount_msats))?)
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        let channels: Vec<Value> = decode(self.replay("list_channels", json!({}))?)?;
+        channels.into_iter().map(channel_from_json).collect()
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        node_info_from_json(self.replay("get_node_info", json!({}))?)
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        decode(self.replay("health_check", json!({}))?)
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.provider_type.clone()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,180 @@
+//! Request timeout and retry policy shared by providers
+//!
+//! Retries are only ever applied to idempotent operations (payment lookups),
+//! or to invoice creation when a label makes the request safe to repeat.
+
+use crate::error::LightningError;
+use blvm_node::module::traits::ModuleContext;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// Default total request timeout
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Default number of retries after the first attempt
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Default base delay for exponential backoff
+pub const DEFAULT_BACKOFF_BASE_MS: u64 = 1_000;
+
+/// Class of transient failure a policy may retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// Connection could not be established
+    Connect,
+    /// Request timed out
+    Timeout,
+    /// Server returned a 5xx response
+    ServerError,
+    /// Server returned 429 Too Many Requests
+    RateLimited,
+}
+
+impl ErrorClass {
+    /// Parse a config name ("connect", "timeout", "server_error", "rate_limited")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "connect" => Some(ErrorClass::Connect),
+            "timeout" => Some(ErrorClass::Timeout),
+            "server_error" | "5xx" => Some(ErrorClass::ServerError),
+            "rate_limited" | "429" => Some(ErrorClass::RateLimited),
+            _ => None,
+        }
+    }
+}
+
+/// Request timeout and retry policy for a provider
+#[derive(Debug, Clone)]
+pub struct ProviderRetryPolicy {
+    /// Timeout for a single attempt
+    pub request_timeout: Duration,
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further retry
+    pub backoff_base: Duration,
+    /// Failure classes that are retried
+    pub retry_on: Vec<ErrorClass>,
+}
+
+impl Default for ProviderRetryPolicy {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff_base: Duration::from_millis(DEFAULT_BACKOFF_BASE_MS),
+            retry_on: vec![ErrorClass::Connect, ErrorClass::Timeout, ErrorClass::ServerError, ErrorClass::RateLimited],
+        }
+    }
+}
+
+impl ProviderRetryPolicy {
+    /// Read the policy from config
+    ///
+    /// Global keys (`lightning.request_timeout_secs`, `lightning.max_retries`,
+    /// `lightning.backoff_base_ms`, `lightning.retry_on`) can be overridden per
+    /// provider under `lightning.<provider>.*`. `timeout_secs` and `backoff_ms`
+    /// are accepted as aliases for `request_timeout_secs` and `backoff_base_ms`.
+    /// The older millisecond keys `request_timeout_ms` and `retry_delay_ms`
+    /// are still read, with a deprecation warning.
+    pub fn from_config(ctx: &ModuleContext, provider: &str) -> Self {
+        let defaults = Self::default();
+        let lookup = |key: &str| {
+            ctx.get_config(&format!("lightning.{}.{}", provider, key))
+                .or_else(|| ctx.get_config(&format!("lightning.{}", key)))
+        };
+        let lookup_u64 = |key: &str| lookup(key).and_then(|s| s.trim().parse::<u64>().ok());
+        let deprecated_u64 = |key: &str, replacement: &str| {
+            let value = lookup_u64(key);
+            if value.is_some() {
+                warn!("lightning.{}.{} is deprecated, use {} instead", provider, key, replacement);
+            }
+            value
+        };
+
+        Self {
+            request_timeout: lookup_u64("request_timeout_secs")
+                .or_else(|| lookup_u64("timeout_secs"))
+                .map(Duration::from_secs)
+                .or_else(|| deprecated_u64("request_timeout_ms", "request_timeout_secs").map(Duration::from_millis))
+                .unwrap_or(defaults.request_timeout),
+            max_retries: lookup_u64("max_retries")
+                .map(|n| n as u32)
+                .unwrap_or(defaults.max_retries),
+            backoff_base: lookup_u64("backoff_base_ms")
+                .or_else(|| lookup_u64("backoff_ms"))
+                .or_else(|| deprecated_u64("retry_delay_ms", "backoff_base_ms"))
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.backoff_base),
+            retry_on: lookup("retry_on")
+                .map(|s| s.split(',').filter_map(ErrorClass::from_name).collect())
+                .unwrap_or(defaults.retry_on),
+        }
+    }
+
+    /// Whether a failure of `class` on retry number `attempt` (1-based) should be retried
+    pub fn should_retry(&self, class: ErrorClass, attempt: u32) -> bool {
+        attempt <= self.max_retries && self.retry_on.contains(&class)
+    }
+
+    /// Delay before retry number `attempt` (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff_base
+            .saturating_mul(1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX))
+    }
+
+    /// `backoff` with jitter: a random delay between half and all of it
+    ///
+    /// Keeps clients that failed together from retrying in lockstep.
+    pub fn jittered_backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        backoff / 2 + backoff.mul_f64(rand::random::<f64>() / 2.0)
+    }
+}
+
+/// Classify a retryable error; `None` for permanent errors
+fn error_class(error: &LightningError) -> Option<ErrorClass> {
+    if !error.is_retryable() {
+        return None;
+    }
+    match error {
+        LightningError::ProcessorError(msg) if !msg.to_lowercase().contains("connection refused") => {
+            Some(ErrorClass::Timeout)
+        }
+        _ => Some(ErrorClass::Connect),
+    }
+}
+
+/// Run an idempotent operation with the policy's timeout and retries
+///
+/// Only errors for which `LightningError::is_retryable` holds are retried;
+/// permanent errors are returned immediately.
+pub async fn retry_idempotent<T, F, Fut>(
+    policy: &ProviderRetryPolicy,
+    operation: &str,
+    mut op: F,
+) -> Result<T, LightningError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, LightningError>>,
+{
+    let mut attempt: u32 = 0;
+    loop {
+        let (class, error) = match tokio::time::timeout(policy.request_timeout, op()).await {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(e)) => match error_class(&e) {
+                Some(class) => (class, e),
+                None => return Err(e),
+            },
+            Err(_) => (
+                ErrorClass::Timeout,
+                LightningError::NodeConnectionError(format!("{} timed out after {:?}", operation, policy.request_timeout)),
+            ),
+        };
+
+        attempt += 1;
+        if !policy.should_retry(class, attempt) {
+            return Err(error);
+        }
+        warn!("{} failed (attempt {}/{}): {}", operation, attempt, policy.max_retries, error);
+        tokio::time::sleep(policy.backoff(attempt)).await;
+    }
+}
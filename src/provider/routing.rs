@@ -0,0 +1,329 @@
+//! Amount-based routing provider
+//!
+//! Sends each invoice to a provider chosen by amount, e.g. micro-payments to
+//! a custodial LNBits wallet and larger payments to our own LDK node. The
+//! provider that created an invoice is remembered by payment hash, so the
+//! payment is later verified against the same provider. The index is kept
+//! in module storage so it survives restarts.
+
+use crate::invoice::InvoiceParser;
+use crate::provider::payment_index::PaymentIndex;
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, PaymentStream, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams,
+};
+use crate::error::LightningError;
+use async_trait::async_trait;
+use bitcoin::Txid;
+use blvm_node::module::traits::NodeAPI;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Storage tree holding the payment hash -> provider index
+pub const ROUTING_INDEX_TREE: &str = "routing_index";
+
+/// Route invoices up to `max_msats` (inclusive) to `provider`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutingRule {
+    /// Largest amount handled by this rule; `None` matches any amount
+    #[serde(default)]
+    pub max_msats: Option<u64>,
+    /// Provider name, as accepted by `lightning.provider`
+    pub provider: String,
+}
+
+impl RoutingRule {
+    /// Parse `lightning.routing.rules`, a JSON array of rules
+    ///
+    /// Rules are checked in order and the first one whose `max_msats` covers
+    /// the amount wins, so they must be sorted by ascending `max_msats`.
+    pub fn parse_rules(json: &str) -> Result<Vec<RoutingRule>, LightningError> {
+        let rules: Vec<RoutingRule> = serde_json::from_str(json)
+            .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.routing.rules: {}", e)))?;
+        if rules.is_empty() {
+            return Err(LightningError::ConfigError("lightning.routing.rules needs at least one rule".to_string()));
+        }
+        for pair in rules.windows(2) {
+            match (pair[0].max_msats, pair[1].max_msats) {
+                (None, _) => {
+                    return Err(LightningError::ConfigError(format!(
+                        "Routing rule for {} matches every amount, so later rules are unreachable",
+                        pair[0].provider
+                    )));
+                }
+                (Some(first), Some(second)) if second <= first => {
+                    return Err(LightningError::ConfigError(
+                        "Routing rules must be sorted by ascending max_msats".to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Whether the rule handles `amount_msats`
+    pub fn matches(&self, amount_msats: u64) -> bool {
+        self.max_msats.map_or(true, |max| amount_msats <= max)
+    }
+}
+
+/// A routing rule with the provider it dispatches to
+pub struct Route {
+    pub rule: RoutingRule,
+    pub provider: Box<dyn LightningProvider>,
+}
+
+/// Provider that dispatches by invoice amount
+pub struct RoutingProvider {
+    routes: Vec<Route>,
+    /// Payment hash -> provider name of the route that created it
+    index: PaymentIndex,
+}
+
+impl RoutingProvider {
+    /// Create a routing provider
+    ///
+    /// The payment index is kept in memory until module storage is attached.
+    pub fn new(routes: Vec<Route>) -> Result<Self, LightningError> {
+        if routes.is_empty() {
+            return Err(LightningError::ConfigError("Routing provider needs at least one route".to_string()));
+        }
+        Ok(Self {
+            routes,
+            index: PaymentIndex::in_tree("routing index", ROUTING_INDEX_TREE),
+        })
+    }
+
+    /// Routes in rule order
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// The route handling `amount_msats`
+    pub fn route_for_amount(&self, amount_msats: u64) -> Result<&Route, LightningError> {
+        self.routes
+            .iter()
+            .find(|route| route.rule.matches(amount_msats))
+            .ok_or_else(|| LightningError::ConfigError(format!("No routing rule covers {} msats", amount_msats)))
+    }
+
+    /// Provider name recorded for a payment hash, if the invoice was created through this provider
+    pub fn provider_for_payment(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.index.get(payment_hash)
+    }
+
+    /// The route for an existing payment
+    ///
+    /// Uses the recorded provider if there is one; otherwise falls back to
+    /// routing by the invoice amount, for invoices created before routing
+    /// was enabled. Fails if the hash is unknown and there is no invoice to
+    /// route by.
+    fn route_for_payment(&self, invoice: Option<&str>, payment_hash: &[u8; 32]) -> Result<&Route, LightningError> {
+        if let Some(name) = self.provider_for_payment(payment_hash) {
+            return self.routes
+                .iter()
+                .find(|route| route.rule.provider == name)
+                .ok_or_else(|| LightningError::ConfigError(format!(
+                    "Payment {} was created by {}, which is no longer routed to",
+                    hex::encode(payment_hash),
+                    name
+                )));
+        }
+
+        match invoice.filter(|s| !s.is_empty()).map(InvoiceParser::parse) {
+            Some(Ok(data)) => {
+                debug!("No route recorded for payment {}, routing by amount", hex::encode(payment_hash));
+                self.route_for_amount(data.amount_msats)
+            }
+            _ => Err(LightningError::ProcessorError(format!(
+                "Unknown payment hash {}: no provider recorded",
+                hex::encode(payment_hash)
+            ))),
+        }
+    }
+
+    /// Remember which provider created an invoice
+    async fn record_invoice(&self, route: &Route, invoice: &str) {
+        let payment_hash = match InvoiceParser::parse(invoice) {
+            Ok(data) => data.payment_hash_hex(),
+            Err(e) => {
+                warn!("Can't index invoice from {}, verification will route by amount: {}", route.rule.provider, e);
+                return;
+            }
+        };
+        if let Err(e) = self.index.insert(payment_hash, route.rule.provider.clone()).await {
+            warn!("{}", e);
+        }
+    }
+
+    /// Create an invoice through the route for its amount
+    async fn create_routed(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let route = self.route_for_amount(params.amount_msats)?;
+        debug!("Routing {} msats invoice to {}", params.amount_msats, route.rule.provider);
+        let invoice = route.provider.create_invoice_ex(params).await?;
+        self.record_invoice(route, &invoice).await;
+        Ok(invoice)
+    }
+}
+
+#[async_trait]
+impl LightningProvider for RoutingProvider {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        let route = self.route_for_payment(Some(invoice), payment_hash)?;
+        let mut result = route.provider.verify_payment(invoice, payment_hash, payment_id).await?;
+        if let Some(metadata) = result.metadata.as_object_mut() {
+            metadata.insert("routed_to".to_string(), serde_json::Value::String(route.rule.provider.clone()));
+        }
+        Ok(result)
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_routed(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        self.create_routed(params).await
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        self.route_for_payment(None, payment_hash)?
+            .provider
+            .is_payment_confirmed(payment_hash)
+            .await
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        self.route_for_payment(None, payment_hash)?
+            .provider
+            .cancel_invoice(payment_hash)
+            .await
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        let mut total = 0u64;
+        for route in &self.routes {
+            total = total.saturating_add(route.provider.balance_msats().await?);
+        }
+        Ok(total)
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        // Withdraw from the first provider that holds on-chain funds
+        let route = self.routes
+            .iter()
+            .find(|route| route.provider.capabilities().can_withdraw_onchain)
+            .ok_or_else(|| LightningError::ProcessorError("No routed provider supports on-chain withdrawals".to_string()))?;
+        route.provider.withdraw_onchain(address, amount_sats, fee_rate).await
+    }
+
+    async fn subscribe_payments(&self) -> Result<PaymentStream, LightningError> {
+        let mut streams = Vec::new();
+        for route in &self.routes {
+            match route.provider.subscribe_payments().await {
+                Ok(stream) => streams.push(stream),
+                Err(e) => debug!("{} has no payment subscription: {}", route.rule.provider, e),
+            }
+        }
+        if streams.is_empty() {
+            return Err(LightningError::ProcessorError("No routed provider supports payment subscriptions".to_string()));
+        }
+        Ok(Box::pin(futures::stream::select_all(streams)))
+    }
+
+    async fn estimate_fee(&self, invoice: &str, amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        let amount = match amount_msats {
+            Some(amount) => amount,
+            None => InvoiceParser::parse(invoice)?.amount_msats,
+        };
+        self.route_for_amount(amount)?.provider.estimate_fee(invoice, amount_msats).await
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        // Channels of every provider that has any
+        let mut channels = Vec::new();
+        let mut last_error = None;
+        for route in &self.routes {
+            match route.provider.list_channels().await {
+                Ok(mut list) => channels.append(&mut list),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if channels.is_empty() => Err(e),
+            _ => Ok(channels),
+        }
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        // The first route whose provider can identify its node
+        let mut last_error = None;
+        for route in &self.routes {
+            match route.provider.get_node_info().await {
+                Ok(info) => return Ok(info),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| LightningError::ConfigError("Routing provider has no routes".to_string())))
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        self.index.attach(node_api.clone()).await?;
+        for route in &self.routes {
+            route.provider.attach_storage(node_api.clone()).await?;
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // Every route has to be up for payments of any amount to work
+        let mut first = None;
+        for route in &self.routes {
+            let status = route.provider.health_check().await?;
+            if !status.reachable {
+                return Ok(status);
+            }
+            first.get_or_insert(status);
+        }
+        first.ok_or_else(|| LightningError::ConfigError("Routing provider has no routes".to_string()))
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.routes.iter().fold(ProviderCapabilities::default(), |acc, route| {
+            let caps = route.provider.capabilities();
+            ProviderCapabilities {
+                can_verify: acc.can_verify || caps.can_verify,
+                can_create_invoices: acc.can_create_invoices || caps.can_create_invoices,
+                can_pay: acc.can_pay || caps.can_pay,
+                can_keysend: acc.can_keysend || caps.can_keysend,
+                can_hold_invoices: acc.can_hold_invoices || caps.can_hold_invoices,
+                can_manage_channels: acc.can_manage_channels || caps.can_manage_channels,
+                can_list_payments: acc.can_list_payments || caps.can_list_payments,
+                can_withdraw_onchain: acc.can_withdraw_onchain || caps.can_withdraw_onchain,
+            }
+        })
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Routing
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,527 @@
+//! Strike provider implementation
+//!
+//! Talks to Strike's REST API, authenticated with a bearer API key. A
+//! Strike invoice is created with `POST /v1/invoices` and only gets a BOLT11
+//! once quoted (`POST /v1/invoices/{id}/quote`). Quotes expire after about a
+//! minute; asking for an invoice again with the same label returns the
+//! current quote while it is valid and re-quotes the Strike invoice once it
+//! has expired. Amounts are BTC decimal strings.
+//!
+//! Strike looks invoices up by its own id, so the invoice id of every quote
+//! is recorded in a payment hash index, and the latest quote for each label
+//! in a second one. Both are kept in module storage once the processor
+//! attaches it, so labelled invoices are re-quoted across restarts.
+
+use crate::provider::payment_index::PaymentIndex;
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+    DescriptionKind,
+};
+use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use blvm_node::module::traits::NodeAPI;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Strike's API endpoint
+pub const DEFAULT_STRIKE_URL: &str = "https://api.strike.me";
+
+/// Storage tree holding the payment hash -> invoice id index
+pub const STRIKE_INVOICES_TREE: &str = "strike_invoices";
+
+/// Storage tree holding the latest quote for each label
+pub const STRIKE_QUOTES_TREE: &str = "strike_quotes";
+
+const SATS_PER_BTC: u64 = 100_000_000;
+
+/// Strike provider configuration
+#[derive(Debug, Clone)]
+pub struct StrikeConfig {
+    /// API endpoint
+    pub url: String,
+    /// API key with invoice scopes
+    pub api_key: String,
+    /// Request timeout and retry policy
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+impl Default for StrikeConfig {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_STRIKE_URL.to_string(),
+            api_key: String::new(),
+            retry_policy: ProviderRetryPolicy::default(),
+        }
+    }
+}
+
+/// Strike invoice state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum InvoiceState {
+    Unpaid,
+    Pending,
+    Paid,
+    Cancelled,
+}
+
+/// Currency amount, e.g. `{"amount": "0.00000002", "currency": "BTC"}`
+#[derive(Debug, Deserialize)]
+struct Amount {
+    amount: String,
+    currency: String,
+}
+
+/// Invoice from `POST /v1/invoices` or `GET /v1/invoices/{id}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Invoice {
+    invoice_id: String,
+    state: InvoiceState,
+    #[serde(default)]
+    amount: Option<Amount>,
+    #[serde(default)]
+    correlation_id: Option<String>,
+}
+
+/// Quote from `POST /v1/invoices/{id}/quote`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Quote {
+    quote_id: String,
+    ln_invoice: String,
+    expiration_in_sec: u64,
+}
+
+/// Error body, e.g. `{"data": {"status": 422, "code": "INVALID_DATA", "message": "..."}}`
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    data: ErrorData,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorData {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Latest quote for a labelled invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CurrentQuote {
+    invoice_id: String,
+    bolt11: String,
+    /// Unix time the quote expires
+    expires_at: u64,
+}
+
+/// Strike provider implementation
+pub struct StrikeProvider {
+    config: StrikeConfig,
+    http_client: Client,
+    /// Payment hash -> invoice id of every quote handed out
+    invoices: PaymentIndex,
+    /// Label -> latest quote (JSON), so a caller asking again gets the same invoice re-quoted
+    quotes: PaymentIndex,
+}
+
+impl StrikeProvider {
+    /// Create a new Strike provider
+    ///
+    /// The invoice and quote indexes are kept in memory until module storage
+    /// is attached.
+    pub fn new(config: StrikeConfig) -> Result<Self, LightningError> {
+        if config.api_key.is_empty() {
+            return Err(LightningError::ConfigError("lightning.strike.api_key is required".to_string()));
+        }
+        let http_client = Client::builder()
+            .timeout(config.retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            config,
+            http_client,
+            invoices: PaymentIndex::in_tree("Strike invoice index", STRIKE_INVOICES_TREE),
+            quotes: PaymentIndex::in_tree("Strike quotes", STRIKE_QUOTES_TREE),
+        })
+    }
+
+    /// Strike invoice id recorded for a payment hash, if it was quoted through this provider
+    pub fn invoice_id(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.invoices.get(payment_hash)
+    }
+
+    /// Latest quote recorded for a label
+    fn current_quote(&self, label: &str) -> Option<CurrentQuote> {
+        let value = self.quotes.get_key(label)?;
+        match serde_json::from_str(&value) {
+            Ok(current) => Some(current),
+            Err(e) => {
+                warn!("Corrupt Strike quote for label {}, creating a new invoice: {}", label, e);
+                None
+            }
+        }
+    }
+
+    /// Make an API request
+    ///
+    /// Returns `Ok(None)` for 404 responses (unknown invoice).
+    /// Idempotent requests are retried according to the retry policy.
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+        idempotent: bool,
+    ) -> Result<Option<T>, LightningError> {
+        let url = format!("{}{}", self.config.url.trim_end_matches('/'), path);
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let mut request = self
+                .http_client
+                .request(method.clone(), &url)
+                .bearer_auth(&self.config.api_key);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if idempotent && policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("Strike {} failed (attempt {}/{}): {}", path, attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("Strike request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && idempotent && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("Strike {} returned {} (attempt {}/{})", path, status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            match status {
+                StatusCode::NOT_FOUND => return Ok(None),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    return Err(LightningError::ConfigError(format!("Strike rejected the API key ({})", status)));
+                }
+                StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+                    let text = response.text().await.unwrap_or_default();
+                    let message = match serde_json::from_str::<ErrorResponse>(&text) {
+                        Ok(error) => format!("{}: {}", error.data.code, error.data.message),
+                        Err(_) => text,
+                    };
+                    return Err(LightningError::InvoiceError(format!("Strike rejected {}: {}", path, message)));
+                }
+                _ => {}
+            }
+
+            if !status.is_success() {
+                return Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                });
+            }
+
+            return response
+                .json::<T>()
+                .await
+                .map(Some)
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse Strike response: {}", e)));
+        }
+    }
+
+    /// Look up a Strike invoice by id
+    async fn get_invoice(&self, invoice_id: &str) -> Result<Option<Invoice>, LightningError> {
+        self.request(Method::GET, &format!("/v1/invoices/{}", invoice_id), None, true).await
+    }
+
+    /// Quote a Strike invoice and record the new BOLT11's payment hash
+    ///
+    /// Each quote is a new BOLT11 with its own payment hash, valid for
+    /// about a minute.
+    pub async fn quote(&self, invoice_id: &str) -> Result<String, LightningError> {
+        self.quote_labelled(None, invoice_id).await
+    }
+
+    /// Quote `invoice_id` and remember the quote under `label`
+    async fn quote_labelled(&self, label: Option<&str>, invoice_id: &str) -> Result<String, LightningError> {
+        let started = now_unix();
+        let path = format!("/v1/invoices/{}/quote", invoice_id);
+        // A retried quote would be a new BOLT11, so quotes aren't retried
+        let quote: Option<Quote> = self.request(Method::POST, &path, None, false).await?;
+        let quote = quote
+            .ok_or_else(|| LightningError::InvoiceError(format!("Strike invoice {} not found", invoice_id)))?;
+
+        // Without the invoice id the payment can't be verified later, so fail the quote
+        let payment_hash = InvoiceParser::parse(&quote.ln_invoice)?.payment_hash_hex();
+        self.invoices.insert(payment_hash.clone(), invoice_id.to_string()).await?;
+        debug!(
+            "Strike quote {} for invoice {}: payment_hash={}, expires in {}s",
+            quote.quote_id, invoice_id, payment_hash, quote.expiration_in_sec
+        );
+
+        if let Some(label) = label {
+            let current = CurrentQuote {
+                invoice_id: invoice_id.to_string(),
+                bolt11: quote.ln_invoice.clone(),
+                expires_at: started + quote.expiration_in_sec,
+            };
+            // The quote is still valid; a label that can't be stored just gets a new invoice next time
+            match serde_json::to_string(&current) {
+                Ok(value) => {
+                    if let Err(e) = self.quotes.insert(label.to_string(), value).await {
+                        warn!("{}", e);
+                    }
+                }
+                Err(e) => warn!("Failed to serialize Strike quote for label {}: {}", label, e),
+            }
+        }
+        Ok(quote.ln_invoice)
+    }
+
+    /// BOLT11 for a label that was already invoiced
+    ///
+    /// Returns the current quote while it is valid. Once it has expired, the
+    /// Strike invoice is re-quoted if it is still unpaid.
+    async fn requote(&self, label: &str, current: CurrentQuote) -> Result<String, LightningError> {
+        if now_unix() < current.expires_at {
+            debug!("Reusing Strike quote for label {}", label);
+            return Ok(current.bolt11);
+        }
+
+        let invoice = self
+            .get_invoice(&current.invoice_id)
+            .await?
+            .ok_or_else(|| LightningError::InvoiceError(format!("Strike invoice {} not found", current.invoice_id)))?;
+        if invoice.state != InvoiceState::Unpaid {
+            return Err(LightningError::InvoiceError(format!(
+                "Strike invoice {} for label {} is {:?}",
+                invoice.invoice_id, label, invoice.state
+            )));
+        }
+        debug!("Strike quote for label {} expired, re-quoting invoice {}", label, invoice.invoice_id);
+        self.quote_labelled(Some(label), &invoice.invoice_id).await
+    }
+}
+
+/// Current unix time in seconds
+fn now_unix() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// BTC amount for msats, rounded up to whole sats (e.g. 1500 -> "0.00000002")
+fn btc_amount(amount_msats: u64) -> String {
+    let sats = amount_msats.div_ceil(1000);
+    format!("{}.{:08}", sats / SATS_PER_BTC, sats % SATS_PER_BTC)
+}
+
+/// msats for a BTC decimal string, if it is a valid amount with at most 8 decimals
+fn parse_btc_msats(amount: &str) -> Option<u64> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if fraction.len() > 8 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let whole = whole.parse::<u64>().ok()?;
+    let fraction = format!("{:0<8}", fraction).parse::<u64>().ok()?;
+    whole
+        .checked_mul(SATS_PER_BTC)?
+        .checked_add(fraction)?
+        .checked_mul(1000)
+}
+
+#[async_trait]
+impl LightningProvider for StrikeProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "strike", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment via Strike: payment_id={}", payment_id);
+
+        let invoice_id = match self.invoice_id(payment_hash) {
+            Some(invoice_id) => invoice_id,
+            None => {
+                warn!("No Strike invoice recorded for payment hash {}", hex::encode(payment_hash));
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: json!({
+                        "provider": "strike",
+                        "payment_hash": hex::encode(payment_hash),
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        let invoice = match self.get_invoice(&invoice_id).await? {
+            Some(invoice) => invoice,
+            None => {
+                return Ok(PaymentVerificationResult {
+                    verified: false,
+                    amount_msats: None,
+                    timestamp: None,
+                    metadata: json!({
+                        "provider": "strike",
+                        "payment_hash": hex::encode(payment_hash),
+                        "invoice_id": invoice_id,
+                        "error": "invoice_not_found",
+                    }),
+                });
+            }
+        };
+
+        let paid = invoice.state == InvoiceState::Paid;
+        debug!("Strike invoice {}: {:?}", invoice.invoice_id, invoice.state);
+
+        // Invoices are created in BTC; anything else can't be converted here
+        let amount_msats = invoice
+            .amount
+            .as_ref()
+            .filter(|amount| amount.currency == "BTC")
+            .and_then(|amount| parse_btc_msats(&amount.amount))
+            .filter(|_| paid);
+
+        Ok(PaymentVerificationResult {
+            verified: paid,
+            amount_msats,
+            timestamp: None,
+            metadata: json!({
+                "provider": "strike",
+                "payment_hash": hex::encode(payment_hash),
+                "invoice_id": invoice.invoice_id,
+                "state": format!("{:?}", invoice.state).to_lowercase(),
+                "correlation_id": invoice.correlation_id,
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        amount_msats: u64,
+        description: &str,
+        expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        self.create_invoice_ex(&InvoiceParams::new(amount_msats, description, expiry_seconds)).await
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "strike", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        let description = match &params.description {
+            DescriptionKind::Direct(description) => description.clone(),
+            DescriptionKind::Hash(_) => {
+                return Err(LightningError::InvoiceError(
+                    "Strike does not support description hashes".to_string(),
+                ));
+            }
+        };
+
+        let label = params.label.as_deref();
+        let current = label.and_then(|label| self.current_quote(label));
+        if let (Some(label), Some(current)) = (label, current) {
+            return self.requote(label, current).await;
+        }
+
+        // Strike sets the quote expiry (about a minute); expiry_seconds is ignored
+        let amount = btc_amount(params.amount_msats);
+        debug!("Creating invoice via Strike: amount={} BTC", amount);
+        let mut body = json!({
+            "description": description,
+            "amount": { "currency": "BTC", "amount": amount },
+        });
+        if let Some(label) = label {
+            body["correlationId"] = json!(label);
+        }
+
+        // Invoice creation is only idempotent with a correlation id, so it is never retried
+        let invoice: Option<Invoice> = self.request(Method::POST, "/v1/invoices", Some(&body), false).await?;
+        let invoice = invoice
+            .ok_or_else(|| LightningError::ProcessorError("Strike invoices endpoint not found".to_string()))?;
+
+        self.quote_labelled(label, &invoice.invoice_id).await
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        let invoice_id = match self.invoice_id(payment_hash) {
+            Some(invoice_id) => invoice_id,
+            None => return Ok(false),
+        };
+        Ok(self
+            .get_invoice(&invoice_id)
+            .await?
+            .map(|invoice| invoice.state == InvoiceState::Paid)
+            .unwrap_or(false))
+    }
+
+    async fn attach_storage(&self, node_api: Arc<dyn NodeAPI>) -> Result<(), LightningError> {
+        self.invoices.attach(node_api.clone()).await?;
+        self.quotes.attach(node_api).await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // Needs a key with the balance read scope; others report unreachable
+        let started = std::time::Instant::now();
+        let result = self.request::<Value>(Method::GET, "/v1/balances", None, true).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let reachable = matches!(result, Ok(Some(_)));
+        if let Err(e) = &result {
+            warn!("Strike health check failed: {}", e);
+        }
+        Ok(HealthStatus {
+            reachable,
+            latency_ms,
+            block_height: None,
+            synced_to_chain: None,
+            version: None,
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Strike
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
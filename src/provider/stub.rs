@@ -1,45 +1,406 @@
 //! Stub provider implementation
 //!
-//! For testing and development. Always succeeds verification.
+//! For testing and development. Always succeeds verification unless a
+//! `StubScenario` scripts per-payment outcomes (pending, failed, timeouts),
+//! pre-programmed results are returned for specific payment hashes, or a
+//! `StubConfig` injects failures and latency.
 
-use crate::provider::{ProviderType, LightningProvider, PaymentVerificationResult};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, ChannelInfo, NodeInfo, FeeEstimate, LightningProvider, PaymentVerificationResult, InvoiceParams, DescriptionKind,
+    MAX_DESCRIPTION_BYTES,
+};
 use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
 use async_trait::async_trait;
+use bitcoin::hashes::Hash;
+use bitcoin::Txid;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
 use tracing::debug;
 
+/// Balance reported by the stub provider
+pub const STUB_BALANCE_MSATS: u64 = 100_000_000;
+
+/// Node ID reported by the stub provider
+pub const STUB_NODE_ID: [u8; 33] = [2u8; 33];
+
+/// Key the stub signs its regtest invoices with
+const STUB_INVOICE_KEY: [u8; 32] = [0x11; 32];
+
+/// Amount reported for verified payments unless a scenario overrides it
+pub const STUB_AMOUNT_MSATS: u64 = 1000;
+
+/// Failure and latency injection for the stub provider
+#[derive(Debug, Clone)]
+pub struct StubConfig {
+    /// Fraction of calls that fail (0.0 never, 1.0 always); failures are
+    /// evenly spaced, e.g. 0.5 fails every second call
+    pub failure_rate: f64,
+    /// Error returned by failing calls (`NodeConnectionError("stub failure")` if unset)
+    pub failure_error: Option<LightningError>,
+    /// Amount reported for verified payments to amountless or unparseable invoices,
+    /// unless a scenario overrides it
+    pub fixed_amount_msats: u64,
+    /// Delay added to every call
+    pub latency_ms: u64,
+}
+
+impl Default for StubConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.0,
+            failure_error: None,
+            fixed_amount_msats: STUB_AMOUNT_MSATS,
+            latency_ms: 0,
+        }
+    }
+}
+
+/// Scripted verification outcome for a payment hash
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum StubOutcome {
+    /// Verified on every call
+    Verified {
+        #[serde(default)]
+        amount_msats: Option<u64>,
+    },
+    /// Unverified for the first `calls` verifications, verified afterwards
+    PendingThenSettled {
+        calls: u32,
+        #[serde(default)]
+        amount_msats: Option<u64>,
+    },
+    /// Never verified
+    Failed {
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Waits `after_ms`, then fails with a (retryable) timeout error
+    Timeout { after_ms: u64 },
+}
+
+/// Per-payment-hash outcomes for the stub provider
+///
+/// Loaded from `lightning.stub.scenario` (inline JSON or a path to a JSON
+/// file), or built in tests:
+///
+/// ```json
+/// {
+///   "default": { "outcome": "failed" },
+///   "payments": {
+///     "<payment hash hex>": { "outcome": "pending_then_settled", "calls": 2, "amount_msats": 5000 }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct StubScenario {
+    /// Outcome per payment hash (hex)
+    #[serde(default)]
+    pub payments: HashMap<String, StubOutcome>,
+    /// Outcome for unlisted payment hashes (verified if unset)
+    #[serde(default)]
+    pub default: Option<StubOutcome>,
+}
+
+impl StubScenario {
+    /// Empty scenario: every payment verifies
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Script the outcome for a payment hash
+    pub fn payment(mut self, payment_hash: &[u8; 32], outcome: StubOutcome) -> Self {
+        self.payments.insert(hex::encode(payment_hash), outcome);
+        self
+    }
+
+    /// Outcome for payment hashes without a scripted outcome
+    pub fn default_outcome(mut self, outcome: StubOutcome) -> Self {
+        self.default = Some(outcome);
+        self
+    }
+
+    /// Parse a scenario from JSON
+    pub fn from_json(json: &str) -> Result<Self, LightningError> {
+        serde_json::from_str(json)
+            .map_err(|e| LightningError::ConfigError(format!("Invalid stub scenario: {}", e)))
+    }
+
+    /// Load a scenario from `lightning.stub.scenario`: inline JSON, or a path to a JSON file
+    pub fn from_config_value(value: &str) -> Result<Self, LightningError> {
+        let value = value.trim();
+        if value.starts_with('{') {
+            return Self::from_json(value);
+        }
+        let json = std::fs::read_to_string(Path::new(value))
+            .map_err(|e| LightningError::ConfigError(format!("Failed to read stub scenario {}: {}", value, e)))?;
+        Self::from_json(&json)
+    }
+
+    fn outcome(&self, payment_hash: &[u8; 32]) -> Option<&StubOutcome> {
+        self.payments.get(&hex::encode(payment_hash)).or(self.default.as_ref())
+    }
+}
+
+/// A call made to the stub provider, in the order received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StubCall {
+    VerifyPayment { payment_hash: [u8; 32], payment_id: String },
+    CreateInvoice { amount_msats: u64 },
+    IsPaymentConfirmed { payment_hash: [u8; 32] },
+    CancelInvoice { payment_hash: [u8; 32] },
+    WithdrawOnchain { address: String, amount_sats: Option<u64> },
+}
+
 /// Stub provider implementation
-pub struct StubProvider;
+pub struct StubProvider {
+    /// Metadata attached to created invoices (payment hash -> metadata)
+    invoice_metadata: Arc<RwLock<HashMap<[u8; 32], serde_json::Value>>>,
+    /// Invoices created with a label (label -> invoice string)
+    invoice_labels: Arc<RwLock<HashMap<String, String>>>,
+    /// Scripted outcomes (always verified if unset)
+    scenario: Option<StubScenario>,
+    /// Verification results returned as-is for a payment hash, ahead of the scenario
+    responses: HashMap<[u8; 32], PaymentVerificationResult>,
+    /// Invoices returned for an amount instead of a fake invoice string
+    invoice_map: HashMap<u64, String>,
+    /// Verifications seen per payment hash, for `PendingThenSettled`
+    verify_counts: std::sync::Mutex<HashMap<[u8; 32], u32>>,
+    /// Every call made, in order
+    calls: std::sync::Mutex<Vec<StubCall>>,
+    /// Failure and latency injection
+    config: StubConfig,
+    /// Calls subject to failure injection so far
+    call_count: AtomicU64,
+}
 
 impl StubProvider {
     /// Create a new stub provider
     pub fn new() -> Self {
-        Self
+        Self::new_with_config(StubConfig::default())
+    }
+
+    /// Create a stub provider that injects failures and latency
+    pub fn new_with_config(config: StubConfig) -> Self {
+        Self {
+            invoice_metadata: Arc::new(RwLock::new(HashMap::new())),
+            invoice_labels: Arc::new(RwLock::new(HashMap::new())),
+            scenario: None,
+            responses: HashMap::new(),
+            invoice_map: HashMap::new(),
+            verify_counts: std::sync::Mutex::new(HashMap::new()),
+            calls: std::sync::Mutex::new(Vec::new()),
+            config,
+            call_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a stub provider that follows a scripted scenario
+    pub fn with_scenario(scenario: StubScenario) -> Self {
+        Self::new().scenario(scenario)
+    }
+
+    /// Follow a scripted scenario
+    pub fn scenario(mut self, scenario: StubScenario) -> Self {
+        self.scenario = Some(scenario);
+        self
+    }
+
+    /// Create a stub provider that returns pre-programmed verification results
+    ///
+    /// Payment hashes without a result verify as usual.
+    pub fn with_responses(responses: HashMap<[u8; 32], PaymentVerificationResult>) -> Self {
+        Self::new().responses(responses)
+    }
+
+    /// Return pre-programmed verification results
+    pub fn responses(mut self, responses: HashMap<[u8; 32], PaymentVerificationResult>) -> Self {
+        self.responses = responses;
+        self
+    }
+
+    /// Create a stub provider that returns pre-canned invoices by amount
+    ///
+    /// Amounts without an invoice get a fake invoice string as usual.
+    pub fn with_invoice_map(invoices: HashMap<u64, String>) -> Self {
+        Self::new().invoice_map(invoices)
+    }
+
+    /// Return pre-canned invoices by amount
+    pub fn invoice_map(mut self, invoices: HashMap<u64, String>) -> Self {
+        self.invoice_map = invoices;
+        self
+    }
+
+    /// Calls made so far, in order
+    pub fn calls(&self) -> Vec<StubCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, call: StubCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+
+    /// Apply the configured latency, then fail if this call is due to fail
+    async fn simulate(&self) -> Result<(), LightningError> {
+        if self.config.latency_ms > 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(self.config.latency_ms)).await;
+        }
+        if self.config.failure_rate <= 0.0 {
+            return Ok(());
+        }
+        let period = (1.0 / self.config.failure_rate.min(1.0)).round().max(1.0) as u64;
+        let call = self.call_count.fetch_add(1, Ordering::SeqCst) + 1;
+        if call % period == 0 {
+            debug!("Stub provider: injecting failure on call {}", call);
+            return Err(self
+                .config
+                .failure_error
+                .clone()
+                .unwrap_or_else(|| LightningError::NodeConnectionError("stub failure".into())));
+        }
+        Ok(())
+    }
+
+    /// Pre-canned invoice for the amount, else a signed regtest invoice with a random payment hash
+    fn make_invoice(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        self.record(StubCall::CreateInvoice { amount_msats: params.amount_msats });
+        if let Some(invoice) = self.invoice_map.get(&params.amount_msats) {
+            return Ok(invoice.clone());
+        }
+
+        let preimage: [u8; 32] = rand::random();
+        let builder = lightning_invoice::InvoiceBuilder::new(lightning_invoice::Currency::Regtest);
+        // Like LDK, commit to the hash of descriptions too long for the `d` field
+        let builder = match &params.description {
+            DescriptionKind::Direct(description) if description.len() <= MAX_DESCRIPTION_BYTES => {
+                builder.description(description.clone())
+            }
+            DescriptionKind::Direct(description) => {
+                builder.description_hash(bitcoin::hashes::sha256::Hash::hash(description.as_bytes()))
+            }
+            DescriptionKind::Hash(hash) => builder.description_hash(bitcoin::hashes::sha256::Hash::from_byte_array(*hash)),
+        };
+        let builder = if params.amount_msats > 0 {
+            builder.amount_milli_satoshis(params.amount_msats)
+        } else {
+            builder
+        };
+        let secp = secp256k1::Secp256k1::new();
+        let key = secp256k1::SecretKey::from_slice(&STUB_INVOICE_KEY)
+            .map_err(|e| LightningError::ProcessorError(format!("Invalid stub invoice key: {}", e)))?;
+        let invoice = builder
+            .payment_hash(bitcoin::hashes::sha256::Hash::hash(&preimage))
+            .payment_secret(lightning_invoice::PaymentSecret(rand::random()))
+            .expiry_time(std::time::Duration::from_secs(params.expiry_seconds))
+            .min_final_cltv_expiry_delta(params.min_final_cltv_expiry.unwrap_or(18))
+            .current_timestamp()
+            .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &key))
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to build stub invoice: {}", e)))?;
+        Ok(invoice.to_string())
+    }
+
+    /// Resolve the scripted outcome for a verification
+    ///
+    /// Returns whether the payment is verified and the amount to report:
+    /// the scripted amount, else the invoice amount, else `fixed_amount_msats`.
+    /// `advance` counts the call towards `PendingThenSettled`.
+    async fn resolve(&self, invoice: &str, payment_hash: &[u8; 32], advance: bool) -> Result<(bool, u64), LightningError> {
+        let default_amount = InvoiceParser::parse(invoice)
+            .map(|data| data.amount_msats)
+            .ok()
+            .filter(|amount| *amount > 0)
+            .unwrap_or(self.config.fixed_amount_msats);
+        let outcome = match self.scenario.as_ref().and_then(|scenario| scenario.outcome(payment_hash)) {
+            Some(outcome) => outcome,
+            None => return Ok((true, default_amount)),
+        };
+        match outcome {
+            StubOutcome::Verified { amount_msats } => Ok((true, amount_msats.unwrap_or(default_amount))),
+            StubOutcome::PendingThenSettled { calls, amount_msats } => {
+                let mut counts = self.verify_counts.lock().unwrap();
+                let seen = counts.entry(*payment_hash).or_insert(0);
+                let settled = *seen >= *calls;
+                if advance {
+                    *seen += 1;
+                }
+                Ok((settled, amount_msats.unwrap_or(default_amount)))
+            }
+            StubOutcome::Failed { .. } => Ok((false, 0)),
+            StubOutcome::Timeout { after_ms } => {
+                tokio::time::sleep(std::time::Duration::from_millis(*after_ms)).await;
+                Err(LightningError::ProcessorError(format!(
+                    "Stub provider: verification timed out after {} ms",
+                    after_ms
+                )))
+            }
+        }
+    }
+}
+
+impl Default for StubProvider {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[async_trait]
 impl LightningProvider for StubProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "stub", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
     async fn verify_payment(
         &self,
-        _invoice: &str,
-        _payment_hash: &[u8; 32],
+        invoice: &str,
+        payment_hash: &[u8; 32],
         payment_id: &str,
     ) -> Result<PaymentVerificationResult, LightningError> {
-        debug!("Stub provider: verifying payment (always succeeds): payment_id={}", payment_id);
+        debug!("Stub provider: verifying payment: payment_id={}", payment_id);
+        self.record(StubCall::VerifyPayment {
+            payment_hash: *payment_hash,
+            payment_id: payment_id.to_string(),
+        });
+        self.simulate().await?;
+        if let Some(result) = self.responses.get(payment_hash) {
+            return Ok(result.clone());
+        }
+        
+        let invoice_metadata = self.invoice_metadata.read().await
+            .get(payment_hash)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        
+        let (verified, amount_msats) = self.resolve(invoice, payment_hash, true).await?;
+        let reason = match self.scenario.as_ref().and_then(|scenario| scenario.outcome(payment_hash)) {
+            Some(StubOutcome::Failed { reason }) => reason.clone(),
+            _ => None,
+        };
         
-        // Stub: Always return verified
         Ok(PaymentVerificationResult {
-            verified: true,
-            amount_msats: Some(1000), // Stub amount
-            timestamp: Some(
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            ),
+            verified,
+            amount_msats: Some(amount_msats).filter(|_| verified),
+            timestamp: if verified {
+                Some(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                )
+            } else {
+                None
+            },
             metadata: serde_json::json!({
                 "provider": "stub",
                 "note": "This is a stub implementation for testing",
+                "invoice_metadata": invoice_metadata,
+                "error": reason,
             }),
         })
     }
@@ -48,22 +409,155 @@ impl LightningProvider for StubProvider {
         &self,
         amount_msats: u64,
         description: &str,
-        _expiry_seconds: u64,
+        expiry_seconds: u64,
     ) -> Result<String, LightningError> {
         debug!("Stub provider: creating invoice: amount={} msats, description={}", amount_msats, description);
+        self.simulate().await?;
+        self.make_invoice(&InvoiceParams::new(amount_msats, description, expiry_seconds))
+    }
+
+    #[tracing::instrument(
+        name = "lightning.provider.create_invoice",
+        skip_all,
+        fields(provider = "stub", invoice.amount_msats = params.amount_msats)
+    )]
+    async fn create_invoice_ex(&self, params: &InvoiceParams) -> Result<String, LightningError> {
+        // Stub: Labels are unique, reuse the invoice created for a known label
+        if let Some(label) = &params.label {
+            if let Some(existing) = self.invoice_labels.read().await.get(label) {
+                return Ok(existing.clone());
+            }
+        }
+        
+        let description = match &params.description {
+            DescriptionKind::Direct(description) => description.clone(),
+            DescriptionKind::Hash(hash) => hex::encode(hash),
+        };
+        debug!("Stub provider: creating invoice: amount={} msats, description={}", params.amount_msats, description);
+        self.simulate().await?;
+        let invoice = self.make_invoice(params)?;
         
-        // Stub: Return a fake invoice
-        // In production, this would be a real BOLT11 invoice
-        Ok(format!("lnbc{}u1pstub_invoice", amount_msats))
+        // Stub: Remember metadata so verification can echo it back
+        match InvoiceParser::parse(&invoice) {
+            Ok(data) => {
+                self.invoice_metadata.write().await.insert(
+                    data.payment_hash(),
+                    serde_json::json!({
+                        "label": params.label,
+                        "metadata": params.metadata,
+                    }),
+                );
+            }
+            Err(e) => debug!("Stub provider: not keeping metadata for unparseable invoice: {}", e),
+        }
+        if let Some(label) = &params.label {
+            self.invoice_labels.write().await.insert(label.clone(), invoice.clone());
+        }
+        Ok(invoice)
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        self.record(StubCall::IsPaymentConfirmed { payment_hash: *payment_hash });
+        self.simulate().await?;
+        if let Some(result) = self.responses.get(payment_hash) {
+            return Ok(result.verified);
+        }
+        // Stub: Confirmed unless scripted otherwise; doesn't count towards pending calls
+        Ok(self.resolve("", payment_hash, false).await?.0)
     }
 
-    async fn is_payment_confirmed(&self, _payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
-        // Stub: Always return true
-        Ok(true)
+    async fn cancel_invoice(&self, payment_hash: &[u8; 32]) -> Result<(), LightningError> {
+        debug!("Stub provider: cancelling invoice {}", hex::encode(payment_hash));
+        self.record(StubCall::CancelInvoice { payment_hash: *payment_hash });
+        self.simulate().await
+    }
+
+    async fn balance_msats(&self) -> Result<u64, LightningError> {
+        // Stub: Fixed balance
+        Ok(STUB_BALANCE_MSATS)
+    }
+
+    async fn withdraw_onchain(
+        &self,
+        address: &str,
+        amount_sats: Option<u64>,
+        _fee_rate: Option<f64>,
+    ) -> Result<Txid, LightningError> {
+        debug!("Stub provider: simulating withdrawal of {:?} sats to {}", amount_sats, address);
+        self.record(StubCall::WithdrawOnchain {
+            address: address.to_string(),
+            amount_sats,
+        });
+        
+        // Stub: Return a random txid
+        Ok(Txid::from_byte_array(rand::random()))
+    }
+
+    async fn estimate_fee(&self, _invoice: &str, _amount_msats: Option<u64>) -> Result<FeeEstimate, LightningError> {
+        // Stub: Flat 1 msat fee
+        Ok(FeeEstimate {
+            fee_base_msats: 1,
+            fee_proportional: 0.0,
+            estimated_total_fee_msats: 1,
+            confidence: 1.0,
+        })
+    }
+
+    async fn list_channels(&self) -> Result<Vec<ChannelInfo>, LightningError> {
+        // Stub: A single fake channel
+        Ok(vec![ChannelInfo {
+            channel_id: [1u8; 32],
+            counterparty_node_id: [2u8; 33],
+            capacity_msats: 1_000_000_000,
+            local_balance_msats: 600_000_000,
+            remote_balance_msats: 400_000_000,
+            is_active: true,
+            short_channel_id: Some(1),
+            is_public: false,
+        }])
+    }
+
+    async fn get_node_info(&self) -> Result<NodeInfo, LightningError> {
+        // Stub: A fixed node with the fake channel from `list_channels`
+        Ok(NodeInfo {
+            node_id: STUB_NODE_ID,
+            alias: Some("stub".to_string()),
+            color: None,
+            num_peers: 1,
+            num_active_channels: 1,
+            num_pending_channels: 0,
+            block_height: 0,
+            version: Some("stub".to_string()),
+        })
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        // Stub: Always perfectly healthy
+        Ok(HealthStatus {
+            reachable: true,
+            latency_ms: 0,
+            block_height: Some(0),
+            synced_to_chain: Some(true),
+            version: Some("stub".to_string()),
+        })
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            can_create_invoices: true,
+            can_manage_channels: true,
+            can_withdraw_onchain: true,
+            ..Default::default()
+        }
     }
 
     fn provider_type(&self) -> ProviderType {
         ProviderType::Stub
     }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
 }
 
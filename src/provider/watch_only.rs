@@ -0,0 +1,291 @@
+//! Watch-only provider implementation
+//!
+//! Verifies payments to invoices created elsewhere (e.g. a phone wallet)
+//! without running a node. It can't create invoices. Payments are checked
+//! against, in order:
+//!
+//! - an LNURL-verify (LUD-21) URL registered for the payment hash, as
+//!   returned alongside the invoice by an LNURL-pay service. A settled
+//!   payment only counts if the returned preimage hashes to the payment hash.
+//! - an LNBits wallet queried with its read-only invoice key, if configured.
+
+use crate::provider::lnbits::{LNBitsConfig, LNBitsProvider};
+use crate::provider::payment_index::PaymentIndex;
+use crate::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use crate::provider::{
+    ProviderType, ProviderCapabilities, HealthStatus, LightningProvider, PaymentVerificationResult, InvoiceParams,
+};
+use crate::error::LightningError;
+use crate::invoice::InvoiceParser;
+use async_trait::async_trait;
+use bitcoin::hashes::{sha256, Hash};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// File in the data directory holding the payment hash -> LNURL-verify URL index
+pub const WATCH_ONLY_VERIFY_URL_FILE: &str = "watch_only_verify_urls.json";
+
+/// Watch-only provider configuration
+#[derive(Debug, Clone, Default)]
+pub struct WatchOnlyConfig {
+    /// LNBits wallet to check payments against, with its invoice key (not the admin key)
+    pub lnbits: Option<LNBitsConfig>,
+    /// Where registered LNURL-verify URLs are kept (in memory only if unset)
+    pub verify_url_index_path: Option<PathBuf>,
+    /// Request timeout and retry policy for LNURL-verify requests
+    pub retry_policy: ProviderRetryPolicy,
+}
+
+/// LUD-21 verify response
+#[derive(Debug, Deserialize)]
+struct VerifyResponse {
+    status: String,
+    #[serde(default)]
+    settled: bool,
+    #[serde(default)]
+    preimage: Option<String>,
+    #[serde(default)]
+    pr: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Watch-only provider implementation
+pub struct WatchOnlyProvider {
+    config: WatchOnlyConfig,
+    http_client: Client,
+    lnbits: Option<LNBitsProvider>,
+    /// Payment hash -> LNURL-verify URL
+    verify_urls: PaymentIndex,
+}
+
+impl WatchOnlyProvider {
+    /// Create a new watch-only provider, loading registered verify URLs if they exist
+    pub fn new(config: WatchOnlyConfig) -> Result<Self, LightningError> {
+        let http_client = Client::builder()
+            .timeout(config.retry_policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+        let lnbits = match &config.lnbits {
+            Some(lnbits) if lnbits.admin_key.is_some() => {
+                return Err(LightningError::ConfigError(
+                    "Watch-only provider takes the LNBits invoice key, not the admin key".to_string(),
+                ));
+            }
+            Some(lnbits) => Some(LNBitsProvider::new(lnbits.clone())?),
+            None => None,
+        };
+        let verify_urls = PaymentIndex::open("watch-only verify URL index", config.verify_url_index_path.clone())?;
+
+        Ok(Self {
+            config,
+            http_client,
+            lnbits,
+            verify_urls,
+        })
+    }
+
+    /// Register the LNURL-verify URL returned with an invoice
+    pub async fn register_verify_url(&self, payment_hash: &[u8; 32], url: &str) -> Result<(), LightningError> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| LightningError::ConfigError(format!("Invalid LNURL-verify URL {}: {}", url, e)))?;
+        if !matches!(parsed.scheme(), "https" | "http") {
+            return Err(LightningError::ConfigError(format!("Invalid LNURL-verify URL {}: not http(s)", url)));
+        }
+        self.verify_urls.insert(hex::encode(payment_hash), url.to_string()).await
+    }
+
+    /// LNURL-verify URL registered for a payment hash
+    pub fn verify_url(&self, payment_hash: &[u8; 32]) -> Option<String> {
+        self.verify_urls.get(payment_hash)
+    }
+
+    /// Query an LNURL-verify URL, retrying according to the retry policy
+    async fn lnurl_verify(&self, url: &str) -> Result<VerifyResponse, LightningError> {
+        let policy = &self.config.retry_policy;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let response = match self.http_client.get(url).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    let class = if e.is_timeout() { ErrorClass::Timeout } else { ErrorClass::Connect };
+                    if policy.should_retry(class, attempt + 1) {
+                        attempt += 1;
+                        warn!("LNURL-verify request failed (attempt {}/{}): {}", attempt, policy.max_retries, e);
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(LightningError::NodeConnectionError(format!("LNURL-verify request failed: {}", e)));
+                }
+            };
+
+            let status = response.status();
+            if status.is_server_error() && policy.should_retry(ErrorClass::ServerError, attempt + 1) {
+                attempt += 1;
+                warn!("LNURL-verify returned {} (attempt {}/{})", status, attempt, policy.max_retries);
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                continue;
+            }
+
+            if !status.is_success() {
+                return Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                });
+            }
+
+            return response
+                .json::<VerifyResponse>()
+                .await
+                .map_err(|e| LightningError::ProcessorError(format!("Failed to parse LNURL-verify response: {}", e)));
+        }
+    }
+
+    /// Verify a payment against its LNURL-verify URL
+    async fn verify_with_lnurl(
+        &self,
+        url: &str,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        let response = self.lnurl_verify(url).await?;
+        if response.status != "OK" {
+            return Ok(PaymentVerificationResult {
+                verified: false,
+                amount_msats: None,
+                timestamp: None,
+                metadata: json!({
+                    "provider": "watch_only",
+                    "source": "lnurl_verify",
+                    "payment_hash": hex::encode(payment_hash),
+                    "error": response.reason.unwrap_or(response.status),
+                }),
+            });
+        }
+
+        // Don't take the service's word for it: the preimage proves payment
+        let preimage_valid = response
+            .preimage
+            .as_deref()
+            .and_then(|preimage| hex::decode(preimage).ok())
+            .map(|preimage| sha256::Hash::hash(&preimage).to_byte_array() == *payment_hash)
+            .unwrap_or(false);
+        if response.settled && !preimage_valid {
+            warn!("LNURL-verify reported {} settled without a matching preimage", hex::encode(payment_hash));
+        }
+        let verified = response.settled && preimage_valid;
+
+        let invoice = response.pr.as_deref().filter(|_| invoice.is_empty()).unwrap_or(invoice);
+        let amount_msats = InvoiceParser::parse(invoice)
+            .ok()
+            .map(|data| data.amount_msats)
+            .filter(|amount| verified && *amount > 0);
+
+        Ok(PaymentVerificationResult {
+            verified,
+            amount_msats,
+            timestamp: None,
+            metadata: json!({
+                "provider": "watch_only",
+                "source": "lnurl_verify",
+                "payment_hash": hex::encode(payment_hash),
+                "settled": response.settled,
+                "preimage": response.preimage,
+                "error": if response.settled && !preimage_valid { Some("preimage_mismatch") } else { None },
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl LightningProvider for WatchOnlyProvider {
+    #[tracing::instrument(
+        name = "lightning.provider.verify_payment",
+        skip_all,
+        fields(provider = "watch_only", payment.id = %payment_id, payment.hash = %hex::encode(payment_hash))
+    )]
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        payment_hash: &[u8; 32],
+        payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        debug!("Verifying payment watch-only: payment_id={}", payment_id);
+
+        if let Some(url) = self.verify_url(payment_hash) {
+            return self.verify_with_lnurl(&url, invoice, payment_hash).await;
+        }
+        if let Some(lnbits) = &self.lnbits {
+            let mut result = lnbits.verify_payment(invoice, payment_hash, payment_id).await?;
+            result.metadata["source"] = json!("lnbits");
+            return Ok(result);
+        }
+
+        Ok(PaymentVerificationResult {
+            verified: false,
+            amount_msats: None,
+            timestamp: None,
+            metadata: json!({
+                "provider": "watch_only",
+                "payment_hash": hex::encode(payment_hash),
+                "error": "no_verify_source",
+            }),
+        })
+    }
+
+    async fn create_invoice(
+        &self,
+        _amount_msats: u64,
+        _description: &str,
+        _expiry_seconds: u64,
+    ) -> Result<String, LightningError> {
+        Err(LightningError::Unsupported("Watch-only provider can't create invoices".to_string()))
+    }
+
+    async fn create_invoice_ex(&self, _params: &InvoiceParams) -> Result<String, LightningError> {
+        Err(LightningError::Unsupported("Watch-only provider can't create invoices".to_string()))
+    }
+
+    async fn is_payment_confirmed(&self, payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        if let Some(url) = self.verify_url(payment_hash) {
+            return Ok(self.verify_with_lnurl(&url, "", payment_hash).await?.verified);
+        }
+        match &self.lnbits {
+            Some(lnbits) => lnbits.is_payment_confirmed(payment_hash).await,
+            None => Ok(false),
+        }
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        match &self.lnbits {
+            Some(lnbits) => lnbits.health_check().await,
+            // LNURL-verify services are only known per payment; there is nothing to probe
+            None => Ok(HealthStatus {
+                reachable: true,
+                latency_ms: 0,
+                block_height: None,
+                synced_to_chain: None,
+                version: None,
+            }),
+        }
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            can_verify: true,
+            ..Default::default()
+        }
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::WatchOnly
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,60 @@
+//! Fixed-window rate limiting for incoming payment requests
+//!
+//! Each key gets a counter that resets once its window has elapsed. The
+//! processor keys on a `payment_id` prefix so a single client flooding
+//! `PaymentRequestCreated` events can't monopolize the provider.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Number of tracked keys above which expired windows are pruned
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Per-key request counters
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    /// `(count, window_start)` for each key
+    windows: HashMap<String, (u32, Instant)>,
+}
+
+impl RateLimiter {
+    /// Create a limiter with no recorded requests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a request for `key` and return whether it is within the limit
+    ///
+    /// A request is allowed if fewer than `max_per_window` requests have been
+    /// recorded for `key` in the current window. Rejected requests are not
+    /// counted, so a client that backs off regains access when the window ends.
+    pub fn check_and_record(&mut self, key: &str, max_per_window: u32, window_secs: u64) -> bool {
+        let now = Instant::now();
+        let window = Duration::from_secs(window_secs);
+
+        if self.windows.len() >= PRUNE_THRESHOLD {
+            self.windows.retain(|_, (_, start)| now.duration_since(*start) < window);
+        }
+
+        let (count, start) = self.windows.entry(key.to_string()).or_insert((0, now));
+        if now.duration_since(*start) >= window {
+            *count = 0;
+            *start = now;
+        }
+        if *count >= max_per_window {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    /// Number of keys currently tracked
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Whether no keys are tracked
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+}
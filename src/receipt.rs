@@ -0,0 +1,34 @@
+//! Payment receipts for settled payments
+
+use crate::error::LightningError;
+use crate::provider::ProviderType;
+use serde::{Deserialize, Serialize};
+
+/// Storage tree holding receipts keyed by payment id
+pub const PAYMENT_RECEIPTS_TREE: &str = "payment_receipts";
+
+/// Receipt for a settled payment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentReceipt {
+    pub payment_id: String,
+    pub payment_hash: [u8; 32],
+    pub preimage: Option<[u8; 32]>,
+    pub amount_msats: u64,
+    pub fee_msats: u64,
+    pub settled_at: u64,
+    pub provider: ProviderType,
+    pub metadata: serde_json::Value,
+}
+
+impl PaymentReceipt {
+    /// Serialize receipt to JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("PaymentReceipt serialization cannot fail")
+    }
+
+    /// Deserialize receipt from JSON
+    pub fn from_json(s: &str) -> Result<Self, LightningError> {
+        serde_json::from_str(s)
+            .map_err(|e| LightningError::ProcessorError(format!("Invalid payment receipt: {}", e)))
+    }
+}
@@ -0,0 +1,84 @@
+//! Graceful shutdown
+//!
+//! `ShutdownController` turns SIGTERM/SIGINT into a cancelled
+//! `CancellationToken`. The event loop stops taking new events once the
+//! token is cancelled, and `main` then gives in-flight payments a window to
+//! settle (see `LightningProcessor::drain_inflight`) before exiting.
+
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Shutdown signal shared by the module's tasks
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownController {
+    token: CancellationToken,
+}
+
+impl ShutdownController {
+    /// Create a controller that hasn't been triggered
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token cancelled when shutdown starts
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Start shutting down, as if a signal had been received
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    /// Whether shutdown has started
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Spawn a task that triggers shutdown on SIGTERM or SIGINT
+    pub fn listen_for_signals(&self) -> tokio::task::JoinHandle<()> {
+        let controller = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                signal = wait_for_signal() => {
+                    info!("Received {}, shutting down", signal);
+                    controller.trigger();
+                }
+                _ = controller.token.cancelled() => {}
+            }
+        })
+    }
+}
+
+/// Wait for SIGTERM or SIGINT and return its name
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            warn!("Failed to install SIGTERM handler: {}", e);
+            return wait_for_ctrl_c().await;
+        }
+    };
+    tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        signal = wait_for_ctrl_c() => signal,
+    }
+}
+
+/// Wait for Ctrl-C and return its name
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    wait_for_ctrl_c().await
+}
+
+async fn wait_for_ctrl_c() -> &'static str {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("Failed to listen for SIGINT: {}", e);
+        // Never resolve rather than shutting down on a broken handler
+        std::future::pending::<()>().await;
+    }
+    "SIGINT"
+}
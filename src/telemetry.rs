@@ -0,0 +1,59 @@
+//! OpenTelemetry export of tracing spans
+//!
+//! The payment flow is instrumented with `tracing` spans
+//! (`lightning.process_payment`, `lightning.provider.*`). With the
+//! `telemetry` feature, `init_tracer` installs a subscriber that also exports
+//! them to an OTLP collector.
+
+use crate::error::LightningError;
+
+/// Service name reported to the collector
+#[cfg(feature = "telemetry")]
+const SERVICE_NAME: &str = "blvm-lightning";
+
+/// Install a tracing subscriber that logs to stdout and exports spans via OTLP (gRPC)
+///
+/// Fails if the module was built without the `telemetry` feature or a global
+/// subscriber is already set; the caller should then fall back to plain logging.
+#[cfg(feature = "telemetry")]
+pub fn init_tracer(endpoint: &str) -> Result<(), LightningError> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(opentelemetry_sdk::trace::Config::default().with_resource(
+            opentelemetry_sdk::Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]),
+        ))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .map_err(|e| LightningError::ConfigError(format!("Failed to create OTLP exporter for {}: {}", endpoint, e)))?;
+    let tracer = provider.tracer(SERVICE_NAME);
+    opentelemetry::global::set_tracer_provider(provider);
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()
+        .map_err(|e| LightningError::ConfigError(format!("Failed to install tracing subscriber: {}", e)))
+}
+
+/// Install a tracing subscriber that exports spans via OTLP
+///
+/// Always fails: the module was built without the `telemetry` feature.
+#[cfg(not(feature = "telemetry"))]
+pub fn init_tracer(_endpoint: &str) -> Result<(), LightningError> {
+    Err(LightningError::ConfigError(
+        "OpenTelemetry export requires building with the `telemetry` feature".to_string(),
+    ))
+}
+
+/// Flush pending spans before exit
+pub fn shutdown_tracer() {
+    #[cfg(feature = "telemetry")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
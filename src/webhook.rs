@@ -0,0 +1,333 @@
+//! Payment webhooks
+//!
+//! Incoming: LNBits POSTs the payment record to `lightning.lnbits.webhook_url`
+//! when an invoice is paid. The receiver served on `lightning.webhook_listen`
+//! only takes the payment hash from the body: the payment is settled through
+//! the same provider verification as `process_payment`, so a forged or
+//! replayed webhook can't mark anything paid.
+//!
+//! Outgoing: with `lightning.webhook.url` set, the processor POSTs a JSON
+//! event to it as invoices and payments move through their lifecycle
+//! (filtered by `lightning.webhook.events`). The body is signed with
+//! HMAC-SHA256 under `lightning.webhook.secret`, hex encoded in the
+//! `X-Webhook-Signature` header, so the receiver can check it came from us.
+
+use crate::error::LightningError;
+use crate::processor::LightningProcessor;
+use crate::provider::retry::{retry_idempotent, ProviderRetryPolicy};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+use blvm_node::module::traits::ModuleContext;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+/// Path LNBits webhooks are received on
+pub const LNBITS_WEBHOOK_PATH: &str = "/webhook/lnbits";
+
+/// Header carrying the hex HMAC-SHA256 of an outgoing webhook body
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Default retries after the first delivery attempt
+pub const DEFAULT_WEBHOOK_MAX_RETRIES: u32 = 3;
+
+/// Default timeout for a single delivery attempt
+pub const DEFAULT_WEBHOOK_TIMEOUT_MS: u64 = 5_000;
+
+/// Delay before the first redelivery; doubled for each further one
+const WEBHOOK_BACKOFF_BASE_MS: u64 = 500;
+
+/// Result of handling a payment webhook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookOutcome {
+    /// The provider confirmed the payment and it is now settled
+    Settled { payment_id: String },
+    /// The payment was already settled; nothing was done
+    AlreadySettled { payment_id: String },
+    /// The provider doesn't (yet) report the payment as paid
+    Unverified { payment_id: String },
+    /// The payment hash doesn't belong to an invoice created by this module
+    UnknownInvoice,
+}
+
+/// Fields read from an LNBits webhook body (the rest of the payment record is ignored)
+#[derive(Debug, Deserialize)]
+struct LNBitsWebhook {
+    payment_hash: String,
+}
+
+/// Serve the webhook receiver on `addr` until the task is dropped
+pub async fn serve(processor: Arc<LightningProcessor>, addr: SocketAddr) -> Result<(), LightningError> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| LightningError::ConfigError(format!("Failed to bind webhook listener {}: {}", addr, e)))?;
+    serve_on(processor, listener).await
+}
+
+/// Serve the webhook receiver on an already bound listener
+pub async fn serve_on(processor: Arc<LightningProcessor>, listener: TcpListener) -> Result<(), LightningError> {
+    let app = axum::Router::new()
+        .route(LNBITS_WEBHOOK_PATH, axum::routing::post(lnbits_webhook))
+        .with_state(processor);
+
+    if let Ok(addr) = listener.local_addr() {
+        info!("Receiving payment webhooks on http://{}{}", addr, LNBITS_WEBHOOK_PATH);
+    }
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| LightningError::ProcessorError(format!("Webhook listener failed: {}", e)))
+}
+
+async fn lnbits_webhook(
+    State(processor): State<Arc<LightningProcessor>>,
+    body: axum::body::Bytes,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let payment_hash = match serde_json::from_slice::<LNBitsWebhook>(&body)
+        .ok()
+        .and_then(|webhook| hex::decode(webhook.payment_hash).ok())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+    {
+        Some(payment_hash) => payment_hash,
+        None => {
+            warn!("Rejected malformed LNBits webhook");
+            return (StatusCode::BAD_REQUEST, Json(json!({"error": "invalid payment_hash"})));
+        }
+    };
+
+    match processor.handle_webhook(&payment_hash).await {
+        Ok(WebhookOutcome::Settled { payment_id }) => {
+            (StatusCode::OK, Json(json!({"status": "settled", "payment_id": payment_id})))
+        }
+        Ok(WebhookOutcome::AlreadySettled { payment_id }) => {
+            (StatusCode::OK, Json(json!({"status": "already_settled", "payment_id": payment_id})))
+        }
+        Ok(WebhookOutcome::Unverified { payment_id }) => {
+            (StatusCode::ACCEPTED, Json(json!({"status": "unverified", "payment_id": payment_id})))
+        }
+        Ok(WebhookOutcome::UnknownInvoice) => {
+            warn!("LNBits webhook for unknown payment hash {}", hex::encode(payment_hash));
+            (StatusCode::NOT_FOUND, Json(json!({"error": "unknown invoice"})))
+        }
+        Err(e) => {
+            warn!("LNBits webhook for {} failed: {}", hex::encode(payment_hash), e);
+            (StatusCode::BAD_GATEWAY, Json(json!({"error": e.to_string()})))
+        }
+    }
+}
+
+/// Outgoing webhook settings (`lightning.webhook.*`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    /// Endpoint events are POSTed to
+    pub url: String,
+    /// HMAC key the body signature is computed with
+    pub secret: String,
+    /// Retries after the first attempt, for connection failures, timeouts and 5xx/429 responses
+    pub max_retries: u32,
+    /// Timeout for a single attempt
+    pub timeout_ms: u64,
+    /// Events delivered to the endpoint
+    pub filter: WebhookFilter,
+}
+
+impl WebhookConfig {
+    /// Read `lightning.webhook.url`, `secret`, `max_retries`, `timeout_ms` and `events`
+    ///
+    /// `None` when no URL is set. A URL without a secret is rejected, since
+    /// receivers couldn't tell our events from anyone else's.
+    pub fn from_config(ctx: &ModuleContext) -> Result<Option<Self>, LightningError> {
+        let url = match ctx.get_config("lightning.webhook.url") {
+            Some(url) if !url.trim().is_empty() => url.trim().to_string(),
+            _ => return Ok(None),
+        };
+        let parsed = reqwest::Url::parse(&url)
+            .map_err(|e| LightningError::ConfigError(format!("Invalid lightning.webhook.url {}: {}", url, e)))?;
+        if !matches!(parsed.scheme(), "https" | "http") {
+            return Err(LightningError::ConfigError(format!("Invalid lightning.webhook.url {}: not http(s)", url)));
+        }
+        let secret = match ctx.get_config("lightning.webhook.secret") {
+            Some(secret) if !secret.is_empty() => secret.to_string(),
+            _ => return Err(LightningError::ConfigError("lightning.webhook.url requires lightning.webhook.secret".to_string())),
+        };
+        Ok(Some(Self {
+            url,
+            secret,
+            max_retries: ctx.get_config_or("lightning.webhook.max_retries", "")
+                .parse::<u32>()
+                .unwrap_or(DEFAULT_WEBHOOK_MAX_RETRIES),
+            timeout_ms: match ctx.get_config_or("lightning.webhook.timeout_ms", "").parse::<u64>() {
+                Ok(timeout_ms) if timeout_ms > 0 => timeout_ms,
+                _ => DEFAULT_WEBHOOK_TIMEOUT_MS,
+            },
+            filter: WebhookFilter::parse(&ctx.get_config_or("lightning.webhook.events", ""))?,
+        }))
+    }
+}
+
+/// Kind of outgoing webhook event, as subscribed to in `lightning.webhook.events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    InvoiceCreated,
+    PaymentReceived,
+    PaymentSent,
+    PaymentFailed,
+    InvoiceExpired,
+}
+
+impl WebhookEventKind {
+    /// Parse a config name: the `"event"` string or its short form
+    /// ("created", "received", "sent", "failed", "expired")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "invoice_created" | "created" => Some(WebhookEventKind::InvoiceCreated),
+            "payment_received" | "received" => Some(WebhookEventKind::PaymentReceived),
+            "payment_sent" | "sent" => Some(WebhookEventKind::PaymentSent),
+            "payment_failed" | "failed" => Some(WebhookEventKind::PaymentFailed),
+            "invoice_expired" | "expired" => Some(WebhookEventKind::InvoiceExpired),
+            _ => None,
+        }
+    }
+}
+
+/// Events an endpoint is subscribed to (all of them if `events` is empty)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebhookFilter {
+    pub events: Vec<WebhookEventKind>,
+}
+
+impl WebhookFilter {
+    /// Parse a comma-separated list of event names, e.g. `"received,failed"`
+    pub fn parse(events: &str) -> Result<Self, LightningError> {
+        let events = events
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| {
+                WebhookEventKind::from_name(name).ok_or_else(|| {
+                    LightningError::ConfigError(format!("Unknown webhook event in lightning.webhook.events: {}", name.trim()))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { events })
+    }
+
+    pub fn allows(&self, kind: WebhookEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// Outgoing webhook event, serialized with its kind in `"event"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// An invoice was created; `payment_id` is its payment hash (hex)
+    InvoiceCreated { payment_id: String, bolt11: String, amount_msats: u64, expiry_at: u64 },
+    /// An incoming payment settled
+    PaymentReceived { payment_id: String, payment_hash: String, amount_msats: u64, fee_msats: u64 },
+    /// An outgoing payment completed
+    PaymentSent { payment_id: String, payment_hash: String, preimage: String, fee_msats: u64 },
+    /// A payment was rejected or cancelled
+    PaymentFailed { payment_id: String, reason: String },
+    /// A payment arrived for an invoice that had already expired
+    InvoiceExpired { payment_id: String, payment_hash: String },
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::InvoiceCreated { .. } => WebhookEventKind::InvoiceCreated,
+            WebhookEvent::PaymentReceived { .. } => WebhookEventKind::PaymentReceived,
+            WebhookEvent::PaymentSent { .. } => WebhookEventKind::PaymentSent,
+            WebhookEvent::PaymentFailed { .. } => WebhookEventKind::PaymentFailed,
+            WebhookEvent::InvoiceExpired { .. } => WebhookEventKind::InvoiceExpired,
+        }
+    }
+
+    pub fn payment_id(&self) -> &str {
+        match self {
+            WebhookEvent::InvoiceCreated { payment_id, .. }
+            | WebhookEvent::PaymentReceived { payment_id, .. }
+            | WebhookEvent::PaymentSent { payment_id, .. }
+            | WebhookEvent::PaymentFailed { payment_id, .. }
+            | WebhookEvent::InvoiceExpired { payment_id, .. } => payment_id,
+        }
+    }
+}
+
+/// Body POSTed to the endpoint: the event's fields plus when it was sent
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+    /// Unix seconds
+    timestamp: u64,
+}
+
+/// Hex HMAC-SHA256 of `body` under `secret`, as sent in `X-Webhook-Signature`
+pub fn sign_webhook(secret: &str, body: &[u8]) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body);
+    hex::encode(hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array())
+}
+
+/// Delivers outgoing webhooks
+pub struct WebhookDelivery;
+
+impl WebhookDelivery {
+    /// POST `event` to the configured URL, signed, retrying transient failures
+    ///
+    /// Events the endpoint isn't subscribed to are skipped. A 4xx response
+    /// other than 429 is the receiver rejecting the event and isn't retried.
+    pub async fn deliver(event: &WebhookEvent, config: &WebhookConfig) -> Result<(), LightningError> {
+        if !config.filter.allows(event.kind()) {
+            debug!("Skipping {:?} webhook for payment_id={}: not subscribed", event.kind(), event.payment_id());
+            return Ok(());
+        }
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let body = serde_json::to_vec(&WebhookBody { event, timestamp })
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to serialize webhook event: {}", e)))?;
+        let signature = sign_webhook(&config.secret, &body);
+        let policy = ProviderRetryPolicy {
+            request_timeout: Duration::from_millis(config.timeout_ms),
+            max_retries: config.max_retries,
+            backoff_base: Duration::from_millis(WEBHOOK_BACKOFF_BASE_MS),
+            ..ProviderRetryPolicy::default()
+        };
+        let client = reqwest::Client::builder()
+            .timeout(policy.request_timeout)
+            .build()
+            .map_err(|e| LightningError::ProcessorError(format!("Failed to create HTTP client: {}", e)))?;
+
+        // Receivers identify events by payment_id, so a redelivery is harmless
+        retry_idempotent(&policy, "Webhook delivery", || async {
+            let response = client
+                .post(&config.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(WEBHOOK_SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(|e| LightningError::NodeConnectionError(format!("Webhook request failed: {}", e)))?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(LightningError::HttpError {
+                    status_code: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                });
+            }
+            Ok(())
+        })
+        .await?;
+        debug!("Delivered {:?} webhook for payment_id={}", event.kind(), event.payment_id());
+        Ok(())
+    }
+}
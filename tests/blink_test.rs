@@ -0,0 +1,255 @@
+//! Blink provider tests against recorded GraphQL responses
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::blink::{BlinkConfig, BlinkProvider, WalletCurrency};
+use blvm_lightning::provider::{create_provider, DescriptionKind, InvoiceParams, LightningProvider, ProviderType};
+use common::test_context;
+use mockito::Matcher;
+
+const PAYMENT_HASH: &str = "7f4c8d4bb3b8a2b0c2a1e0f5f2d5b3c4a9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4";
+const API_KEY: &str = "blink_test_key";
+const BTC_WALLET: &str = "b1a2c3d4-btc0-4e5f-8a9b-0c1d2e3f4a5b";
+const USD_WALLET: &str = "u1a2c3d4-usd0-4e5f-8a9b-0c1d2e3f4a5b";
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/blink/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn provider_for(server: &mockito::Server, wallet_id: Option<&str>) -> BlinkProvider {
+    BlinkProvider::new(BlinkConfig {
+        url: server.url(),
+        api_key: API_KEY.to_string(),
+        wallet_id: wallet_id.map(|s| s.to_string()),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn payment_hash() -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hex::decode(PAYMENT_HASH).unwrap());
+    hash
+}
+
+/// Create a real signed BOLT11 invoice via the LDK provider
+async fn make_invoice(amount_msats: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    provider.create_invoice(amount_msats, "test", 3600).await.unwrap()
+}
+
+/// Mock a GraphQL operation, matched by name
+async fn mock_operation(server: &mut mockito::Server, operation: &str, body: &str) -> mockito::Mock {
+    server
+        .mock("POST", "/")
+        .match_header("X-API-KEY", API_KEY)
+        .match_body(Matcher::Regex(format!(r#""query":"(query|mutation) {}[ (]"#, operation)))
+        .with_status(200)
+        .with_body(fixture(body))
+        .create_async()
+        .await
+}
+
+#[tokio::test]
+async fn test_create_invoice_on_btc_wallet() {
+    let mut server = mockito::Server::new_async().await;
+    let wallets = mock_operation(&mut server, "Wallets", "wallets.json").await;
+    let invoice = server
+        .mock("POST", "/")
+        .match_header("X-API-KEY", API_KEY)
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "variables": { "input": {
+                "walletId": BTC_WALLET,
+                // 1500 msats rounds up to 2 sats, 90 seconds to 2 minutes
+                "amount": 2,
+                "memo": "order 1",
+                "expiresIn": 2,
+            }}
+        })))
+        .with_status(200)
+        .with_body(fixture("ln_invoice_create.json"))
+        .expect(2)
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server, None);
+    let bolt11 = provider.create_invoice_ex(&InvoiceParams::new(1500, "order 1", 90)).await.unwrap();
+    assert_eq!(bolt11, "lntb20n1pjblinktest");
+    assert_eq!(provider.wallet().await.unwrap().wallet_currency, WalletCurrency::Btc);
+
+    // The wallet is only looked up once
+    provider.create_invoice(1500, "order 1", 90).await.unwrap();
+    wallets.assert_async().await;
+    invoice.assert_async().await;
+    assert_eq!(provider.provider_type(), ProviderType::Blink);
+}
+
+#[tokio::test]
+async fn test_create_invoice_on_stablesats_wallet() {
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "Wallets", "wallets.json").await;
+    let invoice = server
+        .mock("POST", "/")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::Regex("lnUsdInvoiceBtcDenominatedCreateOnBehalfOfRecipient".to_string()),
+            Matcher::PartialJson(serde_json::json!({
+                "variables": { "input": { "recipientWalletId": USD_WALLET, "amount": 2 } }
+            })),
+        ]))
+        .with_status(200)
+        .with_body(fixture("ln_usd_invoice_create.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server, Some(USD_WALLET));
+    let bolt11 = provider.create_invoice(2000, "order 1", 3600).await.unwrap();
+    assert_eq!(bolt11, "lntb20n1pjblinkusdtest");
+    invoice.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_unknown_wallet_id() {
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "Wallets", "wallets.json").await;
+
+    let err = provider_for(&server, Some("not-my-wallet")).create_invoice(2000, "order 1", 3600).await.unwrap_err();
+    assert!(matches!(err, LightningError::ConfigError(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_invoice_errors_mapped() {
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "Wallets", "wallets.json").await;
+    mock_operation(&mut server, "LnInvoiceCreate", "ln_invoice_create_invalid_amount.json").await;
+
+    let provider = provider_for(&server, None);
+    let err = provider.create_invoice(0, "order 1", 3600).await.unwrap_err();
+    match err {
+        LightningError::InvoiceError(msg) => assert!(msg.contains("Amount must be greater than zero"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let mut params = InvoiceParams::new(2000, "", 3600);
+    params.description = DescriptionKind::Hash([0u8; 32]);
+    assert!(matches!(provider.create_invoice_ex(&params).await, Err(LightningError::InvoiceError(_))));
+}
+
+#[tokio::test]
+async fn test_verify_paid_payment() {
+    let invoice = make_invoice(2000).await;
+    let mut server = mockito::Server::new_async().await;
+    let status = server
+        .mock("POST", "/")
+        .match_header("X-API-KEY", API_KEY)
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "variables": { "input": { "paymentRequest": invoice } }
+        })))
+        .with_status(200)
+        .with_body(fixture("status_paid.json"))
+        .create_async()
+        .await;
+
+    let result = provider_for(&server, None).verify_payment(&invoice, &payment_hash(), "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(2000));
+    assert_eq!(result.metadata["provider"], "blink");
+    assert_eq!(result.metadata["status"], "paid");
+    assert_eq!(
+        result.metadata["preimage"],
+        "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0"
+    );
+    status.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_verify_unpaid_payment() {
+    let invoice = make_invoice(2000).await;
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "LnInvoicePaymentStatus", "status_pending.json").await;
+
+    let result = provider_for(&server, None).verify_payment(&invoice, &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, None);
+    assert_eq!(result.metadata["status"], "pending");
+    assert_eq!(result.metadata["expired"], false);
+}
+
+#[tokio::test]
+async fn test_verify_expired_payment() {
+    let invoice = make_invoice(2000).await;
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "LnInvoicePaymentStatus", "status_expired.json").await;
+
+    let result = provider_for(&server, None).verify_payment(&invoice, &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["status"], "expired");
+    assert_eq!(result.metadata["expired"], true);
+}
+
+#[tokio::test]
+async fn test_verify_unknown_invoice() {
+    let invoice = make_invoice(2000).await;
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "LnInvoicePaymentStatus", "status_not_found.json").await;
+
+    let result = provider_for(&server, None).verify_payment(&invoice, &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "invoice_not_found");
+}
+
+#[tokio::test]
+async fn test_confirmation_by_hash() {
+    let mut server = mockito::Server::new_async().await;
+    let status = server
+        .mock("POST", "/")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "variables": { "input": { "paymentHash": PAYMENT_HASH } }
+        })))
+        .with_status(200)
+        .with_body(fixture("status_by_hash_paid.json"))
+        .create_async()
+        .await;
+
+    assert!(provider_for(&server, None).is_payment_confirmed(&payment_hash()).await.unwrap());
+    status.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_top_level_errors_mapped() {
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "Wallets", "unauthenticated.json").await;
+    mock_operation(&mut server, "LnInvoicePaymentStatusByHash", "rate_limited.json").await;
+
+    let provider = provider_for(&server, None);
+    let err = provider.create_invoice(2000, "order 1", 3600).await.unwrap_err();
+    assert!(matches!(err, LightningError::ConfigError(_)), "{:?}", err);
+
+    let err = provider.is_payment_confirmed(&payment_hash()).await.unwrap_err();
+    assert_eq!(err.status_code(), Some(429));
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    mock_operation(&mut server, "Me", "me.json").await;
+    assert!(provider_for(&server, None).health_check().await.unwrap().reachable);
+
+    let mut server = mockito::Server::new_async().await;
+    server.mock("POST", "/").with_status(401).create_async().await;
+    assert!(!provider_for(&server, None).health_check().await.unwrap().reachable);
+}
+
+#[test]
+fn test_create_provider_from_config() {
+    assert_eq!("blink".parse::<ProviderType>().unwrap(), ProviderType::Blink);
+
+    let ctx = test_context(&[("lightning.blink.api_key", API_KEY), ("lightning.blink.wallet_id", USD_WALLET)]);
+    let provider = create_provider(ProviderType::Blink, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::Blink);
+
+    let ctx = test_context(&[]);
+    assert!(matches!(create_provider(ProviderType::Blink, &ctx), Err(LightningError::ConfigError(_))));
+}
@@ -0,0 +1,106 @@
+//! Channel acceptance policy tests
+
+mod common;
+
+use blvm_lightning::provider::channel_policy::{
+    ChannelDecision, ChannelOpenRequest, ChannelPolicy, ChannelVisibility,
+};
+use common::test_context;
+
+const PEER: [u8; 33] = [2; 33];
+const TRUSTED_PEER: [u8; 33] = [3; 33];
+
+fn policy() -> ChannelPolicy {
+    ChannelPolicy {
+        min_channel_size_sats: 20_000,
+        max_channel_size_sats: Some(10_000_000),
+        accept_zero_conf: true,
+        zero_conf_allowlist: vec![TRUSTED_PEER],
+        max_channels_per_peer: Some(2),
+        visibility: ChannelVisibility::Private,
+    }
+}
+
+fn request(counterparty_node_id: [u8; 33], funding_satoshis: u64, is_public: bool, requires_zero_conf: bool) -> ChannelOpenRequest {
+    ChannelOpenRequest { counterparty_node_id, funding_satoshis, is_public, requires_zero_conf }
+}
+
+#[test]
+fn test_evaluate() {
+    let accept = |zero_conf| Some(zero_conf);
+    let reject = None;
+    // (name, policy, request, peer channels, accepted as zero-conf or rejected)
+    let cases: Vec<(&str, ChannelPolicy, ChannelOpenRequest, usize, Option<bool>)> = vec![
+        ("default accepts anything", ChannelPolicy::default(), request(PEER, 1, true, false), 10, accept(false)),
+        ("default refuses zero-conf", ChannelPolicy::default(), request(PEER, 100_000, false, true), 0, reject),
+        ("below minimum", policy(), request(PEER, 19_999, false, false), 0, reject),
+        ("at minimum", policy(), request(PEER, 20_000, false, false), 0, accept(false)),
+        ("at maximum", policy(), request(PEER, 10_000_000, false, false), 0, accept(false)),
+        ("above maximum", policy(), request(PEER, 10_000_001, false, false), 0, reject),
+        ("public when private required", policy(), request(PEER, 100_000, true, false), 0, reject),
+        (
+            "private when public required",
+            ChannelPolicy { visibility: ChannelVisibility::Public, ..policy() },
+            request(PEER, 100_000, false, false),
+            0,
+            reject,
+        ),
+        ("below channel limit", policy(), request(PEER, 100_000, false, false), 1, accept(false)),
+        ("at channel limit", policy(), request(PEER, 100_000, false, false), 2, reject),
+        ("allowlisted zero-conf", policy(), request(TRUSTED_PEER, 100_000, false, true), 0, accept(true)),
+        ("allowlisted peer may confirm", policy(), request(TRUSTED_PEER, 100_000, false, false), 0, accept(true)),
+        ("zero-conf not allowlisted", policy(), request(PEER, 100_000, false, true), 0, reject),
+        (
+            "empty allowlist trusts everyone",
+            ChannelPolicy { zero_conf_allowlist: Vec::new(), ..policy() },
+            request(PEER, 100_000, false, true),
+            0,
+            accept(true),
+        ),
+        (
+            "zero-conf disabled",
+            ChannelPolicy { accept_zero_conf: false, ..policy() },
+            request(TRUSTED_PEER, 100_000, false, true),
+            0,
+            reject,
+        ),
+    ];
+
+    for (name, policy, request, peer_channels, expected) in cases {
+        let decision = policy.evaluate(&request, peer_channels);
+        match (expected, &decision) {
+            (Some(zero_conf), ChannelDecision::Accept { zero_conf: got }) => assert_eq!(*got, zero_conf, "{}", name),
+            (None, ChannelDecision::Reject { reason }) => assert!(!reason.is_empty(), "{}", name),
+            _ => panic!("{}: unexpected {:?}", name, decision),
+        }
+    }
+}
+
+#[test]
+fn test_from_config() {
+    let allowlisted = hex::encode(TRUSTED_PEER);
+    let ctx = test_context(&[
+        ("lightning.ldk.channel_policy.min_channel_size_sats", "20000"),
+        ("lightning.ldk.channel_policy.max_channel_size_sats", "10000000"),
+        ("lightning.ldk.channel_policy.accept_zero_conf", "true"),
+        ("lightning.ldk.channel_policy.zero_conf_allowlist", &format!("[\"{}\"]", allowlisted)),
+        ("lightning.ldk.channel_policy.max_channels_per_peer", "2"),
+        ("lightning.ldk.channel_policy.visibility", "private"),
+    ]);
+    assert_eq!(ChannelPolicy::from_config(&ctx).unwrap(), policy());
+
+    assert_eq!(ChannelPolicy::from_config(&test_context(&[])).unwrap(), ChannelPolicy::default());
+    let ctx = test_context(&[("lightning.ldk.channel_policy.zero_conf_allowlist", &format!("{}, {}", allowlisted, hex::encode(PEER)))]);
+    assert_eq!(ChannelPolicy::from_config(&ctx).unwrap().zero_conf_allowlist, vec![TRUSTED_PEER, PEER]);
+    let ctx = test_context(&[("lightning.ldk.channel_policy.visibility", "PUBLIC")]);
+    assert_eq!(ChannelPolicy::from_config(&ctx).unwrap().visibility, ChannelVisibility::Public);
+
+    for (key, value) in [
+        ("lightning.ldk.channel_policy.min_channel_size_sats", "lots"),
+        ("lightning.ldk.channel_policy.accept_zero_conf", "sometimes"),
+        ("lightning.ldk.channel_policy.zero_conf_allowlist", "02abcd"),
+        ("lightning.ldk.channel_policy.visibility", "announced"),
+    ] {
+        assert!(ChannelPolicy::from_config(&test_context(&[(key, value)])).is_err(), "{}", key);
+    }
+}
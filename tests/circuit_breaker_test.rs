@@ -0,0 +1,137 @@
+//! Circuit breaker wrapper tests
+
+use async_trait::async_trait;
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::circuit_breaker::{CircuitBreakerConfig, CircuitBreakerProvider, CircuitState};
+use blvm_lightning::provider::{HealthStatus, LightningProvider, PaymentVerificationResult, ProviderCapabilities, ProviderType};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Provider whose calls fail while `failing` is set, and never return while `hanging` is set
+#[derive(Default)]
+struct FlakyProvider {
+    failing: Arc<AtomicBool>,
+    hanging: Arc<AtomicBool>,
+    calls: Arc<AtomicU32>,
+}
+
+#[async_trait]
+impl LightningProvider for FlakyProvider {
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        _payment_hash: &[u8; 32],
+        _payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        unimplemented!()
+    }
+
+    async fn create_invoice(&self, _amount_msats: u64, _description: &str, _expiry_seconds: u64) -> Result<String, LightningError> {
+        unimplemented!()
+    }
+
+    async fn is_payment_confirmed(&self, _payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.hanging.load(Ordering::SeqCst) {
+            std::future::pending::<()>().await;
+        }
+        if self.failing.load(Ordering::SeqCst) {
+            Err(LightningError::NodeConnectionError("connection refused".to_string()))
+        } else {
+            Ok(true)
+        }
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        unimplemented!()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Stub
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+fn breaker(reset_timeout_seconds: u64) -> (CircuitBreakerProvider, Arc<AtomicBool>, Arc<AtomicU32>) {
+    let inner = FlakyProvider::default();
+    let failing = inner.failing.clone();
+    let calls = inner.calls.clone();
+    let config = CircuitBreakerConfig {
+        failure_threshold: 3,
+        window_seconds: 60,
+        reset_timeout_seconds,
+    };
+    (CircuitBreakerProvider::new(Box::new(inner), config), failing, calls)
+}
+
+#[tokio::test]
+async fn test_opens_after_threshold() {
+    let (provider, failing, calls) = breaker(60);
+    failing.store(true, Ordering::SeqCst);
+
+    for _ in 0..3 {
+        assert!(provider.is_payment_confirmed(&[0u8; 32]).await.is_err());
+    }
+    assert_eq!(provider.state(), CircuitState::Open);
+
+    // Rejected without reaching the inner provider
+    match provider.is_payment_confirmed(&[0u8; 32]).await {
+        Err(LightningError::NodeConnectionError(msg)) => assert_eq!(msg, "circuit open"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_half_open_trial() {
+    let (provider, failing, calls) = breaker(0);
+    failing.store(true, Ordering::SeqCst);
+    for _ in 0..3 {
+        let _ = provider.is_payment_confirmed(&[0u8; 32]).await;
+    }
+
+    // Reset timeout of zero: next call is the half-open trial, which fails and reopens
+    assert_eq!(provider.state(), CircuitState::HalfOpen);
+    assert!(provider.is_payment_confirmed(&[0u8; 32]).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // Successful trial closes the circuit
+    failing.store(false, Ordering::SeqCst);
+    assert!(provider.is_payment_confirmed(&[0u8; 32]).await.unwrap());
+    assert_eq!(provider.state(), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn test_cancelled_trial_reopens() {
+    let inner = FlakyProvider::default();
+    let failing = inner.failing.clone();
+    let hanging = inner.hanging.clone();
+    let config = CircuitBreakerConfig {
+        failure_threshold: 3,
+        window_seconds: 60,
+        reset_timeout_seconds: 0,
+    };
+    let provider = CircuitBreakerProvider::new(Box::new(inner), config);
+    failing.store(true, Ordering::SeqCst);
+    for _ in 0..3 {
+        let _ = provider.is_payment_confirmed(&[0u8; 32]).await;
+    }
+
+    // The trial request is dropped before it finishes
+    hanging.store(true, Ordering::SeqCst);
+    let trial = tokio::time::timeout(std::time::Duration::from_millis(10), provider.is_payment_confirmed(&[0u8; 32]));
+    assert!(trial.await.is_err());
+
+    // It counted as a failed trial, so the next request is a new trial rather than rejected
+    hanging.store(false, Ordering::SeqCst);
+    failing.store(false, Ordering::SeqCst);
+    assert!(provider.is_payment_confirmed(&[0u8; 32]).await.unwrap());
+    assert_eq!(provider.state(), CircuitState::Closed);
+}
@@ -0,0 +1,49 @@
+//! IPC client reconnection tests
+
+mod common;
+
+use blvm_lightning::client::{ModuleClient, ReconnectPolicy, MAX_RECONNECT_BACKOFF_MS, RECONNECT_MAX_ATTEMPTS};
+use common::test_context;
+use std::time::Duration;
+
+#[test]
+fn test_reconnect_backoff() {
+    let policy = ReconnectPolicy {
+        max_attempts: 20,
+        backoff_base: Duration::from_millis(100),
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(policy.backoff(4), Duration::from_millis(800));
+    // Capped, including attempts past the shift width
+    assert_eq!(policy.backoff(12), Duration::from_millis(MAX_RECONNECT_BACKOFF_MS));
+    assert_eq!(policy.backoff(40), Duration::from_millis(MAX_RECONNECT_BACKOFF_MS));
+}
+
+#[test]
+fn test_reconnect_policy_from_config() {
+    let policy = ReconnectPolicy::from_config(&test_context(&[]));
+    assert_eq!(policy, ReconnectPolicy::default());
+    assert_eq!(policy.max_attempts, RECONNECT_MAX_ATTEMPTS);
+
+    let ctx = test_context(&[
+        ("lightning.reconnect_max_attempts", "3"),
+        ("lightning.reconnect_backoff_ms", "50"),
+    ]);
+    let policy = ReconnectPolicy::from_config(&ctx);
+    assert_eq!(policy.max_attempts, 3);
+    assert_eq!(policy.backoff(2), Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_connect_fails_without_node() {
+    let socket_path = std::env::temp_dir().join(format!("blvm-lightning-missing-{}.sock", rand::random::<u64>()));
+    let result = ModuleClient::connect(
+        socket_path,
+        "test".to_string(),
+        "blvm-lightning".to_string(),
+        "0.0.0".to_string(),
+    )
+    .await;
+    assert!(result.is_err());
+}
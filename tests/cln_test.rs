@@ -0,0 +1,124 @@
+//! CLN provider tests against recorded clnrest responses
+
+use blvm_lightning::provider::cln::{CLNConfig, CLNProvider};
+use blvm_lightning::provider::{InvoiceParams, LightningProvider, ProviderType};
+
+const PAYMENT_HASH: &str = "7f4c8d4bb3b8a2b0c2a1e0f5f2d5b3c4a9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4";
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/cln/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn provider_for(server: &mockito::Server) -> CLNProvider {
+    CLNProvider::new(CLNConfig {
+        url: server.url(),
+        rune: "test_rune".to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn payment_hash() -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hex::decode(PAYMENT_HASH).unwrap());
+    hash
+}
+
+#[tokio::test]
+async fn test_create_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    let invoice = server
+        .mock("POST", "/v1/invoice")
+        .match_header("Rune", "test_rune")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "amount_msat": 1000,
+            "label": "order-1",
+            "description": "order 1",
+        })))
+        .with_status(201)
+        .with_body(fixture("invoice.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let mut params = InvoiceParams::new(1000, "order 1", 3600);
+    params.label = Some("order-1".to_string());
+    let bolt11 = provider.create_invoice_ex(&params).await.unwrap();
+
+    assert!(bolt11.starts_with("lntb10n1"));
+    assert_eq!(provider.provider_type(), ProviderType::CLN);
+    invoice.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_verify_paid_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/listinvoices")
+        .match_body(mockito::Matcher::Json(serde_json::json!({ "payment_hash": PAYMENT_HASH })))
+        .with_status(200)
+        .with_body(fixture("listinvoices_paid.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(1000));
+    assert_eq!(result.timestamp, Some(1700000123));
+    assert_eq!(result.metadata["label"], "order-1");
+    assert!(provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_unknown_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/listinvoices")
+        .with_status(200)
+        .with_body(fixture("listinvoices_empty.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert!(!provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/getinfo")
+        .with_status(200)
+        .with_body(fixture("getinfo.json"))
+        .create_async()
+        .await;
+
+    let status = provider_for(&server).health_check().await.unwrap();
+    assert!(status.reachable);
+    assert_eq!(status.block_height, Some(2500000));
+    assert_eq!(status.synced_to_chain, Some(true));
+    assert_eq!(status.version.as_deref(), Some("v24.05"));
+}
+
+#[test]
+fn test_socks5_proxy() {
+    let config = CLNConfig {
+        url: "https://clnrestexampleonionaddress.onion:3010".to_string(),
+        rune: "test_rune".to_string(),
+        socks5_proxy: Some("socks5://127.0.0.1:9050".to_string()),
+        ..Default::default()
+    };
+    assert!(config.validate().is_ok());
+    assert!(CLNProvider::new(config).is_ok());
+
+    let config = CLNConfig {
+        socks5_proxy: Some("not a url".to_string()),
+        ..Default::default()
+    };
+    assert!(config.validate().is_err());
+    assert!(CLNProvider::new(config).is_err());
+}
@@ -0,0 +1,362 @@
+//! Shared test helpers
+
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use blvm_node::module::ipc::protocol::EventPayload;
+use blvm_node::module::traits::{ModuleContext, ModuleError, NodeAPI, PaymentState};
+use blvm_node::module::EventType;
+use blvm_protocol::{Block, BlockHeader, Hash, OutPoint, Transaction, UTXO};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Build a module context with the given config entries
+pub fn test_context(entries: &[(&str, &str)]) -> ModuleContext {
+    let config: HashMap<String, String> = entries
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let data_dir = std::env::temp_dir().join(format!("blvm-lightning-test-{}", rand::random::<u64>()));
+
+    ModuleContext {
+        module_id: "test".to_string(),
+        config,
+        data_dir: data_dir.to_string_lossy().to_string(),
+        socket_path: "/tmp/test.sock".to_string(),
+    }
+}
+
+/// In-memory NodeAPI used to exercise the processor without a running node
+#[derive(Default)]
+pub struct MockNodeApi {
+    trees: Mutex<HashMap<String, BTreeMap<Vec<u8>, Vec<u8>>>>,
+    payment_states: Mutex<HashMap<String, PaymentState>>,
+}
+
+impl MockNodeApi {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Get a raw value from a storage tree
+    pub fn get(&self, tree: &str, key: &[u8]) -> Option<Vec<u8>> {
+        self.trees
+            .lock()
+            .unwrap()
+            .get(tree)
+            .and_then(|t| t.get(key).cloned())
+    }
+
+    /// Number of entries in a storage tree
+    pub fn len(&self, tree: &str) -> usize {
+        self.trees.lock().unwrap().get(tree).map(|t| t.len()).unwrap_or(0)
+    }
+
+    /// Assert that `key` is stored in `tree` with exactly `value`
+    #[track_caller]
+    pub fn assert_stored(&self, tree: &str, key: &[u8], value: &[u8]) {
+        match self.get(tree, key) {
+            Some(stored) => assert_eq!(
+                stored,
+                value,
+                "unexpected value for {:?} in tree {}",
+                String::from_utf8_lossy(key),
+                tree
+            ),
+            None => panic!("{:?} not stored in tree {}", String::from_utf8_lossy(key), tree),
+        }
+    }
+
+    /// Set the payment state the node reports for a payment id
+    pub fn set_payment_state(&self, payment_id: &str, state: PaymentState) {
+        self.payment_states.lock().unwrap().insert(payment_id.to_string(), state);
+    }
+}
+
+fn not_implemented<T>() -> Result<T, ModuleError> {
+    Err(ModuleError::OperationError("Not implemented".to_string()))
+}
+
+#[async_trait]
+impl NodeAPI for MockNodeApi {
+    async fn get_block(&self, _hash: &Hash) -> Result<Option<Block>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn get_block_header(&self, _hash: &Hash) -> Result<Option<BlockHeader>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn get_transaction(&self, _hash: &Hash) -> Result<Option<Transaction>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn has_transaction(&self, _hash: &Hash) -> Result<bool, ModuleError> {
+        Ok(false)
+    }
+
+    async fn get_chain_tip(&self) -> Result<Hash, ModuleError> {
+        Ok([0u8; 32])
+    }
+
+    async fn get_block_height(&self) -> Result<u64, ModuleError> {
+        Ok(0)
+    }
+
+    async fn get_utxo(&self, _outpoint: &OutPoint) -> Result<Option<UTXO>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn subscribe_events(
+        &self,
+        _event_types: Vec<blvm_node::module::traits::EventType>,
+    ) -> Result<tokio::sync::mpsc::Receiver<blvm_node::module::ipc::protocol::ModuleMessage>, ModuleError> {
+        let (_tx, rx) = tokio::sync::mpsc::channel(1);
+        Ok(rx)
+    }
+
+    async fn get_mempool_transactions(&self) -> Result<Vec<Hash>, ModuleError> {
+        Ok(Vec::new())
+    }
+
+    async fn get_mempool_transaction(&self, _tx_hash: &Hash) -> Result<Option<Transaction>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn get_mempool_size(&self) -> Result<blvm_node::module::traits::MempoolSize, ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_network_stats(&self) -> Result<blvm_node::module::traits::NetworkStats, ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_network_peers(&self) -> Result<Vec<blvm_node::module::traits::PeerInfo>, ModuleError> {
+        Ok(Vec::new())
+    }
+
+    async fn get_chain_info(&self) -> Result<blvm_node::module::traits::ChainInfo, ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_block_by_height(&self, _height: u64) -> Result<Option<Block>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn get_lightning_node_url(&self) -> Result<Option<String>, ModuleError> {
+        Ok(Some("http://127.0.0.1:5000".to_string()))
+    }
+
+    async fn get_lightning_info(&self) -> Result<Option<blvm_node::module::traits::LightningInfo>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn get_payment_state(&self, payment_id: &str) -> Result<Option<PaymentState>, ModuleError> {
+        Ok(self.payment_states.lock().unwrap().get(payment_id).cloned())
+    }
+
+    async fn check_transaction_in_mempool(&self, _tx_hash: &Hash) -> Result<bool, ModuleError> {
+        Ok(false)
+    }
+
+    async fn get_fee_estimate(&self, _target_blocks: u32) -> Result<u64, ModuleError> {
+        Ok(1)
+    }
+
+    async fn read_file(&self, _path: String) -> Result<Vec<u8>, ModuleError> {
+        not_implemented()
+    }
+
+    async fn write_file(&self, _path: String, _data: Vec<u8>) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn delete_file(&self, _path: String) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn list_directory(&self, _path: String) -> Result<Vec<String>, ModuleError> {
+        Ok(Vec::new())
+    }
+
+    async fn create_directory(&self, _path: String) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_file_metadata(
+        &self,
+        _path: String,
+    ) -> Result<blvm_node::module::ipc::protocol::FileMetadata, ModuleError> {
+        not_implemented()
+    }
+
+    async fn storage_open_tree(&self, name: String) -> Result<String, ModuleError> {
+        self.trees.lock().unwrap().entry(name.clone()).or_default();
+        Ok(name)
+    }
+
+    async fn storage_insert(&self, tree_id: String, key: Vec<u8>, value: Vec<u8>) -> Result<(), ModuleError> {
+        self.trees.lock().unwrap().entry(tree_id).or_default().insert(key, value);
+        Ok(())
+    }
+
+    async fn storage_get(&self, tree_id: String, key: Vec<u8>) -> Result<Option<Vec<u8>>, ModuleError> {
+        Ok(self.get(&tree_id, &key))
+    }
+
+    async fn storage_remove(&self, tree_id: String, key: Vec<u8>) -> Result<(), ModuleError> {
+        if let Some(tree) = self.trees.lock().unwrap().get_mut(&tree_id) {
+            tree.remove(&key);
+        }
+        Ok(())
+    }
+
+    async fn storage_contains_key(&self, tree_id: String, key: Vec<u8>) -> Result<bool, ModuleError> {
+        Ok(self.get(&tree_id, &key).is_some())
+    }
+
+    async fn storage_iter(&self, tree_id: String) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ModuleError> {
+        Ok(self
+            .trees
+            .lock()
+            .unwrap()
+            .get(&tree_id)
+            .map(|t| t.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default())
+    }
+
+    async fn storage_transaction(
+        &self,
+        _tree_id: String,
+        _operations: Vec<blvm_node::module::ipc::protocol::StorageOperation>,
+    ) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn register_rpc_endpoint(&self, _method: String, _description: String) -> Result<(), ModuleError> {
+        Ok(())
+    }
+
+    async fn unregister_rpc_endpoint(&self, _method: &str) -> Result<(), ModuleError> {
+        Ok(())
+    }
+
+    async fn register_timer(
+        &self,
+        _interval_seconds: u64,
+        _callback: Arc<dyn blvm_node::module::timers::manager::TimerCallback>,
+    ) -> Result<blvm_node::module::timers::manager::TimerId, ModuleError> {
+        not_implemented()
+    }
+
+    async fn cancel_timer(
+        &self,
+        _timer_id: blvm_node::module::timers::manager::TimerId,
+    ) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn schedule_task(
+        &self,
+        _delay_seconds: u64,
+        _callback: Arc<dyn blvm_node::module::timers::manager::TaskCallback>,
+    ) -> Result<blvm_node::module::timers::manager::TaskId, ModuleError> {
+        not_implemented()
+    }
+
+    async fn report_metric(&self, _metric: blvm_node::module::metrics::manager::Metric) -> Result<(), ModuleError> {
+        Ok(())
+    }
+
+    async fn get_module_metrics(
+        &self,
+        _module_id: &str,
+    ) -> Result<Vec<blvm_node::module::metrics::manager::Metric>, ModuleError> {
+        Ok(Vec::new())
+    }
+
+    async fn initialize_module(
+        &self,
+        _module_id: String,
+        _module_data_dir: std::path::PathBuf,
+        _base_data_dir: std::path::PathBuf,
+    ) -> Result<(), ModuleError> {
+        Ok(())
+    }
+
+    async fn discover_modules(&self) -> Result<Vec<blvm_node::module::traits::ModuleInfo>, ModuleError> {
+        Ok(Vec::new())
+    }
+
+    async fn get_module_info(&self, _module_id: &str) -> Result<Option<blvm_node::module::traits::ModuleInfo>, ModuleError> {
+        Ok(None)
+    }
+
+    async fn is_module_available(&self, _module_id: &str) -> Result<bool, ModuleError> {
+        Ok(false)
+    }
+
+    async fn publish_event(&self, _event_type: EventType, _payload: EventPayload) -> Result<(), ModuleError> {
+        Ok(())
+    }
+
+    async fn send_mesh_packet_to_peer(&self, _peer_addr: String, _packet_data: Vec<u8>) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_all_metrics(&self) -> Result<HashMap<String, Vec<blvm_node::module::metrics::manager::Metric>>, ModuleError> {
+        not_implemented()
+    }
+
+    async fn call_module(
+        &self,
+        _target_module_id: Option<&str>,
+        _method: &str,
+        _params: Vec<u8>,
+    ) -> Result<Vec<u8>, ModuleError> {
+        not_implemented()
+    }
+
+    async fn register_module_api(
+        &self,
+        _api: Arc<dyn blvm_node::module::inter_module::api::ModuleAPI>,
+    ) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn unregister_module_api(&self) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn send_mesh_packet_to_module(
+        &self,
+        _module_id: &str,
+        _packet_data: Vec<u8>,
+        _peer_addr: String,
+    ) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn send_stratum_v2_message_to_peer(
+        &self,
+        _peer_addr: String,
+        _message_data: Vec<u8>,
+    ) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_module_health(&self, _module_id: &str) -> Result<Option<blvm_node::module::process::monitor::ModuleHealth>, ModuleError> {
+        not_implemented()
+    }
+
+    async fn get_all_module_health(&self) -> Result<Vec<(String, blvm_node::module::process::monitor::ModuleHealth)>, ModuleError> {
+        not_implemented()
+    }
+
+    async fn report_module_health(
+        &self,
+        _health: blvm_node::module::process::monitor::ModuleHealth,
+    ) -> Result<(), ModuleError> {
+        not_implemented()
+    }
+}
@@ -0,0 +1,180 @@
+//! Composite provider tests
+
+mod common;
+
+use async_trait::async_trait;
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::composite::{CompositeProvider, RoutingStrategy, DEFAULT_LNBITS_MAX_MSATS};
+use blvm_lightning::provider::{
+    create_provider, HealthStatus, LightningProvider, PaymentVerificationResult, ProviderCapabilities, ProviderType,
+};
+use common::test_context;
+use std::sync::{Arc, Mutex};
+
+/// 100k sats
+const THRESHOLD_MSATS: u64 = 100_000_000;
+
+/// Provider that records the invoices it creates and verifies only those
+struct MockProvider {
+    provider_type: ProviderType,
+    invoices: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockProvider {
+    fn new(provider_type: ProviderType) -> (Box<dyn LightningProvider>, Arc<Mutex<Vec<String>>>) {
+        let invoices = Arc::new(Mutex::new(Vec::new()));
+        (Box::new(Self { provider_type, invoices: invoices.clone() }), invoices)
+    }
+}
+
+#[async_trait]
+impl LightningProvider for MockProvider {
+    async fn verify_payment(
+        &self,
+        invoice: &str,
+        _payment_hash: &[u8; 32],
+        _payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        if self.provider_type == ProviderType::Stub {
+            return Err(LightningError::NodeConnectionError("down".to_string()));
+        }
+        Ok(PaymentVerificationResult {
+            verified: self.invoices.lock().unwrap().iter().any(|created| created == invoice),
+            amount_msats: None,
+            timestamp: None,
+            metadata: serde_json::json!({}),
+        })
+    }
+
+    async fn create_invoice(&self, amount_msats: u64, _description: &str, _expiry_seconds: u64) -> Result<String, LightningError> {
+        let invoice = format!("{}-{}", self.provider_type, amount_msats);
+        self.invoices.lock().unwrap().push(invoice.clone());
+        Ok(invoice)
+    }
+
+    async fn is_payment_confirmed(&self, _payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        Ok(false)
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        Err(LightningError::NodeConnectionError("not needed".to_string()))
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.provider_type.clone()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[tokio::test]
+async fn test_threshold_routing() {
+    let (lnbits, lnbits_invoices) = MockProvider::new(ProviderType::LNBits);
+    let (ldk, ldk_invoices) = MockProvider::new(ProviderType::LDK);
+    // Members are found by type, not position
+    let provider = CompositeProvider::new(
+        vec![ldk, lnbits],
+        RoutingStrategy::Threshold { lnbits_max_msats: THRESHOLD_MSATS },
+    ).unwrap();
+
+    for (amount_msats, expected) in [
+        (1_000, ProviderType::LNBits),
+        (THRESHOLD_MSATS, ProviderType::LNBits),
+        (THRESHOLD_MSATS + 1, ProviderType::LDK),
+        (u64::MAX, ProviderType::LDK),
+    ] {
+        assert_eq!(provider.route(amount_msats).provider_type(), expected, "{} msats", amount_msats);
+    }
+
+    let small = provider.create_invoice(1_000, "small", 3600).await.unwrap();
+    let large = provider.create_invoice(THRESHOLD_MSATS * 2, "large", 3600).await.unwrap();
+    assert_eq!(*lnbits_invoices.lock().unwrap(), vec![small.clone()]);
+    assert_eq!(*ldk_invoices.lock().unwrap(), vec![large.clone()]);
+
+    // Verified by whichever member created the invoice
+    let result = provider.verify_payment(&small, &[1; 32], "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.metadata["verified_by"], "lnbits");
+    let result = provider.verify_payment(&large, &[2; 32], "payment-2").await.unwrap();
+    assert_eq!(result.metadata["verified_by"], "ldk");
+    assert!(!provider.verify_payment("unknown", &[3; 32], "payment-3").await.unwrap().verified);
+}
+
+#[tokio::test]
+async fn test_round_robin_routing() {
+    let (lnbits, lnbits_invoices) = MockProvider::new(ProviderType::LNBits);
+    let (ldk, ldk_invoices) = MockProvider::new(ProviderType::LDK);
+    let (stub, stub_invoices) = MockProvider::new(ProviderType::Stub);
+    let provider = CompositeProvider::new(vec![stub, lnbits, ldk], RoutingStrategy::RoundRobin).unwrap();
+
+    // Amount doesn't matter; members take turns
+    for amount_msats in [1_000, THRESHOLD_MSATS * 2, 5_000, 6_000, 7_000] {
+        provider.create_invoice(amount_msats, "coffee", 3600).await.unwrap();
+    }
+    assert_eq!(stub_invoices.lock().unwrap().len(), 2);
+    assert_eq!(lnbits_invoices.lock().unwrap().len(), 2);
+    assert_eq!(ldk_invoices.lock().unwrap().len(), 1);
+    assert_eq!(provider.route(1_000).provider_type(), ProviderType::LDK);
+
+    // The stub member fails to answer, which doesn't stop the others verifying
+    let invoice = lnbits_invoices.lock().unwrap()[0].clone();
+    assert!(provider.verify_payment(&invoice, &[1; 32], "payment-1").await.unwrap().verified);
+}
+
+#[test]
+fn test_threshold_needs_lnbits_and_ldk() {
+    let (lnbits, _) = MockProvider::new(ProviderType::LNBits);
+    let (stub, _) = MockProvider::new(ProviderType::Stub);
+    let strategy = RoutingStrategy::Threshold { lnbits_max_msats: THRESHOLD_MSATS };
+    assert!(matches!(CompositeProvider::new(vec![lnbits, stub], strategy), Err(LightningError::ConfigError(_))));
+    assert!(CompositeProvider::new(Vec::new(), RoutingStrategy::RoundRobin).is_err());
+}
+
+#[test]
+fn test_create_from_config() {
+    assert_eq!("composite".parse::<ProviderType>().unwrap(), ProviderType::Composite);
+    assert_eq!("threshold".parse::<RoutingStrategy>().unwrap(), RoutingStrategy::Threshold { lnbits_max_msats: DEFAULT_LNBITS_MAX_MSATS });
+    assert_eq!("round_robin".parse::<RoutingStrategy>().unwrap(), RoutingStrategy::RoundRobin);
+    assert!("fastest".parse::<RoutingStrategy>().is_err());
+
+    let ctx = test_context(&[
+        ("lightning.composite.strategy", "threshold"),
+        ("lightning.composite.lnbits_max_msats", "50000000"),
+        ("lightning.lnbits.api_url", "http://127.0.0.1:1"),
+        ("lightning.ldk.network", "testnet"),
+    ]);
+    let provider = create_provider(ProviderType::Composite, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::Composite);
+    let composite = provider.as_any().downcast_ref::<CompositeProvider>().unwrap();
+    assert_eq!(composite.strategy(), RoutingStrategy::Threshold { lnbits_max_msats: 50_000_000 });
+    assert_eq!(composite.route(50_000_000).provider_type(), ProviderType::LNBits);
+    assert_eq!(composite.route(50_000_001).provider_type(), ProviderType::LDK);
+
+    let ctx = test_context(&[
+        ("lightning.composite.strategy", "round_robin"),
+        ("lightning.composite.providers", "ldk, lnbits"),
+        ("lightning.lnbits.api_url", "http://127.0.0.1:1"),
+        ("lightning.ldk.network", "testnet"),
+    ]);
+    let provider = create_provider(ProviderType::Composite, &ctx).unwrap();
+    let composite = provider.as_any().downcast_ref::<CompositeProvider>().unwrap();
+    let members: Vec<ProviderType> = composite.providers().iter().map(|provider| provider.provider_type()).collect();
+    assert_eq!(members, vec![ProviderType::LDK, ProviderType::LNBits]);
+
+    for (key, value) in [
+        ("lightning.composite.strategy", "fastest"),
+        ("lightning.composite.providers", "ldk,composite"),
+        ("lightning.composite.providers", "watch_only"),
+        // Threshold without an LDK member
+        ("lightning.composite.providers", "lnbits"),
+    ] {
+        let ctx = test_context(&[("lightning.lnbits.api_url", "http://127.0.0.1:1"), (key, value)]);
+        assert!(matches!(create_provider(ProviderType::Composite, &ctx), Err(LightningError::ConfigError(_))), "{}", value);
+    }
+}
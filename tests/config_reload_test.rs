@@ -0,0 +1,163 @@
+//! Live config reload tests
+
+mod common;
+
+use async_trait::async_trait;
+use blvm_lightning::config_reload::{ConfigReload, ConfigSource};
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::processor::LightningProcessor;
+use blvm_lightning::provider::ldk::LDKProvider;
+use blvm_lightning::provider::ProviderType;
+use common::{test_context, MockNodeApi};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Config source standing in for the node's module config
+#[derive(Default)]
+struct MockConfigSource {
+    values: Mutex<HashMap<String, String>>,
+    fail: Mutex<bool>,
+}
+
+impl MockConfigSource {
+    fn set(&self, key: &str, value: &str) {
+        self.values.lock().unwrap().insert(key.to_string(), value.to_string());
+    }
+}
+
+#[async_trait]
+impl ConfigSource for MockConfigSource {
+    async fn get_module_config(&self, key: &str) -> Result<Option<String>, LightningError> {
+        if *self.fail.lock().unwrap() {
+            return Err(LightningError::ModuleError("node unavailable".to_string()));
+        }
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+}
+
+#[tokio::test]
+async fn test_refresh_applies_rate_limit_in_place() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    let source = MockConfigSource::default();
+    source.set("lightning.provider", "stub");
+
+    for i in 0..5 {
+        processor.check_rate_limit(&format!("wallet01-payment-{}", i)).unwrap();
+    }
+
+    source.set("lightning.rate_limit.max_per_minute", "1");
+    let reload = processor.refresh_config(&source).await.unwrap();
+    assert_eq!(reload.changed, vec!["lightning.rate_limit.max_per_minute".to_string()]);
+    assert!(!reload.provider_recreated);
+
+    processor.check_rate_limit("wallet02-payment-1").unwrap();
+    assert!(processor.check_rate_limit("wallet02-payment-2").is_err());
+
+    // Nothing changed since the last refresh
+    assert_eq!(processor.refresh_config(&source).await.unwrap(), ConfigReload::default());
+}
+
+#[tokio::test]
+async fn test_refresh_recreates_provider_on_type_change() {
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.ldk.network", "testnet")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    assert_eq!(processor.provider_type(), ProviderType::Stub);
+    node_api.assert_stored("lightning_config", b"provider_type", b"stub");
+
+    let source = MockConfigSource::default();
+    source.set("lightning.provider", "ldk");
+    let reload = processor.refresh_config(&source).await.unwrap();
+    assert!(reload.provider_recreated);
+    assert_eq!(processor.provider_type(), ProviderType::LDK);
+    node_api.assert_stored("lightning_config", b"provider_type", b"ldk");
+}
+
+#[tokio::test]
+async fn test_refresh_keeps_provider_on_failure() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    let source = MockConfigSource::default();
+
+    source.set("lightning.provider", "no-such-provider");
+    assert!(matches!(
+        processor.refresh_config(&source).await.unwrap_err(),
+        LightningError::ConfigError(_)
+    ));
+    assert_eq!(processor.provider_type(), ProviderType::Stub);
+
+    *source.fail.lock().unwrap() = true;
+    assert!(processor.refresh_config(&source).await.is_err());
+    assert_eq!(processor.provider_type(), ProviderType::Stub);
+
+    // The rejected value is retried on the next refresh
+    *source.fail.lock().unwrap() = false;
+    source.set("lightning.provider", "stub");
+    assert!(processor.refresh_config(&source).await.unwrap().changed.is_empty());
+}
+
+#[tokio::test]
+async fn test_refresh_replaces_ldk_only_when_needed() {
+    let ctx = test_context(&[("lightning.provider", "ldk"), ("lightning.ldk.network", "testnet")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    let original = processor.provider();
+    let invoice = original.create_invoice(1000, "before reload", 3600).await.unwrap();
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+
+    // LDK doesn't read the LNBits keys
+    let source = MockConfigSource::default();
+    source.set("lightning.provider", "ldk");
+    source.set("lightning.lnbits.api_key", "new_key");
+    let reload = processor.refresh_config(&source).await.unwrap();
+    assert_eq!(reload.changed, vec!["lightning.lnbits.api_key".to_string()]);
+    assert!(!reload.provider_recreated);
+    assert!(Arc::ptr_eq(&original, &processor.provider()));
+
+    // A retry setting it does read replaces it, keeping what the old one stored
+    source.set("lightning.max_retries", "1");
+    assert!(processor.refresh_config(&source).await.unwrap().provider_recreated);
+    let provider = processor.provider();
+    assert!(!Arc::ptr_eq(&original, &provider));
+    let ldk = provider.as_any().downcast_ref::<LDKProvider>().unwrap();
+    assert_eq!(ldk.get_invoice(&payment_hash).await, Some(invoice));
+}
+
+#[tokio::test]
+async fn test_refresh_picks_up_new_lnbits_key() {
+    let mut server = mockito::Server::new_async().await;
+    let ctx = test_context(&[
+        ("lightning.provider", "lnbits"),
+        ("lightning.lnbits.api_url", &server.url()),
+        ("lightning.lnbits.api_key", "old_key"),
+        ("lightning.lnbits.max_retries", "0"),
+    ]);
+    let processor = Arc::new(LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap());
+    let source = MockConfigSource::default();
+    source.set("lightning.provider", "lnbits");
+    source.set("lightning.lnbits.api_key", "new_key");
+    source.set("lightning.lnbits.max_retries", "0");
+
+    let reload = processor.refresh_config(&source).await.unwrap();
+    assert_eq!(reload.changed, vec!["lightning.lnbits.api_key".to_string()]);
+    assert!(reload.provider_recreated);
+
+    let mock = server
+        .mock("GET", "/api/v1/wallet")
+        .match_header("X-Api-Key", "new_key")
+        .with_status(200)
+        .with_body(r#"{"name": "shop", "balance": 21000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    assert_eq!(processor.provider().balance_msats().await.unwrap(), 21000);
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_config_refresh_disabled_by_default() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = Arc::new(LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap());
+    assert!(processor.spawn_config_refresh(Arc::new(MockConfigSource::default())).is_none());
+}
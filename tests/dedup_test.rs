@@ -0,0 +1,74 @@
+//! Tests for event deduplication
+
+use blvm_lightning::dedup::SeenSet;
+use std::time::Duration;
+
+#[test]
+fn test_duplicate_ids_rejected() {
+    let seen = SeenSet::new(Duration::from_secs(60));
+    assert!(seen.is_empty());
+
+    assert!(seen.insert("event-1"));
+    assert!(!seen.insert("event-1"));
+    assert!(seen.insert("event-2"));
+    assert!(seen.contains("event-1"));
+    assert!(!seen.contains("event-3"));
+    assert_eq!(seen.len(), 2);
+}
+
+#[test]
+fn test_removed_ids_accepted_again() {
+    let seen = SeenSet::new(Duration::from_secs(60));
+    assert!(seen.insert("event-1"));
+
+    // Handling failed: the redelivered event is dispatched again
+    seen.remove("event-1");
+    assert!(!seen.contains("event-1"));
+    assert!(seen.insert("event-1"));
+    assert!(!seen.insert("event-1"));
+}
+
+#[test]
+fn test_clones_share_entries() {
+    let seen = SeenSet::new(Duration::from_secs(60));
+    let clone = seen.clone();
+
+    assert!(seen.insert("event-1"));
+    assert!(!clone.insert("event-1"));
+}
+
+#[test]
+fn test_expired_ids_accepted_again() {
+    let seen = SeenSet::new(Duration::from_millis(20));
+    assert!(seen.insert("event-1"));
+
+    std::thread::sleep(Duration::from_millis(40));
+    assert!(!seen.contains("event-1"));
+    assert!(seen.insert("event-1"));
+}
+
+#[test]
+fn test_cleanup_removes_expired() {
+    let seen = SeenSet::new(Duration::from_millis(20));
+    seen.insert("event-1");
+    seen.insert("event-2");
+
+    assert_eq!(seen.cleanup(), 0);
+    std::thread::sleep(Duration::from_millis(40));
+    seen.insert("event-3");
+
+    assert_eq!(seen.cleanup(), 2);
+    assert_eq!(seen.len(), 1);
+    assert!(seen.contains("event-3"));
+}
+
+#[tokio::test]
+async fn test_background_cleanup() {
+    let seen = SeenSet::new(Duration::from_millis(10));
+    seen.insert("event-1");
+    let cleanup = seen.spawn_cleanup(1);
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(seen.is_empty());
+    cleanup.abort();
+}
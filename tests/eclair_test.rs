@@ -0,0 +1,149 @@
+//! Eclair provider tests against recorded API responses
+
+use blvm_lightning::provider::eclair::{EclairConfig, EclairProvider};
+use blvm_lightning::provider::{InvoiceParams, LightningProvider, ProviderType};
+use mockito::Matcher;
+
+const PAYMENT_HASH: &str = "7f4c8d4bb3b8a2b0c2a1e0f5f2d5b3c4a9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4";
+/// Basic auth with an empty user and password "test_password"
+const AUTH_HEADER: &str = "Basic OnRlc3RfcGFzc3dvcmQ=";
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/eclair/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn provider_for(server: &mockito::Server) -> EclairProvider {
+    EclairProvider::new(EclairConfig {
+        url: server.url(),
+        password: "test_password".to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn payment_hash() -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hex::decode(PAYMENT_HASH).unwrap());
+    hash
+}
+
+#[tokio::test]
+async fn test_create_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    let invoice = server
+        .mock("POST", "/createinvoice")
+        .match_header("Authorization", AUTH_HEADER)
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("amountMsat".into(), "1000".into()),
+            Matcher::UrlEncoded("description".into(), "order 1".into()),
+            Matcher::UrlEncoded("expireIn".into(), "3600".into()),
+        ]))
+        .with_status(200)
+        .with_body(fixture("createinvoice.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let bolt11 = provider.create_invoice_ex(&InvoiceParams::new(1000, "order 1", 3600)).await.unwrap();
+
+    assert!(bolt11.starts_with("lntb10n1"));
+    assert_eq!(provider.provider_type(), ProviderType::Eclair);
+    invoice.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_verify_received_payment() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/parseinvoice")
+        .with_status(200)
+        .with_body(fixture("parseinvoice.json"))
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/getreceivedinfo")
+        .match_body(Matcher::UrlEncoded("paymentHash".into(), PAYMENT_HASH.into()))
+        .with_status(200)
+        .with_body(fixture("getreceivedinfo_received.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("lntb10n1pjtest", &payment_hash(), "payment-1").await.unwrap();
+
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(1000));
+    assert_eq!(result.timestamp, Some(1700000100));
+    assert_eq!(result.metadata["status"], "received");
+    assert!(provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_pending_payment() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/getreceivedinfo")
+        .with_status(200)
+        .with_body(fixture("getreceivedinfo_pending.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, Some(1000));
+    assert_eq!(result.timestamp, None);
+    assert!(!provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_rejects_mismatched_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/parseinvoice")
+        .with_status(200)
+        .with_body(fixture("parseinvoice.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("lntb10n1pjtest", &[0u8; 32], "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "payment_hash_mismatch");
+}
+
+#[tokio::test]
+async fn test_verify_unknown_payment() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/getreceivedinfo")
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "invoice_not_found");
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/getinfo")
+        .with_status(200)
+        .with_body(fixture("getinfo.json"))
+        .create_async()
+        .await;
+
+    let status = provider_for(&server).health_check().await.unwrap();
+    assert!(status.reachable);
+    assert_eq!(status.block_height, Some(2500000));
+    assert_eq!(status.version.as_deref(), Some("0.10.0-a1b2c3d"));
+}
+
+#[test]
+fn test_provider_type_from_str() {
+    assert_eq!("eclair".parse::<ProviderType>().unwrap(), ProviderType::Eclair);
+}
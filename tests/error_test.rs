@@ -0,0 +1,88 @@
+//! LightningError classification tests
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::retry::{retry_idempotent, ProviderRetryPolicy};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+#[test]
+fn test_is_retryable() {
+    assert!(LightningError::NodeConnectionError("refused".into()).is_retryable());
+    assert!(LightningError::RoutingError("no route".into()).is_retryable());
+    assert!(LightningError::ProcessorError("request timeout".into()).is_retryable());
+    assert!(LightningError::ProcessorError("Connection refused (os error 111)".into()).is_retryable());
+
+    assert!(!LightningError::ProcessorError("Invalid API key".into()).is_retryable());
+    assert!(!LightningError::InvoiceError("expired".into()).is_retryable());
+    assert!(!LightningError::InvoiceParseError("bad bech32".into()).is_retryable());
+    assert!(!LightningError::ConfigError("missing url".into()).is_retryable());
+    assert!(!LightningError::PaymentVerificationFailed("unpaid".into()).is_retryable());
+}
+
+#[test]
+fn test_suggested_retry_delay() {
+    assert_eq!(LightningError::InvoiceError("expired".into()).suggested_retry_delay_ms(), 0);
+    assert_eq!(LightningError::ProcessorError("Invalid API key".into()).suggested_retry_delay_ms(), 0);
+    assert!(LightningError::NodeConnectionError("refused".into()).suggested_retry_delay_ms() > 0);
+    assert!(
+        LightningError::RoutingError("no route".into()).suggested_retry_delay_ms()
+            > LightningError::NodeConnectionError("refused".into()).suggested_retry_delay_ms()
+    );
+}
+
+#[tokio::test]
+async fn test_retry_skips_permanent_errors() {
+    let policy = ProviderRetryPolicy {
+        backoff_base: Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    let calls = AtomicU32::new(0);
+    let result: Result<(), _> = retry_idempotent(&policy, "lookup", || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        async { Err(LightningError::InvoiceError("unknown invoice".into())) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let calls = AtomicU32::new(0);
+    let result: Result<(), _> = retry_idempotent(&policy, "lookup", || {
+        calls.fetch_add(1, Ordering::SeqCst);
+        async { Err(LightningError::ProcessorError("upstream timeout".into())) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), policy.max_retries + 1);
+}
+
+#[test]
+fn test_http_error_status() {
+    let rate_limited = LightningError::HttpError { status_code: 429, body: String::new() };
+    assert_eq!(rate_limited.status_code(), Some(429));
+    assert!(rate_limited.is_retryable());
+
+    assert!(LightningError::HttpError { status_code: 502, body: "bad gateway".into() }.is_retryable());
+
+    let bad_request = LightningError::HttpError { status_code: 400, body: "bad request".into() };
+    assert!(!bad_request.is_retryable());
+    assert_eq!(bad_request.suggested_retry_delay_ms(), 0);
+
+    assert_eq!(LightningError::ConfigError("invalid API key".into()).status_code(), None);
+}
+
+#[test]
+fn test_lnbits_api_error() {
+    let err = LightningError::LNBitsApiError {
+        status: 400,
+        detail: "Amount must be positive".into(),
+        endpoint: "/api/v1/payments".into(),
+    };
+    assert_eq!(err.status_code(), Some(400));
+    assert!(!err.is_retryable());
+    assert_eq!(err.to_string(), "LNBits API error 400 at /api/v1/payments: Amount must be positive");
+
+    assert!(!LightningError::InsufficientBalance("Insufficient balance.".into()).is_retryable());
+    assert!(!LightningError::InvoiceExpired("Invoice expired.".into()).is_retryable());
+    assert!(!LightningError::WalletNotFound("Wallet not found.".into()).is_retryable());
+}
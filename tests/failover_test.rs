@@ -0,0 +1,171 @@
+//! Failover provider tests with a flaky primary
+
+use async_trait::async_trait;
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::circuit_breaker::CircuitBreakerConfig;
+use blvm_lightning::provider::failover::FailoverProvider;
+use blvm_lightning::provider::{HealthStatus, LightningProvider, PaymentVerificationResult, ProviderCapabilities, ProviderType};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Provider that can be taken down, or made to report an unknown payment
+struct MockProvider {
+    provider_type: ProviderType,
+    down: Arc<AtomicBool>,
+    unknown_payment: Arc<AtomicBool>,
+    calls: Arc<AtomicU32>,
+}
+
+impl MockProvider {
+    fn new(provider_type: ProviderType) -> Self {
+        Self {
+            provider_type,
+            down: Arc::default(),
+            unknown_payment: Arc::default(),
+            calls: Arc::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LightningProvider for MockProvider {
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        _payment_hash: &[u8; 32],
+        _payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if self.down.load(Ordering::SeqCst) {
+            return Err(LightningError::NodeConnectionError("connection refused".to_string()));
+        }
+        if self.unknown_payment.load(Ordering::SeqCst) {
+            return Err(LightningError::PaymentVerificationFailed("payment not found".to_string()));
+        }
+        Ok(PaymentVerificationResult {
+            verified: true,
+            amount_msats: Some(1000),
+            timestamp: None,
+            metadata: serde_json::json!({ "provider": self.provider_type.to_string() }),
+        })
+    }
+
+    async fn create_invoice(&self, _amount_msats: u64, _description: &str, _expiry_seconds: u64) -> Result<String, LightningError> {
+        unimplemented!()
+    }
+
+    async fn is_payment_confirmed(&self, _payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        unimplemented!()
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        unimplemented!()
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::default()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        self.provider_type.clone()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+struct Fixture {
+    provider: FailoverProvider,
+    primary_down: Arc<AtomicBool>,
+    primary_unknown_payment: Arc<AtomicBool>,
+    primary_calls: Arc<AtomicU32>,
+    secondary_calls: Arc<AtomicU32>,
+}
+
+fn failover(cooldown_seconds: u64) -> Fixture {
+    let primary = MockProvider::new(ProviderType::LNBits);
+    let secondary = MockProvider::new(ProviderType::LDK);
+    let primary_down = primary.down.clone();
+    let primary_unknown_payment = primary.unknown_payment.clone();
+    let primary_calls = primary.calls.clone();
+    let secondary_calls = secondary.calls.clone();
+    let config = CircuitBreakerConfig {
+        failure_threshold: 2,
+        window_seconds: 60,
+        reset_timeout_seconds: cooldown_seconds,
+    };
+    Fixture {
+        provider: FailoverProvider::with_circuit_breakers(vec![Box::new(primary), Box::new(secondary)], config).unwrap(),
+        primary_down,
+        primary_unknown_payment,
+        primary_calls,
+        secondary_calls,
+    }
+}
+
+async fn verify(provider: &FailoverProvider) -> Result<PaymentVerificationResult, LightningError> {
+    provider.verify_payment("", &[0u8; 32], "payment-1").await
+}
+
+#[tokio::test]
+async fn test_primary_answers_when_up() {
+    let fixture = failover(60);
+    let result = verify(&fixture.provider).await.unwrap();
+
+    assert_eq!(result.metadata["answered_by"], "lnbits");
+    assert_eq!(fixture.provider.provider_type(), ProviderType::LNBits);
+    assert_eq!(fixture.secondary_calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_outage_and_recovery() {
+    let fixture = failover(1);
+    fixture.primary_down.store(true, Ordering::SeqCst);
+
+    // Outage: the secondary answers
+    for _ in 0..4 {
+        let result = verify(&fixture.provider).await.unwrap();
+        assert_eq!(result.metadata["answered_by"], "ldk");
+        assert_eq!(result.metadata["provider"], "ldk");
+    }
+    assert_eq!(fixture.provider.provider_type(), ProviderType::LDK);
+    // The breaker opened after two failures, so the dead primary isn't called every time
+    assert_eq!(fixture.primary_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(fixture.secondary_calls.load(Ordering::SeqCst), 4);
+
+    // Recovery: after the cooldown a trial call reaches the primary again
+    fixture.primary_down.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let result = verify(&fixture.provider).await.unwrap();
+    assert_eq!(result.metadata["answered_by"], "lnbits");
+    assert_eq!(fixture.provider.provider_type(), ProviderType::LNBits);
+    assert_eq!(fixture.primary_calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_unknown_payment_does_not_fail_over() {
+    let fixture = failover(60);
+    fixture.primary_unknown_payment.store(true, Ordering::SeqCst);
+
+    for _ in 0..3 {
+        let err = verify(&fixture.provider).await.unwrap_err();
+        assert!(matches!(err, LightningError::PaymentVerificationFailed(_)), "{:?}", err);
+    }
+    assert_eq!(fixture.secondary_calls.load(Ordering::SeqCst), 0);
+    // An answering primary doesn't trip its breaker
+    assert_eq!(fixture.primary_calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_all_providers_down() {
+    let primary = MockProvider::new(ProviderType::LNBits);
+    let secondary = MockProvider::new(ProviderType::LDK);
+    primary.down.store(true, Ordering::SeqCst);
+    secondary.down.store(true, Ordering::SeqCst);
+    let provider = FailoverProvider::new(vec![Box::new(primary), Box::new(secondary)]).unwrap();
+
+    let err = verify(&provider).await.unwrap_err();
+    assert!(err.is_retryable());
+}
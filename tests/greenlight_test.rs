@@ -0,0 +1,51 @@
+//! Greenlight integration test
+//!
+//! Requires the `greenlight` feature and a reachable Greenlight scheduler:
+//!
+//! ```text
+//! BLVM_GREENLIGHT_TEST=1 \
+//! GL_DEVELOPER_CERT=/path/to/client.crt GL_DEVELOPER_KEY=/path/to/client-key.pem \
+//! cargo test --features greenlight --test greenlight_test
+//! ```
+#![cfg(feature = "greenlight")]
+
+mod common;
+
+use blvm_lightning::provider::keystore;
+use blvm_lightning::provider::{create_provider, LightningProvider, ProviderType};
+use common::test_context;
+
+#[tokio::test]
+async fn test_invoice_round_trip() {
+    if std::env::var("BLVM_GREENLIGHT_TEST").is_err() {
+        eprintln!("skipping: BLVM_GREENLIGHT_TEST not set");
+        return;
+    }
+    let cert = std::env::var("GL_DEVELOPER_CERT").unwrap_or_default();
+    let key = std::env::var("GL_DEVELOPER_KEY").unwrap_or_default();
+
+    let ctx = test_context(&[
+        ("lightning.greenlight.network", "testnet"),
+        ("lightning.greenlight.seed_passphrase", "integration test"),
+        ("lightning.greenlight.developer_cert", cert.as_str()),
+        ("lightning.greenlight.developer_key", key.as_str()),
+    ]);
+    let provider = create_provider(ProviderType::Greenlight, &ctx).unwrap();
+
+    let seed = std::fs::read(std::path::Path::new(&ctx.data_dir).join("greenlight/seed.enc")).unwrap();
+    assert!(keystore::is_encrypted(&seed));
+
+    let invoice = provider.create_invoice(10_000, "blvm-lightning greenlight test", 600).await.unwrap();
+    let data = blvm_lightning::invoice::InvoiceParser::parse(&invoice).unwrap();
+    assert_eq!(data.amount_msats, 10_000);
+
+    let result = provider.verify_payment(&invoice, &data.payment_hash(), "gl-test").await.unwrap();
+    assert!(!result.verified);
+    assert!(!provider.is_payment_confirmed(&data.payment_hash()).await.unwrap());
+}
+
+#[test]
+fn test_requires_passphrase() {
+    let ctx = test_context(&[("lightning.greenlight.network", "testnet")]);
+    assert!(create_provider(ProviderType::Greenlight, &ctx).is_err());
+}
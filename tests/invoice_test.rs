@@ -0,0 +1,153 @@
+//! BOLT11 invoice parsing tests
+
+use bitcoin_hashes::{sha256, Hash};
+use blvm_lightning::invoice::{InvoiceParser, RouteHintHop};
+use lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret, RouteHint, RoutingFees};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+type RouteHop = lightning_invoice::RouteHintHop;
+
+fn pubkey(secret: u8) -> PublicKey {
+    PublicKey::from_secret_key(&Secp256k1::new(), &SecretKey::from_slice(&[secret; 32]).unwrap())
+}
+
+fn hop(secret: u8, short_channel_id: u64, fee_base_msat: u32, fee_proportional_millionths: u32, cltv_expiry_delta: u16) -> RouteHop {
+    RouteHop {
+        src_node_id: pubkey(secret),
+        short_channel_id,
+        fees: RoutingFees {
+            base_msat: fee_base_msat,
+            proportional_millionths: fee_proportional_millionths,
+        },
+        cltv_expiry_delta,
+        htlc_minimum_msat: None,
+        htlc_maximum_msat: None,
+    }
+}
+
+/// Build a signed testnet invoice with the given routing hints
+fn invoice_with_routes(routes: Vec<Vec<RouteHop>>) -> String {
+    let mut builder = InvoiceBuilder::new(Currency::BitcoinTestnet)
+        .amount_milli_satoshis(1_000)
+        .description("routing hints".to_string());
+    for hops in routes {
+        builder = builder.private_route(RouteHint(hops));
+    }
+    let secp = Secp256k1::new();
+    let node_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+    builder
+        .payment_hash(sha256::Hash::hash(b"routing hints"))
+        .payment_secret(PaymentSecret([0u8; 32]))
+        .min_final_cltv_expiry_delta(18)
+        .current_timestamp()
+        .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &node_key))
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_routing_hints() {
+    // 700000x1234x1 and 650000x42x0 in block x tx x output form
+    let first_scid = (700_000u64 << 40) | (1_234 << 16) | 1;
+    let second_scid = (650_000u64 << 40) | (42 << 16);
+    let invoice = invoice_with_routes(vec![
+        vec![hop(2, first_scid, 1_000, 100, 40)],
+        vec![hop(3, second_scid, 0, 2_500, 144), hop(4, first_scid + 1, 21, 1, 18)],
+    ]);
+
+    let data = InvoiceParser::parse(&invoice).unwrap();
+    assert!(data.has_private_hints());
+    assert_eq!(data.routing_hints.len(), 2);
+    assert_eq!(
+        data.routing_hints[0].hops,
+        vec![RouteHintHop {
+            src_node_id: pubkey(2).serialize(),
+            short_channel_id: first_scid,
+            fee_base_msats: 1_000,
+            fee_proportional_millionths: 100,
+            cltv_expiry_delta: 40,
+        }]
+    );
+
+    let hops = &data.routing_hints[1].hops;
+    assert_eq!(hops.len(), 2);
+    assert_eq!(hops[0].src_node_id, pubkey(3).serialize());
+    assert_eq!(hops[0].short_channel_id, second_scid);
+    assert_eq!(hops[0].fee_base_msats, 0);
+    assert_eq!(hops[0].fee_proportional_millionths, 2_500);
+    assert_eq!(hops[0].cltv_expiry_delta, 144);
+    assert_eq!(hops[1].src_node_id, pubkey(4).serialize());
+    assert_eq!(hops[1].short_channel_id, first_scid + 1);
+    assert_eq!(hops[1].fee_base_msats, 21);
+    assert_eq!(hops[1].cltv_expiry_delta, 18);
+}
+
+#[test]
+fn test_no_routing_hints() {
+    let data = InvoiceParser::parse(&invoice_with_routes(Vec::new())).unwrap();
+    assert!(data.routing_hints.is_empty());
+    assert!(!data.has_private_hints());
+}
+
+#[test]
+fn test_verify_signature_against_pubkey() {
+    // Signed with secret key [1; 32]
+    let known_pubkey: [u8; 33] = hex::decode("031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f")
+        .unwrap()
+        .try_into()
+        .unwrap();
+    let data = InvoiceParser::parse(&invoice_with_routes(Vec::new())).unwrap();
+    assert_eq!(data.payee_pubkey, Some(known_pubkey));
+
+    assert!(InvoiceParser::verify_signature(&data.invoice).unwrap());
+    assert!(InvoiceParser::verify_signature_against_pubkey(&data.invoice, &known_pubkey).unwrap());
+    assert!(!InvoiceParser::verify_signature_against_pubkey(&data.invoice, &pubkey(2).serialize()).unwrap());
+}
+
+#[test]
+fn test_clone_debug_and_summary() {
+    let invoice = invoice_with_routes(Vec::new());
+    let data = InvoiceParser::parse(&invoice).unwrap();
+    assert_eq!(data.raw, invoice);
+
+    let cloned = data.clone();
+    assert_eq!(cloned.payment_hash, data.payment_hash);
+    assert_eq!(cloned.raw, data.raw);
+
+    let hash_hex = data.payment_hash_hex();
+    let debug = format!("{:?}", data);
+    assert!(debug.contains(&hash_hex));
+    assert!(!debug.contains(&invoice));
+    assert_eq!(
+        data.to_summary(),
+        format!("Invoice {{ hash: {}, amount: 1000 msats, expiry_at: {} }}", hash_hex, data.timestamp + 3600)
+    );
+}
+
+#[test]
+fn test_payment_hash_bytes_preserved() {
+    // Hashes with leading zero bytes and every byte value survive parsing unchanged
+    let mut expected = [0u8; 32];
+    for (i, byte) in expected.iter_mut().enumerate() {
+        *byte = (i as u8).wrapping_mul(37);
+    }
+    let secp = Secp256k1::new();
+    let node_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+    let invoice = InvoiceBuilder::new(Currency::Regtest)
+        .amount_milli_satoshis(5_000)
+        .description("hash bytes".to_string())
+        .payment_hash(sha256::Hash::from_byte_array(expected))
+        .payment_secret(PaymentSecret([1u8; 32]))
+        .min_final_cltv_expiry_delta(18)
+        .current_timestamp()
+        .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &node_key))
+        .unwrap()
+        .to_string();
+    assert!(invoice.starts_with("lnbcrt"));
+
+    let data = InvoiceParser::parse(&invoice).unwrap();
+    assert_eq!(data.payment_hash(), expected);
+    assert_eq!(data.payment_hash, expected.to_vec());
+    assert_eq!(data.payment_hash_hex(), hex::encode(expected));
+    assert_eq!(data.amount_msats, 5_000);
+}
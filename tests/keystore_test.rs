@@ -0,0 +1,41 @@
+//! Encrypted key storage tests
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::keystore;
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir()
+        .join(format!("blvm-lightning-keystore-{}", rand::random::<u64>()))
+        .join(name)
+}
+
+#[test]
+fn test_round_trip() {
+    let path = temp_path("seed.enc");
+    let secret = [7u8; 32];
+
+    keystore::save_encrypted(&path, &secret, "correct horse").unwrap();
+    let loaded = keystore::load_encrypted(&path, "correct horse").unwrap();
+    assert_eq!(loaded.as_deref(), Some(&secret[..]));
+
+    // Never stored in plaintext
+    let raw = std::fs::read(&path).unwrap();
+    assert!(keystore::is_encrypted(&raw));
+    assert!(!raw.windows(secret.len()).any(|w| w == secret));
+}
+
+#[test]
+fn test_wrong_passphrase() {
+    let path = temp_path("seed.enc");
+    keystore::save_encrypted(&path, &[7u8; 32], "correct horse").unwrap();
+
+    match keystore::load_encrypted(&path, "battery staple") {
+        Err(LightningError::ConfigError(msg)) => assert!(msg.contains("Wrong passphrase")),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_missing_file() {
+    assert!(keystore::load_encrypted(&temp_path("missing.enc"), "pass").unwrap().is_none());
+}
@@ -0,0 +1,1260 @@
+//! LNBits provider tests against a mock HTTP server
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::provider::lnbits::{
+    LNBitsConfig, LNBitsPayment, LNBitsProvider, LNBitsWallet, DEFAULT_CONNECT_TIMEOUT_MS, DEFAULT_POOL_IDLE_TIMEOUT_SECS,
+    DEFAULT_POOL_MAX_IDLE_PER_HOST, DEFAULT_PROXY_CONNECT_TIMEOUT_MS, DEFAULT_PROXY_REQUEST_TIMEOUT_SECS, LNBITS_WALLETS_TREE,
+};
+use blvm_lightning::provider::retry::ProviderRetryPolicy;
+use blvm_lightning::provider::{create_provider, InvoiceParams, LightningProvider, ProviderType};
+use blvm_lightning::processor::LightningProcessor;
+use common::{test_context, MockNodeApi};
+use futures::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+use std::time::Duration;
+use std::path::PathBuf;
+
+fn config_for(server: &mockito::Server) -> LNBitsConfig {
+    LNBitsConfig {
+        api_url: server.url(),
+        invoice_key: "test_key".to_string(),
+        retry_policy: ProviderRetryPolicy {
+            backoff_base: Duration::from_millis(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_retries_server_errors() {
+    let mut server = mockito::Server::new_async().await;
+    let payment_hash = [1u8; 32];
+    let path = format!("/api/v1/payments/{}", hex::encode(payment_hash));
+
+    let failing = server
+        .mock("GET", path.as_str())
+        .with_status(503)
+        .expect(2)
+        .create_async()
+        .await;
+    let succeeding = server
+        .mock("GET", path.as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    assert!(provider.is_payment_confirmed(&payment_hash).await.unwrap());
+
+    failing.assert_async().await;
+    succeeding.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_retries_rate_limits() {
+    let mut server = mockito::Server::new_async().await;
+    let payment_hash = [3u8; 32];
+    let path = format!("/api/v1/payments/{}", hex::encode(payment_hash));
+
+    let limited = server
+        .mock("GET", path.as_str())
+        .with_status(429)
+        .expect(2)
+        .create_async()
+        .await;
+    let succeeding = server
+        .mock("GET", path.as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 1000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let result = provider.verify_payment("", &payment_hash, "payment-1").await.unwrap();
+    assert!(result.verified);
+
+    limited.assert_async().await;
+    succeeding.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_invoice_creation_retried_after_connect_failure() {
+    // Reserve a port with nothing listening on it yet
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut server = mockito::Server::new_with_opts_async(mockito::ServerOpts {
+            host: "127.0.0.1",
+            port,
+            ..Default::default()
+        })
+        .await;
+        let mock = server
+            .mock("POST", "/api/v1/payments")
+            .with_status(201)
+            .with_body(r#"{"payment_hash": "00", "payment_request": "lnbc1test"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        (server, mock)
+    });
+
+    let mut config = LNBitsConfig {
+        api_url: format!("http://127.0.0.1:{}", port),
+        invoice_key: "test_key".to_string(),
+        ..Default::default()
+    };
+    config.retry_policy.backoff_base = Duration::from_millis(200);
+    let provider = LNBitsProvider::new(config).unwrap();
+
+    // No label, so only the refused connection makes this safe to repeat
+    assert_eq!(provider.create_invoice(1000, "test", 3600).await.unwrap(), "lnbc1test");
+    let (_server, mock) = server.await.unwrap();
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_retries() {
+    let mut server = mockito::Server::new_async().await;
+    let payment_hash = [2u8; 32];
+    let path = format!("/api/v1/payments/{}", hex::encode(payment_hash));
+
+    let failing = server
+        .mock("GET", path.as_str())
+        .with_status(500)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let mut config = config_for(&server);
+    config.retry_policy.max_retries = 1;
+    let provider = LNBitsProvider::new(config).unwrap();
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+
+    failing.assert_async().await;
+}
+
+#[test]
+fn test_custom_ca_cert() {
+    let config = LNBitsConfig {
+        api_url: "https://lnbits.local".to_string(),
+        tls_ca_cert_path: Some(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test_ca.pem"))),
+        ..Default::default()
+    };
+    assert!(LNBitsProvider::new(config).is_ok());
+
+    let config = LNBitsConfig {
+        tls_ca_cert_path: Some(PathBuf::from("/nonexistent/ca.pem")),
+        ..Default::default()
+    };
+    assert!(LNBitsProvider::new(config).is_err());
+}
+
+#[tokio::test]
+async fn test_websocket_tls_settings() {
+    // The websocket trusts the same extra CA and honours accept-invalid like the REST client
+    let config = LNBitsConfig {
+        api_url: "https://lnbits.local".to_string(),
+        tls_ca_cert_path: Some(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/test_ca.pem"))),
+        websocket: true,
+        ..Default::default()
+    };
+    assert!(LNBitsProvider::new(config).is_ok());
+
+    let config = LNBitsConfig {
+        api_url: "https://lnbits.local".to_string(),
+        tls_accept_invalid_certs: true,
+        websocket: true,
+        ..Default::default()
+    };
+    assert!(LNBitsProvider::new(config).is_ok());
+}
+
+#[test]
+fn test_socks5_proxy() {
+    let config = LNBitsConfig {
+        api_url: "http://lnbitsexampleonionaddress.onion".to_string(),
+        socks5_proxy: Some("socks5h://127.0.0.1:9050".to_string()),
+        ..Default::default()
+    };
+    assert!(config.validate().is_ok());
+    assert!(LNBitsProvider::new(config).is_ok());
+
+    for proxy in ["127.0.0.1:9050", "http://127.0.0.1:9050", "socks5h://127.0.0.1", "socks5h://:9050"] {
+        let config = LNBitsConfig {
+            socks5_proxy: Some(proxy.to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(config.validate(), Err(LightningError::ConfigError(_))), "{}", proxy);
+        assert!(LNBitsProvider::new(config).is_err(), "{}", proxy);
+    }
+}
+
+#[test]
+fn test_tor_config_keys() {
+    let lnbits_config = |entries: &[(&str, &str)]| {
+        let provider = create_provider(ProviderType::LNBits, &test_context(entries)).unwrap();
+        provider.as_any().downcast_ref::<LNBitsProvider>().unwrap().config().clone()
+    };
+
+    // Building the client makes no requests, so nothing needs to listen on the proxy
+    let config = lnbits_config(&[
+        ("lightning.lnbits.api_url", "http://lnbitsexampleonionaddress.onion"),
+        ("lightning.lnbits.proxy", "socks5h://127.0.0.1:9050"),
+        ("lightning.lnbits.accept_invalid_certs", "true"),
+        ("lightning.lnbits.ca_cert_path", "tests/fixtures/test_ca.pem"),
+    ]);
+    assert_eq!(config.socks5_proxy.as_deref(), Some("socks5h://127.0.0.1:9050"));
+    assert!(config.tls_accept_invalid_certs);
+    assert_eq!(config.tls_ca_cert_path, Some(PathBuf::from("tests/fixtures/test_ca.pem")));
+    assert_eq!(config.retry_policy.request_timeout, Duration::from_secs(DEFAULT_PROXY_REQUEST_TIMEOUT_SECS));
+    assert_eq!(config.connect_timeout_ms, DEFAULT_PROXY_CONNECT_TIMEOUT_MS);
+
+    let config = lnbits_config(&[
+        ("lightning.lnbits.proxy", "socks5h://127.0.0.1:9050"),
+        ("lightning.lnbits.timeout_secs", "120"),
+    ]);
+    assert_eq!(config.retry_policy.request_timeout, Duration::from_secs(120));
+
+    // Without a proxy the usual defaults apply
+    let config = lnbits_config(&[("lightning.lnbits.timeout_secs", "45")]);
+    assert_eq!(config.socks5_proxy, None);
+    assert_eq!(config.retry_policy.request_timeout, Duration::from_secs(45));
+    assert_eq!(config.connect_timeout_ms, DEFAULT_CONNECT_TIMEOUT_MS);
+
+    // The millisecond keys from before the shared retry policy are still read
+    let config = lnbits_config(&[
+        ("lightning.lnbits.request_timeout_ms", "2500"),
+        ("lightning.lnbits.max_retries", "5"),
+        ("lightning.lnbits.retry_delay_ms", "200"),
+        ("lightning.lnbits.socks5_proxy", "socks5h://127.0.0.1:9050"),
+    ]);
+    assert_eq!(config.retry_policy.request_timeout, Duration::from_millis(2500));
+    assert_eq!(config.retry_policy.max_retries, 5);
+    assert_eq!(config.retry_policy.backoff_base, Duration::from_millis(200));
+    let config = lnbits_config(&[("lightning.lnbits.request_timeout_ms", "2500"), ("lightning.lnbits.timeout_secs", "45")]);
+    assert_eq!(config.retry_policy.request_timeout, Duration::from_secs(45));
+
+    let ctx = test_context(&[("lightning.lnbits.proxy", "http://127.0.0.1:8080")]);
+    assert!(matches!(create_provider(ProviderType::LNBits, &ctx), Err(LightningError::ConfigError(_))));
+}
+
+#[tokio::test]
+async fn test_get_node_info() {
+    let mut server = mockito::Server::new_async().await;
+    let wallet = server
+        .mock("GET", "/api/v1/wallet")
+        .with_status(200)
+        .with_body(r#"{"id": "wallet-1", "name": "shop", "balance": 1000}"#)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let info = provider.get_node_info().await.unwrap();
+    assert_eq!(info.alias.as_deref(), Some("shop"));
+    assert_eq!(info.node_id, [0u8; 33]);
+    wallet.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_get_wallet_balance() {
+    for (body, expected) in [
+        (r#"{"id": "wallet-1", "name": "shop", "balance": 21000}"#, 21000),
+        (r#"{"name": "shop", "balance": 21000.0}"#, 21000),
+        (r#"{"name": "shop", "balance": -3000}"#, 0),
+    ] {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/wallet")
+            .match_header("X-Api-Key", "test_key")
+            .with_status(200)
+            .with_body(body)
+            .create_async()
+            .await;
+        let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+        assert_eq!(provider.get_wallet_balance().await.unwrap(), expected, "{}", body);
+    }
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/api/v1/wallet")
+        .with_status(200)
+        .with_body(r#"{"name": "shop"}"#)
+        .create_async()
+        .await;
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    assert!(matches!(provider.get_wallet_balance().await, Err(LightningError::ProcessorError(_))));
+
+    // A revoked key won't start working on the next try
+    let mut server = mockito::Server::new_async().await;
+    let unauthorized = server
+        .mock("GET", "/api/v1/wallet")
+        .with_status(401)
+        .with_body(r#"{"detail": "Invalid key"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    assert!(matches!(provider.get_wallet_balance().await, Err(LightningError::ConfigError(_))));
+    unauthorized.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    let wallet = server
+        .mock("GET", "/api/v1/wallet")
+        .with_status(200)
+        .with_body(r#"{"name": "test", "balance": 1000}"#)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let status = provider.health_check().await.unwrap();
+    assert!(status.reachable);
+    wallet.assert_async().await;
+
+    let mut config = LNBitsConfig {
+        api_url: "http://127.0.0.1:1".to_string(),
+        ..Default::default()
+    };
+    config.retry_policy.max_retries = 0;
+    let provider = LNBitsProvider::new(config).unwrap();
+    assert!(!provider.health_check().await.unwrap().reachable);
+}
+
+#[tokio::test]
+async fn test_invoice_creation_not_retried_without_label() {
+    let mut server = mockito::Server::new_async().await;
+    let failing = server
+        .mock("POST", "/api/v1/payments")
+        .with_status(503)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    assert!(provider.create_invoice(1000, "test", 3600).await.is_err());
+    failing.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_labelled_invoice_creation_retried() {
+    let mut server = mockito::Server::new_async().await;
+    let failing = server
+        .mock("POST", "/api/v1/payments")
+        .with_status(503)
+        .expect(2)
+        .create_async()
+        .await;
+    let succeeding = server
+        .mock("POST", "/api/v1/payments")
+        .with_status(201)
+        .with_body(r#"{"payment_hash": "00", "payment_request": "lnbc1test"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let mut params = InvoiceParams::new(1000, "test", 3600);
+    params.label = Some("order-1".to_string());
+    assert_eq!(provider.create_invoice_ex(&params).await.unwrap(), "lnbc1test");
+
+    failing.assert_async().await;
+    succeeding.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_amounts_round_trip_in_msats() {
+    let payment_hash = [6u8; 32];
+    let mut server = mockito::Server::new_async().await;
+    // 10_000 msats is 10 sats, LNBits' default unit
+    let create = server
+        .mock("POST", "/api/v1/payments")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({ "out": false, "amount": 10 })))
+        .with_status(201)
+        .with_body(r#"{"payment_hash": "06", "payment_request": "lnbc100n1test"}"#)
+        .create_async()
+        .await;
+    let verify = server
+        .mock("GET", format!("/api/v1/payments/{}", hex::encode(payment_hash)).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 10000, "time": 1700000000}"#)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let invoice = provider.create_invoice(10_000, "test", 3600).await.unwrap();
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(10_000));
+    create.assert_async().await;
+    verify.assert_async().await;
+
+    // Sub-sat amounts round up rather than undercharging
+    server
+        .mock("POST", "/api/v1/payments")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({ "amount": 2 })))
+        .with_status(201)
+        .with_body(r#"{"payment_hash": "07", "payment_request": "lnbc20n1test"}"#)
+        .create_async()
+        .await;
+    assert_eq!(provider.create_invoice(1_001, "test", 3600).await.unwrap(), "lnbc20n1test");
+}
+
+#[tokio::test]
+async fn test_verify_amount_from_details_and_outgoing() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", format!("/api/v1/payments/{}", hex::encode([1u8; 32])).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "details": {"amount": 21000, "time": 1700000000}}"#)
+        .create_async()
+        .await;
+    server
+        .mock("GET", format!("/api/v1/payments/{}", hex::encode([2u8; 32])).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": -5000}"#)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let result = provider.verify_payment("", &[1u8; 32], "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(21_000));
+
+    // Negative amounts used to fail deserialization; they mark outgoing payments
+    let result = provider.verify_payment("", &[2u8; 32], "payment-2").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, Some(5_000));
+    assert_eq!(result.metadata["outgoing"], true);
+}
+
+#[tokio::test]
+async fn test_estimate_fee_from_reserve() {
+    let mut server = mockito::Server::new_async().await;
+    let reserve = server
+        .mock("GET", "/api/v1/payments/fee-reserve")
+        .match_query(mockito::Matcher::UrlEncoded("invoice".into(), "lnbc1test".into()))
+        .with_status(200)
+        .with_body(r#"{"fee_reserve": 2000}"#)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let estimate = provider.estimate_fee("lnbc1test", Some(100_000)).await.unwrap();
+    assert_eq!(estimate.estimated_total_fee_msats, 2000);
+    assert!(estimate.confidence > 0.0 && estimate.confidence <= 1.0);
+    reserve.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_estimate_fee_falls_back_to_fee_rate() {
+    let mut server = mockito::Server::new_async().await;
+    let _missing = server
+        .mock("GET", mockito::Matcher::Regex("^/api/v1/payments/fee-reserve".into()))
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(LNBitsConfig {
+        fee_rate_ppm: 5_000,
+        default_fee_estimate_msats: 500,
+        ..config_for(&server)
+    })
+    .unwrap();
+    let estimate = provider.estimate_fee("lnbc1test", Some(100_000)).await.unwrap();
+    assert_eq!(estimate.fee_base_msats, 500);
+    assert_eq!(estimate.estimated_total_fee_msats, 1_000);
+    assert!(estimate.fee_proportional >= 0.0);
+}
+
+#[tokio::test]
+async fn test_http_status_mapping() {
+    let mut server = mockito::Server::new_async().await;
+    let _unauthorized = server
+        .mock("GET", "/api/v1/wallet")
+        .with_status(401)
+        .with_body(r#"{"detail": "Invalid key"}"#)
+        .create_async()
+        .await;
+    let _rate_limited = server
+        .mock("POST", "/api/v1/payments")
+        .with_status(429)
+        .create_async()
+        .await;
+
+    let mut config = config_for(&server);
+    config.retry_policy.max_retries = 0;
+    let provider = LNBitsProvider::new(config).unwrap();
+
+    let err = provider.balance_msats().await.unwrap_err();
+    assert!(matches!(err, LightningError::ConfigError(ref msg) if msg == "invalid API key"), "{:?}", err);
+    assert!(!err.is_retryable());
+
+    let err = provider.create_invoice(1000, "test", 3600).await.unwrap_err();
+    assert!(matches!(err, LightningError::NodeConnectionError(ref msg) if msg == "rate limited"), "{:?}", err);
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_structured_api_errors() {
+    let create_invoice_error = |status: usize, body: &'static str| async move {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v1/payments")
+            .with_status(status)
+            .with_body(body)
+            .create_async()
+            .await;
+        let mut config = config_for(&server);
+        config.retry_policy.max_retries = 0;
+        let provider = LNBitsProvider::new(config).unwrap();
+        provider.create_invoice(1000, "test", 3600).await.unwrap_err()
+    };
+
+    let err = create_invoice_error(400, r#"{"detail": "Amount must be positive"}"#).await;
+    match &err {
+        LightningError::LNBitsApiError { status, detail, endpoint } => {
+            assert_eq!(*status, 400);
+            assert_eq!(detail, "Amount must be positive");
+            assert_eq!(endpoint, "/api/v1/payments");
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(err.status_code(), Some(400));
+    assert!(!err.is_retryable());
+
+    // FastAPI validation errors carry a list of messages
+    let err = create_invoice_error(
+        422,
+        r#"{"detail": [{"loc": ["body", "amount"], "msg": "field required", "type": "value_error.missing"}]}"#,
+    )
+    .await;
+    assert!(matches!(err, LightningError::LNBitsApiError { status: 422, ref detail, .. } if detail == "field required"), "{:?}", err);
+
+    let err = create_invoice_error(400, "not json").await;
+    assert!(matches!(err, LightningError::LNBitsApiError { ref detail, .. } if detail == "not json"), "{:?}", err);
+
+    let err = create_invoice_error(400, r#"{"detail": "Insufficient balance."}"#).await;
+    assert!(matches!(err, LightningError::InsufficientBalance(ref detail) if detail == "Insufficient balance."), "{:?}", err);
+    let err = create_invoice_error(400, r#"{"detail": "Invoice expired."}"#).await;
+    assert!(matches!(err, LightningError::InvoiceExpired(_)), "{:?}", err);
+    let err = create_invoice_error(404, r#"{"detail": "Wallet not found."}"#).await;
+    assert!(matches!(err, LightningError::WalletNotFound(_)), "{:?}", err);
+
+    let err = create_invoice_error(500, r#"{"detail": "Internal error"}"#).await;
+    assert!(matches!(err, LightningError::NodeConnectionError(ref msg) if msg.contains("Internal error")), "{:?}", err);
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_verify_not_found_vs_unauthorized() {
+    let payment_hash = [4u8; 32];
+    let path = format!("/api/v1/payments/{}", hex::encode(payment_hash));
+
+    // Not found: not paid yet
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", path.as_str())
+        .with_status(404)
+        .with_body(r#"{"detail": "Payment does not exist."}"#)
+        .create_async()
+        .await;
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    assert!(!provider.verify_payment("", &payment_hash, "payment-1").await.unwrap().verified);
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+
+    // A rejected key must not look like an unpaid invoice
+    for status in [401, 403] {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", path.as_str())
+            .with_status(status)
+            .with_body(r#"{"detail": "Invalid key"}"#)
+            .create_async()
+            .await;
+        let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+        let err = provider.verify_payment("", &payment_hash, "payment-1").await.unwrap_err();
+        assert!(matches!(err, LightningError::ConfigError(_)), "{}: {:?}", status, err);
+        assert!(matches!(provider.is_payment_confirmed(&payment_hash).await, Err(LightningError::ConfigError(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_cross_check_decode() {
+    let ldk = create_provider(ProviderType::LDK, &test_context(&[("lightning.ldk.network", "testnet")])).unwrap();
+    let invoice = ldk.create_invoice(21_000, "cross check", 600).await.unwrap();
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+    let lookup_path = format!("/api/v1/payments/{}", hex::encode(payment_hash));
+
+    // LNBits disagrees on the amount: fail without looking the payment up
+    let mut server = mockito::Server::new_async().await;
+    let decode = server
+        .mock("POST", "/api/v1/payments/decode")
+        .match_header("X-Api-Key", "test_key")
+        .match_body(mockito::Matcher::Json(serde_json::json!({ "data": invoice })))
+        .with_status(200)
+        .with_body(serde_json::json!({ "payment_hash": hex::encode(payment_hash), "amount_msat": 2_100 }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+    let lookup = server
+        .mock("GET", lookup_path.as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 21000}"#)
+        .expect(0)
+        .create_async()
+        .await;
+    let provider = LNBitsProvider::new(LNBitsConfig { cross_check_decode: true, ..config_for(&server) }).unwrap();
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "decode_mismatch");
+    assert_eq!(result.metadata["mismatch"]["field"], "amount_msats");
+    assert_eq!(result.metadata["mismatch"]["local"], 21_000);
+    assert_eq!(result.metadata["mismatch"]["lnbits"], 2_100);
+    decode.assert_async().await;
+    lookup.assert_async().await;
+
+    // Agreement: verification carries on as usual
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/api/v1/payments/decode")
+        .with_status(200)
+        .with_body(serde_json::json!({ "payment_hash": hex::encode(payment_hash), "amount_msat": 21_000 }).to_string())
+        .create_async()
+        .await;
+    let lookup = server
+        .mock("GET", lookup_path.as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 21000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let provider = LNBitsProvider::new(LNBitsConfig { cross_check_decode: true, ..config_for(&server) }).unwrap();
+    assert!(provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap().verified);
+    lookup.assert_async().await;
+
+    // An invoice LNBits can't decode doesn't verify
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/api/v1/payments/decode")
+        .with_status(400)
+        .with_body(r#"{"detail": "Failed to decode"}"#)
+        .create_async()
+        .await;
+    let provider = LNBitsProvider::new(LNBitsConfig { cross_check_decode: true, ..config_for(&server) }).unwrap();
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["mismatch"]["field"], "invoice");
+}
+
+#[tokio::test]
+async fn test_wallet_management_requires_admin_key() {
+    let server = mockito::Server::new_async().await;
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+
+    let err = provider.get_wallet_details().await.unwrap_err();
+    assert!(matches!(err, LightningError::ConfigError(ref msg) if msg.contains("admin key required")), "{:?}", err);
+    assert!(provider.create_wallet("user-1", "tenant").await.is_err());
+    assert!(provider.delete_wallet().await.is_err());
+}
+
+#[tokio::test]
+async fn test_wallet_management() {
+    let mut server = mockito::Server::new_async().await;
+    let create = server
+        .mock("POST", "/usermanager/api/v1/wallets")
+        .match_header("X-Api-Key", "test_key")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "user_id": "user-1",
+            "wallet_name": "tenant",
+        })))
+        .with_status(201)
+        .with_body(r#"{"id": "wallet-2", "name": "tenant", "user": "user-1", "adminkey": "admin-2", "inkey": "invoice-2"}"#)
+        .create_async()
+        .await;
+    let details = server
+        .mock("GET", "/api/v1/wallet")
+        .with_status(200)
+        .with_body(r#"{"id": "wallet-1", "name": "main", "balance": 21000}"#)
+        .create_async()
+        .await;
+    let delete = server
+        .mock("DELETE", "/api/v1/wallet")
+        .with_status(200)
+        .with_body("null")
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(LNBitsConfig {
+        invoice_key: String::new(),
+        admin_key: Some("test_key".to_string()),
+        ..config_for(&server)
+    })
+    .unwrap();
+
+    let wallet = provider.create_wallet("user-1", "tenant").await.unwrap();
+    assert_eq!(wallet.id, "wallet-2");
+    assert_eq!(wallet.balance_msats, 0);
+    assert_eq!(wallet.adminkey, "admin-2");
+    assert_eq!(wallet.inkey, "invoice-2");
+    assert!(!format!("{:?}", wallet).contains("admin-2"));
+
+    let wallet = provider.get_wallet_details().await.unwrap();
+    assert_eq!(wallet.name, "main");
+    assert_eq!(wallet.balance_msats, 21000);
+    assert_eq!(wallet.adminkey, "test_key");
+
+    provider.delete_wallet().await.unwrap();
+
+    create.assert_async().await;
+    details.assert_async().await;
+    delete.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_least_privileged_key_per_endpoint() {
+    let mut server = mockito::Server::new_async().await;
+    let payment_hash = [9u8; 32];
+    let verify = server
+        .mock("GET", format!("/api/v1/payments/{}", hex::encode(payment_hash)).as_str())
+        .match_header("X-Api-Key", "invoice_key")
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 1000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let create = server
+        .mock("POST", "/api/v1/payments")
+        .match_header("X-Api-Key", "invoice_key")
+        .with_status(201)
+        .with_body(r#"{"payment_hash": "09", "payment_request": "lnbc10n1test"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let balance = server
+        .mock("GET", "/api/v1/wallet")
+        .match_header("X-Api-Key", "invoice_key")
+        .with_status(200)
+        .with_body(r#"{"name": "shop", "balance": 1000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+    let details = server
+        .mock("GET", "/api/v1/wallet")
+        .match_header("X-Api-Key", "admin_key")
+        .with_status(200)
+        .with_body(r#"{"id": "wallet-1", "name": "shop", "balance": 1000, "adminkey": "admin_key", "inkey": "invoice_key"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(LNBitsConfig {
+        invoice_key: "invoice_key".to_string(),
+        admin_key: Some("admin_key".to_string()),
+        ..config_for(&server)
+    })
+    .unwrap();
+
+    assert!(provider.verify_payment("", &payment_hash, "p1").await.unwrap().verified);
+    provider.create_invoice(1000, "keys", 600).await.unwrap();
+    assert_eq!(provider.balance_msats().await.unwrap(), 1000);
+    assert_eq!(provider.get_wallet_details().await.unwrap().id, "wallet-1");
+
+    verify.assert_async().await;
+    create.assert_async().await;
+    balance.assert_async().await;
+    details.assert_async().await;
+}
+
+#[test]
+fn test_legacy_api_key_fallback() {
+    let lnbits_config = |entries: &[(&str, &str)]| {
+        let provider = create_provider(ProviderType::LNBits, &test_context(entries)).unwrap();
+        provider.as_any().downcast_ref::<LNBitsProvider>().unwrap().config().clone()
+    };
+
+    let config = lnbits_config(&[("lightning.lnbits.api_key", "legacy")]);
+    assert_eq!(config.invoice_key, "legacy");
+    assert_eq!(config.admin_key.as_deref(), Some("legacy"));
+
+    let config = lnbits_config(&[
+        ("lightning.lnbits.api_key", "legacy"),
+        ("lightning.lnbits.invoice_key", "invoice"),
+    ]);
+    assert_eq!(config.invoice_api_key(), "invoice");
+    assert_eq!(config.admin_api_key().unwrap(), "legacy");
+
+    // An admin key alone serves both
+    let config = lnbits_config(&[("lightning.lnbits.admin_key", "admin")]);
+    assert_eq!(config.invoice_api_key(), "admin");
+    assert_eq!(config.admin_api_key().unwrap(), "admin");
+
+    let config = lnbits_config(&[("lightning.lnbits.invoice_key", "invoice")]);
+    assert_eq!(config.invoice_api_key(), "invoice");
+    assert!(matches!(config.admin_api_key(), Err(LightningError::ConfigError(_))));
+}
+
+/// Settlement notification as pushed by the LNBits wallet websocket
+fn settlement_message(payment_hash: &[u8; 32]) -> Message {
+    Message::Text(
+        serde_json::json!({
+            "wallet_balance": 21,
+            "payment": {
+                "payment_hash": hex::encode(payment_hash),
+                "amount": 21000,
+                "status": "success",
+                "preimage": hex::encode([9u8; 32]),
+                "time": 1700000000,
+            },
+        })
+        .to_string(),
+    )
+}
+
+#[tokio::test]
+async fn test_websocket_settlements() {
+    let payment_hash = [4u8; 32];
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (send_tx, send_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        // The first connection drops straight away, so the provider has to reconnect
+        let (stream, _) = listener.accept().await.unwrap();
+        drop(stream);
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut path = String::new();
+        let mut socket = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response: Response| {
+            path = request.uri().path().to_string();
+            Ok(response)
+        })
+        .await
+        .unwrap();
+        send_rx.await.unwrap();
+        socket.send(Message::Text(r#"{"wallet_balance": 0}"#.to_string())).await.unwrap();
+        socket.send(settlement_message(&payment_hash)).await.unwrap();
+        // Hold the connection open until the test is done
+        let _ = socket.next().await;
+        path
+    });
+
+    let mut config = LNBitsConfig {
+        api_url: format!("http://127.0.0.1:{}", port),
+        invoice_key: "test_key".to_string(),
+        websocket: true,
+        ..Default::default()
+    };
+    config.retry_policy.backoff_base = Duration::from_millis(10);
+    let provider = LNBitsProvider::new(config).unwrap();
+    let mut settlements = provider.subscribe_payments().await.unwrap();
+    send_tx.send(()).unwrap();
+
+    let update = tokio::time::timeout(Duration::from_secs(5), settlements.next()).await.unwrap().unwrap();
+    assert_eq!(update.payment_hash, payment_hash);
+    assert_eq!(update.amount_msats, Some(21_000));
+    assert_eq!(update.preimage, Some([9u8; 32]));
+
+    // Served from the cache; a REST lookup would hit the websocket server and fail
+    let result = provider.verify_payment("", &payment_hash, "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(21_000));
+    assert_eq!(result.metadata["source"], "websocket");
+    assert!(provider.is_payment_confirmed(&payment_hash).await.unwrap());
+
+    drop(provider);
+    assert_eq!(server.await.unwrap(), "/api/v1/ws/test_key");
+}
+
+#[tokio::test]
+async fn test_websocket_disabled() {
+    let server = mockito::Server::new_async().await;
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    assert!(provider.subscribe_payments().await.is_err());
+
+    let config = LNBitsConfig {
+        socks5_proxy: Some("socks5h://127.0.0.1:9050".to_string()),
+        websocket: true,
+        ..config_for(&server)
+    };
+    assert!(matches!(LNBitsProvider::new(config), Err(LightningError::ConfigError(_))));
+}
+
+#[test]
+fn test_pool_config_keys() {
+    let lnbits_config = |entries: &[(&str, &str)]| {
+        let provider = create_provider(ProviderType::LNBits, &test_context(entries)).unwrap();
+        provider.as_any().downcast_ref::<LNBitsProvider>().unwrap().config().clone()
+    };
+
+    let config = lnbits_config(&[("lightning.lnbits.api_url", "https://lnbits.example.com")]);
+    assert_eq!(config.pool_max_idle_per_host, DEFAULT_POOL_MAX_IDLE_PER_HOST);
+    assert_eq!(config.pool_idle_timeout_secs, DEFAULT_POOL_IDLE_TIMEOUT_SECS);
+    assert!(!config.http2);
+
+    let config = lnbits_config(&[
+        ("lightning.lnbits.api_url", "https://lnbits.example.com"),
+        ("lightning.lnbits.pool_max_idle_per_host", "128"),
+        ("lightning.lnbits.pool_idle_timeout_secs", "300"),
+        ("lightning.lnbits.http2", "true"),
+    ]);
+    assert_eq!(config.pool_max_idle_per_host, 128);
+    assert_eq!(config.pool_idle_timeout_secs, 300);
+    assert!(config.http2);
+}
+
+/// Throughput of 1000 concurrent confirmation checks, without and with connection pooling
+///
+/// Run with `cargo test --release --test lnbits_test -- --ignored --nocapture`.
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn bench_concurrent_confirmations() {
+    const CALLS: usize = 1000;
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", mockito::Matcher::Regex(r"^/api/v1/payments/[0-9a-f]{64}$".to_string()))
+        .with_status(200)
+        .with_body(r#"{"paid": true}"#)
+        .expect_at_least(1)
+        .create_async()
+        .await;
+
+    for (label, pool_max_idle_per_host) in [("unpooled", 0), ("pooled", DEFAULT_POOL_MAX_IDLE_PER_HOST)] {
+        let provider = std::sync::Arc::new(
+            LNBitsProvider::new(LNBitsConfig { pool_max_idle_per_host, ..config_for(&server) }).unwrap(),
+        );
+        let started = std::time::Instant::now();
+        let checks = (0..CALLS).map(|i| {
+            let provider = provider.clone();
+            tokio::spawn(async move {
+                let mut payment_hash = [0u8; 32];
+                payment_hash[..8].copy_from_slice(&(i as u64).to_be_bytes());
+                provider.is_payment_confirmed(&payment_hash).await
+            })
+        });
+        for check in futures::future::join_all(checks).await {
+            assert!(check.unwrap().unwrap());
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "{}: {} calls in {:?} ({:.0} calls/s)",
+            label,
+            CALLS,
+            elapsed,
+            CALLS as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_status_cache() {
+    let mut server = mockito::Server::new_async().await;
+    let unpaid_hash = [11u8; 32];
+    let paid_hash = [12u8; 32];
+    let unpaid = server
+        .mock("GET", format!("/api/v1/payments/{}", hex::encode(unpaid_hash)).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": false}"#)
+        .expect(2)
+        .create_async()
+        .await;
+    let paid = server
+        .mock("GET", format!("/api/v1/payments/{}", hex::encode(paid_hash)).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 1000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let provider = LNBitsProvider::new(LNBitsConfig { status_cache_ms: 100, ..config_for(&server) }).unwrap();
+
+    // Back-to-back checks of one hash make one request
+    assert!(!provider.verify_payment("", &unpaid_hash, "payment-1").await.unwrap().verified);
+    assert!(!provider.is_payment_confirmed(&unpaid_hash).await.unwrap());
+    assert!(provider.verify_payment("", &paid_hash, "payment-2").await.unwrap().verified);
+    assert!(provider.is_payment_confirmed(&paid_hash).await.unwrap());
+
+    // Unpaid lookups expire, paid ones don't
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(!provider.is_payment_confirmed(&unpaid_hash).await.unwrap());
+    let result = provider.verify_payment("", &paid_hash, "payment-2").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(1000));
+
+    unpaid.assert_async().await;
+    paid.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_status_cache_is_bounded() {
+    let mut server = mockito::Server::new_async().await;
+    let hashes = [[21u8; 32], [22u8; 32], [23u8; 32]];
+    let mut mocks = Vec::new();
+    for (hash, expected) in hashes.iter().zip([2, 1, 1]) {
+        mocks.push(
+            server
+                .mock("GET", format!("/api/v1/payments/{}", hex::encode(hash)).as_str())
+                .with_status(200)
+                .with_body(r#"{"paid": true}"#)
+                .expect(expected)
+                .create_async()
+                .await,
+        );
+    }
+
+    let provider = LNBitsProvider::new(LNBitsConfig { status_cache_capacity: 2, ..config_for(&server) }).unwrap();
+    for hash in &hashes {
+        assert!(provider.is_payment_confirmed(hash).await.unwrap());
+    }
+    // The first hash was evicted, the last two are still cached
+    for hash in hashes.iter().rev() {
+        assert!(provider.is_payment_confirmed(hash).await.unwrap());
+    }
+
+    for mock in mocks {
+        mock.assert_async().await;
+    }
+}
+
+#[tokio::test]
+async fn test_multiple_wallets() {
+    let ldk = create_provider(ProviderType::LDK, &test_context(&[("lightning.ldk.network", "testnet")])).unwrap();
+    let mut server = mockito::Server::new_async().await;
+    let mut mocks = Vec::new();
+    let mut hashes = Vec::new();
+    for tenant in ["alice", "bob"] {
+        let invoice = ldk.create_invoice(1000, tenant, 3600).await.unwrap();
+        let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+        mocks.push(
+            server
+                .mock("POST", "/api/v1/payments")
+                .match_query(mockito::Matcher::UrlEncoded("wallet".into(), format!("{}_wallet", tenant)))
+                .match_header("X-Api-Key", format!("{}_key", tenant).as_str())
+                .with_status(201)
+                .with_body(serde_json::json!({"payment_request": invoice}).to_string())
+                .expect(1)
+                .create_async()
+                .await,
+        );
+        mocks.push(
+            server
+                .mock("GET", format!("/api/v1/payments/{}", hex::encode(payment_hash)).as_str())
+                .match_header("X-Api-Key", format!("{}_key", tenant).as_str())
+                .with_status(200)
+                .with_body(r#"{"paid": true, "amount": 1000}"#)
+                .expect(1)
+                .create_async()
+                .await,
+        );
+        hashes.push((tenant, payment_hash));
+    }
+    // No wallet hint: the default wallet and key, as before
+    let default_create = server
+        .mock("POST", "/api/v1/payments")
+        .match_query(mockito::Matcher::Missing)
+        .match_header("X-Api-Key", "test_key")
+        .with_status(201)
+        .with_body(r#"{"payment_request": "lnbc10n1default"}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let node_api = MockNodeApi::new();
+    let config = LNBitsConfig {
+        wallets: ["alice", "bob"]
+            .into_iter()
+            .map(|tenant| {
+                (
+                    tenant.to_string(),
+                    LNBitsWallet {
+                        wallet_id: format!("{}_wallet", tenant),
+                        invoice_key: format!("{}_key", tenant),
+                    },
+                )
+            })
+            .collect(),
+        ..config_for(&server)
+    };
+    let provider = LNBitsProvider::new(config.clone()).unwrap();
+    provider.attach_storage(node_api.clone()).await.unwrap();
+    for (tenant, _) in &hashes {
+        let params = InvoiceParams { wallet: Some(tenant.to_string()), ..InvoiceParams::new(1000, tenant, 3600) };
+        provider.create_invoice_ex(&params).await.unwrap();
+    }
+    assert_eq!(provider.create_invoice(1000, "default", 3600).await.unwrap(), "lnbc10n1default");
+    let unknown = InvoiceParams { wallet: Some("carol".to_string()), ..InvoiceParams::new(1000, "carol", 3600) };
+    assert!(matches!(provider.create_invoice_ex(&unknown).await, Err(LightningError::ConfigError(_))));
+
+    // The issuing wallet survives a restart and picks the key for verification
+    assert_eq!(node_api.len(LNBITS_WALLETS_TREE), hashes.len());
+    let provider = LNBitsProvider::new(config).unwrap();
+    provider.attach_storage(node_api.clone()).await.unwrap();
+    for (tenant, payment_hash) in &hashes {
+        assert_eq!(provider.issuing_wallet(payment_hash).as_deref(), Some(*tenant));
+        assert!(provider.verify_payment("", payment_hash, "payment-1").await.unwrap().verified);
+    }
+
+    for mock in mocks {
+        mock.assert_async().await;
+    }
+    default_create.assert_async().await;
+}
+
+#[test]
+fn test_wallets_config() {
+    let ctx = test_context(&[
+        ("lightning.lnbits.api_url", "https://lnbits.example.com"),
+        ("lightning.lnbits.invoice_key", "default_key"),
+        ("lightning.lnbits.wallets", r#"{"alice": {"wallet_id": "w1", "invoice_key": "k1"}}"#),
+    ]);
+    let provider = create_provider(ProviderType::LNBits, &ctx).unwrap();
+    let config = provider.as_any().downcast_ref::<LNBitsProvider>().unwrap().config().clone();
+    assert_eq!(
+        config.wallets.get("alice"),
+        Some(&LNBitsWallet { wallet_id: "w1".to_string(), invoice_key: "k1".to_string() })
+    );
+
+    let ctx = test_context(&[
+        ("lightning.lnbits.api_url", "https://lnbits.example.com"),
+        ("lightning.lnbits.wallets", r#"{"alice": "w1"}"#),
+    ]);
+    assert!(matches!(create_provider(ProviderType::LNBits, &ctx), Err(LightningError::ConfigError(_))));
+}
+
+/// `GET /api/v1/payments` as returned by LNBits: an incoming payment and an outgoing one
+const PAYMENTS_RESPONSE: &str = r#"[
+    {
+        "checking_id": "2c5b4b1ab1d7d0f54f49ea8f5c5e9c0e8a3b3e6c8b0e5a1f9d8c7b6a5f4e3d2c",
+        "pending": false,
+        "amount": 2000,
+        "fee": 0,
+        "memo": "coffee",
+        "time": 1700000000,
+        "bolt11": "lnbc20n1pjincoming",
+        "preimage": "0000000000000000000000000000000000000000000000000000000000000000",
+        "payment_hash": "2c5b4b1ab1d7d0f54f49ea8f5c5e9c0e8a3b3e6c8b0e5a1f9d8c7b6a5f4e3d2c",
+        "expiry": 1700003600.0,
+        "extra": {},
+        "wallet_id": "a1b2c3",
+        "webhook": null,
+        "webhook_status": null
+    },
+    {
+        "checking_id": "internal_9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0",
+        "pending": true,
+        "amount": -5000,
+        "fee": -12,
+        "memo": null,
+        "time": 1700000100,
+        "bolt11": "lnbc50n1pjoutgoing",
+        "preimage": null,
+        "payment_hash": "9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0",
+        "expiry": null,
+        "extra": {},
+        "wallet_id": "a1b2c3",
+        "webhook": null,
+        "webhook_status": null
+    }
+]"#;
+
+#[test]
+fn test_payment_history_deserialization() {
+    let payments: Vec<LNBitsPayment> = serde_json::from_str(PAYMENTS_RESPONSE).unwrap();
+    assert_eq!(
+        payments,
+        vec![
+            LNBitsPayment {
+                payment_hash: "2c5b4b1ab1d7d0f54f49ea8f5c5e9c0e8a3b3e6c8b0e5a1f9d8c7b6a5f4e3d2c".to_string(),
+                bolt11: "lnbc20n1pjincoming".to_string(),
+                amount: 2000,
+                memo: "coffee".to_string(),
+                time: 1_700_000_000,
+                fee: 0,
+                pending: false,
+            },
+            LNBitsPayment {
+                payment_hash: "9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0".to_string(),
+                bolt11: "lnbc50n1pjoutgoing".to_string(),
+                amount: -5000,
+                memo: String::new(),
+                time: 1_700_000_100,
+                fee: 12,
+                pending: true,
+            },
+        ]
+    );
+
+    // Stored records read back unchanged
+    let stored = serde_json::to_string(&payments[1]).unwrap();
+    assert_eq!(serde_json::from_str::<LNBitsPayment>(&stored).unwrap(), payments[1]);
+}
+
+#[tokio::test]
+async fn test_list_payments_and_sync_history() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/api/v1/payments")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("limit".into(), "50".into()),
+            mockito::Matcher::UrlEncoded("offset".into(), "0".into()),
+        ]))
+        .match_header("X-Api-Key", "test_key")
+        .with_status(200)
+        .with_body(PAYMENTS_RESPONSE)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let ctx = test_context(&[
+        ("lightning.provider", "lnbits"),
+        ("lightning.lnbits.api_url", &server.url()),
+        ("lightning.lnbits.invoice_key", "test_key"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    assert_eq!(processor.sync_payment_history(50).await.unwrap(), 2);
+    // Already synced
+    assert_eq!(processor.sync_payment_history(50).await.unwrap(), 0);
+    assert_eq!(node_api.len("lnbits_payments"), 2);
+    let outgoing = processor
+        .synced_payment("9f8e7d6c5b4a39281706f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(outgoing.amount, -5000);
+    assert!(outgoing.pending);
+    mock.assert_async().await;
+
+    let provider = LNBitsProvider::new(config_for(&server)).unwrap();
+    let page = server
+        .mock("GET", "/api/v1/payments")
+        .match_query(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::UrlEncoded("limit".into(), "1".into()),
+            mockito::Matcher::UrlEncoded("offset".into(), "1".into()),
+        ]))
+        .with_status(200)
+        .with_body("[]")
+        .create_async()
+        .await;
+    assert!(provider.list_payments(1, 1).await.unwrap().is_empty());
+    page.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_sync_history_requires_lnbits() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    assert!(matches!(processor.sync_payment_history(10).await, Err(LightningError::ProcessorError(_))));
+}
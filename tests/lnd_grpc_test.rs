@@ -0,0 +1,136 @@
+//! LND gRPC provider tests (requires the `lnd-grpc` feature)
+#![cfg(feature = "lnd-grpc")]
+
+mod common;
+
+use async_trait::async_trait;
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::lnd::grpc::invoice_event;
+use blvm_lightning::provider::lnd::{
+    proto, resumable_settlements, saved_settle_index, subscription_progress, InvoiceEvent, InvoiceSubscriber,
+    LND_SUBSCRIPTION_TREE, SETTLE_INDEX_KEY,
+};
+use common::MockNodeApi;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+
+fn settled_invoice(add_index: u64, settle_index: u64, hash_byte: u8) -> proto::Invoice {
+    proto::Invoice {
+        r_hash: vec![hash_byte; 32],
+        r_preimage: vec![hash_byte.wrapping_add(1); 32],
+        add_index,
+        settle_index,
+        settle_date: 1_700_000_000,
+        amt_paid_msat: 5_000,
+        state: proto::InvoiceState::Settled as i32,
+        ..Default::default()
+    }
+}
+
+/// Subscriber that drops the first subscription after one settlement,
+/// then replays that settlement plus a new one on resubscription
+struct FlakySubscriber {
+    calls: Mutex<Vec<(u64, u64)>>,
+}
+
+#[async_trait]
+impl InvoiceSubscriber for FlakySubscriber {
+    async fn subscribe(
+        &self,
+        add_index: u64,
+        settle_index: u64,
+    ) -> Result<BoxStream<'static, Result<InvoiceEvent, LightningError>>, LightningError> {
+        let mut calls = self.calls.lock().unwrap();
+        calls.push((add_index, settle_index));
+
+        let items = if calls.len() == 1 {
+            vec![
+                Ok(invoice_event(&settled_invoice(1, 1, 0xaa)).unwrap()),
+                Err(LightningError::NodeConnectionError("stream reset".to_string())),
+            ]
+        } else {
+            vec![
+                Ok(invoice_event(&settled_invoice(1, 1, 0xaa)).unwrap()),
+                Ok(invoice_event(&settled_invoice(2, 2, 0xbb)).unwrap()),
+            ]
+        };
+        Ok(futures::stream::iter(items).chain(futures::stream::pending()).boxed())
+    }
+}
+
+#[tokio::test]
+async fn test_subscription_resumes_from_last_index() {
+    let subscriber = Arc::new(FlakySubscriber { calls: Mutex::new(Vec::new()) });
+    let mut stream = resumable_settlements(subscriber.clone(), 0, 0, None);
+
+    let first = stream.next().await.unwrap();
+    let second = stream.next().await.unwrap();
+
+    // The replayed settlement is not delivered twice
+    assert_eq!(first.payment_hash, [0xaa; 32]);
+    assert_eq!(second.payment_hash, [0xbb; 32]);
+    assert_eq!(*subscriber.calls.lock().unwrap(), vec![(0, 0), (1, 1)]);
+}
+
+#[tokio::test]
+async fn test_settle_index_persisted() {
+    let node_api = MockNodeApi::new();
+    let progress = Arc::new(subscription_progress());
+    progress.attach(node_api.clone()).await.unwrap();
+    assert_eq!(saved_settle_index(&progress), 0);
+
+    let subscriber = Arc::new(FlakySubscriber { calls: Mutex::new(Vec::new()) });
+    let mut stream = resumable_settlements(subscriber, 0, 0, Some(progress.clone()));
+    stream.next().await.unwrap();
+    stream.next().await.unwrap();
+
+    // Recorded right after delivery
+    for _ in 0..50 {
+        if saved_settle_index(&progress) == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    node_api.assert_stored(LND_SUBSCRIPTION_TREE, SETTLE_INDEX_KEY.as_bytes(), b"2");
+
+    // After a restart the subscription replays from there
+    let restarted = subscription_progress();
+    restarted.attach(node_api.clone()).await.unwrap();
+    assert_eq!(saved_settle_index(&restarted), 2);
+
+    let subscriber = Arc::new(FlakySubscriber { calls: Mutex::new(Vec::new()) });
+    let _stream = resumable_settlements(subscriber.clone(), 0, saved_settle_index(&restarted), None);
+    for _ in 0..50 {
+        if !subscriber.calls.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(subscriber.calls.lock().unwrap()[0], (0, 2));
+}
+
+#[test]
+fn test_invoice_event_conversion() {
+    let event = invoice_event(&settled_invoice(3, 2, 0x01)).unwrap();
+    assert!(event.settled);
+    assert_eq!(event.add_index, 3);
+    assert_eq!(event.settle_index, 2);
+    assert_eq!(event.update.amount_msats, Some(5_000));
+    assert_eq!(event.update.settled_at, Some(1_700_000_000));
+    assert_eq!(event.update.preimage, Some([0x02; 32]));
+
+    let open = proto::Invoice {
+        r_hash: vec![0x01; 32],
+        ..Default::default()
+    };
+    let event = invoice_event(&open).unwrap();
+    assert!(!event.settled);
+    assert_eq!(event.update.amount_msats, None);
+
+    let malformed = proto::Invoice {
+        r_hash: vec![0x01; 4],
+        ..Default::default()
+    };
+    assert!(invoice_event(&malformed).is_none());
+}
@@ -0,0 +1,34 @@
+//! Metrics collector tests
+
+use blvm_lightning::metrics::MetricsCollector;
+
+#[test]
+fn test_render_prometheus() {
+    let metrics = MetricsCollector::new();
+    metrics.record_verification(true, 40);
+    metrics.record_verification(false, 60);
+    metrics.record_invoice_created();
+
+    let text = metrics.render_prometheus();
+    assert!(text.contains("# TYPE lightning_payments_verified_total counter\n"));
+    assert!(text.contains("lightning_payments_verified_total 1\n"));
+    assert!(text.contains("lightning_payments_failed_total 1\n"));
+    assert!(text.contains("lightning_invoices_created_total 1\n"));
+    assert!(text.contains("lightning_verification_latency_ms_sum 100\n"));
+    assert!(text.contains("lightning_verification_latency_ms_count 2\n"));
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let metrics = MetricsCollector::new();
+    metrics.record_invoice_created();
+
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    let server = tokio::spawn(metrics.clone().serve(addr));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let body = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap().text().await.unwrap();
+    assert!(body.contains("lightning_invoices_created_total 1"));
+    server.abort();
+}
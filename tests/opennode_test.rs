@@ -0,0 +1,256 @@
+//! OpenNode provider tests against recorded API responses
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::provider::opennode::{OpenNodeConfig, OpenNodeEnv, OpenNodeProvider, OPENNODE_CHARGES_TREE};
+use blvm_lightning::provider::{create_provider, DescriptionKind, InvoiceParams, LightningProvider, ProviderType};
+use common::{test_context, MockNodeApi};
+use mockito::Matcher;
+
+const API_KEY: &str = "opennode_test_key";
+const CHARGE_ID: &str = "3f5e8d2a-7c41-4b9e-a0d6-1e2f3a4b5c6d";
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/opennode/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+/// A real invoice, its payment hash, and `name` with the invoice filled in
+async fn charge_fixture(name: &str) -> (String, [u8; 32], String) {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    let invoice = ldk.create_invoice(2000, "order 1", 3600).await.unwrap();
+    let mut payment_hash = [0u8; 32];
+    payment_hash.copy_from_slice(&InvoiceParser::parse(&invoice).unwrap().payment_hash);
+    let body = fixture(name).replace("{{invoice}}", &invoice);
+    (invoice, payment_hash, body)
+}
+
+fn provider_for(server: &mockito::Server) -> OpenNodeProvider {
+    OpenNodeProvider::new(OpenNodeConfig {
+        url: server.url(),
+        api_key: API_KEY.to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+/// Provider that has created the charge in `charge_created.json`
+async fn provider_with_charge(server: &mut mockito::Server) -> (OpenNodeProvider, String, [u8; 32]) {
+    let (invoice, payment_hash, body) = charge_fixture("charge_created.json").await;
+    server
+        .mock("POST", "/v1/charges")
+        .match_header("Authorization", API_KEY)
+        .with_status(201)
+        .with_body(body)
+        .create_async()
+        .await;
+    let provider = provider_for(server);
+    assert_eq!(provider.create_invoice(2000, "order 1", 3600).await.unwrap(), invoice);
+    (provider, invoice, payment_hash)
+}
+
+#[tokio::test]
+async fn test_create_invoice_records_charge() {
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, payment_hash, body) = charge_fixture("charge_created.json").await;
+    let charge = server
+        .mock("POST", "/v1/charges")
+        .match_header("Authorization", API_KEY)
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            // 1500 msats rounds up to 2 sats, 90 seconds to 2 minutes
+            "amount": 2,
+            "description": "order 1",
+            "ttl": 2,
+            "auto_settle": false,
+            "order_id": "order-1",
+        })))
+        .with_status(201)
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let mut params = InvoiceParams::new(1500, "order 1", 90);
+    params.label = Some("order-1".to_string());
+    assert_eq!(provider.create_invoice_ex(&params).await.unwrap(), invoice);
+    assert_eq!(provider.charge_id(&payment_hash).as_deref(), Some(CHARGE_ID));
+    assert_eq!(provider.provider_type(), ProviderType::OpenNode);
+    charge.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_verify_paid_charge() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_charge(&mut server).await;
+    let body = fixture("charge_paid.json").replace("{{invoice}}", &invoice);
+    let lookup = server
+        .mock("GET", format!("/v1/charge/{}", CHARGE_ID).as_str())
+        .match_header("Authorization", API_KEY)
+        .with_status(200)
+        .with_body(body)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(2000));
+    assert_eq!(result.timestamp, Some(1718000042));
+    assert_eq!(result.metadata["charge_id"], CHARGE_ID);
+    assert_eq!(result.metadata["status"], "paid");
+    assert!(provider.is_payment_confirmed(&payment_hash).await.unwrap());
+    lookup.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_verify_unpaid_charge() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_charge(&mut server).await;
+    server
+        .mock("GET", format!("/v1/charge/{}", CHARGE_ID).as_str())
+        .with_status(200)
+        .with_body(fixture("charge_unpaid.json").replace("{{invoice}}", &invoice))
+        .create_async()
+        .await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, None);
+    assert_eq!(result.metadata["expired"], false);
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_expired_charge() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_charge(&mut server).await;
+    server
+        .mock("GET", format!("/v1/charge/{}", CHARGE_ID).as_str())
+        .with_status(200)
+        .with_body(fixture("charge_expired.json").replace("{{invoice}}", &invoice))
+        .create_async()
+        .await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["status"], "expired");
+    assert_eq!(result.metadata["expired"], true);
+}
+
+#[tokio::test]
+async fn test_charge_created_outside_module() {
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, payment_hash, _) = charge_fixture("charge_paid.json").await;
+    // No charge id is recorded, so OpenNode must not be asked
+    let lookup = server
+        .mock("GET", Matcher::Regex("^/v1/charge/".to_string()))
+        .expect(0)
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "charge_not_found");
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+    lookup.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_charge_unknown_to_opennode() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_charge(&mut server).await;
+    server
+        .mock("GET", format!("/v1/charge/{}", CHARGE_ID).as_str())
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "invoice_not_found");
+}
+
+#[tokio::test]
+async fn test_charge_index_persisted() {
+    let node_api = MockNodeApi::new();
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, payment_hash, body) = charge_fixture("charge_created.json").await;
+    server.mock("POST", "/v1/charges").with_status(201).with_body(body).create_async().await;
+    let provider = provider_for(&server);
+    provider.attach_storage(node_api.clone()).await.unwrap();
+    provider.create_invoice(2000, "order 1", 3600).await.unwrap();
+    assert_eq!(node_api.len(OPENNODE_CHARGES_TREE), 1);
+
+    let restarted = provider_for(&server);
+    restarted.attach_storage(node_api.clone()).await.unwrap();
+    assert_eq!(restarted.charge_id(&payment_hash).as_deref(), Some(CHARGE_ID));
+    server
+        .mock("GET", format!("/v1/charge/{}", CHARGE_ID).as_str())
+        .with_status(200)
+        .with_body(fixture("charge_paid.json").replace("{{invoice}}", &invoice))
+        .create_async()
+        .await;
+    assert!(restarted.is_payment_confirmed(&payment_hash).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_errors_mapped() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/charges")
+        .with_status(400)
+        .with_body(fixture("bad_request.json"))
+        .create_async()
+        .await;
+    let provider = provider_for(&server);
+    match provider.create_invoice(0, "order 1", 3600).await.unwrap_err() {
+        LightningError::InvoiceError(msg) => assert!(msg.contains("Amount must be at least 1 sat"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let mut params = InvoiceParams::new(2000, "", 3600);
+    params.description = DescriptionKind::Hash([0u8; 32]);
+    assert!(matches!(provider.create_invoice_ex(&params).await, Err(LightningError::InvoiceError(_))));
+
+    let mut server = mockito::Server::new_async().await;
+    server.mock("POST", "/v1/charges").with_status(401).create_async().await;
+    let err = provider_for(&server).create_invoice(2000, "order 1", 3600).await.unwrap_err();
+    assert!(matches!(err, LightningError::ConfigError(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/v1/account/balance")
+        .match_header("Authorization", API_KEY)
+        .with_status(200)
+        .with_body(fixture("balance.json"))
+        .create_async()
+        .await;
+    assert!(provider_for(&server).health_check().await.unwrap().reachable);
+
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/v1/account/balance").with_status(403).create_async().await;
+    assert!(!provider_for(&server).health_check().await.unwrap().reachable);
+}
+
+#[test]
+fn test_create_provider_from_config() {
+    assert_eq!("opennode".parse::<ProviderType>().unwrap(), ProviderType::OpenNode);
+    assert_eq!("live".parse::<OpenNodeEnv>().unwrap(), OpenNodeEnv::Live);
+    assert!("staging".parse::<OpenNodeEnv>().is_err());
+
+    let ctx = test_context(&[("lightning.opennode.api_key", API_KEY), ("lightning.opennode.env", "live")]);
+    let provider = create_provider(ProviderType::OpenNode, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::OpenNode);
+
+    let ctx = test_context(&[]);
+    assert!(matches!(create_provider(ProviderType::OpenNode, &ctx), Err(LightningError::ConfigError(_))));
+
+    let ctx = test_context(&[("lightning.opennode.api_key", API_KEY), ("lightning.opennode.env", "staging")]);
+    assert!(matches!(create_provider(ProviderType::OpenNode, &ctx), Err(LightningError::ConfigError(_))));
+}
@@ -0,0 +1,234 @@
+//! Payment history tests
+
+mod common;
+
+use blvm_lightning::payment_history::{
+    export_csv, history_tree, Page, PaymentDirection, PaymentFilter, PaymentHistory, PaymentRecord, CSV_HEADER,
+    PAYMENT_HISTORY_DAYS_TREE,
+};
+use blvm_lightning::payment_state::PaymentState;
+use blvm_lightning::provider::ProviderType;
+use blvm_lightning::receipt::PaymentReceipt;
+use common::MockNodeApi;
+
+fn record(id: u64, created_at: u64, amount_msats: u64, provider: ProviderType, state: PaymentState) -> PaymentRecord {
+    PaymentRecord {
+        payment_id: format!("payment-{}", id),
+        payment_hash: [id as u8; 32],
+        preimage: None,
+        amount_msats,
+        fee_msats: 0,
+        settled_at: None,
+        provider,
+        metadata: serde_json::json!({}),
+        direction: PaymentDirection::Inbound,
+        state,
+        created_at,
+    }
+}
+
+fn ids(records: &[PaymentRecord]) -> Vec<&str> {
+    records.iter().map(|r| r.payment_id.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_query_pages_newest_first() {
+    let history = PaymentHistory::new(MockNodeApi::new());
+    for i in 0..5 {
+        history.record(&record(i, 1_000 + i, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    }
+
+    let page = history.query(&PaymentFilter::default(), Page { page: 0, per_page: 2 }).await.unwrap();
+    assert_eq!(ids(&page.records), vec!["payment-4", "payment-3"]);
+    assert_eq!((page.total, page.page, page.per_page), (5, 0, 2));
+
+    let page = history.query(&PaymentFilter::default(), Page { page: 2, per_page: 2 }).await.unwrap();
+    assert_eq!(ids(&page.records), vec!["payment-0"]);
+    assert_eq!(page.total, 5);
+
+    let page = history.query(&PaymentFilter::default(), Page { page: 3, per_page: 2 }).await.unwrap();
+    assert!(page.records.is_empty());
+}
+
+#[tokio::test]
+async fn test_query_filters() {
+    let history = PaymentHistory::new(MockNodeApi::new());
+    let settled = PaymentState::Settled { settled_at: 2_000 };
+    history.record(&record(1, 1_000, 500, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    history.record(&record(2, 1_100, 5_000, ProviderType::LNBits, settled.clone())).await.unwrap();
+    history.record(&record(3, 1_200, 50_000, ProviderType::Stub, settled)).await.unwrap();
+    history.record(&record(4, 1_300, 50_000, ProviderType::Stub, PaymentState::InFlight)).await.unwrap();
+
+    let query = |filter: PaymentFilter| {
+        let history = &history;
+        async move { history.query(&filter, Page::default()).await.unwrap() }
+    };
+
+    let page = query(PaymentFilter { date_from: Some(1_100), date_to: Some(1_200), ..Default::default() }).await;
+    assert_eq!(ids(&page.records), vec!["payment-3", "payment-2"]);
+
+    let page = query(PaymentFilter { min_amount_msats: Some(5_000), ..Default::default() }).await;
+    assert_eq!(ids(&page.records), vec!["payment-4", "payment-3", "payment-2"]);
+
+    let page = query(PaymentFilter { provider: Some(ProviderType::Stub), ..Default::default() }).await;
+    assert_eq!(ids(&page.records), vec!["payment-4", "payment-3", "payment-1"]);
+
+    // State matches on the variant, whatever its settlement time
+    let page = query(PaymentFilter {
+        state: Some(PaymentState::Settled { settled_at: 0 }),
+        provider: Some(ProviderType::Stub),
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(ids(&page.records), vec!["payment-3"]);
+    assert_eq!(page.total, 1);
+}
+
+#[tokio::test]
+async fn test_record_replaces_earlier_record() {
+    let node_api = MockNodeApi::new();
+    let history = PaymentHistory::new(node_api.clone());
+    history.record(&record(1, 1_000, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+
+    let receipt = PaymentReceipt {
+        payment_id: "payment-1".to_string(),
+        payment_hash: [1; 32],
+        preimage: Some([9; 32]),
+        amount_msats: 1_000,
+        fee_msats: 3,
+        settled_at: 1_500,
+        provider: ProviderType::Stub,
+        metadata: serde_json::json!({}),
+    };
+    let settled = PaymentRecord::from_receipt(receipt, PaymentDirection::Inbound);
+    history.record(&settled).await.unwrap();
+
+    assert_eq!(node_api.len(&history_tree(1_500)), 1);
+    let page = history.query(&PaymentFilter::default(), Page::default()).await.unwrap();
+    assert_eq!(page.records, vec![settled]);
+    assert_eq!(page.records[0].state, PaymentState::Settled { settled_at: 1_500 });
+}
+
+#[tokio::test]
+async fn test_records_partitioned_by_day() {
+    const DAY: u64 = 86_400;
+    let node_api = MockNodeApi::new();
+    let history = PaymentHistory::new(node_api.clone());
+    history.record(&record(1, 10 * DAY + 5, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    history.record(&record(2, 10 * DAY + 9, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    history.record(&record(3, 12 * DAY, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    history.record(&record(4, 15 * DAY - 1, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+
+    assert_eq!(node_api.len(PAYMENT_HISTORY_DAYS_TREE), 3);
+    assert_eq!(node_api.len(&history_tree(10 * DAY)), 2);
+    assert_eq!(node_api.len(&history_tree(12 * DAY)), 1);
+
+    // Bounds inside a day are still exact
+    let filter = PaymentFilter { date_from: Some(10 * DAY + 6), date_to: Some(12 * DAY), ..Default::default() };
+    let page = history.query(&filter, Page::default()).await.unwrap();
+    assert_eq!(ids(&page.records), vec!["payment-3", "payment-2"]);
+
+    // Pages continue across days
+    let page = history.query(&PaymentFilter::default(), Page { page: 1, per_page: 3 }).await.unwrap();
+    assert_eq!(ids(&page.records), vec!["payment-1"]);
+    assert_eq!(page.total, 4);
+
+    // A record moving to another day leaves the old day's tree
+    history.record(&record(1, 12 * DAY + 1, 1_000, ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    assert_eq!(node_api.len(&history_tree(10 * DAY)), 1);
+    assert_eq!(node_api.len(&history_tree(12 * DAY)), 2);
+}
+
+/// Parse RFC 4180 CSV into rows of fields
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    rows
+}
+
+#[test]
+fn test_export_csv_round_trip() {
+    let mut settled = record(1, 1_700_000_000, 21_000, ProviderType::Stub, PaymentState::Settled { settled_at: 1_700_000_090 });
+    settled.payment_id = "order \"42\", table 7".to_string();
+    settled.fee_msats = 12;
+    settled.settled_at = Some(1_700_000_090);
+    let mut pending = record(2, 1_700_086_400, 500, ProviderType::Failover(vec![ProviderType::LNBits, ProviderType::Stub]), PaymentState::Pending);
+    pending.direction = PaymentDirection::Outbound;
+
+    let csv = export_csv(&[settled, pending]);
+    assert!(csv.ends_with("\r\n"));
+    let rows = parse_csv(&csv);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].join(","), CSV_HEADER);
+    assert_eq!(
+        rows[1],
+        vec![
+            "order \"42\", table 7".to_string(),
+            hex::encode([1u8; 32]),
+            "inbound".to_string(),
+            "21000".to_string(),
+            "12".to_string(),
+            "settled".to_string(),
+            "stub".to_string(),
+            "2023-11-14T22:13:20Z".to_string(),
+            "2023-11-14T22:14:50Z".to_string(),
+        ]
+    );
+    assert_eq!(
+        rows[2],
+        vec![
+            "payment-2".to_string(),
+            hex::encode([2u8; 32]),
+            "outbound".to_string(),
+            "500".to_string(),
+            "0".to_string(),
+            "pending".to_string(),
+            "lnbits,stub".to_string(),
+            "2023-11-15T22:13:20Z".to_string(),
+            String::new(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_export_to_file() {
+    let history = PaymentHistory::new(MockNodeApi::new());
+    for i in 0..4 {
+        history.record(&record(i, 1_000 + i, 1_000 * (i + 1), ProviderType::Stub, PaymentState::Pending)).await.unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("blvm-lightning-export-{}.csv", rand::random::<u64>()));
+
+    let filter = PaymentFilter { min_amount_msats: Some(2_000), ..Default::default() };
+    assert_eq!(history.export_to_file(&path, &filter).await.unwrap(), 3);
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Oldest first, same rows as export_csv
+    let page = history.query(&filter, Page::default()).await.unwrap();
+    let mut records = page.records;
+    records.reverse();
+    assert_eq!(contents, export_csv(&records));
+    let rows = parse_csv(&contents);
+    assert_eq!(rows[1..].iter().map(|row| row[0].as_str()).collect::<Vec<_>>(), vec!["payment-1", "payment-2", "payment-3"]);
+}
@@ -0,0 +1,163 @@
+//! phoenixd provider tests against recorded API responses
+
+mod common;
+
+use blvm_lightning::provider::phoenixd::{PhoenixdConfig, PhoenixdProvider};
+use blvm_lightning::provider::{create_provider, InvoiceParams, LightningProvider, ProviderType};
+use common::test_context;
+use mockito::Matcher;
+
+const PAYMENT_HASH: &str = "7f4c8d4bb3b8a2b0c2a1e0f5f2d5b3c4a9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4";
+/// Basic auth with an empty user and password "test_password"
+const AUTH_HEADER: &str = "Basic OnRlc3RfcGFzc3dvcmQ=";
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/phoenixd/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+/// Unpaid payment record for a real invoice with the given expiry
+async fn unpaid_fixture(expiry_seconds: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    let invoice = ldk.create_invoice(2000, "order 1", expiry_seconds).await.unwrap();
+    fixture("incoming_unpaid.json").replace("{{invoice}}", &invoice)
+}
+
+fn provider_for(server: &mockito::Server) -> PhoenixdProvider {
+    PhoenixdProvider::new(PhoenixdConfig {
+        url: server.url(),
+        password: "test_password".to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn payment_hash() -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hex::decode(PAYMENT_HASH).unwrap());
+    hash
+}
+
+#[tokio::test]
+async fn test_create_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    let invoice = server
+        .mock("POST", "/createinvoice")
+        .match_header("Authorization", AUTH_HEADER)
+        .match_body(Matcher::AllOf(vec![
+            // 1500 msats rounds up to 2 sats
+            Matcher::UrlEncoded("amountSat".into(), "2".into()),
+            Matcher::UrlEncoded("description".into(), "order 1".into()),
+            Matcher::UrlEncoded("expirySeconds".into(), "3600".into()),
+            Matcher::UrlEncoded("externalId".into(), "order-1".into()),
+        ]))
+        .with_status(200)
+        .with_body(fixture("createinvoice.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let mut params = InvoiceParams::new(1500, "order 1", 3600);
+    params.label = Some("order-1".to_string());
+    let bolt11 = provider.create_invoice_ex(&params).await.unwrap();
+
+    assert!(bolt11.starts_with("lntb20n1"));
+    assert_eq!(provider.provider_type(), ProviderType::Phoenixd);
+    invoice.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_verify_paid_payment() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", format!("/payments/incoming/{}", PAYMENT_HASH).as_str())
+        .match_header("Authorization", AUTH_HEADER)
+        .with_status(200)
+        .with_body(fixture("incoming_paid.json"))
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+
+    assert!(result.verified);
+    // receivedSat is converted to msats
+    assert_eq!(result.amount_msats, Some(2000));
+    assert_eq!(result.timestamp, Some(1700000100));
+    assert_eq!(result.metadata["external_id"], "order-1");
+    assert_eq!(result.metadata["expired"], false);
+    assert!(provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_unpaid_payment() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", format!("/payments/incoming/{}", PAYMENT_HASH).as_str())
+        .with_status(200)
+        .with_body(unpaid_fixture(3600).await)
+        .create_async()
+        .await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, None);
+    assert_eq!(result.timestamp, None);
+    assert_eq!(result.metadata["expired"], false);
+    assert!(!provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_expired_payment() {
+    let body = unpaid_fixture(0).await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", format!("/payments/incoming/{}", PAYMENT_HASH).as_str())
+        .with_status(200)
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let result = provider_for(&server).verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["expired"], true);
+}
+
+#[tokio::test]
+async fn test_verify_unknown_payment() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", Matcher::Regex("^/payments/incoming/".into()))
+        .with_status(404)
+        .create_async()
+        .await;
+
+    let result = provider_for(&server).verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "invoice_not_found");
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/getinfo")
+        .with_status(200)
+        .with_body(fixture("getinfo.json"))
+        .create_async()
+        .await;
+
+    let status = provider_for(&server).health_check().await.unwrap();
+    assert!(status.reachable);
+    assert_eq!(status.block_height, Some(2500000));
+    assert_eq!(status.version.as_deref(), Some("0.4.2"));
+}
+
+#[test]
+fn test_provider_type_from_str() {
+    assert_eq!("phoenixd".parse::<ProviderType>().unwrap(), ProviderType::Phoenixd);
+}
@@ -0,0 +1,1069 @@
+//! Integration tests for LightningProcessor
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::{InvoiceCache, InvoiceParser};
+use blvm_lightning::payment_state::{PaymentState, PendingInvoice, PENDING_INVOICES_TREE};
+use blvm_lightning::processor::{
+    LightningProcessor, SweepRecord, PENDING_INVOICE_GRACE_SECONDS, SWEEPS_TREE, SWEEP_KEY,
+};
+use blvm_lightning::provider::stub::{StubCall, StubProvider, STUB_BALANCE_MSATS};
+use blvm_lightning::provider::{
+    create_provider, InvoiceParams, PaymentVerificationResult, ProviderCapabilities, ProviderType,
+};
+use blvm_lightning::receipt::PaymentReceipt;
+use common::{test_context, MockNodeApi};
+use std::collections::HashMap;
+
+/// Create a real signed BOLT11 invoice via the LDK provider
+async fn make_invoice(amount_msats: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    provider.create_invoice(amount_msats, "test", 3600).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_duplicate_payment_rejected() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let invoice = make_invoice(1000).await;
+
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    assert_eq!(node_api.len("processed_payments"), 1);
+
+    let err = processor
+        .process_payment(&invoice, "payment-1", node_api.as_ref())
+        .await
+        .unwrap_err();
+    match err {
+        LightningError::ProcessorError(msg) => assert!(msg.contains("duplicate payment hash")),
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_duplicate_rejected() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    // The same invoice replayed under two payment ids at once verifies only once
+    let invoice = make_invoice(1000).await;
+    let (first, second) = tokio::join!(
+        processor.process_payment(&invoice, "payment-1", node_api.as_ref()),
+        processor.process_payment(&invoice, "payment-2", node_api.as_ref()),
+    );
+    assert_eq!([first.is_ok(), second.is_ok()].iter().filter(|ok| **ok).count(), 1);
+    assert_eq!(node_api.len("processed_payments"), 1);
+}
+
+#[tokio::test]
+async fn test_duplicate_outside_window_allowed() {
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.dedup_window_seconds", "0"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let invoice = make_invoice(1000).await;
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+
+    // Records older than the window no longer block the hash
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_invoice_label_lookup() {
+    let ctx = test_context(&[("lightning.provider", "ldk")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let mut params = InvoiceParams::new(5000, "order 42", 3600);
+    params.label = Some("order-42".to_string());
+    let invoice = processor.create_invoice_ex(&params).await.unwrap();
+
+    let expected = InvoiceParser::parse(&invoice).unwrap().payment_hash_hex();
+    assert_eq!(processor.payment_hash_for_label("order-42").await.unwrap(), Some(expected));
+    assert_eq!(processor.payment_hash_for_label("unknown").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_create_invoice_stores_pending_invoice() {
+    let ctx = test_context(&[("lightning.provider", "ldk")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let result = processor.create_invoice(5000, "order 7", 3600, "payment-7").await.unwrap();
+    let invoice_data = InvoiceParser::parse(&result.bolt11).unwrap();
+    assert_eq!(result.payment_hash, invoice_data.payment_hash());
+    assert_eq!(result.payment_id, "payment-7");
+    assert_eq!(result.expires_at, invoice_data.timestamp + 3600);
+
+    let stored = node_api.get(PENDING_INVOICES_TREE, b"payment-7").unwrap();
+    let pending: PendingInvoice = serde_json::from_slice(&stored).unwrap();
+    assert_eq!(pending.bolt11, result.bolt11);
+    assert_eq!(pending.payment_hash, hex::encode(result.payment_hash));
+    assert_eq!(pending.amount_msats, 5000);
+    assert_eq!(pending.state, PaymentState::Pending);
+    assert_eq!(pending.delete_after, result.expires_at + PENDING_INVOICE_GRACE_SECONDS);
+    assert_eq!(processor.load_pending_invoice("payment-7").await.unwrap(), Some(pending));
+
+    // Recorded like any created invoice, with the payment waiting for it
+    assert_eq!(processor.issued_invoice(&hex::encode(result.payment_hash)).await.unwrap(), Some(result.bolt11.clone()));
+    let payment = processor.load_payment("payment-7").await.unwrap().unwrap();
+    assert_eq!(payment.invoice, result.bolt11);
+    assert_eq!(payment.state, PaymentState::Pending);
+    assert_eq!(processor.list_pending_payments().await.unwrap().len(), 1);
+
+    assert!(processor.create_invoice(5000, "order 7", 3600, "payment-7").await.is_err());
+    assert!(processor.create_invoice(5000, "no id", 3600, "").await.is_err());
+    assert_eq!(node_api.len(PENDING_INVOICES_TREE), 1);
+}
+
+#[tokio::test]
+async fn test_create_invoice_concurrent_same_id() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    // Only one of two racing calls with the same payment id creates an invoice
+    let (first, second) = tokio::join!(
+        processor.create_invoice(5000, "order 8", 3600, "payment-8"),
+        processor.create_invoice(5000, "order 8", 3600, "payment-8"),
+    );
+    assert_eq!([first.is_ok(), second.is_ok()].iter().filter(|ok| **ok).count(), 1);
+    assert_eq!(node_api.len(PENDING_INVOICES_TREE), 1);
+
+    // Other ids aren't held up
+    processor.create_invoice(5000, "order 9", 3600, "payment-9").await.unwrap();
+    assert_eq!(node_api.len(PENDING_INVOICES_TREE), 2);
+}
+
+#[tokio::test]
+async fn test_prune_pending_invoices() {
+    let ctx = test_context(&[("lightning.provider", "ldk")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    let short = processor.create_invoice(1000, "short", 60, "payment-1").await.unwrap();
+    let long = processor.create_invoice(1000, "long", 86400, "payment-2").await.unwrap();
+
+    // Kept until the grace period after expiry has passed
+    assert_eq!(processor.prune_pending_invoices(short.expires_at).await.unwrap(), 0);
+    let ttl = short.expires_at + PENDING_INVOICE_GRACE_SECONDS;
+    assert_eq!(processor.prune_pending_invoices(ttl).await.unwrap(), 1);
+    assert!(node_api.get(PENDING_INVOICES_TREE, b"payment-1").is_none());
+    assert!(node_api.get(PENDING_INVOICES_TREE, b"payment-2").is_some());
+    assert!(long.expires_at + PENDING_INVOICE_GRACE_SECONDS > ttl);
+
+    // The unpaid payment failed with its invoice
+    match processor.load_payment("payment-1").await.unwrap().unwrap().state {
+        PaymentState::Failed { reason, .. } => assert_eq!(reason, "invoice expired"),
+        state => panic!("unexpected state {:?}", state),
+    }
+    assert_eq!(processor.load_payment("payment-2").await.unwrap().unwrap().state, PaymentState::Pending);
+}
+
+#[tokio::test]
+async fn test_generate_receipt() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let invoice = make_invoice(2000).await;
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+
+    let receipt = processor.generate_receipt("payment-1").await.unwrap();
+    assert_eq!(receipt.payment_id, "payment-1");
+    assert_eq!(receipt.payment_hash, payment_hash);
+    assert_eq!(receipt.provider, ProviderType::Stub);
+
+    let stored = node_api.get("payment_receipts", b"payment-1").unwrap();
+    let restored = PaymentReceipt::from_json(std::str::from_utf8(&stored).unwrap()).unwrap();
+    assert_eq!(restored, receipt);
+
+    assert!(processor.generate_receipt("unknown").await.is_err());
+}
+
+#[tokio::test]
+async fn test_create_invoices_batch_partial_failure() {
+    let ctx = test_context(&[("lightning.provider", "ldk")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let good = InvoiceParams::new(1000, "first", 3600);
+    let mut bad = InvoiceParams::new(2000, "second", 3600);
+    // Expiry too large to encode in a BOLT11 invoice
+    bad.expiry_seconds = u64::MAX;
+    let also_good = InvoiceParams::new(3000, "third", 3600);
+
+    let results = processor
+        .create_invoices_batch(&[good, bad, also_good])
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    assert!(results[2].is_ok());
+    assert_eq!(node_api.len("issued_invoices"), 2);
+}
+
+const MAINNET_ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+const TESTNET_ADDRESS: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+
+#[tokio::test]
+async fn test_sweep_address_network_validation() {
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.network", "testnet"),
+        ("lightning.sweep.address", MAINNET_ADDRESS),
+        ("lightning.sweep.threshold_sats", "1000"),
+    ]);
+    let result = LightningProcessor::new(&ctx, MockNodeApi::new()).await;
+    assert!(matches!(result, Err(LightningError::ConfigError(_))));
+}
+
+#[tokio::test]
+async fn test_withdraw_rejects_wrong_network() {
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.network", "testnet")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+
+    assert!(processor.withdraw_onchain(MAINNET_ADDRESS, Some(1000), None).await.is_err());
+    assert!(processor.withdraw_onchain(TESTNET_ADDRESS, Some(1000), None).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_sweep_above_threshold() {
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.network", "testnet"),
+        ("lightning.sweep.address", TESTNET_ADDRESS),
+        ("lightning.sweep.threshold_sats", "1000"),
+        ("lightning.sweep.reserve_sats", "5000"),
+    ]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    let txid = processor.maybe_sweep().await.unwrap().unwrap();
+
+    // Everything above the reserve is swept, and the sweep is recorded
+    let sweep_sats = STUB_BALANCE_MSATS / 1000 - 5000;
+    let provider = processor.provider();
+    let stub = provider.as_any().downcast_ref::<StubProvider>().unwrap();
+    assert_eq!(
+        stub.calls().last(),
+        Some(&StubCall::WithdrawOnchain { address: TESTNET_ADDRESS.to_string(), amount_sats: Some(sweep_sats) })
+    );
+    let record = processor.last_sweep().await.unwrap().unwrap();
+    assert_eq!(record.amount_sats, sweep_sats);
+    assert_eq!(record.txid, Some(txid.to_string()));
+
+    // The balance hasn't moved, but a second sweep waits out the interval
+    assert!(processor.maybe_sweep().await.unwrap().is_none());
+
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.network", "testnet"),
+        ("lightning.sweep.address", TESTNET_ADDRESS),
+        ("lightning.sweep.threshold_sats", "1000000000"),
+    ]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    assert!(processor.maybe_sweep().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_sweep_runs_once() {
+    use blvm_node::module::traits::NodeAPI;
+
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.network", "testnet"),
+        ("lightning.sweep.address", TESTNET_ADDRESS),
+        ("lightning.sweep.threshold_sats", "1000"),
+    ]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+
+    // Concurrent checks make one sweep between them
+    let (first, second) = tokio::join!(processor.maybe_sweep(), processor.maybe_sweep());
+    assert_eq!(first.unwrap().is_some() as u8 + second.unwrap().is_some() as u8, 1);
+
+    // A sweep interrupted before recording a txid isn't repeated on restart
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    let started_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let record = SweepRecord { amount_sats: 1000, started_at, txid: None };
+    node_api
+        .storage_insert(SWEEPS_TREE.to_string(), SWEEP_KEY.to_vec(), serde_json::to_vec(&record).unwrap())
+        .await
+        .unwrap();
+    assert!(processor.maybe_sweep().await.unwrap().is_none());
+
+    // Payments no longer sweep inline
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    let invoice = make_invoice(1000).await;
+    processor.process_payment(&invoice, "payment-1", MockNodeApi::new().as_ref()).await.unwrap();
+    assert!(processor.last_sweep().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_capabilities_stored() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let stored = node_api.get("lightning_config", b"capabilities").unwrap();
+    let caps: ProviderCapabilities = serde_json::from_slice(&stored).unwrap();
+    assert_eq!(caps, processor.capabilities());
+}
+
+#[tokio::test]
+async fn test_replayed_label_returns_existing_invoice() {
+    let ctx = test_context(&[("lightning.provider", "ldk")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    // Same payment request delivered twice (e.g. event replay)
+    let mut params = InvoiceParams::new(1000, "payment-7", 3600);
+    params.label = Some("payment-7".to_string());
+    let first = processor.create_invoice_ex(&params).await.unwrap();
+    let second = processor.create_invoice_ex(&params).await.unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(node_api.len("lightning_invoices"), 1);
+    assert_eq!(node_api.len("issued_invoices"), 1);
+    assert_eq!(processor.invoice_for_label("payment-7").await.unwrap(), Some(first.clone()));
+
+    // Batches reuse the stored invoice as well
+    let results = processor.create_invoices_batch(&[params]).await.unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), &first);
+    assert_eq!(node_api.len("issued_invoices"), 1);
+}
+
+#[tokio::test]
+async fn test_replayed_label_concurrently() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    // The same request delivered twice at once creates one invoice
+    let mut params = InvoiceParams::new(1000, "payment-7", 3600);
+    params.label = Some("payment-7".to_string());
+    let (first, second) = tokio::join!(
+        processor.create_invoice_ex(&params),
+        processor.create_invoice_ex(&params),
+    );
+    assert_eq!(first.unwrap(), second.unwrap());
+    assert_eq!(node_api.len("issued_invoices"), 1);
+
+    // A label repeated within one batch is created once too
+    let mut other = InvoiceParams::new(2000, "payment-8", 3600);
+    other.label = Some("payment-8".to_string());
+    let results = processor.create_invoices_batch(&[other.clone(), other]).await.unwrap();
+    assert_eq!(results[0].as_ref().unwrap(), results[1].as_ref().unwrap());
+    assert_eq!(node_api.len("issued_invoices"), 2);
+    assert_eq!(node_api.len("lightning_invoices"), 2);
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+
+    let status = processor.health_check().await.unwrap();
+    assert!(status.reachable);
+    assert_eq!(status.synced_to_chain, Some(true));
+}
+
+#[tokio::test]
+async fn test_list_channels_updates_stats() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let channels = processor.list_channels().await.unwrap();
+    assert_eq!(channels.len(), 1);
+
+    node_api.assert_stored("lightning_config", b"channel_count", &1u64.to_be_bytes());
+    node_api.assert_stored(
+        "lightning_config",
+        b"total_capacity_sats",
+        &(channels[0].capacity_msats / 1000).to_be_bytes(),
+    );
+}
+
+#[tokio::test]
+async fn test_check_balance() {
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.min_balance_msats", "1000")]);
+    let node_api = MockNodeApi::new();
+    let processor = std::sync::Arc::new(LightningProcessor::new(&ctx, node_api.clone()).await.unwrap());
+
+    assert_eq!(processor.check_balance().await.unwrap(), STUB_BALANCE_MSATS);
+    node_api.assert_stored("lightning_config", b"balance_msats", &STUB_BALANCE_MSATS.to_be_bytes());
+    processor.spawn_balance_monitor().unwrap().abort();
+
+    // Nothing to warn about without a minimum
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = std::sync::Arc::new(LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap());
+    assert!(processor.spawn_balance_monitor().is_none());
+
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.min_balance_msats", "1000"),
+        ("lightning.balance_check_interval_seconds", "0"),
+    ]);
+    let processor = std::sync::Arc::new(LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap());
+    assert!(processor.spawn_balance_monitor().is_none());
+}
+
+#[tokio::test]
+async fn test_node_id() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    assert_eq!(processor.node_id().await.unwrap(), blvm_lightning::provider::stub::STUB_NODE_ID);
+}
+
+#[tokio::test]
+async fn test_payment_metadata_round_trip() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    assert!(processor.get_payment_metadata("payment-1").await.unwrap().is_none());
+
+    let metadata = serde_json::json!({
+        "provider": "lnbits",
+        "route": [{ "node": "02ab", "fee_msats": 12 }, { "node": "03cd", "fee_msats": 0 }],
+        "amount_msats": 18446744073709551615u64,
+        "settled": true,
+        "memo": "caf\u{e9} \u{2615}",
+        "extra": null,
+    });
+    processor.store_payment_metadata("payment-1", &metadata).await.unwrap();
+    assert_eq!(processor.get_payment_metadata("payment-1").await.unwrap(), Some(metadata));
+}
+
+#[tokio::test]
+async fn test_process_payment_stores_metadata() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let invoice = make_invoice(1000).await;
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+
+    let metadata = processor.get_payment_metadata("payment-1").await.unwrap().unwrap();
+    assert_eq!(metadata["provider"], "stub");
+}
+
+#[tokio::test]
+async fn test_metrics_recorded() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let invoice = make_invoice(1000).await;
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    processor.create_invoice_ex(&InvoiceParams::new(1000, "test", 3600)).await.unwrap();
+
+    let text = processor.metrics().render_prometheus();
+    assert!(text.contains("lightning_payments_verified_total 1\n"));
+    assert!(text.contains("lightning_verification_latency_ms_count 1\n"));
+    assert!(text.contains("lightning_invoices_created_total 1\n"));
+}
+
+#[tokio::test]
+async fn test_invoice_cache_evicts_least_recently_used() {
+    let cache = InvoiceCache::new(2);
+    let first = make_invoice(1000).await;
+    let second = make_invoice(2000).await;
+    let third = make_invoice(3000).await;
+
+    let parsed = cache.get_or_parse(&first).unwrap();
+    assert_eq!(parsed.amount_msats, 1000);
+    // A hit returns the cached value
+    assert!(std::sync::Arc::ptr_eq(&parsed, &cache.get_or_parse(&first).unwrap()));
+
+    cache.get_or_parse(&second).unwrap();
+    // Touch the first invoice so the second is the oldest
+    cache.get_or_parse(&first).unwrap();
+    cache.get_or_parse(&third).unwrap();
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.contains(&first));
+    assert!(!cache.contains(&second));
+    assert!(cache.contains(&third));
+
+    // Parse errors aren't cached
+    assert!(cache.get_or_parse("lnbc1invalid").is_err());
+    assert_eq!(cache.len(), 2);
+}
+
+#[tokio::test]
+async fn test_processor_invoice_cache() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    assert!(processor.invoice_cache().is_none());
+
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.invoice_cache_size", "16")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    let invoice = make_invoice(1000).await;
+
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    let cache = processor.invoice_cache().unwrap();
+    assert_eq!(cache.max_entries(), 16);
+    assert!(cache.contains(&invoice));
+
+    assert_eq!(processor.verify_payments_batch(&[(invoice.as_str(), "payment-1")]).await.unwrap(), vec![true]);
+    assert_eq!(cache.len(), 1);
+}
+
+#[tokio::test]
+async fn test_rate_limit_by_payment_id_prefix() {
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.rate_limit.max_per_minute", "2"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    processor.check_rate_limit("wallet01-payment-1").unwrap();
+    processor.check_rate_limit("wallet01-payment-2").unwrap();
+    match processor.check_rate_limit("wallet01-payment-3").unwrap_err() {
+        LightningError::ProcessorError(msg) => assert_eq!(msg, "rate limit exceeded"),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    // A different prefix is unaffected
+    processor.check_rate_limit("wallet02-payment-1").unwrap();
+}
+
+#[tokio::test]
+async fn test_rate_limit_disabled_by_default() {
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    for i in 0..100 {
+        processor.check_rate_limit(&format!("wallet01-payment-{}", i)).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_poll_confirmations_settles_inflight_payments() {
+    let pending = make_invoice(2000).await;
+    let failed = make_invoice(3000).await;
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1}}, "{}": {{"outcome": "failed"}}}}}}"#,
+        InvoiceParser::parse(&pending).unwrap().payment_hash_hex(),
+        InvoiceParser::parse(&failed).unwrap().payment_hash_hex()
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    processor.process_payment(&pending, "payment-1", node_api.as_ref()).await.unwrap();
+    processor.process_payment(&failed, "payment-2", node_api.as_ref()).await.unwrap();
+    // Verified but not paid: pending, not in flight
+    assert!(processor.load_inflight_payments().await.unwrap().is_empty());
+    assert_eq!(processor.load_unconfirmed_payments(None, 10).await.unwrap().len(), 2);
+
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 1);
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+    assert_eq!(node_api.len("processed_payments"), 1);
+
+    // The unconfirmed payment stays pending for the next poll
+    let unconfirmed = processor.load_unconfirmed_payments(None, 10).await.unwrap();
+    assert_eq!(unconfirmed.len(), 1);
+    assert_eq!(unconfirmed[0].payment_id, "payment-2");
+    assert_eq!(unconfirmed[0].state, PaymentState::Pending);
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_poll_confirmations_skips_processed_hashes() {
+    let invoice = make_invoice(2000).await;
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1}}}}}}"#,
+        InvoiceParser::parse(&invoice).unwrap().payment_hash_hex()
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    // Another payment for the same invoice settles before the poller gets to payment-1
+    processor.process_payment(&invoice, "payment-2", node_api.as_ref()).await.unwrap();
+    assert!(matches!(
+        processor.load_payment("payment-2").await.unwrap().unwrap().state,
+        PaymentState::Settled { .. }
+    ));
+
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Failed { ref reason, .. } if reason.contains("duplicate payment hash")));
+    let processed = processor.seen_within_last(&payment.payment_hash, u64::MAX).await.unwrap().unwrap();
+    assert_eq!(processed.payment_id, "payment-2");
+}
+
+#[tokio::test]
+async fn test_poll_confirmations_checks_amount() {
+    let invoice = make_invoice(2000).await;
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1, "amount_msats": 1000}}}}}}"#,
+        InvoiceParser::parse(&invoice).unwrap().payment_hash_hex()
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+
+    // Confirmed by the provider, but for half the invoice amount
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Failed { ref reason, .. } if reason.contains("underpaid")));
+    assert_eq!(node_api.len("processed_payments"), 0);
+}
+
+#[tokio::test]
+async fn test_poll_confirmations_pages_through_payments() {
+    let mut invoices = Vec::new();
+    for amount in [2000, 3000, 4000] {
+        invoices.push(make_invoice(amount).await);
+    }
+    // Only the last payment ever confirms
+    let scenario = format!(
+        r#"{{"default": {{"outcome": "failed"}}, "payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1}}}}}}"#,
+        InvoiceParser::parse(&invoices[2]).unwrap().payment_hash_hex()
+    );
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.stub.scenario", &scenario),
+        ("lightning.poller.batch_size", "1"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    for (i, invoice) in invoices.iter().enumerate() {
+        processor.process_payment(invoice, &format!("payment-{}", i), node_api.as_ref()).await.unwrap();
+    }
+
+    // Unconfirmed payments don't starve the ones after them
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 1);
+    let payment = processor.load_payment("payment-2").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+
+    // Then it starts over
+    let unconfirmed = processor.load_unconfirmed_payments(Some("payment-0"), 10).await.unwrap();
+    assert_eq!(unconfirmed.iter().map(|p| p.payment_id.as_str()).collect::<Vec<_>>(), vec!["payment-1"]);
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_poll_confirmations_expires_stale_payments() {
+    use blvm_lightning::payment_state::PAYMENT_STATES_TREE;
+    use blvm_node::module::traits::NodeAPI;
+
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", r#"{"default": {"outcome": "failed"}}"#)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    processor.process_payment(&make_invoice(2000).await, "payment-old", node_api.as_ref()).await.unwrap();
+    processor.process_payment(&make_invoice(3000).await, "payment-stuck", node_api.as_ref()).await.unwrap();
+
+    // One payment is past lightning.poller.max_age_seconds, the other was left
+    // in flight by a verification that never finished
+    let mut old = processor.load_payment("payment-old").await.unwrap().unwrap();
+    old.created_at = 1;
+    let mut stuck = processor.load_payment("payment-stuck").await.unwrap().unwrap();
+    stuck.state = PaymentState::InFlight;
+    stuck.last_attempt_at = Some(1);
+    for payment in [old, stuck] {
+        node_api
+            .storage_insert(PAYMENT_STATES_TREE.to_string(), payment.payment_id.clone().into_bytes(), serde_json::to_vec(&payment).unwrap())
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(processor.poll_confirmations().await.unwrap(), 0);
+    let old = processor.load_payment("payment-old").await.unwrap().unwrap();
+    assert!(matches!(old.state, PaymentState::Failed { ref reason, .. } if reason == "not confirmed within 86400s"));
+    assert_eq!(processor.load_payment("payment-stuck").await.unwrap().unwrap().state, PaymentState::Pending);
+    assert!(processor.load_inflight_payments().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_confirmation_poller_task() {
+    let invoice = make_invoice(2000).await;
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1}}}}}}"#,
+        InvoiceParser::parse(&invoice).unwrap().payment_hash_hex()
+    );
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.stub.scenario", &scenario),
+        ("lightning.poller.batch_size", "1"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = std::sync::Arc::new(LightningProcessor::new(&ctx, node_api.clone()).await.unwrap());
+
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+
+    // The first poll runs immediately
+    let poller = std::sync::Arc::clone(&processor).start_confirmation_poller(60).await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    poller.abort();
+
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+}
+
+#[tokio::test]
+async fn test_verify_payments_batch_detailed_mixed_results() {
+    let verified = make_invoice(5000).await;
+    let failed = make_invoice(1000).await;
+    let timed_out = make_invoice(1000).await;
+    let hash_hex = |invoice: &str| InvoiceParser::parse(invoice).unwrap().payment_hash_hex();
+    let scenario = serde_json::json!({
+        "payments": {
+            (hash_hex(&verified)): { "outcome": "verified", "amount_msats": 5000 },
+            (hash_hex(&failed)): { "outcome": "failed", "reason": "payment not found" },
+            (hash_hex(&timed_out)): { "outcome": "timeout", "after_ms": 10 },
+        }
+    })
+    .to_string();
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", scenario.as_str())]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+
+    let payments = [
+        (verified.as_str(), "payment-1"),
+        ("lnbc1invalid", "payment-2"),
+        (failed.as_str(), "payment-3"),
+        (timed_out.as_str(), "payment-4"),
+    ];
+    let results = processor.verify_payments_batch_detailed(&payments).await.unwrap();
+    assert_eq!(results.len(), 4);
+
+    let result = results[0].as_ref().unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(5000));
+    assert!(matches!(results[1], Err(LightningError::InvoiceError(_))), "{:?}", results[1]);
+    let result = results[2].as_ref().unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "payment not found");
+    assert!(results[3].as_ref().unwrap_err().is_retryable());
+
+    // The plain batch reports failures as unverified
+    assert_eq!(
+        processor.verify_payments_batch(&payments).await.unwrap(),
+        vec![true, false, false, false]
+    );
+    assert!(processor.verify_payments_batch_detailed(&[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_list_pending_payments() {
+    let settled_invoice = make_invoice(1000).await;
+    let settled_hash = InvoiceParser::parse(&settled_invoice).unwrap().payment_hash_hex();
+    let scenario = format!(
+        r#"{{"default": {{"outcome": "pending_then_settled", "calls": 100}}, "payments": {{"{}": {{"outcome": "verified"}}}}}}"#,
+        settled_hash
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    for i in 0..5 {
+        let invoice = make_invoice(2000 + i).await;
+        processor.process_payment(&invoice, &format!("payment-{}", i), node_api.as_ref()).await.unwrap();
+    }
+    processor.process_payment(&settled_invoice, "payment-settled", node_api.as_ref()).await.unwrap();
+
+    let pending = processor.list_pending_payments().await.unwrap();
+    let mut ids: Vec<_> = pending.iter().map(|p| p.payment_id.clone()).collect();
+    ids.sort();
+    assert_eq!(ids, (0..5).map(|i| format!("payment-{}", i)).collect::<Vec<_>>());
+    for payment in &pending {
+        assert_eq!(payment.state, PaymentState::Pending);
+        assert_eq!(payment.attempts, 1);
+        assert!(payment.last_attempt_at.is_some());
+        assert!(payment.amount_msats.is_some());
+    }
+
+    let pages: Vec<_> = futures::future::join_all((0..4).map(|page| processor.list_pending_payments_page(page, 2)))
+        .await
+        .into_iter()
+        .map(|page| page.unwrap())
+        .collect();
+    assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1, 0]);
+    assert_eq!(pages.concat(), pending);
+}
+
+#[tokio::test]
+async fn test_cancel_pending_payment() {
+    let pending_invoice = make_invoice(1000).await;
+    let settled_invoice = make_invoice(2000).await;
+    let pending_hash = InvoiceParser::parse(&pending_invoice).unwrap().payment_hash();
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 100}}}}}}"#,
+        hex::encode(pending_hash)
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    processor.process_payment(&pending_invoice, "payment-pending", node_api.as_ref()).await.unwrap();
+    processor.process_payment(&settled_invoice, "payment-settled", node_api.as_ref()).await.unwrap();
+
+    // In flight: cancelled with the provider, then failed
+    processor.cancel_pending_payment("payment-pending", "timed out").await.unwrap();
+    let payment = processor.load_payment("payment-pending").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Failed { ref reason, .. } if reason == "timed out"));
+    let provider = processor.provider();
+    let stub = provider.as_any().downcast_ref::<StubProvider>().unwrap();
+    assert_eq!(stub.calls().last(), Some(&StubCall::CancelInvoice { payment_hash: pending_hash }));
+    assert!(processor.list_pending_payments().await.unwrap().is_empty());
+
+    // Already failed
+    let err = processor.cancel_pending_payment("payment-pending", "again").await.unwrap_err();
+    assert!(matches!(err, LightningError::ProcessorError(_)));
+
+    // Settled
+    let err = processor.cancel_pending_payment("payment-settled", "too late").await.unwrap_err();
+    assert!(matches!(err, LightningError::ProcessorError(ref msg) if msg == "cannot cancel settled payment"));
+    let payment = processor.load_payment("payment-settled").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+
+    // Unknown
+    let err = processor.cancel_pending_payment("payment-unknown", "gone").await.unwrap_err();
+    assert!(matches!(err, LightningError::PaymentVerificationFailed(ref msg) if msg == "payment not found"));
+}
+
+#[tokio::test]
+async fn test_cancel_pending_payment_provider_failure() {
+    let invoice = make_invoice(1000).await;
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.stub.scenario", r#"{"default": {"outcome": "pending_then_settled", "calls": 100}}"#),
+        ("lightning.stub.failure_rate", "0.5"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+
+    // The provider fails the cancellation (call 2): the payment stays pending
+    let err = processor.cancel_pending_payment("payment-1", "timed out").await.unwrap_err();
+    assert!(err.is_retryable());
+    assert!(processor.load_payment("payment-1").await.unwrap().unwrap().state.is_pending());
+
+    processor.cancel_pending_payment("payment-1", "timed out").await.unwrap();
+    assert!(matches!(
+        processor.load_payment("payment-1").await.unwrap().unwrap().state,
+        PaymentState::Failed { .. }
+    ));
+}
+
+/// Create a signed testnet invoice without an amount
+fn make_amountless_invoice() -> String {
+    use bitcoin_hashes::{sha256, Hash};
+    use lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+
+    let secp = secp256k1::Secp256k1::new();
+    let key = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+    InvoiceBuilder::new(Currency::BitcoinTestnet)
+        .description("donation".to_string())
+        .payment_hash(sha256::Hash::hash(&rand::random::<[u8; 32]>()))
+        .payment_secret(PaymentSecret(rand::random()))
+        .min_final_cltv_expiry_delta(18)
+        .current_timestamp()
+        .build_signed(|hash| secp.sign_ecdsa_recoverable(hash, &key))
+        .unwrap()
+        .to_string()
+}
+
+/// Process `invoice` against a stub reporting `received_msats`
+async fn process_with_received(
+    invoice: &str,
+    received_msats: u64,
+    tolerance_ppm: &str,
+) -> (Result<(), LightningError>, PaymentState) {
+    let payment_hash = InvoiceParser::parse(invoice).unwrap().payment_hash();
+    let result = PaymentVerificationResult {
+        verified: true,
+        amount_msats: Some(received_msats),
+        timestamp: None,
+        metadata: serde_json::Value::Null,
+    };
+    let stub = StubProvider::with_responses(HashMap::from([(payment_hash, result)]));
+    let ctx = test_context(&[("lightning.amount_tolerance_ppm", tolerance_ppm)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::with_provider(&ctx, node_api.clone(), Box::new(stub)).await.unwrap();
+
+    let result = processor.process_payment(invoice, "payment-1", node_api.as_ref()).await;
+    let state = processor.load_payment("payment-1").await.unwrap().unwrap().state;
+    (result, state)
+}
+
+#[tokio::test]
+async fn test_amount_tolerance() {
+    let invoice = make_invoice(1_000_000).await;
+
+    // Exact
+    let (result, state) = process_with_received(&invoice, 1_000_000, "").await;
+    assert!(result.is_ok());
+    assert!(matches!(state, PaymentState::Settled { .. }));
+
+    // Underpaid by one msat with no tolerance
+    let (result, state) = process_with_received(&invoice, 999_999, "").await;
+    match result {
+        Err(LightningError::PaymentVerificationFailed(reason)) => assert!(reason.starts_with("underpaid"), "{}", reason),
+        other => panic!("expected underpaid, got {:?}", other),
+    }
+    assert!(matches!(state, PaymentState::Failed { .. }));
+
+    // 0.1% tolerance: exactly at the limit is accepted, below it isn't
+    assert!(process_with_received(&invoice, 999_000, "1000").await.0.is_ok());
+    assert!(matches!(
+        process_with_received(&invoice, 998_999, "1000").await.0,
+        Err(LightningError::PaymentVerificationFailed(_))
+    ));
+
+    // Overpaid
+    let (result, state) = process_with_received(&invoice, 2_000_000, "").await;
+    assert!(result.is_ok());
+    assert!(matches!(state, PaymentState::Settled { .. }));
+}
+
+#[tokio::test]
+async fn test_amount_tolerance_amountless_invoice() {
+    let invoice = make_amountless_invoice();
+    assert_eq!(InvoiceParser::parse(&invoice).unwrap().amount_msats, 0);
+
+    let (result, state) = process_with_received(&invoice, 1, "").await;
+    assert!(result.is_ok());
+    assert!(matches!(state, PaymentState::Settled { .. }));
+}
+
+#[tokio::test]
+async fn test_statistics_aggregates_payments() {
+    use blvm_lightning::payment_history::{PaymentDirection, PaymentHistory, PaymentRecord};
+    use blvm_lightning::payment_state::{StoredPayment, PAYMENT_STATES_TREE};
+    use blvm_lightning::processor::PaymentStats;
+    use blvm_lightning::receipt::PAYMENT_RECEIPTS_TREE;
+    use blvm_node::module::traits::NodeAPI;
+
+    let ctx = test_context(&[("lightning.provider", "stub")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    let since = 1_000_000;
+
+    let store = |id: u32, created_at: u64, amount_msats: u64, state: PaymentState| {
+        let node_api = node_api.clone();
+        async move {
+            let payment = StoredPayment {
+                payment_id: format!("payment-{}", id),
+                payment_hash: hex::encode([id as u8; 32]),
+                invoice: String::new(),
+                amount_msats: Some(amount_msats),
+                fee_msats: 10,
+                preimage: None,
+                provider: ProviderType::Stub,
+                created_at,
+                last_attempt_at: None,
+                attempts: 1,
+                state,
+                metadata: serde_json::Value::Null,
+            };
+            node_api
+                .storage_insert(PAYMENT_STATES_TREE.to_string(), payment.payment_id.clone().into_bytes(), serde_json::to_vec(&payment).unwrap())
+                .await
+                .unwrap();
+        }
+    };
+    let settled = |at: u64| PaymentState::Settled { settled_at: at };
+    let failed = |at: u64| PaymentState::Failed { reason: "expired".to_string(), failed_at: at };
+
+    // Five received in the period, settling after 2, 4, 6, 8 and 10 seconds
+    for i in 0..5u32 {
+        store(i, since + 100, 1_000, settled(since + 100 + 2 * (i as u64 + 1))).await;
+    }
+    // Settled before the period
+    store(5, since - 100, 50_000, settled(since - 50)).await;
+    // Failed in and before the period
+    store(6, since + 10, 1_000, failed(since + 20)).await;
+    store(7, since + 10, 1_000, failed(since + 30)).await;
+    store(8, since - 100, 1_000, failed(since - 10)).await;
+    // Pending and in flight
+    store(9, since + 40, 1_000, PaymentState::Pending).await;
+    store(10, since + 50, 1_000, PaymentState::InFlight).await;
+    store(11, since - 50, 1_000, PaymentState::Pending).await;
+
+    // A receipt overrides the stored amount and fee of payment-0
+    let receipt = PaymentReceipt {
+        payment_id: "payment-0".to_string(),
+        payment_hash: [0; 32],
+        preimage: None,
+        amount_msats: 1_500,
+        fee_msats: 25,
+        settled_at: since + 102,
+        provider: ProviderType::Stub,
+        metadata: serde_json::Value::Null,
+    };
+    node_api
+        .storage_insert(PAYMENT_RECEIPTS_TREE.to_string(), b"payment-0".to_vec(), receipt.to_json().into_bytes())
+        .await
+        .unwrap();
+
+    // One payment sent
+    let history = PaymentHistory::new(node_api.clone());
+    history
+        .record(&PaymentRecord {
+            payment_id: "payment-out".to_string(),
+            payment_hash: [42; 32],
+            preimage: None,
+            amount_msats: 7_000,
+            fee_msats: 7,
+            settled_at: Some(since + 200),
+            provider: ProviderType::Stub,
+            metadata: serde_json::Value::Null,
+            direction: PaymentDirection::Outbound,
+            state: settled(since + 200),
+            created_at: since + 190,
+        })
+        .await
+        .unwrap();
+
+    let stats = processor.statistics(since).await.unwrap();
+    assert_eq!(
+        stats,
+        PaymentStats {
+            total_received_msats: 5_500,
+            total_sent_msats: 7_000,
+            total_fees_paid_msats: 25 + 4 * 10 + 7,
+            num_received: 5,
+            num_sent: 1,
+            num_failed: 2,
+            num_pending: 2,
+            success_rate: 6.0 / 8.0,
+            avg_settlement_time_ms: 6_000,
+        }
+    );
+
+    // Everything, since the epoch
+    let all = processor.statistics(0).await.unwrap();
+    assert_eq!((all.num_received, all.num_failed, all.num_pending), (6, 3, 3));
+    assert_eq!(processor.statistics_since_hours(1).await.unwrap(), PaymentStats::default());
+}
+
+#[tokio::test]
+async fn test_fresh_invoice_not_expired() {
+    let invoice = InvoiceParser::parse(&make_invoice(1000).await).unwrap();
+
+    // Expiry counts from the invoice timestamp, not the unix epoch
+    assert!(!invoice.is_expired());
+    assert_eq!(invoice.expires_at(), invoice.timestamp + 3600);
+}
@@ -1,8 +1,18 @@
 //! Unit tests for Lightning providers
 
-use bllvm_lightning::provider::{create_provider, ProviderType, LightningProvider};
-use bllvm_node::module::traits::ModuleContext;
+mod common;
+
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::provider::{
+    create_provider, DescriptionKind, InvoiceParams, LightningProvider, PaymentVerificationResult, ProviderType,
+};
+use blvm_lightning::provider::retry::{ErrorClass, ProviderRetryPolicy};
+use blvm_node::module::traits::ModuleContext;
+use common::test_context;
+use bitcoin_hashes::Hash as _;
+use lightning_invoice::Bolt11InvoiceDescription;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[tokio::test]
 async fn test_stub_provider() {
@@ -12,7 +22,7 @@ async fn test_stub_provider() {
     let ctx = ModuleContext {
         module_id: "test".to_string(),
         config,
-        data_dir: std::path::PathBuf::from("/tmp"),
+        data_dir: "/tmp".to_string(),
         socket_path: "/tmp/test.sock".to_string(),
     };
     
@@ -43,7 +53,7 @@ async fn test_ldk_provider_creation() {
     let ctx = ModuleContext {
         module_id: "test".to_string(),
         config,
-        data_dir: std::path::PathBuf::from("/tmp"),
+        data_dir: "/tmp".to_string(),
         socket_path: "/tmp/test.sock".to_string(),
     };
     
@@ -61,3 +71,363 @@ async fn test_ldk_provider_creation() {
     assert!(result.is_ok());
 }
 
+
+#[tokio::test]
+async fn test_ldk_long_description_is_hashed() {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+
+    let long_description = "x".repeat(1000);
+    let params = InvoiceParams::new(1000, &long_description, 3600);
+    let invoice = provider.create_invoice_ex(&params).await.unwrap();
+
+    let parsed = InvoiceParser::parse(&invoice).unwrap();
+    assert!(matches!(parsed.invoice.description(), InvoiceDescription::Hash(_)));
+}
+
+#[tokio::test]
+async fn test_ldk_description_hash() {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+
+    let mut params = InvoiceParams::new(1000, "", 3600);
+    params.description = DescriptionKind::Hash([7u8; 32]);
+    let invoice = provider.create_invoice_ex(&params).await.unwrap();
+
+    let parsed = InvoiceParser::parse(&invoice).unwrap();
+    match parsed.invoice.description() {
+        Bolt11InvoiceDescription::Hash(hash) => assert_eq!(hash.0.to_byte_array(), [7u8; 32]),
+        _ => panic!("expected description hash"),
+    }
+}
+
+#[tokio::test]
+async fn test_stub_echoes_invoice_metadata() {
+    let ctx = test_context(&[]);
+    let provider = create_provider(ProviderType::Stub, &ctx).unwrap();
+
+    let mut params = InvoiceParams::new(1000, "order", 3600);
+    params.label = Some("order-42".to_string());
+    params.metadata = serde_json::json!({ "sku": "abc" });
+    let invoice = provider.create_invoice_ex(&params).await.unwrap();
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "order-42").await.unwrap();
+    assert_eq!(result.metadata["invoice_metadata"]["label"], "order-42");
+    assert_eq!(result.metadata["invoice_metadata"]["metadata"]["sku"], "abc");
+
+    // Looked up by payment hash, so an invoice string that differs (e.g. in case) still matches
+    let result = provider.verify_payment(&invoice.to_uppercase(), &payment_hash, "order-42").await.unwrap();
+    assert_eq!(result.metadata["invoice_metadata"]["label"], "order-42");
+
+    let result = provider.verify_payment(&invoice, &[0u8; 32], "order-42").await.unwrap();
+    assert!(result.metadata["invoice_metadata"].is_null());
+}
+
+#[tokio::test]
+async fn test_provider_capabilities() {
+    let ctx = test_context(&[]);
+    let stub = create_provider(ProviderType::Stub, &ctx).unwrap();
+    let caps = stub.capabilities();
+    assert!(caps.can_verify);
+    assert!(caps.can_create_invoices);
+    assert!(caps.can_manage_channels);
+
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    assert!(!ldk.capabilities().can_withdraw_onchain);
+    assert!(ldk.capabilities().can_manage_channels);
+}
+
+#[tokio::test]
+async fn test_ldk_label_idempotent() {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+
+    let mut params = InvoiceParams::new(1000, "order", 3600);
+    params.label = Some("order-1".to_string());
+    let first = provider.create_invoice_ex(&params).await.unwrap();
+    assert_eq!(provider.create_invoice_ex(&params).await.unwrap(), first);
+
+    params.label = Some("order-2".to_string());
+    assert_ne!(provider.create_invoice_ex(&params).await.unwrap(), first);
+}
+
+#[test]
+fn test_retry_policy_from_config() {
+    let ctx = test_context(&[
+        ("lightning.request_timeout_secs", "10"),
+        ("lightning.max_retries", "5"),
+        ("lightning.lnbits.max_retries", "1"),
+        ("lightning.retry_on", "connect,timeout"),
+    ]);
+
+    let lnbits = ProviderRetryPolicy::from_config(&ctx, "lnbits");
+    assert_eq!(lnbits.request_timeout, Duration::from_secs(10));
+    assert_eq!(lnbits.max_retries, 1);
+    assert!(!lnbits.should_retry(ErrorClass::ServerError, 1));
+
+    let ldk = ProviderRetryPolicy::from_config(&ctx, "ldk");
+    assert_eq!(ldk.max_retries, 5);
+    assert!(ldk.should_retry(ErrorClass::Timeout, 5));
+    assert!(!ldk.should_retry(ErrorClass::Timeout, 6));
+    assert_eq!(ldk.backoff(3), ldk.backoff_base * 4);
+    assert!(!ldk.should_retry(ErrorClass::RateLimited, 1));
+
+    let ctx = test_context(&[("lightning.lnbits.backoff_ms", "250")]);
+    let lnbits = ProviderRetryPolicy::from_config(&ctx, "lnbits");
+    assert_eq!(lnbits.backoff_base, Duration::from_millis(250));
+    assert!(lnbits.should_retry(ErrorClass::RateLimited, 1));
+    for attempt in 1..=3 {
+        let delay = lnbits.jittered_backoff(attempt);
+        assert!(delay >= lnbits.backoff(attempt) / 2 && delay <= lnbits.backoff(attempt));
+    }
+}
+
+#[test]
+fn test_provider_type_display_and_serde() {
+    for provider_type in ProviderType::all() {
+        let name = provider_type.to_string();
+        assert_eq!(name, provider_type.as_str());
+        assert_eq!(name.parse::<ProviderType>().unwrap(), *provider_type);
+
+        let json = serde_json::to_string(provider_type).unwrap();
+        assert_eq!(serde_json::from_str::<ProviderType>(&json).unwrap(), *provider_type);
+    }
+    for name in ["lnbits", "ldk", "stub", "watch_only", "lnbits,stub"] {
+        assert_eq!(name.parse::<ProviderType>().unwrap().to_string(), name);
+    }
+
+    assert_eq!(serde_json::to_string(&ProviderType::LNBits).unwrap(), r#""lnbits""#);
+    assert_eq!(serde_json::to_string(&ProviderType::WatchOnly).unwrap(), r#""watch_only""#);
+    let chain = ProviderType::Failover(vec![ProviderType::LNBits, ProviderType::Stub]);
+    assert_eq!(serde_json::to_string(&chain).unwrap(), r#"{"failover":["lnbits","stub"]}"#);
+    assert_eq!(serde_json::from_str::<ProviderType>(r#"{"failover":["lnbits","stub"]}"#).unwrap(), chain);
+    // Records written before the lowercase names still load
+    assert_eq!(serde_json::from_str::<ProviderType>(r#""LNBits""#).unwrap(), ProviderType::LNBits);
+    assert_eq!(serde_json::from_str::<ProviderType>(r#""WatchOnly""#).unwrap(), ProviderType::WatchOnly);
+    assert_eq!(ProviderType::all().len(), 16);
+}
+
+#[tokio::test]
+async fn test_failover_to_ldk() {
+    let provider_type: ProviderType = "lnbits,ldk".parse().unwrap();
+    assert_eq!(provider_type, ProviderType::Failover(vec![ProviderType::LNBits, ProviderType::LDK]));
+
+    // Unreachable LNBits instance as primary
+    let ctx = test_context(&[
+        ("lightning.lnbits.api_url", "http://127.0.0.1:1"),
+        ("lightning.lnbits.max_retries", "0"),
+        ("lightning.ldk.network", "testnet"),
+    ]);
+    let provider = create_provider(provider_type, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::LNBits);
+
+    let invoice = provider.create_invoice(1000, "test", 3600).await.unwrap();
+    assert!(invoice.starts_with("lntb"));
+    // Reports the backend that answered
+    assert_eq!(provider.provider_type(), ProviderType::LDK);
+    assert!(provider.health_check().await.unwrap().reachable);
+}
+
+#[tokio::test]
+async fn test_failover_from_config() {
+    let provider_type: ProviderType = "failover".parse().unwrap();
+    let ctx = test_context(&[
+        ("lightning.failover.primary", "lnbits"),
+        ("lightning.failover.secondary", "ldk"),
+        ("lightning.lnbits.api_url", "http://127.0.0.1:1"),
+        ("lightning.lnbits.max_retries", "0"),
+        ("lightning.ldk.network", "testnet"),
+    ]);
+    let provider = create_provider(provider_type.clone(), &ctx).unwrap();
+
+    // The secondary answers, and an unpaid invoice stays unverified
+    let invoice = provider.create_invoice(1000, "test", 3600).await.unwrap();
+    let payment_hash: [u8; 32] = InvoiceParser::parse(&invoice).unwrap().payment_hash.try_into().unwrap();
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["answered_by"], "ldk");
+    assert_eq!(provider.provider_type(), ProviderType::LDK);
+
+    let ctx = test_context(&[("lightning.failover.primary", "lnbits")]);
+    assert!(create_provider(provider_type, &ctx).is_err());
+}
+
+/// The stub verifies every payment, so it can't back up a real provider
+#[cfg(not(feature = "test-utils"))]
+#[test]
+fn test_stub_rejected_as_member() {
+    let ctx = test_context(&[
+        ("lightning.failover.primary", "lnbits"),
+        ("lightning.failover.secondary", "stub"),
+        ("lightning.routing.rules", r#"[{"provider": "stub"}]"#),
+        ("lightning.composite.strategy", "round_robin"),
+        ("lightning.composite.providers", "stub,ldk"),
+        ("lightning.lnbits.api_url", "http://127.0.0.1:1"),
+        ("lightning.ldk.network", "testnet"),
+    ]);
+    for provider_type in ["lnbits,stub", "failover", "routing", "composite"] {
+        let result = create_provider(provider_type.parse().unwrap(), &ctx);
+        assert!(matches!(result, Err(blvm_lightning::error::LightningError::ConfigError(_))), "{}", provider_type);
+    }
+}
+
+#[tokio::test]
+async fn test_list_channels() {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    assert!(ldk.list_channels().await.unwrap().is_empty());
+
+    let lnbits = create_provider(ProviderType::LNBits, &ctx).unwrap();
+    assert!(lnbits.list_channels().await.is_err());
+}
+
+#[tokio::test]
+async fn test_get_node_info() {
+    let stub = create_provider(ProviderType::Stub, &test_context(&[])).unwrap();
+    let info = stub.get_node_info().await.unwrap();
+    assert_eq!(info.node_id, blvm_lightning::provider::stub::STUB_NODE_ID);
+    assert_eq!(info.num_active_channels, 1);
+
+    // Secret key 1 gives the generator point as the node key
+    let ctx = test_context(&[
+        ("lightning.ldk.network", "testnet"),
+        ("lightning.ldk.node_private_key", "0000000000000000000000000000000000000000000000000000000000000001"),
+    ]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    let info = ldk.get_node_info().await.unwrap();
+    assert_eq!(hex::encode(info.node_id), "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+    assert_eq!(info.num_peers, 0);
+    assert_eq!(info.num_active_channels, 0);
+    assert_eq!(info.block_height, 0);
+}
+
+#[cfg(not(feature = "lnd-grpc"))]
+#[test]
+fn test_lnd_requires_feature() {
+    let provider_type: ProviderType = "lnd".parse().unwrap();
+    assert_eq!(provider_type, ProviderType::LND);
+
+    let ctx = test_context(&[]);
+    assert!(matches!(
+        create_provider(provider_type, &ctx),
+        Err(blvm_lightning::error::LightningError::ConfigError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_estimate_fee() {
+    let stub = create_provider(ProviderType::Stub, &test_context(&[])).unwrap();
+    let estimate = stub.estimate_fee("lnbc1test", Some(1000)).await.unwrap();
+    assert_eq!(estimate.estimated_total_fee_msats, 1);
+    assert!(estimate.fee_proportional >= 0.0);
+
+    // LDK has no channels to route through
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    assert!(matches!(
+        ldk.estimate_fee("lnbc1test", Some(1000)).await,
+        Err(blvm_lightning::error::LightningError::RoutingError(_))
+    ));
+}
+
+fn received(amount_msats: Option<u64>, timestamp: Option<u64>) -> PaymentVerificationResult {
+    PaymentVerificationResult {
+        verified: true,
+        amount_msats,
+        timestamp,
+        metadata: serde_json::json!({"provider": "stub"}),
+    }
+}
+
+#[test]
+fn test_verification_result_amount_helpers() {
+    let exact = received(Some(1_000_000), None);
+    assert!(!exact.is_underpaid(1_000_000, 0));
+    assert_eq!(exact.overpaid_by_msats(1_000_000), None);
+
+    let short = received(Some(999_000), None);
+    assert!(short.is_underpaid(1_000_000, 0));
+    assert!(short.is_underpaid(1_000_000, 999));
+    // Exactly at the tolerance limit
+    assert!(!short.is_underpaid(1_000_000, 1000));
+    assert!(!received(Some(0), None).is_underpaid(1_000_000, 2_000_000));
+
+    let over = received(Some(1_000_001), None);
+    assert!(!over.is_underpaid(1_000_000, 0));
+    assert_eq!(over.overpaid_by_msats(1_000_000), Some(1));
+
+    // Amountless invoices and unreported amounts are never under- or overpaid
+    assert!(!short.is_underpaid(0, 0));
+    assert_eq!(over.overpaid_by_msats(0), None);
+    let unknown = received(None, None);
+    assert!(!unknown.is_underpaid(1_000_000, 0));
+    assert_eq!(unknown.overpaid_by_msats(1_000_000), None);
+    assert_eq!(unknown.amount_msats_or_zero(), 0);
+    assert_eq!(over.amount_msats_or_zero(), 1_000_001);
+
+    // No overflow near u64::MAX
+    assert!(!received(Some(u64::MAX), None).is_underpaid(u64::MAX, 0));
+    assert!(received(Some(u64::MAX - 1), None).is_underpaid(u64::MAX, 0));
+}
+
+#[test]
+fn test_verification_result_age_and_serde() {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    assert_eq!(received(None, None).age_seconds(), None);
+    let age = received(None, Some(now - 60)).age_seconds().unwrap();
+    assert!((60..=61).contains(&age));
+    assert_eq!(received(None, Some(now + 3600)).age_seconds(), Some(0));
+
+    let result = received(Some(1000), Some(now));
+    let json = serde_json::to_vec(&result).unwrap();
+    let restored: PaymentVerificationResult = serde_json::from_slice(&json).unwrap();
+    assert_eq!(restored.amount_msats, Some(1000));
+    assert_eq!(restored.timestamp, Some(now));
+    assert!(restored.verified);
+    assert_eq!(restored.metadata, result.metadata);
+}
+
+#[cfg(feature = "test-utils")]
+#[tokio::test]
+async fn test_ldk_injected_payment_confirms() {
+    use blvm_lightning::provider::ldk::LDKProvider;
+
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    let ldk = provider.as_any().downcast_ref::<LDKProvider>().unwrap();
+
+    let payment_hash = [7u8; 32];
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+    ldk.inject_payment_for_test(payment_hash, 5000, false).await;
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+    ldk.inject_payment_for_test(payment_hash, 5000, true).await;
+    assert!(provider.is_payment_confirmed(&payment_hash).await.unwrap());
+}
+
+#[cfg(feature = "test-utils")]
+#[tokio::test]
+async fn test_ldk_injected_channel_listed() {
+    use blvm_lightning::provider::ldk::LDKProvider;
+    use blvm_lightning::provider::ChannelInfo;
+
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    let ldk = provider.as_any().downcast_ref::<LDKProvider>().unwrap();
+    assert!(provider.list_channels().await.unwrap().is_empty());
+
+    let channel = ChannelInfo {
+        channel_id: [1u8; 32],
+        counterparty_node_id: [2u8; 33],
+        capacity_msats: 1_000_000_000,
+        local_balance_msats: 600_000_000,
+        remote_balance_msats: 400_000_000,
+        is_active: true,
+        short_channel_id: Some(42),
+        is_public: false,
+    };
+    ldk.inject_channel_for_test(channel.clone()).await;
+    assert_eq!(provider.list_channels().await.unwrap(), vec![channel]);
+    assert_eq!(provider.get_node_info().await.unwrap().num_active_channels, 1);
+}
@@ -0,0 +1,26 @@
+//! Tests for per-key payment request rate limiting
+
+use blvm_lightning::rate_limiter::RateLimiter;
+
+#[test]
+fn test_limit_per_key() {
+    let mut limiter = RateLimiter::new();
+    assert!(limiter.check_and_record("wallet-a", 2, 60));
+    assert!(limiter.check_and_record("wallet-a", 2, 60));
+    assert!(!limiter.check_and_record("wallet-a", 2, 60));
+
+    // Other keys have their own budget
+    assert!(limiter.check_and_record("wallet-b", 2, 60));
+    assert_eq!(limiter.len(), 2);
+}
+
+#[test]
+fn test_window_resets() {
+    let mut limiter = RateLimiter::new();
+    assert!(limiter.check_and_record("wallet-a", 1, 1));
+    assert!(!limiter.check_and_record("wallet-a", 1, 1));
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert!(limiter.check_and_record("wallet-a", 1, 1));
+    assert!(!limiter.check_and_record("wallet-a", 1, 1));
+}
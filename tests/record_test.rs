@@ -0,0 +1,162 @@
+//! Record-and-replay provider tests
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::record::{RecordedResult, Recording, RecordingProvider, ReplayProvider};
+use blvm_lightning::provider::stub::{StubOutcome, StubProvider, StubScenario};
+use blvm_lightning::provider::{create_provider, InvoiceParams, LightningProvider, PaymentVerificationResult, ProviderType};
+use common::test_context;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const PAID: [u8; 32] = [1u8; 32];
+const SLOW: [u8; 32] = [4u8; 32];
+const INVOICE: &str = "lntb10n1pjrecordtest";
+
+fn recording_path() -> PathBuf {
+    std::env::temp_dir().join(format!("blvm-lightning-recording-{}.json", rand::random::<u64>()))
+}
+
+fn stub() -> Box<StubProvider> {
+    Box::new(StubProvider::with_scenario(
+        StubScenario::new()
+            .payment(&PAID, StubOutcome::Verified { amount_msats: Some(5000) })
+            .payment(&SLOW, StubOutcome::Timeout { after_ms: 10 }),
+    ))
+}
+
+/// Record a short session against the stub provider
+async fn record_session(path: &PathBuf, redact: bool) -> String {
+    let recorder = RecordingProvider::new(stub(), path.clone(), redact);
+    assert!(recorder.verify_payment(INVOICE, &PAID, "payment-1").await.unwrap().verified);
+    assert!(recorder.is_payment_confirmed(&SLOW).await.is_err());
+    let bolt11 = recorder.create_invoice_ex(&InvoiceParams::new(2000, "order 1", 3600)).await.unwrap();
+    assert_eq!(recorder.recording().calls.len(), 3);
+    bolt11
+}
+
+#[tokio::test]
+async fn test_replay_recorded_session() {
+    let path = recording_path();
+    let bolt11 = record_session(&path, false).await;
+
+    let replay = ReplayProvider::from_file(&path).unwrap();
+    assert_eq!(replay.provider_type(), ProviderType::Stub);
+
+    let result = replay.verify_payment(INVOICE, &PAID, "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(5000));
+
+    let err = replay.is_payment_confirmed(&SLOW).await.unwrap_err();
+    assert!(err.is_retryable(), "{:?}", err);
+
+    let replayed = replay.create_invoice_ex(&InvoiceParams::new(2000, "order 1", 3600)).await.unwrap();
+    assert_eq!(replayed, bolt11);
+    assert!(replay.remaining().is_empty());
+}
+
+#[tokio::test]
+async fn test_unexpected_call_fails() {
+    let path = recording_path();
+    record_session(&path, false).await;
+    let replay = ReplayProvider::from_file(&path).unwrap();
+
+    // Different arguments than recorded
+    let err = replay.verify_payment(INVOICE, &PAID, "payment-2").await.unwrap_err();
+    match err {
+        LightningError::ProcessorError(msg) => {
+            assert!(msg.contains("Unexpected verify_payment call"), "{}", msg);
+            assert!(msg.contains("payment-2"), "{}", msg);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    // Each recorded call is served once
+    replay.verify_payment(INVOICE, &PAID, "payment-1").await.unwrap();
+    assert!(replay.verify_payment(INVOICE, &PAID, "payment-1").await.is_err());
+
+    // Never recorded at all
+    assert!(replay.balance_msats().await.is_err());
+}
+
+#[tokio::test]
+async fn test_redacted_recording() {
+    let path = recording_path();
+    let bolt11 = record_session(&path, true).await;
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains(INVOICE));
+    assert!(!contents.contains(&hex::encode(PAID)));
+    assert!(!contents.contains(&bolt11));
+    assert!(Recording::load(&path).unwrap().redacted);
+
+    // Replay hashes the arguments the same way before matching
+    let replay = ReplayProvider::from_file(&path).unwrap();
+    assert!(replay.verify_payment(INVOICE, &PAID, "payment-1").await.unwrap().verified);
+    assert!(replay.is_payment_confirmed(&SLOW).await.is_err());
+    assert!(replay.verify_payment("lntb10n1pjother", &PAID, "payment-1").await.is_err());
+
+    // Results are replayed as recorded, redacted
+    let replayed = replay.create_invoice_ex(&InvoiceParams::new(2000, "order 1", 3600)).await.unwrap();
+    assert!(replayed.starts_with("sha256:"), "{}", replayed);
+}
+
+#[tokio::test]
+async fn test_redacted_result_metadata() {
+    let path = recording_path();
+    let response = PaymentVerificationResult {
+        verified: true,
+        amount_msats: Some(5000),
+        timestamp: None,
+        metadata: json!({ "payment_hash": hex::encode(PAID), "bolt11": INVOICE, "status": "paid" }),
+    };
+    let stub = StubProvider::new().responses(HashMap::from([(PAID, response)]));
+    let recorder = RecordingProvider::new(Box::new(stub), path.clone(), true);
+    recorder.verify_payment(INVOICE, &PAID, "payment-1").await.unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains(INVOICE));
+    assert!(!contents.contains(&hex::encode(PAID)));
+
+    let call = &Recording::load(&path).unwrap().calls[0];
+    let metadata = match &call.result {
+        RecordedResult::Ok(value) => value["metadata"].clone(),
+        other => panic!("unexpected result: {:?}", other),
+    };
+    // Same hash as the redacted argument, so the two can still be correlated
+    assert_eq!(metadata["payment_hash"], call.args["payment_hash"]);
+    assert_eq!(metadata["bolt11"], call.args["invoice"]);
+    assert_eq!(metadata["status"], "paid");
+}
+
+#[tokio::test]
+async fn test_calls_appended() {
+    let path = recording_path();
+    let recorder = RecordingProvider::new(stub(), path.clone(), false);
+    // Header only until a call completes
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+    recorder.verify_payment(INVOICE, &PAID, "payment-1").await.unwrap();
+    recorder.balance_msats().await.unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+    assert_eq!(Recording::load(&path).unwrap().calls, recorder.recording().calls);
+}
+
+#[tokio::test]
+async fn test_record_and_replay_from_config() {
+    let path = recording_path();
+    let path_str = path.to_string_lossy().to_string();
+
+    let ctx = test_context(&[("lightning.record_path", path_str.as_str())]);
+    let recorder = create_provider(ProviderType::Stub, &ctx).unwrap();
+    let health = recorder.health_check().await.unwrap();
+    assert!(path.exists());
+
+    // Replaying ignores the configured provider type
+    let ctx = test_context(&[("lightning.replay_path", path_str.as_str())]);
+    let replay = create_provider(ProviderType::LNBits, &ctx).unwrap();
+    assert_eq!(replay.provider_type(), ProviderType::Stub);
+    assert_eq!(replay.health_check().await.unwrap(), health);
+}
@@ -0,0 +1,218 @@
+//! Amount-based routing provider tests
+
+mod common;
+
+use async_trait::async_trait;
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::provider::routing::{Route, RoutingProvider, RoutingRule, ROUTING_INDEX_TREE};
+use blvm_lightning::provider::{
+    create_provider, HealthStatus, InvoiceParams, LightningProvider, PaymentVerificationResult, ProviderCapabilities, ProviderType,
+};
+use common::{test_context, MockNodeApi};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// 100k sats
+const THRESHOLD_MSATS: u64 = 100_000_000;
+
+/// Provider that issues real invoices (via LDK) and counts verifications
+struct CountingProvider {
+    ldk: Box<dyn LightningProvider>,
+    verifications: Arc<AtomicU32>,
+}
+
+impl CountingProvider {
+    fn new() -> Self {
+        let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+        Self {
+            ldk: create_provider(ProviderType::LDK, &ctx).unwrap(),
+            verifications: Arc::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LightningProvider for CountingProvider {
+    async fn verify_payment(
+        &self,
+        _invoice: &str,
+        _payment_hash: &[u8; 32],
+        _payment_id: &str,
+    ) -> Result<PaymentVerificationResult, LightningError> {
+        self.verifications.fetch_add(1, Ordering::SeqCst);
+        Ok(PaymentVerificationResult {
+            verified: true,
+            amount_msats: None,
+            timestamp: None,
+            metadata: serde_json::json!({}),
+        })
+    }
+
+    async fn create_invoice(&self, amount_msats: u64, description: &str, expiry_seconds: u64) -> Result<String, LightningError> {
+        self.ldk.create_invoice(amount_msats, description, expiry_seconds).await
+    }
+
+    async fn is_payment_confirmed(&self, _payment_hash: &[u8; 32]) -> Result<bool, LightningError> {
+        self.verifications.fetch_add(1, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus, LightningError> {
+        self.ldk.health_check().await
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.ldk.capabilities()
+    }
+
+    fn provider_type(&self) -> ProviderType {
+        ProviderType::Stub
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+struct Fixture {
+    provider: RoutingProvider,
+    small: Arc<AtomicU32>,
+    large: Arc<AtomicU32>,
+}
+
+fn routing() -> Fixture {
+    let small = CountingProvider::new();
+    let large = CountingProvider::new();
+    let counters = (small.verifications.clone(), large.verifications.clone());
+    let routes = vec![
+        Route {
+            rule: RoutingRule { max_msats: Some(THRESHOLD_MSATS), provider: "small".to_string() },
+            provider: Box::new(small),
+        },
+        Route {
+            rule: RoutingRule { max_msats: None, provider: "large".to_string() },
+            provider: Box::new(large),
+        },
+    ];
+    Fixture {
+        provider: RoutingProvider::new(routes).unwrap(),
+        small: counters.0,
+        large: counters.1,
+    }
+}
+
+fn payment_hash(invoice: &str) -> [u8; 32] {
+    InvoiceParser::parse(invoice).unwrap().payment_hash()
+}
+
+#[test]
+fn test_parse_rules() {
+    let rules = RoutingRule::parse_rules(r#"[{"max_msats": 100000000, "provider": "lnbits"}, {"provider": "ldk"}]"#).unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].max_msats, Some(THRESHOLD_MSATS));
+    assert_eq!(rules[1].max_msats, None);
+
+    for invalid in [
+        "",
+        "[]",
+        r#"[{"provider": "ldk"}, {"max_msats": 1000, "provider": "lnbits"}]"#,
+        r#"[{"max_msats": 1000, "provider": "lnbits"}, {"max_msats": 1000, "provider": "ldk"}]"#,
+    ] {
+        assert!(RoutingRule::parse_rules(invalid).is_err(), "accepted {:?}", invalid);
+    }
+}
+
+#[test]
+fn test_route_boundaries() {
+    let fixture = routing();
+    let provider = &fixture.provider;
+    assert_eq!(provider.route_for_amount(0).unwrap().rule.provider, "small");
+    assert_eq!(provider.route_for_amount(THRESHOLD_MSATS).unwrap().rule.provider, "small");
+    assert_eq!(provider.route_for_amount(THRESHOLD_MSATS + 1).unwrap().rule.provider, "large");
+    assert_eq!(provider.route_for_amount(u64::MAX).unwrap().rule.provider, "large");
+
+    // Without a catch-all rule, larger amounts have nowhere to go
+    let bounded = RoutingProvider::new(
+        vec![Route {
+            rule: RoutingRule { max_msats: Some(1000), provider: "small".to_string() },
+            provider: Box::new(CountingProvider::new()),
+        }])
+    .unwrap();
+    assert!(matches!(bounded.route_for_amount(1001), Err(LightningError::ConfigError(_))));
+}
+
+#[tokio::test]
+async fn test_verification_follows_creating_provider() {
+    let fixture = routing();
+    let small_invoice = fixture.provider.create_invoice(THRESHOLD_MSATS, "coffee", 3600).await.unwrap();
+    let large_invoice = fixture.provider.create_invoice_ex(&InvoiceParams::new(THRESHOLD_MSATS + 1, "laptop", 3600)).await.unwrap();
+
+    let result = fixture.provider.verify_payment(&small_invoice, &payment_hash(&small_invoice), "payment-1").await.unwrap();
+    assert_eq!(result.metadata["routed_to"], "small");
+    let result = fixture.provider.verify_payment(&large_invoice, &payment_hash(&large_invoice), "payment-2").await.unwrap();
+    assert_eq!(result.metadata["routed_to"], "large");
+
+    // Confirmation checks only have the hash to go on
+    assert!(fixture.provider.is_payment_confirmed(&payment_hash(&large_invoice)).await.unwrap());
+    assert_eq!(fixture.small.load(Ordering::SeqCst), 1);
+    assert_eq!(fixture.large.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_unknown_payment_hash() {
+    let fixture = routing();
+
+    // Created elsewhere: fall back to routing by the invoice amount
+    let foreign = CountingProvider::new().create_invoice(THRESHOLD_MSATS * 2, "elsewhere", 3600).await.unwrap();
+    let hash = payment_hash(&foreign);
+    assert_eq!(fixture.provider.provider_for_payment(&hash), None);
+    let result = fixture.provider.verify_payment(&foreign, &hash, "payment-1").await.unwrap();
+    assert_eq!(result.metadata["routed_to"], "large");
+
+    // No invoice to route by
+    let err = fixture.provider.is_payment_confirmed(&[9u8; 32]).await.unwrap_err();
+    match err {
+        LightningError::ProcessorError(msg) => assert!(msg.contains("Unknown payment hash"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert!(fixture.provider.verify_payment("", &[9u8; 32], "payment-2").await.is_err());
+    assert_eq!(fixture.small.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_index_persisted() {
+    let node_api = MockNodeApi::new();
+    let fixture = routing();
+    fixture.provider.attach_storage(node_api.clone()).await.unwrap();
+    let invoice = fixture.provider.create_invoice(1000, "tea", 3600).await.unwrap();
+    assert_eq!(node_api.len(ROUTING_INDEX_TREE), 1);
+
+    // A restarted provider still knows which route created the invoice
+    let restarted = routing();
+    restarted.provider.attach_storage(node_api.clone()).await.unwrap();
+    assert_eq!(restarted.provider.provider_for_payment(&payment_hash(&invoice)), Some("small".to_string()));
+    assert!(restarted.provider.is_payment_confirmed(&payment_hash(&invoice)).await.unwrap());
+    assert_eq!(restarted.small.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_create_from_config() {
+    assert_eq!("routing".parse::<ProviderType>().unwrap(), ProviderType::Routing);
+
+    let ctx = test_context(&[
+        ("lightning.routing.rules", r#"[{"max_msats": 100000000, "provider": "lnbits"}, {"provider": "ldk"}]"#),
+        ("lightning.lnbits.api_url", "http://127.0.0.1:1"),
+        ("lightning.ldk.network", "testnet"),
+    ]);
+    let provider = create_provider(ProviderType::Routing, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::Routing);
+    let routing = provider.as_any().downcast_ref::<RoutingProvider>().unwrap();
+    assert_eq!(routing.routes()[0].provider.provider_type(), ProviderType::LNBits);
+    assert_eq!(routing.routes()[1].provider.provider_type(), ProviderType::LDK);
+
+    let ctx = test_context(&[("lightning.routing.rules", r#"[{"provider": "routing"}]"#)]);
+    assert!(create_provider(ProviderType::Routing, &ctx).is_err());
+    let ctx = test_context(&[("lightning.routing.rules", r#"[{"provider": "nonsense"}]"#)]);
+    assert!(create_provider(ProviderType::Routing, &ctx).is_err());
+}
@@ -0,0 +1,116 @@
+//! Graceful shutdown and in-flight payment draining tests
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::payment_state::{PaymentState, PAYMENT_STATES_TREE};
+use blvm_lightning::processor::LightningProcessor;
+use blvm_lightning::provider::{create_provider, ProviderType};
+use blvm_lightning::shutdown::ShutdownController;
+use blvm_node::module::traits::NodeAPI;
+use common::{test_context, MockNodeApi};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Create a real signed BOLT11 invoice via the LDK provider
+async fn make_invoice(amount_msats: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    provider.create_invoice(amount_msats, "test", 3600).await.unwrap()
+}
+
+/// Processor with one in-flight payment scripted by `outcome`
+///
+/// The payment is verified, then put back in flight as if the module had
+/// stopped mid-verification.
+async fn processor_with_inflight(invoice: &str, outcome: &str) -> (Arc<LightningProcessor>, Arc<MockNodeApi>) {
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {}}}}}"#,
+        InvoiceParser::parse(invoice).unwrap().payment_hash_hex(),
+        outcome
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    processor.process_payment(invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    let mut payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert_eq!(payment.state, PaymentState::Pending);
+    payment.state = PaymentState::InFlight;
+    node_api
+        .storage_insert(PAYMENT_STATES_TREE.to_string(), b"payment-1".to_vec(), serde_json::to_vec(&payment).unwrap())
+        .await
+        .unwrap();
+    (Arc::new(processor), node_api)
+}
+
+#[tokio::test]
+async fn test_trigger_cancels_token() {
+    let shutdown = ShutdownController::new();
+    let token = shutdown.token();
+    let listener = shutdown.listen_for_signals();
+    assert!(!shutdown.is_shutting_down());
+
+    shutdown.trigger();
+    assert!(token.is_cancelled());
+    assert!(shutdown.is_shutting_down());
+
+    // The signal listener exits once shutdown has started
+    tokio::time::timeout(Duration::from_secs(1), listener).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_inflight_payment_settles_during_drain() {
+    let invoice = make_invoice(2000).await;
+    let (processor, node_api) =
+        processor_with_inflight(&invoice, r#"{"outcome": "pending_then_settled", "calls": 2}"#).await;
+
+    let shutdown = ShutdownController::new();
+    let token = shutdown.token();
+    let drain = tokio::spawn({
+        let processor = Arc::clone(&processor);
+        async move {
+            token.cancelled().await;
+            processor.drain_inflight(Duration::from_secs(5)).await
+        }
+    });
+
+    // The signal arrives while the payment is still in flight...
+    shutdown.trigger();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!drain.is_finished());
+
+    // ...and the provider sees it settle within the drain window
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+    processor.provider().verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+
+    drain.await.unwrap().unwrap();
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+    assert_eq!(node_api.len("processed_payments"), 1);
+}
+
+#[tokio::test]
+async fn test_drain_times_out() {
+    let invoice = make_invoice(2000).await;
+    let (processor, _node_api) = processor_with_inflight(&invoice, r#"{"outcome": "failed"}"#).await;
+
+    let started = std::time::Instant::now();
+    let err = processor.drain_inflight(Duration::from_millis(1200)).await.unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(3));
+    match err {
+        LightningError::ProcessorError(msg) => assert!(msg.contains("1 payments still in flight"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    // Left for the confirmation poller after a restart
+    assert_eq!(processor.load_payment("payment-1").await.unwrap().unwrap().state, PaymentState::InFlight);
+}
+
+#[tokio::test]
+async fn test_drain_with_nothing_in_flight() {
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.shutdown.drain_timeout_seconds", "5")]);
+    let processor = LightningProcessor::new(&ctx, MockNodeApi::new()).await.unwrap();
+    assert_eq!(processor.drain_timeout(), Duration::from_secs(5));
+    processor.drain_inflight(Duration::from_millis(10)).await.unwrap();
+}
@@ -0,0 +1,292 @@
+//! Strike provider tests against recorded API responses
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::provider::strike::{StrikeConfig, StrikeProvider, STRIKE_INVOICES_TREE, STRIKE_QUOTES_TREE};
+use blvm_lightning::provider::{create_provider, InvoiceParams, LightningProvider, ProviderType};
+use common::{test_context, MockNodeApi};
+use mockito::Matcher;
+
+const API_KEY: &str = "strike_test_key";
+const INVOICE_ID: &str = "5a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d";
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/strike/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+/// A real invoice and its payment hash
+async fn make_invoice() -> (String, [u8; 32]) {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let ldk = create_provider(ProviderType::LDK, &ctx).unwrap();
+    let invoice = ldk.create_invoice(2000, "order 1", 60).await.unwrap();
+    let mut payment_hash = [0u8; 32];
+    payment_hash.copy_from_slice(&InvoiceParser::parse(&invoice).unwrap().payment_hash);
+    (invoice, payment_hash)
+}
+
+/// Quote for `invoice` expiring in `expiration_secs`
+fn quote_fixture(invoice: &str, expiration_secs: u64) -> String {
+    fixture("quote.json")
+        .replace("{{invoice}}", invoice)
+        .replace("{{expiration}}", &expiration_secs.to_string())
+}
+
+fn provider_for(server: &mockito::Server) -> StrikeProvider {
+    StrikeProvider::new(StrikeConfig {
+        url: server.url(),
+        api_key: API_KEY.to_string(),
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+fn labelled(label: &str) -> InvoiceParams {
+    let mut params = InvoiceParams::new(1500, "order 1", 3600);
+    params.label = Some(label.to_string());
+    params
+}
+
+async fn mock_create(server: &mut mockito::Server) -> mockito::Mock {
+    server
+        .mock("POST", "/v1/invoices")
+        .match_header("Authorization", format!("Bearer {}", API_KEY).as_str())
+        .with_status(201)
+        .with_body(fixture("invoice_created.json"))
+        .create_async()
+        .await
+}
+
+async fn mock_quote(server: &mut mockito::Server, invoice: &str, expiration_secs: u64) -> mockito::Mock {
+    server
+        .mock("POST", format!("/v1/invoices/{}/quote", INVOICE_ID).as_str())
+        .match_header("Authorization", format!("Bearer {}", API_KEY).as_str())
+        .with_status(201)
+        .with_body(quote_fixture(invoice, expiration_secs))
+        .create_async()
+        .await
+}
+
+async fn mock_state(server: &mut mockito::Server, name: &str) -> mockito::Mock {
+    server
+        .mock("GET", format!("/v1/invoices/{}", INVOICE_ID).as_str())
+        .match_header("Authorization", format!("Bearer {}", API_KEY).as_str())
+        .with_status(200)
+        .with_body(fixture(name))
+        .create_async()
+        .await
+}
+
+/// Provider that has created and quoted the invoice in `invoice_created.json`
+async fn provider_with_invoice(server: &mut mockito::Server) -> (StrikeProvider, String, [u8; 32]) {
+    let (invoice, payment_hash) = make_invoice().await;
+    mock_create(server).await;
+    mock_quote(server, &invoice, 59).await;
+    let provider = provider_for(server);
+    assert_eq!(provider.create_invoice(2000, "order 1", 3600).await.unwrap(), invoice);
+    (provider, invoice, payment_hash)
+}
+
+#[tokio::test]
+async fn test_create_invoice_quotes_bolt11() {
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, payment_hash) = make_invoice().await;
+    let create = server
+        .mock("POST", "/v1/invoices")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "description": "order 1",
+            // 1500 msats rounds up to 2 sats
+            "amount": { "currency": "BTC", "amount": "0.00000002" },
+            "correlationId": "order-1",
+        })))
+        .with_status(201)
+        .with_body(fixture("invoice_created.json"))
+        .create_async()
+        .await;
+    let quote = mock_quote(&mut server, &invoice, 59).await;
+
+    let provider = provider_for(&server);
+    assert_eq!(provider.create_invoice_ex(&labelled("order-1")).await.unwrap(), invoice);
+    assert_eq!(provider.invoice_id(&payment_hash).as_deref(), Some(INVOICE_ID));
+    assert_eq!(provider.provider_type(), ProviderType::Strike);
+    create.assert_async().await;
+    quote.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_asking_again_reuses_valid_quote() {
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, _) = make_invoice().await;
+    let create = mock_create(&mut server).await;
+    let quote = mock_quote(&mut server, &invoice, 59).await;
+
+    let provider = provider_for(&server);
+    let first = provider.create_invoice_ex(&labelled("order-1")).await.unwrap();
+    let second = provider.create_invoice_ex(&labelled("order-1")).await.unwrap();
+    assert_eq!(first, second);
+    create.assert_async().await;
+    quote.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_asking_again_requotes_expired_quote() {
+    let mut server = mockito::Server::new_async().await;
+    let (first_invoice, first_hash) = make_invoice().await;
+    let (second_invoice, second_hash) = make_invoice().await;
+    let create = mock_create(&mut server).await;
+    let first_quote = mock_quote(&mut server, &first_invoice, 0).await;
+
+    let provider = provider_for(&server);
+    assert_eq!(provider.create_invoice_ex(&labelled("order-1")).await.unwrap(), first_invoice);
+    first_quote.remove_async().await;
+
+    // The quote has expired; the still unpaid Strike invoice is re-quoted, not re-created
+    let state = mock_state(&mut server, "invoice_unpaid.json").await;
+    mock_quote(&mut server, &second_invoice, 59).await;
+    assert_eq!(provider.create_invoice_ex(&labelled("order-1")).await.unwrap(), second_invoice);
+
+    // Both quotes verify against the same Strike invoice
+    assert_eq!(provider.invoice_id(&first_hash).as_deref(), Some(INVOICE_ID));
+    assert_eq!(provider.invoice_id(&second_hash).as_deref(), Some(INVOICE_ID));
+    create.assert_async().await;
+    state.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_expired_quote_of_paid_invoice_not_requoted() {
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, _) = make_invoice().await;
+    mock_create(&mut server).await;
+    mock_quote(&mut server, &invoice, 0).await;
+
+    let provider = provider_for(&server);
+    provider.create_invoice_ex(&labelled("order-1")).await.unwrap();
+
+    mock_state(&mut server, "invoice_paid.json").await;
+    let err = provider.create_invoice_ex(&labelled("order-1")).await.unwrap_err();
+    assert!(matches!(err, LightningError::InvoiceError(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_verify_paid_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_invoice(&mut server).await;
+    mock_state(&mut server, "invoice_paid.json").await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(2000));
+    assert_eq!(result.metadata["invoice_id"], INVOICE_ID);
+    assert_eq!(result.metadata["state"], "paid");
+    assert!(provider.is_payment_confirmed(&payment_hash).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_unpaid_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_invoice(&mut server).await;
+    mock_state(&mut server, "invoice_unpaid.json").await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, None);
+    assert_eq!(result.metadata["state"], "unpaid");
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_verify_cancelled_invoice() {
+    let mut server = mockito::Server::new_async().await;
+    let (provider, invoice, payment_hash) = provider_with_invoice(&mut server).await;
+    mock_state(&mut server, "invoice_cancelled.json").await;
+
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["state"], "cancelled");
+}
+
+#[tokio::test]
+async fn test_verify_unknown_payment_hash() {
+    let server = mockito::Server::new_async().await;
+    let (invoice, payment_hash) = make_invoice().await;
+
+    let provider = provider_for(&server);
+    let result = provider.verify_payment(&invoice, &payment_hash, "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "invoice_not_found");
+    assert!(!provider.is_payment_confirmed(&payment_hash).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_invoice_index_and_quotes_persisted() {
+    let node_api = MockNodeApi::new();
+    let mut server = mockito::Server::new_async().await;
+    let (invoice, payment_hash) = make_invoice().await;
+    let create = mock_create(&mut server).await;
+    let quote = mock_quote(&mut server, &invoice, 59).await;
+
+    // Quoted before storage is attached, so the entries are written on attach
+    let provider = provider_for(&server);
+    let first = provider.create_invoice_ex(&labelled("order-1")).await.unwrap();
+    assert_eq!(node_api.len(STRIKE_INVOICES_TREE), 0);
+    provider.attach_storage(node_api.clone()).await.unwrap();
+    assert_eq!(node_api.len(STRIKE_INVOICES_TREE), 1);
+    assert_eq!(node_api.len(STRIKE_QUOTES_TREE), 1);
+
+    // After a restart the payment still verifies and the label gets the same quote
+    let restarted = provider_for(&server);
+    restarted.attach_storage(node_api.clone()).await.unwrap();
+    assert_eq!(restarted.invoice_id(&payment_hash).as_deref(), Some(INVOICE_ID));
+    assert_eq!(restarted.create_invoice_ex(&labelled("order-1")).await.unwrap(), first);
+    create.assert_async().await;
+    quote.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_errors_mapped() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/invoices")
+        .with_status(422)
+        .with_body(fixture("invalid_amount.json"))
+        .create_async()
+        .await;
+    match provider_for(&server).create_invoice(0, "order 1", 3600).await.unwrap_err() {
+        LightningError::InvoiceError(msg) => assert!(msg.contains("INVALID_DATA"), "{}", msg),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let mut server = mockito::Server::new_async().await;
+    server.mock("POST", "/v1/invoices").with_status(401).create_async().await;
+    let err = provider_for(&server).create_invoice(2000, "order 1", 3600).await.unwrap_err();
+    assert!(matches!(err, LightningError::ConfigError(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_health_check() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/v1/balances")
+        .with_status(200)
+        .with_body(fixture("balances.json"))
+        .create_async()
+        .await;
+    assert!(provider_for(&server).health_check().await.unwrap().reachable);
+
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/v1/balances").with_status(403).create_async().await;
+    assert!(!provider_for(&server).health_check().await.unwrap().reachable);
+}
+
+#[test]
+fn test_create_provider_from_config() {
+    assert_eq!("strike".parse::<ProviderType>().unwrap(), ProviderType::Strike);
+
+    let ctx = test_context(&[("lightning.strike.api_key", API_KEY)]);
+    let provider = create_provider(ProviderType::Strike, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::Strike);
+
+    let ctx = test_context(&[]);
+    assert!(matches!(create_provider(ProviderType::Strike, &ctx), Err(LightningError::ConfigError(_))));
+}
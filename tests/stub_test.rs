@@ -0,0 +1,297 @@
+//! Scenario-scripted stub provider tests
+
+mod common;
+
+use blvm_lightning::error::LightningError;
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::payment_state::PaymentState;
+use blvm_lightning::processor::LightningProcessor;
+use blvm_lightning::provider::stub::{StubCall, StubConfig, StubOutcome, StubProvider, StubScenario, STUB_AMOUNT_MSATS};
+use blvm_lightning::provider::{create_provider, InvoiceParams, LightningProvider, PaymentVerificationResult, ProviderType};
+use std::collections::HashMap;
+use common::{test_context, MockNodeApi};
+
+const PAID: [u8; 32] = [1u8; 32];
+const PENDING: [u8; 32] = [2u8; 32];
+const FAILED: [u8; 32] = [3u8; 32];
+const SLOW: [u8; 32] = [4u8; 32];
+
+fn scenario() -> StubScenario {
+    StubScenario::new()
+        .payment(&PAID, StubOutcome::Verified { amount_msats: Some(5000) })
+        .payment(&PENDING, StubOutcome::PendingThenSettled { calls: 2, amount_msats: None })
+        .payment(&FAILED, StubOutcome::Failed { reason: Some("expired".to_string()) })
+        .payment(&SLOW, StubOutcome::Timeout { after_ms: 50 })
+}
+
+/// Create a real signed BOLT11 invoice via the LDK provider
+async fn make_invoice(amount_msats: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    provider.create_invoice(amount_msats, "test", 3600).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_default_always_verifies() {
+    let stub = StubProvider::new();
+    let result = stub.verify_payment("", &FAILED, "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(STUB_AMOUNT_MSATS));
+    assert!(stub.is_payment_confirmed(&FAILED).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_scripted_outcomes() {
+    let stub = StubProvider::with_scenario(scenario());
+
+    let result = stub.verify_payment("", &PAID, "paid").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(5000));
+
+    let result = stub.verify_payment("", &FAILED, "failed").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.amount_msats, None);
+    assert_eq!(result.metadata["error"], "expired");
+
+    let started = std::time::Instant::now();
+    let err = stub.verify_payment("", &SLOW, "slow").await.unwrap_err();
+    assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+    assert!(err.is_retryable(), "{:?}", err);
+
+    // Unlisted hashes keep the always-succeed behavior
+    assert!(stub.verify_payment("", &[9u8; 32], "other").await.unwrap().verified);
+}
+
+#[tokio::test]
+async fn test_pending_then_settled() {
+    let stub = StubProvider::with_scenario(scenario());
+
+    assert!(!stub.verify_payment("", &PENDING, "pending").await.unwrap().verified);
+    // Confirmation checks don't count towards the pending calls
+    assert!(!stub.is_payment_confirmed(&PENDING).await.unwrap());
+    assert!(!stub.verify_payment("", &PENDING, "pending").await.unwrap().verified);
+    assert!(stub.verify_payment("", &PENDING, "pending").await.unwrap().verified);
+    assert!(stub.is_payment_confirmed(&PENDING).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_call_log_order() {
+    let stub = StubProvider::with_scenario(StubScenario::new().default_outcome(StubOutcome::Failed { reason: None }));
+    stub.create_invoice(1000, "test", 3600).await.unwrap();
+    stub.verify_payment("", &PAID, "payment-1").await.unwrap();
+    stub.is_payment_confirmed(&PAID).await.unwrap();
+
+    assert_eq!(
+        stub.calls(),
+        vec![
+            StubCall::CreateInvoice { amount_msats: 1000 },
+            StubCall::VerifyPayment { payment_hash: PAID, payment_id: "payment-1".to_string() },
+            StubCall::IsPaymentConfirmed { payment_hash: PAID },
+        ]
+    );
+}
+
+#[test]
+fn test_scenario_from_config() {
+    let json = format!(
+        r#"{{"default": {{"outcome": "failed"}}, "payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1, "amount_msats": 7000}}}}}}"#,
+        hex::encode(PENDING)
+    );
+    let expected = StubScenario::new()
+        .default_outcome(StubOutcome::Failed { reason: None })
+        .payment(&PENDING, StubOutcome::PendingThenSettled { calls: 1, amount_msats: Some(7000) });
+    assert_eq!(StubScenario::from_config_value(&json).unwrap(), expected);
+
+    let path = std::env::temp_dir().join(format!("blvm-lightning-scenario-{}.json", rand::random::<u64>()));
+    std::fs::write(&path, &json).unwrap();
+    assert_eq!(StubScenario::from_config_value(path.to_str().unwrap()).unwrap(), expected);
+
+    let ctx = test_context(&[("lightning.stub.scenario", r#"{"default": {"outcome": "explode"}}"#)]);
+    assert!(matches!(create_provider(ProviderType::Stub, &ctx), Err(LightningError::ConfigError(_))));
+}
+
+#[tokio::test]
+async fn test_processor_retries_pending_payment() {
+    let invoice = make_invoice(2000).await;
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+    let scenario = format!(
+        r#"{{"payments": {{"{}": {{"outcome": "pending_then_settled", "calls": 1, "amount_msats": 2000}}}}}}"#,
+        hex::encode(payment_hash)
+    );
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.scenario", &scenario)]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(payment.state.is_pending());
+    assert_eq!(node_api.len("processed_payments"), 0);
+
+    processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap();
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+    assert_eq!(payment.attempts, 2);
+    assert_eq!(payment.amount_msats, Some(2000));
+
+    let provider = processor.provider();
+    let stub = provider.as_any().downcast_ref::<StubProvider>().unwrap();
+    assert_eq!(
+        stub.calls(),
+        vec![
+            StubCall::VerifyPayment { payment_hash, payment_id: "payment-1".to_string() },
+            StubCall::VerifyPayment { payment_hash, payment_id: "payment-1".to_string() },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_processor_surfaces_timeout() {
+    let invoice = make_invoice(2000).await;
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.stub.scenario", r#"{"default": {"outcome": "timeout", "after_ms": 10}}"#),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    let err = processor.process_payment(&invoice, "payment-1", node_api.as_ref()).await.unwrap_err();
+    assert!(err.is_retryable());
+    assert!(processor.load_payment("payment-1").await.unwrap().unwrap().state.is_pending());
+}
+
+#[tokio::test]
+async fn test_failure_rate() {
+    let stub = StubProvider::new_with_config(StubConfig {
+        failure_rate: 0.5,
+        ..Default::default()
+    });
+    let mut failures = Vec::new();
+    for call in 1..=4 {
+        if let Err(err) = stub.verify_payment("", &PAID, "payment-1").await {
+            assert!(matches!(err, LightningError::NodeConnectionError(ref msg) if msg == "stub failure"));
+            failures.push(call);
+        }
+    }
+    assert_eq!(failures, vec![2, 4]);
+
+    let stub = StubProvider::new_with_config(StubConfig {
+        failure_rate: 1.0,
+        failure_error: Some(LightningError::InvoiceExpired("stub".to_string())),
+        ..Default::default()
+    });
+    assert!(matches!(stub.create_invoice(1000, "test", 3600).await, Err(LightningError::InvoiceExpired(_))));
+    assert!(matches!(stub.is_payment_confirmed(&PAID).await, Err(LightningError::InvoiceExpired(_))));
+}
+
+#[tokio::test]
+async fn test_latency_and_fixed_amount() {
+    let stub = StubProvider::new_with_config(StubConfig {
+        fixed_amount_msats: 42_000,
+        latency_ms: 50,
+        ..Default::default()
+    });
+    let started = std::time::Instant::now();
+    let result = stub.verify_payment("", &PAID, "payment-1").await.unwrap();
+    assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+    assert_eq!(result.amount_msats, Some(42_000));
+
+    // Scripted amounts still win
+    let stub = StubProvider::new_with_config(StubConfig {
+        fixed_amount_msats: 42_000,
+        ..Default::default()
+    })
+    .scenario(scenario());
+    assert_eq!(stub.verify_payment("", &PAID, "payment-1").await.unwrap().amount_msats, Some(5000));
+}
+
+#[tokio::test]
+async fn test_processor_recovers_from_injected_failures() {
+    let first = make_invoice(2000).await;
+    let second = make_invoice(3000).await;
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.stub.failure_rate", "0.5")]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+
+    processor.process_payment(&first, "payment-1", node_api.as_ref()).await.unwrap();
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+
+    let err = processor.process_payment(&second, "payment-2", node_api.as_ref()).await.unwrap_err();
+    assert!(err.is_retryable());
+    assert!(processor.load_payment("payment-2").await.unwrap().unwrap().state.is_pending());
+
+    processor.process_payment(&second, "payment-2", node_api.as_ref()).await.unwrap();
+    let payment = processor.load_payment("payment-2").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+}
+
+fn programmed(amount_msats: u64, timestamp: u64) -> PaymentVerificationResult {
+    PaymentVerificationResult {
+        verified: true,
+        amount_msats: Some(amount_msats),
+        timestamp: Some(timestamp),
+        metadata: serde_json::json!({"provider": "stub", "programmed": true}),
+    }
+}
+
+#[tokio::test]
+async fn test_programmed_responses() {
+    let unpaid = PaymentVerificationResult {
+        verified: false,
+        amount_msats: None,
+        timestamp: None,
+        metadata: serde_json::json!({"error": "unpaid"}),
+    };
+    let stub = StubProvider::with_responses(HashMap::from([
+        (PAID, programmed(7777, 1_700_000_000)),
+        (FAILED, unpaid),
+    ]));
+
+    let result = stub.verify_payment("", &PAID, "payment-1").await.unwrap();
+    assert_eq!(result.amount_msats, Some(7777));
+    assert_eq!(result.timestamp, Some(1_700_000_000));
+    assert_eq!(result.metadata["programmed"], true);
+    assert!(!stub.verify_payment("", &FAILED, "payment-2").await.unwrap().verified);
+    assert!(!stub.is_payment_confirmed(&FAILED).await.unwrap());
+
+    // Unknown hashes fall back to the default success
+    let result = stub.verify_payment("", &PENDING, "payment-3").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(STUB_AMOUNT_MSATS));
+}
+
+#[tokio::test]
+async fn test_invoice_map() {
+    let invoice = make_invoice(2000).await;
+    let stub = StubProvider::with_invoice_map(HashMap::from([(2000, invoice.clone())]));
+    assert_eq!(stub.create_invoice(2000, "test", 3600).await.unwrap(), invoice);
+    assert_eq!(stub.create_invoice_ex(&InvoiceParams::new(2000, "test", 3600)).await.unwrap(), invoice);
+
+    // Other amounts get a signed regtest invoice that parses like a real one
+    let created = stub.create_invoice(3000, "test", 3600).await.unwrap();
+    assert!(created.starts_with("lnbcrt"));
+    let parsed = InvoiceParser::parse(&created).unwrap();
+    assert_eq!(parsed.amount_msats, 3000);
+    assert_eq!(parsed.expires_at(), parsed.timestamp + 3600);
+    assert_ne!(stub.create_invoice(3000, "test", 3600).await.unwrap(), created);
+}
+
+#[tokio::test]
+async fn test_processor_uses_programmed_results() {
+    let invoice = make_invoice(2000).await;
+    let payment_hash = InvoiceParser::parse(&invoice).unwrap().payment_hash();
+    let stub = StubProvider::with_responses(HashMap::from([(payment_hash, programmed(2001, 1_700_000_000))]))
+        .invoice_map(HashMap::from([(2000, invoice.clone())]));
+    let ctx = test_context(&[]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::with_provider(&ctx, node_api.clone(), Box::new(stub)).await.unwrap();
+
+    let created = processor.create_invoice_ex(&InvoiceParams::new(2000, "test", 3600)).await.unwrap();
+    assert_eq!(created, invoice);
+
+    processor.process_payment(&created, "payment-1", node_api.as_ref()).await.unwrap();
+    let payment = processor.load_payment("payment-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+    assert_eq!(payment.amount_msats, Some(2001));
+    assert_eq!(payment.metadata["programmed"], true);
+}
@@ -0,0 +1,13 @@
+//! Telemetry initialization tests
+
+#[cfg(not(feature = "telemetry"))]
+#[test]
+fn test_init_tracer_requires_feature() {
+    use blvm_lightning::error::LightningError;
+    use blvm_lightning::telemetry::init_tracer;
+
+    assert!(matches!(
+        init_tracer("http://127.0.0.1:4317"),
+        Err(LightningError::ConfigError(_))
+    ));
+}
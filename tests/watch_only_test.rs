@@ -0,0 +1,141 @@
+//! Watch-only provider tests
+
+mod common;
+
+use bitcoin::hashes::{sha256, Hash};
+use blvm_lightning::error::LightningError;
+use blvm_lightning::provider::watch_only::{WatchOnlyConfig, WatchOnlyProvider};
+use blvm_lightning::provider::{create_provider, InvoiceParams, LightningProvider, ProviderType};
+use common::test_context;
+
+const PREIMAGE: [u8; 32] = [7u8; 32];
+
+fn payment_hash() -> [u8; 32] {
+    sha256::Hash::hash(&PREIMAGE).to_byte_array()
+}
+
+/// Create a real signed BOLT11 invoice via the LDK provider
+async fn make_invoice(amount_msats: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    provider.create_invoice(amount_msats, "test", 3600).await.unwrap()
+}
+
+fn provider() -> WatchOnlyProvider {
+    WatchOnlyProvider::new(WatchOnlyConfig::default()).unwrap()
+}
+
+#[tokio::test]
+async fn test_invoice_creation_unsupported() {
+    let provider = provider();
+    assert!(matches!(provider.create_invoice(1000, "test", 3600).await, Err(LightningError::Unsupported(_))));
+    assert!(matches!(
+        provider.create_invoice_ex(&InvoiceParams::new(1000, "test", 3600)).await,
+        Err(LightningError::Unsupported(_))
+    ));
+    let err = provider.create_invoice(1000, "test", 3600).await.unwrap_err();
+    assert!(!err.is_retryable());
+
+    let capabilities = provider.capabilities();
+    assert!(capabilities.can_verify);
+    assert!(!capabilities.can_create_invoices);
+    assert_eq!(provider.provider_type(), ProviderType::WatchOnly);
+}
+
+#[tokio::test]
+async fn test_lnurl_verify_settled() {
+    let invoice = make_invoice(2000).await;
+    let mut server = mockito::Server::new_async().await;
+    let verify = server
+        .mock("GET", "/lnurlp/verify/abc123")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({
+                "status": "OK",
+                "settled": true,
+                "preimage": hex::encode(PREIMAGE),
+                "pr": invoice,
+            })
+            .to_string(),
+        )
+        .expect(2)
+        .create_async()
+        .await;
+
+    let provider = provider();
+    provider
+        .register_verify_url(&payment_hash(), &format!("{}/lnurlp/verify/abc123", server.url()))
+        .await
+        .unwrap();
+
+    let result = provider.verify_payment(&invoice, &payment_hash(), "payment-1").await.unwrap();
+    assert!(result.verified);
+    assert_eq!(result.amount_msats, Some(2000));
+    assert_eq!(result.metadata["source"], "lnurl_verify");
+    assert!(provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+    verify.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_lnurl_verify_unsettled_and_bad_preimage() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/verify/unsettled")
+        .with_status(200)
+        .with_body(r#"{"status": "OK", "settled": false, "preimage": null, "pr": "lnbc1"}"#)
+        .create_async()
+        .await;
+    // Claims settlement, but the preimage doesn't match the payment hash
+    server
+        .mock("GET", "/verify/lying")
+        .with_status(200)
+        .with_body(serde_json::json!({ "status": "OK", "settled": true, "preimage": hex::encode([8u8; 32]) }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/verify/error")
+        .with_status(200)
+        .with_body(r#"{"status": "ERROR", "reason": "Not found"}"#)
+        .create_async()
+        .await;
+
+    let provider = provider();
+    let hashes = [[1u8; 32], payment_hash(), [3u8; 32]];
+    for (hash, path) in hashes.iter().zip(["/verify/unsettled", "/verify/lying", "/verify/error"]) {
+        provider.register_verify_url(hash, &format!("{}{}", server.url(), path)).await.unwrap();
+    }
+
+    assert!(!provider.verify_payment("", &hashes[0], "payment-1").await.unwrap().verified);
+    let result = provider.verify_payment("", &hashes[1], "payment-2").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "preimage_mismatch");
+    let result = provider.verify_payment("", &hashes[2], "payment-3").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "Not found");
+}
+
+#[tokio::test]
+async fn test_no_verify_source() {
+    let provider = provider();
+    let result = provider.verify_payment("", &payment_hash(), "payment-1").await.unwrap();
+    assert!(!result.verified);
+    assert_eq!(result.metadata["error"], "no_verify_source");
+    assert!(!provider.is_payment_confirmed(&payment_hash()).await.unwrap());
+    assert!(provider.register_verify_url(&payment_hash(), "lnurl1dp68gurn8ghj7").await.is_err());
+}
+
+#[test]
+fn test_never_selected_as_invoice_creator() {
+    let ctx = test_context(&[]);
+    let provider = create_provider(ProviderType::WatchOnly, &ctx).unwrap();
+    assert_eq!(provider.provider_type(), ProviderType::WatchOnly);
+
+    let chain = "ldk,watch_only".parse::<ProviderType>().unwrap();
+    assert!(matches!(create_provider(chain, &ctx), Err(LightningError::ConfigError(_))));
+
+    let ctx = test_context(&[(
+        "lightning.routing.rules",
+        r#"[{"max_msats": 1000, "provider": "watch_only"}, {"provider": "ldk"}]"#,
+    )]);
+    assert!(matches!(create_provider(ProviderType::Routing, &ctx), Err(LightningError::ConfigError(_))));
+}
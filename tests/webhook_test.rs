@@ -0,0 +1,367 @@
+//! Payment webhook receiver and outgoing webhook tests
+
+mod common;
+
+use blvm_lightning::invoice::InvoiceParser;
+use blvm_lightning::payment_state::PaymentState;
+use blvm_lightning::processor::LightningProcessor;
+use blvm_lightning::provider::{create_provider, InvoiceParams, ProviderType};
+use blvm_lightning::error::LightningError;
+use blvm_lightning::webhook::{
+    self, sign_webhook, WebhookConfig, WebhookDelivery, WebhookEvent, WebhookEventKind, WebhookFilter,
+    LNBITS_WEBHOOK_PATH, WEBHOOK_SIGNATURE_HEADER,
+};
+use common::{test_context, MockNodeApi};
+use mockito::Matcher;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+const WEBHOOK_URL: &str = "https://shop.example.com/lightning/webhook";
+
+/// Create a real signed BOLT11 invoice via the LDK provider
+async fn make_invoice(amount_msats: u64) -> String {
+    let ctx = test_context(&[("lightning.ldk.network", "testnet")]);
+    let provider = create_provider(ProviderType::LDK, &ctx).unwrap();
+    provider.create_invoice(amount_msats, "test", 3600).await.unwrap()
+}
+
+/// Processor on a mock LNBits with the webhook receiver listening on a free port
+async fn setup(server: &mockito::Server) -> (Arc<LightningProcessor>, Arc<MockNodeApi>, SocketAddr) {
+    let ctx = test_context(&[
+        ("lightning.provider", "lnbits"),
+        ("lightning.lnbits.api_url", &server.url()),
+        ("lightning.lnbits.invoice_key", "test_key"),
+        ("lightning.lnbits.webhook_url", WEBHOOK_URL),
+        ("lightning.lnbits.max_retries", "0"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = Arc::new(LightningProcessor::new(&ctx, node_api.clone()).await.unwrap());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(webhook::serve_on(processor.clone(), listener));
+    (processor, node_api, addr)
+}
+
+/// Create an invoice through the processor, checking LNBits is asked to call the webhook
+async fn issue_invoice(server: &mut mockito::Server, processor: &LightningProcessor) -> (String, [u8; 32]) {
+    let invoice = make_invoice(2000).await;
+    let mock = server
+        .mock("POST", "/api/v1/payments")
+        .match_body(Matcher::PartialJson(serde_json::json!({"webhook": WEBHOOK_URL})))
+        .with_status(201)
+        .with_body(serde_json::json!({"payment_request": invoice}).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+    let created = processor.create_invoice_ex(&InvoiceParams::new(2000, "test", 3600)).await.unwrap();
+    mock.assert_async().await;
+    let payment_hash = InvoiceParser::parse(&created).unwrap().payment_hash();
+    (created, payment_hash)
+}
+
+async fn post_webhook(addr: SocketAddr, body: serde_json::Value) -> (u16, serde_json::Value) {
+    let response = reqwest::Client::new()
+        .post(format!("http://{}{}", addr, LNBITS_WEBHOOK_PATH))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_webhook_settles_confirmed_payment_once() {
+    let mut server = mockito::Server::new_async().await;
+    let (processor, _node_api, addr) = setup(&server).await;
+    let (_, payment_hash) = issue_invoice(&mut server, &processor).await;
+    let payment_hash_hex = hex::encode(payment_hash);
+
+    let lookup = server
+        .mock("GET", format!("/api/v1/payments/{}", payment_hash_hex).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 2000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    // LNBits sends the whole payment record; only the hash is used
+    let body = serde_json::json!({"payment_hash": payment_hash_hex, "amount": 2000, "pending": false});
+    let (status, response) = post_webhook(addr, body.clone()).await;
+    assert_eq!(status, 200);
+    assert_eq!(response["status"], "settled");
+    let payment = processor.load_payment(&payment_hash_hex).await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+
+    // Redelivery is a no-op
+    let (status, response) = post_webhook(addr, body).await;
+    assert_eq!(status, 200);
+    assert_eq!(response["status"], "already_settled");
+    lookup.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_webhook_settles_the_waiting_payment() {
+    let mut server = mockito::Server::new_async().await;
+    let (processor, node_api, addr) = setup(&server).await;
+    let invoice = make_invoice(2000).await;
+    server
+        .mock("POST", "/api/v1/payments")
+        .with_status(201)
+        .with_body(serde_json::json!({"payment_request": invoice}).to_string())
+        .create_async()
+        .await;
+    let created = processor.create_invoice(2000, "order 1", 3600, "order-1").await.unwrap();
+    let payment_hash_hex = hex::encode(created.payment_hash);
+    server
+        .mock("GET", format!("/api/v1/payments/{}", payment_hash_hex).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 2000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (status, response) = post_webhook(addr, serde_json::json!({"payment_hash": payment_hash_hex})).await;
+    assert_eq!(status, 200);
+    assert_eq!(response["payment_id"], "order-1");
+    let payment = processor.load_payment("order-1").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+    assert!(processor.load_payment(&payment_hash_hex).await.unwrap().is_none());
+
+    // The merchant confirming the payment afterwards isn't a duplicate
+    processor.process_payment(&created.bolt11, "order-1", node_api.as_ref()).await.unwrap();
+    // Replaying it, or another payment for the same invoice, is
+    assert!(processor.process_payment(&created.bolt11, "order-1", node_api.as_ref()).await.is_err());
+    assert!(processor.process_payment(&created.bolt11, "order-2", node_api.as_ref()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_process_payment_takes_over_webhook_settlement() {
+    let mut server = mockito::Server::new_async().await;
+    let (processor, node_api, addr) = setup(&server).await;
+    let (invoice, payment_hash) = issue_invoice(&mut server, &processor).await;
+    let payment_hash_hex = hex::encode(payment_hash);
+    server
+        .mock("GET", format!("/api/v1/payments/{}", payment_hash_hex).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": true, "amount": 2000}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    // Nobody claimed the invoice yet, so the webhook settles it under its hash
+    let (_, response) = post_webhook(addr, serde_json::json!({"payment_hash": payment_hash_hex})).await;
+    assert_eq!(response["payment_id"], payment_hash_hex.as_str());
+
+    processor.process_payment(&invoice, "order-9", node_api.as_ref()).await.unwrap();
+    let payment = processor.load_payment("order-9").await.unwrap().unwrap();
+    assert!(matches!(payment.state, PaymentState::Settled { .. }));
+    assert_eq!(payment.payment_hash, payment_hash_hex);
+    assert!(processor.process_payment(&invoice, "order-9", node_api.as_ref()).await.is_err());
+    assert!(processor.process_payment(&invoice, "order-10", node_api.as_ref()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_webhook_never_settles_without_confirmation() {
+    let mut server = mockito::Server::new_async().await;
+    let (processor, _node_api, addr) = setup(&server).await;
+    let (_, payment_hash) = issue_invoice(&mut server, &processor).await;
+    let payment_hash_hex = hex::encode(payment_hash);
+
+    server
+        .mock("GET", format!("/api/v1/payments/{}", payment_hash_hex).as_str())
+        .with_status(200)
+        .with_body(r#"{"paid": false}"#)
+        .create_async()
+        .await;
+
+    // The body claims the payment is done, the API disagrees
+    let body = serde_json::json!({"payment_hash": payment_hash_hex, "pending": false, "paid": true});
+    let (status, response) = post_webhook(addr, body).await;
+    assert_eq!(status, 202);
+    assert_eq!(response["status"], "unverified");
+    let payment = processor.load_payment(&payment_hash_hex).await.unwrap().unwrap();
+    assert!(!matches!(payment.state, PaymentState::Settled { .. }));
+}
+
+#[tokio::test]
+async fn test_webhook_rejects_unknown_and_malformed() {
+    let server = mockito::Server::new_async().await;
+    let (_processor, node_api, addr) = setup(&server).await;
+
+    let (status, _) = post_webhook(addr, serde_json::json!({"payment_hash": hex::encode([9u8; 32])})).await;
+    assert_eq!(status, 404);
+
+    let (status, _) = post_webhook(addr, serde_json::json!({"payment_hash": "not-hex"})).await;
+    assert_eq!(status, 400);
+    let (status, _) = post_webhook(addr, serde_json::json!({"checking_id": "abc"})).await;
+    assert_eq!(status, 400);
+
+    assert_eq!(node_api.len("payment_states"), 0);
+}
+
+fn outgoing_config(server: &mockito::Server, max_retries: u32) -> WebhookConfig {
+    WebhookConfig {
+        url: format!("{}/hooks/lightning", server.url()),
+        secret: "whsec_test".to_string(),
+        max_retries,
+        timeout_ms: 2_000,
+        filter: WebhookFilter::default(),
+    }
+}
+
+#[test]
+fn test_sign_webhook_known_vector() {
+    // RFC 4231 test case 2
+    assert_eq!(
+        sign_webhook("Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[tokio::test]
+async fn test_settled_payment_delivers_signed_webhook() {
+    let mut server = mockito::Server::new_async().await;
+    let deliveries: Arc<Mutex<Vec<(Vec<u8>, String)>>> = Arc::default();
+    let captured = deliveries.clone();
+    let mock = server
+        .mock("POST", "/hooks/lightning")
+        .with_status(200)
+        .with_body_from_request(move |request| {
+            let signature = request.header(WEBHOOK_SIGNATURE_HEADER)[0].to_str().unwrap().to_string();
+            captured.lock().unwrap().push((request.body().unwrap().clone(), signature));
+            Vec::new()
+        })
+        .expect(1)
+        .create_async()
+        .await;
+
+    let url = format!("{}/hooks/lightning", server.url());
+    let ctx = test_context(&[
+        ("lightning.provider", "stub"),
+        ("lightning.webhook.url", &url),
+        ("lightning.webhook.secret", "whsec_test"),
+        ("lightning.webhook.events", "received,failed"),
+    ]);
+    let node_api = MockNodeApi::new();
+    let processor = LightningProcessor::new(&ctx, node_api.clone()).await.unwrap();
+    let invoice = make_invoice(2000).await;
+    processor.process_payment(&invoice, "order-42", node_api.as_ref()).await.unwrap();
+    mock.assert_async().await;
+
+    let (body, signature) = deliveries.lock().unwrap().pop().unwrap();
+    let event: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(event["event"], "payment_received");
+    assert_eq!(event["payment_id"], "order-42");
+    assert_eq!(event["payment_hash"], InvoiceParser::parse(&invoice).unwrap().payment_hash_hex());
+    assert_eq!(event["amount_msats"], 2000);
+    assert_eq!(event["fee_msats"], 0);
+    let settled_at = match processor.load_payment("order-42").await.unwrap().unwrap().state {
+        PaymentState::Settled { settled_at } => settled_at,
+        other => panic!("expected settled, got {:?}", other),
+    };
+    assert!(event["timestamp"].as_u64().unwrap() >= settled_at);
+
+    // The signature is the HMAC-SHA256 of the exact body under the shared secret
+    use bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(b"whsec_test");
+    engine.input(&body);
+    assert_eq!(signature, hex::encode(hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()));
+}
+
+#[tokio::test]
+async fn test_webhook_delivery_retries_transient_failures() {
+    let mut server = mockito::Server::new_async().await;
+    let unavailable = server.mock("POST", "/hooks/lightning").with_status(503).expect(1).create_async().await;
+    let accepted = server.mock("POST", "/hooks/lightning").with_status(204).expect(1).create_async().await;
+
+    let event = WebhookEvent::PaymentFailed { payment_id: "order-42".to_string(), reason: "underpaid".to_string() };
+    WebhookDelivery::deliver(&event, &outgoing_config(&server, 2)).await.unwrap();
+    unavailable.assert_async().await;
+    accepted.assert_async().await;
+
+    // A receiver rejecting the event isn't retried
+    let mut server = mockito::Server::new_async().await;
+    let rejected = server.mock("POST", "/hooks/lightning").with_status(400).expect(1).create_async().await;
+    match WebhookDelivery::deliver(&event, &outgoing_config(&server, 3)).await {
+        Err(LightningError::HttpError { status_code, .. }) => assert_eq!(status_code, 400),
+        other => panic!("expected HTTP 400, got {:?}", other),
+    }
+    rejected.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_webhook_url_requires_secret() {
+    let ctx = test_context(&[("lightning.provider", "stub"), ("lightning.webhook.url", "https://shop.example.com/hooks")]);
+    assert!(matches!(
+        LightningProcessor::new(&ctx, MockNodeApi::new()).await,
+        Err(LightningError::ConfigError(_))
+    ));
+    assert_eq!(WebhookConfig::from_config(&test_context(&[])).unwrap(), None);
+}
+
+#[test]
+fn test_webhook_event_names() {
+    let events = [
+        (
+            WebhookEvent::InvoiceCreated {
+                payment_id: "ab".repeat(32),
+                bolt11: "lntb20n1...".to_string(),
+                amount_msats: 2000,
+                expiry_at: 1_700_003_600,
+            },
+            "invoice_created",
+        ),
+        (
+            WebhookEvent::PaymentReceived {
+                payment_id: "order-42".to_string(),
+                payment_hash: "ab".repeat(32),
+                amount_msats: 2000,
+                fee_msats: 0,
+            },
+            "payment_received",
+        ),
+        (
+            WebhookEvent::PaymentSent {
+                payment_id: "payout-7".to_string(),
+                payment_hash: "ab".repeat(32),
+                preimage: "cd".repeat(32),
+                fee_msats: 12,
+            },
+            "payment_sent",
+        ),
+        (
+            WebhookEvent::PaymentFailed { payment_id: "order-42".to_string(), reason: "underpaid".to_string() },
+            "payment_failed",
+        ),
+        (
+            WebhookEvent::InvoiceExpired { payment_id: "order-42".to_string(), payment_hash: "ab".repeat(32) },
+            "invoice_expired",
+        ),
+    ];
+    for (event, name) in events {
+        let encoded = serde_json::to_value(&event).unwrap();
+        assert_eq!(encoded["event"], name);
+        assert_eq!(encoded["payment_id"], event.payment_id());
+        assert_eq!(WebhookEventKind::from_name(name), Some(event.kind()));
+        assert_eq!(serde_json::from_value::<WebhookEvent>(encoded).unwrap(), event);
+    }
+}
+
+#[tokio::test]
+async fn test_webhook_filter() {
+    let filter = WebhookFilter::parse("received, failed").unwrap();
+    assert_eq!(filter.events, vec![WebhookEventKind::PaymentReceived, WebhookEventKind::PaymentFailed]);
+    assert!(!filter.allows(WebhookEventKind::InvoiceCreated));
+    assert!(WebhookFilter::parse("").unwrap().allows(WebhookEventKind::InvoiceCreated));
+    assert!(matches!(WebhookFilter::parse("received,settled"), Err(LightningError::ConfigError(_))));
+
+    // Events the endpoint isn't subscribed to are never sent
+    let mut server = mockito::Server::new_async().await;
+    let mock = server.mock("POST", "/hooks/lightning").expect(0).create_async().await;
+    let config = WebhookConfig { filter, ..outgoing_config(&server, 0) };
+    let event = WebhookEvent::InvoiceExpired { payment_id: "order-42".to_string(), payment_hash: "ab".repeat(32) };
+    WebhookDelivery::deliver(&event, &config).await.unwrap();
+    mock.assert_async().await;
+}